@@ -9,10 +9,13 @@ use super::types::{Cli, Commands, DatasetInfo, OpsCommand};
 
 pub fn run_cli() -> Result<(), String> {
     let cli = Cli::parse();
-    let app = AppContext::new();
+    let app = cli
+        .memory_limit
+        .map(AppContext::with_memory_budget)
+        .unwrap_or_else(AppContext::new);
 
     match cli.command {
-        Commands::Info { input } => {
+        Commands::Info { input, history } => {
             let dataset = app
                 .io_service()
                 .read(&input)
@@ -39,6 +42,7 @@ pub fn run_cli() -> Result<(), String> {
                     .map(|path| path.display().to_string()),
                 min: Some(min),
                 max: Some(max),
+                history: history.then(|| dataset.metadata.history.clone()),
             };
             println!(
                 "{}",
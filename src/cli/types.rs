@@ -3,6 +3,8 @@ use std::path::PathBuf;
 use clap::{Parser, Subcommand};
 use serde::Serialize;
 
+use crate::model::{MemoryBudget, ProvenanceEntry};
+
 #[derive(Debug, Parser)]
 #[command(
     name = "image",
@@ -10,14 +12,26 @@ use serde::Serialize;
     about = "ImageJ2-inspired Rust image processing CLI"
 )]
 pub(super) struct Cli {
+    /// Memory budget for dataset allocations, e.g. `4G` or `512M`.
+    /// Defaults to `IMAGE_RS_MEMORY_LIMIT` or three quarters of physical memory.
+    #[arg(long, global = true, value_parser = parse_memory_limit)]
+    pub(super) memory_limit: Option<MemoryBudget>,
     #[command(subcommand)]
     pub(super) command: Commands,
 }
 
+fn parse_memory_limit(value: &str) -> Result<MemoryBudget, String> {
+    MemoryBudget::parse(value)
+        .ok_or_else(|| format!("invalid memory limit `{value}`; expected e.g. 4G or 512M"))
+}
+
 #[derive(Debug, Subcommand)]
 pub(super) enum Commands {
     Info {
         input: PathBuf,
+        /// Includes the processing history recorded by pipeline runs.
+        #[arg(long)]
+        history: bool,
     },
     Convert {
         input: PathBuf,
@@ -59,4 +73,6 @@ pub(super) struct DatasetInfo {
     pub(super) source: Option<String>,
     pub(super) min: Option<f32>,
     pub(super) max: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) history: Option<Vec<ProvenanceEntry>>,
 }
@@ -1,14 +1,30 @@
 mod axes;
+mod calculator;
 mod components;
+mod contrast;
+mod deconvolution;
+mod denoise;
+mod display;
+mod distance;
+mod edges;
 mod error;
+mod extrema;
+mod features;
+mod fft;
 mod gaussian;
+mod gray_morphology;
 mod intensity;
+mod labels;
 mod measurements;
 #[cfg(feature = "morpholib")]
 mod morpholibj;
 mod morphology;
 mod noise;
+mod objects;
 mod params;
+mod particles;
+mod regionprops;
+mod registration;
 mod registry;
 mod schema;
 mod threshold;
@@ -16,17 +32,49 @@ mod threshold;
 mod thunderstorm;
 mod transform;
 mod util;
+mod watershed;
 
 #[cfg(test)]
 mod tests;
 
+pub use axes::{ImageAxesAssignOp, ImageAxesPermuteOp};
+pub use calculator::{ImageApplyMaskOp, ImageCalculatorOp};
 pub use components::ComponentsLabelOp;
+pub use contrast::{IntensityBleachCorrectionOp, IntensityClaheOp, IntensityHistogramMatchOp};
+pub use deconvolution::{ImageDeconvolveRichardsonLucyOp, ImagePsfGenerateOp};
+pub use denoise::{ImageAnisotropicDiffusionOp, ImageBilateralFilterOp, ImageNonLocalMeansOp};
+pub use display::{ImageDisplaySetChannelOp, ImageDisplaySetModeOp};
+pub use distance::MorphologyDistanceTransformOp;
+pub use edges::{ImageEdgesCannyOp, ImageHoughCirclesOp, ImageHoughLinesOp};
 pub use error::{OpsError, Result};
+pub use extrema::{
+    MorphologyExtendedMaximaOp, MorphologyExtendedMinimaOp, MorphologyHMaximaOp,
+    MorphologyHMinimaOp, MorphologyImposeMinimaOp, MorphologyRegionalMaximaOp,
+    MorphologyRegionalMinimaOp,
+};
+pub use features::{
+    FeaturesDifferenceOfGaussiansOp, FeaturesFrangiOp, FeaturesGradientMagnitudeOp,
+    FeaturesHessianOp, FeaturesLaplacianOfGaussianOp, FeaturesMeijeringOp, FeaturesSatoOp,
+    FeaturesStructureTensorOp,
+};
+pub use fft::{
+    ImageFftConvolveOp, ImageFftCorrelateOp, ImageFftCustomFilterOp, ImageFftDeconvolveOp,
+    ImageFftForwardOp, ImageFftInverseOp, ImagePhaseCorrelationOp,
+};
 pub use gaussian::GaussianBlurOp;
+pub use gray_morphology::{
+    MorphologyGrayBlackTopHatOp, MorphologyGrayCloseOp, MorphologyGrayDilateOp,
+    MorphologyGrayErodeOp, MorphologyGrayGradientOp, MorphologyGrayOpenOp,
+    MorphologyGrayWhiteTopHatOp,
+};
 pub use intensity::{
     IntensityEnhanceContrastOp, IntensityInvertOp, IntensityMathOp, IntensityNaNBackgroundOp,
     IntensityNormalizeOp, IntensityWindowOp,
 };
+pub use labels::{
+    LabelsBoundariesOp, LabelsCropOp, LabelsDilateOp, LabelsFillHolesOp, LabelsKeepLargestOp,
+    LabelsMergeOp, LabelsRelabelOp, LabelsRemoveOp,
+};
 pub use measurements::{
     ImageStackStatisticsOp, ImageStackZProfileOp, MeasurementsHistogramOp, MeasurementsProfileOp,
     MeasurementsSummaryOp,
@@ -43,11 +91,18 @@ pub use morphology::{
     MorphologyWatershedOp,
 };
 pub use noise::{NoiseGaussianOp, NoiseSaltAndPepperOp};
+pub use objects::AnalyzeObjects3dOp;
+pub use particles::AnalyzeParticlesOp;
+pub use regionprops::MeasurementsRegionPropsOp;
+pub use registration::{ImageRegisterApplyOp, ImageRegisterStackOp, ImageRegisterTranslationOp};
 pub use registry::{
-    default_registry, execute_operation, execute_operation_with_registry, list_operations,
+    default_registry, execute_operation, execute_operation_with_inputs,
+    execute_operation_with_registry, list_operations,
+};
+pub use schema::{
+    INPUT_PARAM_KIND, MeasurementTable, OpInputs, OpOutput, OpSchema, Operation, ParamSpec,
 };
-pub use schema::{MeasurementTable, OpOutput, OpSchema, Operation, ParamSpec};
-pub use threshold::{ThresholdFixedOp, ThresholdMakeBinaryOp, ThresholdOtsuOp};
+pub use threshold::{ThresholdFixedOp, ThresholdLocalOp, ThresholdMakeBinaryOp, ThresholdOtsuOp};
 #[cfg(feature = "thunderstorm")]
 pub use thunderstorm::{
     ThunderstormGaussianFilterOp, ThunderstormLsqGaussianFitOp, ThunderstormNonMaxSuppressionOp,
@@ -66,8 +121,9 @@ pub use transform::{
     ImageSubtractBackgroundOp, ImageSurfacePlotOp, ImageSwapQuadrantsOp, ImageTranslateOp,
     ImageUnsharpMaskOp,
 };
+pub use watershed::MorphologyMarkerWatershedOp;
 
 pub(crate) use axes::spatial_axes;
-#[cfg(feature = "morpholib")]
-pub(crate) use params::get_optional_bool;
-pub(crate) use params::{get_optional_f32, get_optional_usize, get_required_f32};
+pub(crate) use params::{
+    get_optional_bool, get_optional_f32, get_optional_usize, get_required_f32,
+};
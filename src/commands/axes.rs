@@ -1,4 +1,7 @@
-use crate::model::{AxisKind, DatasetF32};
+use crate::model::{AxisKind, Dataset, DatasetF32};
+use serde_json::Value;
+
+use super::{OpOutput, OpSchema, Operation, OpsError, ParamSpec, Result};
 
 pub(crate) fn spatial_axes(dataset: &DatasetF32) -> Vec<usize> {
     dataset
@@ -8,7 +11,132 @@ pub(crate) fn spatial_axes(dataset: &DatasetF32) -> Vec<usize> {
         .enumerate()
         .filter_map(|(index, dim)| match dim.axis {
             AxisKind::X | AxisKind::Y | AxisKind::Z | AxisKind::Unknown => Some(index),
-            AxisKind::Channel | AxisKind::Time => None,
+            AxisKind::Channel
+            | AxisKind::Time
+            | AxisKind::Spectral
+            | AxisKind::Angle
+            | AxisKind::Position => None,
         })
         .collect()
 }
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageAxesPermuteOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageAxesAssignOp;
+
+impl Operation for ImageAxesPermuteOp {
+    fn name(&self) -> &'static str {
+        "image.axes.permute"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Reorder dimensions and data, e.g. TZCYX -> YXZCT.".to_string(),
+            params: vec![
+                ParamSpec {
+                    name: "order".to_string(),
+                    description: "Target axis codes (X, Y, Z, C, T, L spectral, A angle, P position, U unknown) or an array of source axis indices."
+                        .to_string(),
+                    required: true,
+                    kind: "string|array".to_string(),
+                },
+                ParamSpec {
+                    name: "from".to_string(),
+                    description: "Optional axis codes describing the current dimensions before reordering."
+                        .to_string(),
+                    required: false,
+                    kind: "string".to_string(),
+                },
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let relabeled;
+        let dataset = match params.get("from") {
+            None | Some(Value::Null) => dataset,
+            Some(value) => {
+                let codes = value.as_str().ok_or_else(|| {
+                    OpsError::InvalidParams("`from` must be a string of axis codes".to_string())
+                })?;
+                relabeled = assign_axes(dataset, codes)?;
+                &relabeled
+            }
+        };
+        let order = match params.get("order") {
+            Some(Value::String(codes)) => dataset
+                .metadata
+                .permutation_to(&AxisKind::parse_codes(codes)?)?,
+            Some(Value::Array(values)) => values
+                .iter()
+                .map(|value| {
+                    value.as_u64().map(|index| index as usize).ok_or_else(|| {
+                        OpsError::InvalidParams(
+                            "`order` indices must be non-negative integers".to_string(),
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            _ => {
+                return Err(OpsError::InvalidParams(
+                    "missing `order` (axis codes or index array)".to_string(),
+                ));
+            }
+        };
+        Ok(OpOutput::dataset_only(dataset.permute_axes(&order)?))
+    }
+}
+
+impl Operation for ImageAxesAssignOp {
+    fn name(&self) -> &'static str {
+        "image.axes.assign"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Declare what each dimension means without moving data.".to_string(),
+            params: vec![ParamSpec {
+                name: "axes".to_string(),
+                description: "Axis codes for the current dimensions, e.g. CZYX.".to_string(),
+                required: true,
+                kind: "string".to_string(),
+            }],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let codes = params.get("axes").and_then(Value::as_str).ok_or_else(|| {
+            OpsError::InvalidParams("missing string parameter `axes`".to_string())
+        })?;
+        Ok(OpOutput::dataset_only(assign_axes(dataset, codes)?))
+    }
+}
+
+fn assign_axes(dataset: &DatasetF32, codes: &str) -> Result<DatasetF32> {
+    let axes = AxisKind::parse_codes(codes)?;
+    if axes.len() != dataset.ndim() {
+        return Err(OpsError::InvalidParams(format!(
+            "`{codes}` names {} axes but the dataset has {}",
+            axes.len(),
+            dataset.ndim()
+        )));
+    }
+    let mut metadata = dataset.metadata.clone();
+    for (dim, axis) in metadata.dims.iter_mut().zip(axes) {
+        dim.axis = axis;
+    }
+    metadata.validate_axes()?;
+    Ok(Dataset::new(dataset.data.clone(), metadata)?)
+}
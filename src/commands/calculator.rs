@@ -0,0 +1,306 @@
+use crate::model::{Dataset, DatasetF32, PixelType};
+use ndarray::{ArrayD, IxDyn};
+use serde_json::Value;
+
+use super::{
+    INPUT_PARAM_KIND, OpInputs, OpOutput, OpSchema, Operation, OpsError, ParamSpec, Result,
+    get_optional_bool, get_optional_f32,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageCalculatorOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageApplyMaskOp;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CalculatorOperation {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Min,
+    Max,
+    Average,
+    Difference,
+    And,
+    Or,
+    Xor,
+}
+
+impl CalculatorOperation {
+    fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "add" => Ok(Self::Add),
+            "subtract" => Ok(Self::Subtract),
+            "multiply" => Ok(Self::Multiply),
+            "divide" => Ok(Self::Divide),
+            "min" => Ok(Self::Min),
+            "max" => Ok(Self::Max),
+            "average" => Ok(Self::Average),
+            "difference" => Ok(Self::Difference),
+            "and" => Ok(Self::And),
+            "or" => Ok(Self::Or),
+            "xor" => Ok(Self::Xor),
+            other => Err(OpsError::InvalidParams(format!(
+                "unsupported `operation` `{other}`; expected add, subtract, multiply, divide, min, max, average, difference, and, or or xor"
+            ))),
+        }
+    }
+
+    /// Bitwise operations work on the values rounded to integers.
+    fn apply(self, left: f32, right: f32, max: f32) -> f32 {
+        let bits = |value: f32| value.round() as i64;
+        match self {
+            Self::Add => left + right,
+            Self::Subtract => left - right,
+            Self::Multiply => left * right,
+            // ImageJ saturates integer division by zero.
+            Self::Divide if right == 0.0 && max.is_finite() => max,
+            Self::Divide => left / right,
+            Self::Min => left.min(right),
+            Self::Max => left.max(right),
+            Self::Average => (left + right) / 2.0,
+            Self::Difference => (left - right).abs(),
+            Self::And => (bits(left) & bits(right)) as f32,
+            Self::Or => (bits(left) | bits(right)) as f32,
+            Self::Xor => (bits(left) ^ bits(right)) as f32,
+        }
+    }
+}
+
+impl Operation for ImageCalculatorOp {
+    fn name(&self) -> &'static str {
+        "image.calculator"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Combine the image with a second image pixel by pixel (Image Calculator)."
+                .to_string(),
+            params: vec![
+                ParamSpec {
+                    name: "image2".to_string(),
+                    description: "Second operand; a single plane or channel is applied to every plane or channel of the first image.".to_string(),
+                    required: true,
+                    kind: INPUT_PARAM_KIND.to_string(),
+                },
+                ParamSpec {
+                    name: "operation".to_string(),
+                    description: "add, subtract, multiply, divide, min, max, average, difference, and, or or xor.".to_string(),
+                    required: true,
+                    kind: "string".to_string(),
+                },
+                ParamSpec {
+                    name: "float_result".to_string(),
+                    description: "Return a 32-bit result instead of clamping to the first image's pixel type.".to_string(),
+                    required: false,
+                    kind: "bool".to_string(),
+                },
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        let operation = params
+            .get("operation")
+            .and_then(Value::as_str)
+            .ok_or_else(|| OpsError::InvalidParams("`operation` is required".to_string()))
+            .and_then(CalculatorOperation::parse)?;
+        let image2 = inputs.require("image2")?;
+        let float_result = get_optional_bool(params, "float_result", false);
+        let pixel_type = if float_result {
+            PixelType::F32
+        } else {
+            dataset.metadata.pixel_type
+        };
+        let max = pixel_type_max(pixel_type);
+
+        let second = image2.data.iter().copied().collect::<Vec<_>>();
+        let mut data = dataset.data.clone();
+        for (value, index) in data.iter_mut().zip(broadcast(dataset, image2, "image2")?) {
+            let result = operation.apply(*value, second[index], max);
+            *value = match pixel_type {
+                PixelType::F32 => result,
+                PixelType::U8 | PixelType::U16 => result.round().clamp(0.0, max),
+            };
+        }
+
+        let mut metadata = dataset.metadata.clone();
+        metadata.pixel_type = pixel_type;
+        Ok(OpOutput::dataset_only(Dataset::new(data, metadata)?))
+    }
+}
+
+impl Operation for ImageApplyMaskOp {
+    fn name(&self) -> &'static str {
+        "image.apply_mask"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Replace pixels outside a binary mask image with a background value."
+                .to_string(),
+            params: vec![
+                ParamSpec {
+                    name: "mask".to_string(),
+                    description: "Mask image; pixels with value > 0.5 are kept. A single plane is applied to every plane.".to_string(),
+                    required: true,
+                    kind: INPUT_PARAM_KIND.to_string(),
+                },
+                ParamSpec {
+                    name: "background".to_string(),
+                    description: "Value written outside the mask (default 0; NaN requires 32-bit data).".to_string(),
+                    required: false,
+                    kind: "float".to_string(),
+                },
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        let mask = inputs.require("mask")?;
+        let background = match params.get("background") {
+            Some(Value::String(text)) if text.eq_ignore_ascii_case("nan") => f32::NAN,
+            _ => get_optional_f32(params, "background", 0.0),
+        };
+        if background.is_nan() && dataset.metadata.pixel_type != PixelType::F32 {
+            return Err(OpsError::InvalidParams(
+                "a NaN `background` requires 32-bit data".to_string(),
+            ));
+        }
+
+        let mask_values = mask.data.iter().copied().collect::<Vec<_>>();
+        let mut data = dataset.data.clone();
+        for (value, index) in data.iter_mut().zip(broadcast(dataset, mask, "mask")?) {
+            if mask_values[index] <= 0.5 {
+                *value = background;
+            }
+        }
+        Ok(OpOutput::dataset_only(Dataset::new(
+            data,
+            dataset.metadata.clone(),
+        )?))
+    }
+}
+
+/// Row-major index into `other` for every pixel of `dataset`, in the order of
+/// `dataset.data.iter()`. Axes are matched by kind; an axis of `other` that is
+/// missing from `dataset` must have size 1, and an axis of size 1 in `other`
+/// is repeated along `dataset` (e.g. one plane applied to a whole stack).
+pub(super) fn broadcast(
+    dataset: &DatasetF32,
+    other: &DatasetF32,
+    name: &str,
+) -> Result<Vec<usize>> {
+    let shape = dataset.shape();
+    let other_shape = other.shape();
+    let mut strides = vec![1_usize; other_shape.len()];
+    for axis in (0..other_shape.len().saturating_sub(1)).rev() {
+        strides[axis] = strides[axis + 1] * other_shape[axis + 1];
+    }
+
+    // For each axis of `dataset`, the stride it advances in `other` (0 when broadcast).
+    let mut steps = vec![0_usize; shape.len()];
+    for (other_axis, dim) in other.metadata.dims.iter().enumerate() {
+        let matching = dataset.axis_index(dim.axis);
+        match matching {
+            Some(axis) if shape[axis] == other_shape[other_axis] => {
+                steps[axis] = strides[other_axis];
+            }
+            _ if other_shape[other_axis] == 1 => {}
+            Some(axis) => {
+                return Err(OpsError::UnsupportedLayout(format!(
+                    "input `{name}` has {} samples along {:?} but the image has {}",
+                    other_shape[other_axis], dim.axis, shape[axis]
+                )));
+            }
+            None => {
+                return Err(OpsError::UnsupportedLayout(format!(
+                    "input `{name}` has a {:?} axis that the image lacks",
+                    dim.axis
+                )));
+            }
+        }
+    }
+
+    let mut indices = Vec::with_capacity(dataset.data.len());
+    iterate_indices(shape, |coord| {
+        indices.push(
+            coord
+                .iter()
+                .zip(&steps)
+                .map(|(index, step)| index * step)
+                .sum(),
+        );
+    });
+    Ok(indices)
+}
+
+/// `other` resampled onto the shape of `dataset` with [`broadcast`].
+pub(super) fn broadcast_values(
+    dataset: &DatasetF32,
+    other: &DatasetF32,
+    name: &str,
+) -> Result<ArrayD<f32>> {
+    let values = other.data.iter().copied().collect::<Vec<_>>();
+    let data = broadcast(dataset, other, name)?
+        .into_iter()
+        .map(|index| values[index])
+        .collect::<Vec<_>>();
+    Ok(ArrayD::from_shape_vec(IxDyn(dataset.shape()), data).expect("one value per pixel"))
+}
+
+fn pixel_type_max(pixel_type: PixelType) -> f32 {
+    match pixel_type {
+        PixelType::U8 => 255.0,
+        PixelType::U16 => 65_535.0,
+        PixelType::F32 => f32::INFINITY,
+    }
+}
+
+fn iterate_indices(shape: &[usize], mut callback: impl FnMut(&[usize])) {
+    if shape.is_empty() {
+        callback(&[]);
+        return;
+    }
+    if shape.contains(&0) {
+        return;
+    }
+    let mut index = vec![0usize; shape.len()];
+    loop {
+        callback(&index);
+        let mut dim = shape.len();
+        while dim > 0 {
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+            if dim == 0 {
+                return;
+            }
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::VecDeque;
 
 use crate::model::{Dataset, DatasetF32};
-use ndarray::{Array, ArrayD, Dimension, IxDyn};
+use ndarray::{ArrayD, Dimension, IxDyn};
 use serde_json::{Value, json};
 
 use super::{
@@ -17,6 +17,10 @@ impl Operation for ComponentsLabelOp {
         "components.label"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -76,10 +80,7 @@ impl Operation for ComponentsLabelOp {
             next_label += 1;
         }
 
-        let labeled_values = labels.iter().map(|value| *value as f32).collect::<Vec<_>>();
-        let labeled_array = Array::from_shape_vec(IxDyn(dataset.shape()), labeled_values)
-            .expect("shape is unchanged and valid");
-        let output_dataset = Dataset::new(labeled_array, dataset.metadata.clone())?;
+        let output_dataset = Dataset::new(labels, dataset.metadata.clone())?.to_label_dataset()?;
 
         let mut measurements = MeasurementTable::default();
         measurements.values.insert(
@@ -0,0 +1,838 @@
+use crate::model::{AxisKind, Dataset, DatasetF32, PixelType};
+use ndarray::{Array, IxDyn};
+use rayon::prelude::*;
+use serde_json::{Value, json};
+
+use super::{
+    INPUT_PARAM_KIND, MeasurementTable, OpInputs, OpOutput, OpSchema, Operation, OpsError,
+    ParamSpec, Result,
+    calculator::broadcast,
+    get_optional_bool, get_optional_f32, get_optional_usize,
+    intensity::{pixel_type_max, reject_label_input},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct IntensityClaheOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct IntensityHistogramMatchOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct IntensityBleachCorrectionOp;
+
+fn param(name: &str, description: &str, kind: &str) -> ParamSpec {
+    ParamSpec {
+        name: name.to_string(),
+        description: description.to_string(),
+        required: false,
+        kind: kind.to_string(),
+    }
+}
+
+impl Operation for IntensityClaheOp {
+    fn name(&self) -> &'static str {
+        "intensity.clahe"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Contrast-limited adaptive histogram equalization (CLAHE) per X/Y plane or per volume."
+                    .to_string(),
+            params: vec![
+                param(
+                    "block_size",
+                    "Edge length in pixels of the contextual regions (default 127).",
+                    "int",
+                ),
+                param(
+                    "bins",
+                    "Histogram bins per region (default 256).",
+                    "int",
+                ),
+                param(
+                    "slope",
+                    "Maximum slope of the mapping; 1 leaves the image unchanged (default 3).",
+                    "float",
+                ),
+                param(
+                    "mask",
+                    "Optional mask image; only pixels with value > 0.5 are counted and remapped.",
+                    INPUT_PARAM_KIND,
+                ),
+                param(
+                    "stack_3d",
+                    "Use cubic regions over X/Y/Z instead of equalizing each plane (default false).",
+                    "bool",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        reject_label_input(self.name(), dataset)?;
+        let block_size = get_optional_usize(params, "block_size", 127);
+        let bins = get_optional_usize(params, "bins", 256);
+        let slope = get_optional_f32(params, "slope", 3.0);
+        if block_size < 2 {
+            return Err(OpsError::InvalidParams(
+                "`block_size` must be >= 2".to_string(),
+            ));
+        }
+        if !(2..=65_536).contains(&bins) {
+            return Err(OpsError::InvalidParams(
+                "`bins` must be between 2 and 65536".to_string(),
+            ));
+        }
+        if !slope.is_finite() || slope < 1.0 {
+            return Err(OpsError::InvalidParams("`slope` must be >= 1".to_string()));
+        }
+
+        let x_axis = axis_index(dataset, AxisKind::X)?;
+        let y_axis = axis_index(dataset, AxisKind::Y)?;
+        let z_axis = if get_optional_bool(params, "stack_3d", false) {
+            Some(axis_index(dataset, AxisKind::Z)?)
+        } else {
+            None
+        };
+        let shape = dataset.shape().to_vec();
+        let size = [
+            shape[x_axis],
+            shape[y_axis],
+            z_axis.map_or(1, |axis| shape[axis]),
+        ];
+        let inner = z_axis
+            .into_iter()
+            .chain([y_axis, x_axis])
+            .collect::<Vec<_>>();
+
+        let values = dataset.data.iter().copied().collect::<Vec<_>>();
+        let mask = inputs
+            .get("mask")
+            .map(|mask| {
+                let mask_values = mask.data.iter().copied().collect::<Vec<_>>();
+                broadcast(dataset, mask, "mask").map(|indices| {
+                    indices
+                        .into_iter()
+                        .map(|index| mask_values[index] > 0.5)
+                        .collect::<Vec<_>>()
+                })
+            })
+            .transpose()?;
+
+        let tiles = Tiles::new(size, block_size);
+        let pixel_type = dataset.metadata.pixel_type;
+        let volumes = groups(&shape, &inner);
+        let equalized = volumes
+            .par_iter()
+            .map(|(_, indices)| {
+                let selected = |position: usize| {
+                    let index = indices[position];
+                    values[index].is_finite() && mask.as_ref().is_none_or(|mask| mask[index])
+                };
+                let (min, max) = (0..indices.len())
+                    .filter(|position| selected(*position))
+                    .map(|position| values[indices[position]])
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+                        (min.min(value), max.max(value))
+                    });
+                if max <= min {
+                    return Vec::new();
+                }
+                let bin_of = |value: f32| {
+                    (((value - min) / (max - min)) * (bins - 1) as f32).round() as usize
+                };
+
+                let mut histograms = vec![vec![0.0_f32; bins]; tiles.len()];
+                for position in (0..indices.len()).filter(|position| selected(*position)) {
+                    let tile = tiles.tile_of(tiles.coordinate(position));
+                    histograms[tile][bin_of(values[indices[position]])] += 1.0;
+                }
+                let mappings = histograms
+                    .into_iter()
+                    .map(|histogram| clipped_mapping(histogram, slope))
+                    .collect::<Vec<_>>();
+
+                (0..indices.len())
+                    .filter(|position| selected(*position))
+                    .map(|position| {
+                        let bin = bin_of(values[indices[position]]);
+                        let level = tiles
+                            .neighbours(tiles.coordinate(position))
+                            .into_iter()
+                            .map(|(tile, weight)| weight * mappings[tile][bin])
+                            .sum::<f32>();
+                        (
+                            indices[position],
+                            store(min + level * (max - min), pixel_type),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut output = values;
+        for (index, value) in equalized.into_iter().flatten() {
+            output[index] = value;
+        }
+        dataset_output(dataset, output, None)
+    }
+}
+
+impl Operation for IntensityHistogramMatchOp {
+    fn name(&self) -> &'static str {
+        "intensity.histogram_match"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Remap intensities so their distribution matches a reference image or slice."
+                    .to_string(),
+            params: vec![
+                param(
+                    "reference",
+                    "Optional reference image. When it has as many channels as the image, channels are matched pairwise.",
+                    INPUT_PARAM_KIND,
+                ),
+                param(
+                    "axis",
+                    "t or z: match every slice along this axis separately. Required without `reference`, where it defaults to T (or Z).",
+                    "string",
+                ),
+                param(
+                    "reference_index",
+                    "Slice along `axis` used as the reference when no `reference` image is given (default 0).",
+                    "int",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        reject_label_input(self.name(), dataset)?;
+        let reference = inputs.get("reference");
+        let axis = match (reference, params.get("axis")) {
+            (Some(_), None) => None,
+            _ => Some(stack_axis(dataset, params)?),
+        };
+        let channel_axis = dataset.axis_index(AxisKind::Channel);
+        let shape = dataset.shape().to_vec();
+        let inner = (0..shape.len())
+            .filter(|index| Some(*index) != axis && Some(*index) != channel_axis)
+            .collect::<Vec<_>>();
+        let values = dataset.data.iter().copied().collect::<Vec<_>>();
+        let slices = groups(&shape, &inner);
+        let channel_of = |base: &[usize]| channel_axis.map_or(0, |axis| base[axis]);
+
+        let references = match reference {
+            Some(reference) => {
+                let reference_values = reference.data.iter().copied().collect::<Vec<_>>();
+                match reference.axis_index(AxisKind::Channel) {
+                    Some(reference_channel)
+                        if reference.shape()[reference_channel]
+                            == dataset.metadata.channel_count()
+                            && channel_axis.is_some() =>
+                    {
+                        let reference_inner = (0..reference.shape().len())
+                            .filter(|index| *index != reference_channel)
+                            .collect::<Vec<_>>();
+                        groups(reference.shape(), &reference_inner)
+                            .into_iter()
+                            .map(|(_, indices)| {
+                                sorted_finite(indices.iter().map(|index| reference_values[*index]))
+                            })
+                            .collect::<Vec<_>>()
+                    }
+                    _ => vec![sorted_finite(reference_values.iter().copied())],
+                }
+            }
+            None => {
+                let axis = axis.expect("an axis is required without a reference image");
+                let index = get_optional_usize(params, "reference_index", 0);
+                if index >= shape[axis] {
+                    return Err(OpsError::InvalidParams(format!(
+                        "`reference_index` {index} is outside 0..{}",
+                        shape[axis].saturating_sub(1)
+                    )));
+                }
+                let mut references = Vec::new();
+                for (base, indices) in &slices {
+                    if base[axis] == index {
+                        references.push(sorted_finite(indices.iter().map(|index| values[*index])));
+                    }
+                }
+                references
+            }
+        };
+
+        let pixel_type = dataset.metadata.pixel_type;
+        let matched = slices
+            .par_iter()
+            .map(|(base, indices)| {
+                let reference = &references[channel_of(base).min(references.len() - 1)];
+                let source = indices
+                    .iter()
+                    .map(|index| values[*index])
+                    .collect::<Vec<_>>();
+                match_histogram(&source, reference)
+                    .into_iter()
+                    .map(|value| store(value, pixel_type))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut output = values.clone();
+        for ((_, indices), matched) in slices.iter().zip(matched) {
+            for (index, value) in indices.iter().zip(matched) {
+                output[*index] = value;
+            }
+        }
+        dataset_output(dataset, output, None)
+    }
+}
+
+impl Operation for IntensityBleachCorrectionOp {
+    fn name(&self) -> &'static str {
+        "intensity.bleach_correction"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Compensate photobleaching along a time-lapse, per channel.".to_string(),
+            params: vec![
+                param(
+                    "method",
+                    "simple_ratio (default) scales each frame to the first frame's mean; exponential divides by a fitted a*exp(-b*t)+c decay; histogram_match matches every frame to the first.",
+                    "string",
+                ),
+                param("axis", "t or z (default T, falling back to Z).", "string"),
+                param(
+                    "background",
+                    "Constant background subtracted before scaling and restored afterwards (default 0; ratio and exponential only).",
+                    "float",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        reject_label_input(self.name(), dataset)?;
+        let method = match params.get("method").and_then(Value::as_str) {
+            None => BleachMethod::SimpleRatio,
+            Some(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "simple_ratio" | "ratio" => BleachMethod::SimpleRatio,
+                "exponential" | "exponential_fit" => BleachMethod::Exponential,
+                "histogram_match" | "histogram" => BleachMethod::HistogramMatch,
+                other => {
+                    return Err(OpsError::InvalidParams(format!(
+                        "unsupported `method` `{other}`; expected simple_ratio, exponential or histogram_match"
+                    )));
+                }
+            },
+        };
+        let background = get_optional_f32(params, "background", 0.0);
+        if !background.is_finite() {
+            return Err(OpsError::InvalidParams(
+                "`background` must be finite".to_string(),
+            ));
+        }
+        let axis = stack_axis(dataset, params)?;
+        let channel_axis = dataset.axis_index(AxisKind::Channel);
+        let shape = dataset.shape().to_vec();
+        let inner = (0..shape.len())
+            .filter(|index| *index != axis && Some(*index) != channel_axis)
+            .collect::<Vec<_>>();
+        let values = dataset.data.iter().copied().collect::<Vec<_>>();
+        let pixel_type = dataset.metadata.pixel_type;
+        let code = dataset.metadata.dims[axis]
+            .axis
+            .code()
+            .to_ascii_lowercase()
+            .to_string();
+
+        // Groups are row-major over the outer axes, so each channel's frames
+        // arrive in axis order whichever of the two axes comes first.
+        let channel_count = channel_axis.map_or(1, |axis| shape[axis]);
+        let mut frames = vec![Vec::new(); channel_count];
+        for (base, indices) in groups(&shape, &inner) {
+            frames[channel_axis.map_or(0, |axis| base[axis])].push(indices);
+        }
+
+        let mut output = values.clone();
+        let mut rows = Vec::new();
+        let mut fits = Vec::new();
+        for (channel, frames) in frames.iter().enumerate() {
+            let means = frames
+                .iter()
+                .map(|indices| finite_mean(indices.iter().map(|index| values[*index])))
+                .collect::<Vec<_>>();
+            let factors = match method {
+                BleachMethod::SimpleRatio => {
+                    let first = means.first().copied().flatten();
+                    means
+                        .iter()
+                        .map(|mean| match (first, mean) {
+                            (Some(first), Some(mean)) if *mean - background > 0.0 => {
+                                (first - background) / (mean - background)
+                            }
+                            _ => 1.0,
+                        })
+                        .collect::<Vec<_>>()
+                }
+                BleachMethod::Exponential => {
+                    let samples = means
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(frame, mean)| {
+                            mean.map(|mean| (frame as f64, f64::from(mean - background)))
+                        })
+                        .collect::<Vec<_>>();
+                    let fit = ExponentialFit::fit(&samples).ok_or_else(|| {
+                        OpsError::UnsupportedLayout(
+                            "an exponential fit needs at least three frames".to_string(),
+                        )
+                    })?;
+                    fits.push(json!({"channel": channel, "a": fit.a, "b": fit.b, "c": fit.c}));
+                    let start = fit.value(0.0);
+                    (0..frames.len())
+                        .map(|frame| {
+                            let current = fit.value(frame as f64);
+                            if start > 0.0 && current > 0.0 {
+                                (start / current) as f32
+                            } else {
+                                1.0
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                }
+                BleachMethod::HistogramMatch => vec![1.0; frames.len()],
+            };
+
+            let reference = frames
+                .first()
+                .map(|indices| sorted_finite(indices.iter().map(|index| values[*index])))
+                .unwrap_or_default();
+            for (frame, indices) in frames.iter().enumerate() {
+                let source = indices
+                    .iter()
+                    .map(|index| values[*index])
+                    .collect::<Vec<_>>();
+                let corrected = match method {
+                    BleachMethod::HistogramMatch => match_histogram(&source, &reference),
+                    _ => source
+                        .iter()
+                        .map(|value| (value - background) * factors[frame] + background)
+                        .collect(),
+                };
+                for (index, value) in indices.iter().zip(&corrected) {
+                    output[*index] = store(*value, pixel_type);
+                }
+                let mut row = json!({
+                    "channel": channel,
+                    code.as_str(): frame,
+                    "mean": means[frame],
+                    "corrected_mean": finite_mean(indices.iter().map(|index| output[*index])),
+                });
+                if method != BleachMethod::HistogramMatch {
+                    row["factor"] = json!(factors[frame]);
+                }
+                rows.push(row);
+            }
+        }
+
+        let mut measurements = MeasurementTable::default();
+        measurements
+            .values
+            .insert("method".to_string(), json!(method.name()));
+        measurements
+            .values
+            .insert("count".to_string(), json!(rows.len()));
+        measurements.values.insert("rows".to_string(), json!(rows));
+        if method == BleachMethod::Exponential {
+            measurements.values.insert("fits".to_string(), json!(fits));
+        }
+        dataset_output(dataset, output, Some(measurements))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BleachMethod {
+    SimpleRatio,
+    Exponential,
+    HistogramMatch,
+}
+
+impl BleachMethod {
+    fn name(self) -> &'static str {
+        match self {
+            Self::SimpleRatio => "simple_ratio",
+            Self::Exponential => "exponential",
+            Self::HistogramMatch => "histogram_match",
+        }
+    }
+}
+
+/// Least-squares fit of `a * exp(-b * t) + c`: `a` and `c` are linear for a
+/// fixed rate, so the rate is found by a log-spaced scan refined with a
+/// golden-section search.
+#[derive(Debug, Clone, Copy)]
+struct ExponentialFit {
+    a: f64,
+    b: f64,
+    c: f64,
+}
+
+impl ExponentialFit {
+    fn fit(samples: &[(f64, f64)]) -> Option<Self> {
+        if samples.len() < 3 {
+            return None;
+        }
+        let span = samples.last()?.0 - samples.first()?.0;
+        if span <= 0.0 {
+            return None;
+        }
+        let (low, high) = ((1e-4 / span).ln(), (20.0 / span).ln());
+        let steps = 200;
+        let rate = |step: usize| (low + (high - low) * step as f64 / steps as f64).exp();
+        let best = (0..=steps)
+            .min_by(|left, right| {
+                Self::with_rate(samples, rate(*left))
+                    .1
+                    .total_cmp(&Self::with_rate(samples, rate(*right)).1)
+            })
+            .expect("the scan is non-empty");
+
+        let (mut left, mut right) = (
+            rate(best.saturating_sub(1)).ln(),
+            rate((best + 1).min(steps)).ln(),
+        );
+        let ratio = (5.0_f64.sqrt() - 1.0) / 2.0;
+        for _ in 0..60 {
+            let first = right - ratio * (right - left);
+            let second = left + ratio * (right - left);
+            if Self::with_rate(samples, first.exp()).1 < Self::with_rate(samples, second.exp()).1 {
+                right = second;
+            } else {
+                left = first;
+            }
+        }
+        Some(Self::with_rate(samples, ((left + right) / 2.0).exp()).0)
+    }
+
+    /// Best `a` and `c` for rate `b`, with the residual sum of squares.
+    fn with_rate(samples: &[(f64, f64)], b: f64) -> (Self, f64) {
+        let n = samples.len() as f64;
+        let (mut se, mut see, mut sy, mut sey) = (0.0, 0.0, 0.0, 0.0);
+        for (t, y) in samples {
+            let e = (-b * t).exp();
+            se += e;
+            see += e * e;
+            sy += y;
+            sey += e * y;
+        }
+        let determinant = see * n - se * se;
+        let (a, c) = if determinant.abs() < 1e-12 {
+            (0.0, sy / n)
+        } else {
+            (
+                (sey * n - se * sy) / determinant,
+                (see * sy - se * sey) / determinant,
+            )
+        };
+        let fit = Self { a, b, c };
+        let residual = samples
+            .iter()
+            .map(|(t, y)| (fit.value(*t) - y).powi(2))
+            .sum();
+        (fit, residual)
+    }
+
+    fn value(&self, t: f64) -> f64 {
+        self.a * (-self.b * t).exp() + self.c
+    }
+}
+
+/// Contextual regions of a plane or volume, with bilinear (trilinear in 3D)
+/// interpolation weights between the mappings of neighbouring region centres.
+struct Tiles {
+    size: [usize; 3],
+    counts: [usize; 3],
+}
+
+impl Tiles {
+    fn new(size: [usize; 3], block_size: usize) -> Self {
+        Self {
+            size,
+            counts: size.map(|length| length.div_ceil(block_size).max(1)),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.counts.iter().product()
+    }
+
+    fn coordinate(&self, position: usize) -> [usize; 3] {
+        let [width, height, _] = self.size;
+        [
+            position % width,
+            (position / width) % height,
+            position / (width * height),
+        ]
+    }
+
+    fn tile_of(&self, coordinate: [usize; 3]) -> usize {
+        let tile = [0, 1, 2].map(|axis| coordinate[axis] * self.counts[axis] / self.size[axis]);
+        (tile[2] * self.counts[1] + tile[1]) * self.counts[0] + tile[0]
+    }
+
+    fn neighbours(&self, coordinate: [usize; 3]) -> Vec<(usize, f32)> {
+        let spans = [0, 1, 2].map(|axis| {
+            let count = self.counts[axis];
+            let position =
+                ((coordinate[axis] as f32 + 0.5) * count as f32 / self.size[axis] as f32 - 0.5)
+                    .clamp(0.0, (count - 1) as f32);
+            let lower = position.floor() as usize;
+            (lower, (lower + 1).min(count - 1), position - lower as f32)
+        });
+        let mut neighbours = Vec::with_capacity(8);
+        for corner in 0..8 {
+            let mut tile = [0; 3];
+            let mut weight = 1.0;
+            for (axis, (lower, upper, fraction)) in spans.iter().enumerate() {
+                if corner >> axis & 1 == 1 {
+                    tile[axis] = *upper;
+                    weight *= fraction;
+                } else {
+                    tile[axis] = *lower;
+                    weight *= 1.0 - fraction;
+                }
+            }
+            if weight > 0.0 {
+                neighbours.push((
+                    (tile[2] * self.counts[1] + tile[1]) * self.counts[0] + tile[0],
+                    weight,
+                ));
+            }
+        }
+        neighbours
+    }
+}
+
+/// Clips `histogram` at `slope` times its mean height, redistributing the
+/// excess evenly, and returns the normalized cumulative mapping.
+fn clipped_mapping(mut histogram: Vec<f32>, slope: f32) -> Vec<f32> {
+    let bins = histogram.len() as f32;
+    let total = histogram.iter().sum::<f32>();
+    if total <= 0.0 {
+        return (0..histogram.len())
+            .map(|bin| bin as f32 / (bins - 1.0))
+            .collect();
+    }
+    let limit = (slope * total / bins).max(1.0);
+    for _ in 0..16 {
+        let excess = histogram
+            .iter_mut()
+            .map(|count| {
+                let clipped = (*count - limit).max(0.0);
+                *count -= clipped;
+                clipped
+            })
+            .sum::<f32>();
+        if excess < 1e-3 {
+            break;
+        }
+        let share = excess / bins;
+        histogram.iter_mut().for_each(|count| *count += share);
+    }
+    let first = histogram[0];
+    let total = histogram.iter().sum::<f32>();
+    let mut cumulative = 0.0;
+    histogram
+        .into_iter()
+        .map(|count| {
+            cumulative += count;
+            if total > first {
+                (cumulative - first) / (total - first)
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Maps every finite value to the reference quantile of its mid-rank; ties
+/// share one output value.
+fn match_histogram(values: &[f32], reference: &[f32]) -> Vec<f32> {
+    let mut output = values.to_vec();
+    if reference.is_empty() {
+        return output;
+    }
+    let mut order = (0..values.len())
+        .filter(|index| values[*index].is_finite())
+        .collect::<Vec<_>>();
+    order.sort_by(|left, right| values[*left].total_cmp(&values[*right]));
+    let count = order.len() as f64;
+    let last = (reference.len() - 1) as f64;
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let quantile = ((start + end - 1) as f64 / 2.0 + 0.5) / count;
+        let position = (quantile * reference.len() as f64 - 0.5).clamp(0.0, last);
+        let lower = position.floor() as usize;
+        let upper = (lower + 1).min(reference.len() - 1);
+        let fraction = (position - lower as f64) as f32;
+        let value = reference[lower] + (reference[upper] - reference[lower]) * fraction;
+        for index in &order[start..end] {
+            output[*index] = value;
+        }
+        start = end;
+    }
+    output
+}
+
+fn sorted_finite(values: impl Iterator<Item = f32>) -> Vec<f32> {
+    let mut values = values.filter(|value| value.is_finite()).collect::<Vec<_>>();
+    values.sort_by(f32::total_cmp);
+    values
+}
+
+fn finite_mean(values: impl Iterator<Item = f32>) -> Option<f32> {
+    let (sum, count) = values
+        .filter(|value| value.is_finite())
+        .fold((0.0_f64, 0_usize), |(sum, count), value| {
+            (sum + f64::from(value), count + 1)
+        });
+    (count > 0).then(|| (sum / count as f64) as f32)
+}
+
+/// Rounds and clamps to the integer range of `pixel_type`.
+fn store(value: f32, pixel_type: PixelType) -> f32 {
+    match pixel_type {
+        PixelType::F32 => value,
+        _ if value.is_finite() => value.round().clamp(0.0, pixel_type_max(pixel_type)),
+        _ => 0.0,
+    }
+}
+
+fn dataset_output(
+    dataset: &DatasetF32,
+    values: Vec<f32>,
+    measurements: Option<MeasurementTable>,
+) -> Result<OpOutput> {
+    let data = Array::from_shape_vec(IxDyn(dataset.shape()), values).expect("shape is unchanged");
+    Ok(OpOutput {
+        dataset: Dataset::new(data, dataset.metadata.clone())?,
+        measurements,
+    })
+}
+
+fn stack_axis(dataset: &DatasetF32, params: &Value) -> Result<usize> {
+    match params.get("axis").and_then(Value::as_str) {
+        Some(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "t" | "time" => axis_index(dataset, AxisKind::Time),
+            "z" => axis_index(dataset, AxisKind::Z),
+            other => Err(OpsError::InvalidParams(format!(
+                "unsupported `axis` `{other}`; expected t or z"
+            ))),
+        },
+        None => dataset
+            .axis_index(AxisKind::Time)
+            .filter(|axis| dataset.shape()[*axis] > 1)
+            .or_else(|| dataset.axis_index(AxisKind::Z))
+            .ok_or_else(|| OpsError::UnsupportedLayout("dataset has no T or Z axis".to_string())),
+    }
+}
+
+/// Row-major flat indices of `dataset.data`, grouped by every coordinate of
+/// the axes not in `inner`. Within a group, indices are row-major over
+/// `inner` in the order given.
+fn groups(shape: &[usize], inner: &[usize]) -> Vec<(Vec<usize>, Vec<usize>)> {
+    let mut strides = vec![1_usize; shape.len()];
+    for axis in (0..shape.len().saturating_sub(1)).rev() {
+        strides[axis] = strides[axis + 1] * shape[axis + 1];
+    }
+    let mut outer_shape = shape.to_vec();
+    for axis in inner {
+        outer_shape[*axis] = 1;
+    }
+    let inner_shape = inner.iter().map(|axis| shape[*axis]).collect::<Vec<_>>();
+
+    let mut groups = Vec::new();
+    iterate_indices(&outer_shape, |base| {
+        let offset = base
+            .iter()
+            .zip(&strides)
+            .map(|(index, stride)| index * stride)
+            .sum::<usize>();
+        let mut indices = Vec::new();
+        iterate_indices(&inner_shape, |coordinate| {
+            indices.push(
+                offset
+                    + coordinate
+                        .iter()
+                        .zip(inner)
+                        .map(|(index, axis)| index * strides[*axis])
+                        .sum::<usize>(),
+            );
+        });
+        groups.push((base.to_vec(), indices));
+    });
+    groups
+}
+
+fn axis_index(dataset: &DatasetF32, axis: AxisKind) -> Result<usize> {
+    dataset
+        .axis_index(axis)
+        .ok_or_else(|| OpsError::UnsupportedLayout(format!("missing {axis:?} axis")))
+}
+
+fn iterate_indices(shape: &[usize], mut callback: impl FnMut(&[usize])) {
+    if shape.is_empty() {
+        callback(&[]);
+        return;
+    }
+    if shape.contains(&0) {
+        return;
+    }
+    let mut index = vec![0usize; shape.len()];
+    loop {
+        callback(&index);
+        let mut dim = shape.len();
+        while dim > 0 {
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+            if dim == 0 {
+                return;
+            }
+        }
+    }
+}
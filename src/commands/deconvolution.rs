@@ -0,0 +1,646 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::model::{AxisKind, Dataset, DatasetF32, Dim, Metadata, PixelType};
+use ndarray::{ArrayD, IxDyn};
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use serde_json::Value;
+
+use super::{
+    INPUT_PARAM_KIND, OpInputs, OpOutput, OpSchema, Operation, OpsError, ParamSpec, Result,
+    get_optional_bool, get_optional_f32, get_optional_usize, get_required_f32,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageDeconvolveRichardsonLucyOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImagePsfGenerateOp;
+
+fn param(name: &str, description: &str, kind: &str) -> ParamSpec {
+    ParamSpec {
+        name: name.to_string(),
+        description: description.to_string(),
+        required: false,
+        kind: kind.to_string(),
+    }
+}
+
+impl Operation for ImageDeconvolveRichardsonLucyOp {
+    fn name(&self) -> &'static str {
+        "image.deconvolve.richardson_lucy"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Richardson-Lucy deconvolution of each X/Y plane or X/Y/Z volume with a PSF image (periodic boundaries)."
+                .to_string(),
+            params: vec![
+                ParamSpec {
+                    required: true,
+                    ..param(
+                        "psf",
+                        "PSF with X/Y (and optionally Z) axes no larger than the image; its centre pixel is the origin.",
+                        INPUT_PARAM_KIND,
+                    )
+                },
+                param("iterations", "Number of iterations (default 10).", "int"),
+                param(
+                    "tv_regularization",
+                    "Total-variation weight (default 0, off; around 0.002 suppresses noise amplification).",
+                    "float",
+                ),
+                param(
+                    "stack_3d",
+                    "Deconvolve X/Y/Z volumes when the image has a Z axis (default true); false works per X/Y plane.",
+                    "bool",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        let psf = inputs.require("psf")?;
+        let iterations = get_optional_usize(params, "iterations", 10);
+        let tv_weight = get_optional_f32(params, "tv_regularization", 0.0);
+        if !tv_weight.is_finite() || !(0.0..0.5).contains(&tv_weight) {
+            return Err(OpsError::InvalidParams(
+                "`tv_regularization` must be in 0..0.5".to_string(),
+            ));
+        }
+
+        let mut axes = vec![
+            axis_index(dataset, AxisKind::X)?,
+            axis_index(dataset, AxisKind::Y)?,
+        ];
+        if get_optional_bool(params, "stack_3d", true)
+            && let Some(z_axis) = dataset.axis_index(AxisKind::Z)
+        {
+            axes.push(z_axis);
+        }
+        // Volumes are row-major over (z, y, x); a 2D image has depth 1.
+        let volume_shape = [
+            axes.get(2).map_or(1, |axis| dataset.shape()[*axis]),
+            dataset.shape()[axes[1]],
+            dataset.shape()[axes[0]],
+        ];
+        let fft = VolumeFft::new(volume_shape);
+        let transfer = psf_transfer(psf, volume_shape, &fft)?;
+
+        let mut output = dataset.data.clone();
+        let mut outer_shape = dataset.shape().to_vec();
+        for axis in &axes {
+            outer_shape[*axis] = 1;
+        }
+        let mut coord = vec![0; outer_shape.len()];
+        iterate_indices(&outer_shape, |base| {
+            coord.copy_from_slice(base);
+            let mut observed = Vec::with_capacity(fft.len());
+            for_each_voxel(&volume_shape, |[z, y, x]| {
+                set_volume_coord(&mut coord, &axes, z, y, x);
+                observed.push(dataset.data[IxDyn(&coord)].max(0.0));
+            });
+            let restored = richardson_lucy(&observed, &transfer, &fft, iterations, tv_weight);
+            let mut values = restored.into_iter();
+            for_each_voxel(&volume_shape, |[z, y, x]| {
+                set_volume_coord(&mut coord, &axes, z, y, x);
+                output[IxDyn(&coord)] = values.next().expect("one value per voxel");
+            });
+        });
+
+        let mut metadata = dataset.metadata.clone();
+        metadata.pixel_type = PixelType::F32;
+        Ok(OpOutput::dataset_only(Dataset::new(output, metadata)?))
+    }
+}
+
+impl Operation for ImagePsfGenerateOp {
+    fn name(&self) -> &'static str {
+        "image.psf.generate"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Generate a unit-sum widefield PSF using the input image's X/Y/Z spacing."
+                .to_string(),
+            params: vec![
+                param(
+                    "model",
+                    "gaussian or born_wolf (default born_wolf).",
+                    "string",
+                ),
+                ParamSpec {
+                    required: true,
+                    ..param("na", "Numerical aperture of the objective.", "float")
+                },
+                ParamSpec {
+                    required: true,
+                    ..param("wavelength", "Emission wavelength in nm.", "float")
+                },
+                param(
+                    "refractive_index",
+                    "Refractive index of the immersion medium (default 1.518).",
+                    "float",
+                ),
+                param(
+                    "pixel_size",
+                    "Lateral pixel size in µm (default: X spacing).",
+                    "float",
+                ),
+                param("z_step", "Axial step in µm (default: Z spacing).", "float"),
+                param(
+                    "size_xy",
+                    "Lateral size in pixels, made odd (default: four Airy radii each side).",
+                    "int",
+                ),
+                param(
+                    "size_z",
+                    "Number of Z planes (default: the image's Z size, or 1 without a Z axis).",
+                    "int",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let model = match params.get("model").and_then(Value::as_str) {
+            None => PsfModel::BornWolf,
+            Some(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "gaussian" => PsfModel::Gaussian,
+                "born_wolf" | "born-wolf" => PsfModel::BornWolf,
+                other => {
+                    return Err(OpsError::InvalidParams(format!(
+                        "unsupported `model` `{other}`; expected gaussian or born_wolf"
+                    )));
+                }
+            },
+        };
+        let na = f64::from(get_required_f32(params, "na")?);
+        let wavelength = f64::from(get_required_f32(params, "wavelength")?) / 1000.0;
+        let refractive_index = f64::from(get_optional_f32(params, "refractive_index", 1.518));
+        let valid = na > 0.0 && na < refractive_index && wavelength > 0.0;
+        if !valid {
+            return Err(OpsError::InvalidParams(
+                "`na` must be in (0, refractive_index) and `wavelength` positive".to_string(),
+            ));
+        }
+
+        let z_axis = dataset.axis_index(AxisKind::Z);
+        let pixel_size = spacing_um(dataset, params, "pixel_size", AxisKind::X)?;
+        let size_z = get_optional_usize(
+            params,
+            "size_z",
+            z_axis.map_or(1, |axis| dataset.shape()[axis]),
+        )
+        .max(1);
+        let z_step = if size_z > 1 {
+            spacing_um(dataset, params, "z_step", AxisKind::Z)?
+        } else {
+            1.0
+        };
+        let airy_radius = 0.61 * wavelength / na;
+        let size_xy = get_optional_usize(
+            params,
+            "size_xy",
+            2 * (4.0 * airy_radius / pixel_size).ceil() as usize + 1,
+        )
+        .max(1)
+            | 1;
+
+        let optics = Optics {
+            na,
+            wavelength,
+            refractive_index,
+        };
+        let (centre_xy, centre_z) = ((size_xy / 2) as f64, (size_z / 2) as f64);
+        let mut values = Vec::with_capacity(size_z * size_xy * size_xy);
+        for z in 0..size_z {
+            let defocus = (z as f64 - centre_z) * z_step;
+            for y in 0..size_xy {
+                for x in 0..size_xy {
+                    let radius = ((x as f64 - centre_xy).hypot(y as f64 - centre_xy)) * pixel_size;
+                    values.push(match model {
+                        PsfModel::Gaussian => optics.gaussian(radius, defocus),
+                        PsfModel::BornWolf => optics.born_wolf(radius, defocus),
+                    });
+                }
+            }
+        }
+        let total = values.iter().sum::<f64>();
+        let values = values
+            .into_iter()
+            .map(|value| (value / total) as f32)
+            .collect::<Vec<_>>();
+
+        let calibrated = |axis: AxisKind, size: usize, spacing: f64| Dim {
+            spacing: Some(spacing as f32),
+            unit: Some("µm".to_string()),
+            ..Dim::new(axis, size)
+        };
+        let mut dims = vec![
+            calibrated(AxisKind::Y, size_xy, pixel_size),
+            calibrated(AxisKind::X, size_xy, pixel_size),
+        ];
+        let mut shape = vec![size_xy, size_xy];
+        if size_z > 1 || z_axis.is_some() {
+            dims.insert(0, calibrated(AxisKind::Z, size_z, z_step));
+            shape.insert(0, size_z);
+        }
+        let data = ArrayD::from_shape_vec(IxDyn(&shape), values)
+            .map_err(|_| OpsError::UnsupportedLayout("failed to build PSF".to_string()))?;
+        let metadata = Metadata {
+            dims,
+            pixel_type: PixelType::F32,
+            source: dataset.metadata.source.clone(),
+            ..Metadata::default()
+        };
+        Ok(OpOutput::dataset_only(Dataset::new(data, metadata)?))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PsfModel {
+    Gaussian,
+    BornWolf,
+}
+
+/// Widefield imaging parameters, lengths in µm.
+struct Optics {
+    na: f64,
+    wavelength: f64,
+    refractive_index: f64,
+}
+
+impl Optics {
+    /// Gaussian approximation of the widefield PSF (Zhang et al., 2007).
+    fn gaussian(&self, radius: f64, defocus: f64) -> f64 {
+        let sigma_xy = 0.21 * self.wavelength / self.na;
+        let sigma_z = 0.66 * self.wavelength * self.refractive_index / (self.na * self.na);
+        (-radius * radius / (2.0 * sigma_xy * sigma_xy)
+            - defocus * defocus / (2.0 * sigma_z * sigma_z))
+            .exp()
+    }
+
+    /// Born & Wolf scalar diffraction model of a defocused aberration-free lens:
+    /// `|∫₀¹ J₀(k·NA·r·ρ) exp(-i·k·z·NA²·ρ²/(2n)) ρ dρ|²`.
+    fn born_wolf(&self, radius: f64, defocus: f64) -> f64 {
+        const STEPS: usize = 200;
+        let k = 2.0 * PI / self.wavelength;
+        let lateral = k * self.na * radius;
+        let axial = 0.5 * k * defocus * self.na * self.na / self.refractive_index;
+        // Composite Simpson's rule over ρ.
+        let (mut real, mut imaginary) = (0.0, 0.0);
+        for step in 0..=STEPS {
+            let rho = step as f64 / STEPS as f64;
+            let weight = match step {
+                0 => 1.0,
+                _ if step == STEPS => 1.0,
+                _ if step % 2 == 1 => 4.0,
+                _ => 2.0,
+            };
+            let amplitude = weight * bessel_j0(lateral * rho) * rho;
+            let phase = -axial * rho * rho;
+            real += amplitude * phase.cos();
+            imaginary += amplitude * phase.sin();
+        }
+        let scale = 1.0 / (3.0 * STEPS as f64);
+        (real * scale).powi(2) + (imaginary * scale).powi(2)
+    }
+}
+
+/// Bessel function of the first kind of order zero (rational and asymptotic
+/// approximations from Numerical Recipes).
+fn bessel_j0(x: f64) -> f64 {
+    let ax = x.abs();
+    if ax < 8.0 {
+        let y = x * x;
+        let numerator = 57_568_490_574.0
+            + y * (-13_362_590_354.0
+                + y * (651_619_640.7
+                    + y * (-11_214_424.18 + y * (77_392.330_17 + y * -184.905_245_6))));
+        let denominator = 57_568_490_411.0
+            + y * (1_029_532_985.0
+                + y * (9_494_680.718 + y * (59_272.648_53 + y * (267.853_271_2 + y))));
+        numerator / denominator
+    } else {
+        let z = 8.0 / ax;
+        let y = z * z;
+        let xx = ax - 0.785_398_164;
+        let p = 1.0
+            + y * (-0.109_862_862_7e-2
+                + y * (0.273_451_040_7e-4 + y * (-0.207_337_063_9e-5 + y * 0.209_388_721_1e-6)));
+        let q = -0.156_249_999_5e-1
+            + y * (0.143_048_876_5e-3
+                + y * (-0.691_114_765_1e-5 + y * (0.762_109_516_1e-6 - y * 0.934_935_152e-7)));
+        (std::f64::consts::FRAC_2_PI / ax).sqrt() * (xx.cos() * p - z * xx.sin() * q)
+    }
+}
+
+/// Spacing in µm along `axis`, or the `key` parameter when given.
+fn spacing_um(dataset: &DatasetF32, params: &Value, key: &str, axis: AxisKind) -> Result<f64> {
+    if params.get(key).is_some() {
+        let value = f64::from(get_required_f32(params, key)?);
+        return if value > 0.0 {
+            Ok(value)
+        } else {
+            Err(OpsError::InvalidParams(format!("`{key}` must be positive")))
+        };
+    }
+    let dim = dataset
+        .axis_index(axis)
+        .map(|index| &dataset.metadata.dims[index])
+        .filter(|dim| dim.spacing.is_some_and(|spacing| spacing > 0.0))
+        .ok_or_else(|| {
+            OpsError::InvalidParams(format!(
+                "the image has no {axis:?} spacing; set `{key}` in µm"
+            ))
+        })?;
+    let scale = match dim.unit.as_deref().map(str::trim) {
+        None | Some("um" | "µm" | "μm" | "micron" | "microns" | "micrometer" | "micrometre") => {
+            1.0
+        }
+        Some("nm") => 1e-3,
+        Some("mm") => 1e3,
+        Some(other) => {
+            return Err(OpsError::InvalidParams(format!(
+                "unsupported {axis:?} unit `{other}`; set `{key}` in µm"
+            )));
+        }
+    };
+    Ok(f64::from(dim.spacing.expect("spacing was checked")) * scale)
+}
+
+/// Richardson-Lucy iterations from the observed volume, optionally with the
+/// total-variation term of Dey et al. (2006).
+fn richardson_lucy(
+    observed: &[f32],
+    transfer: &[Complex<f32>],
+    fft: &VolumeFft,
+    iterations: usize,
+    tv_weight: f32,
+) -> Vec<f32> {
+    let convolve = |values: &[f32], adjoint: bool| {
+        let mut spectrum = values
+            .iter()
+            .map(|value| Complex::new(*value, 0.0))
+            .collect::<Vec<_>>();
+        fft.forward(&mut spectrum);
+        for (value, factor) in spectrum.iter_mut().zip(transfer) {
+            *value *= if adjoint { factor.conj() } else { *factor };
+        }
+        fft.inverse(&mut spectrum);
+        spectrum
+            .into_iter()
+            .map(|value| value.re)
+            .collect::<Vec<_>>()
+    };
+
+    let mut estimate = observed.to_vec();
+    for _ in 0..iterations {
+        let blurred = convolve(&estimate, false);
+        let ratio = observed
+            .iter()
+            .zip(&blurred)
+            .map(|(observed, blurred)| {
+                if *blurred > f32::EPSILON {
+                    observed / blurred
+                } else {
+                    0.0
+                }
+            })
+            .collect::<Vec<_>>();
+        let correction = convolve(&ratio, true);
+        let regularizer =
+            (tv_weight > 0.0).then(|| total_variation_divergence(&estimate, fft.shape));
+        for (index, value) in estimate.iter_mut().enumerate() {
+            let mut next = *value * correction[index].max(0.0);
+            if let Some(divergence) = &regularizer {
+                next /= (1.0 - tv_weight * divergence[index]).max(f32::EPSILON);
+            }
+            *value = next;
+        }
+    }
+    estimate
+}
+
+/// `div(∇u / |∇u|)` with forward differences for the gradient and backward
+/// differences for the divergence.
+fn total_variation_divergence(values: &[f32], shape: [usize; 3]) -> Vec<f32> {
+    let strides = [shape[1] * shape[2], shape[2], 1];
+    let mut normalized = vec![[0.0_f32; 3]; values.len()];
+    for_each_voxel(&shape, |coord| {
+        let index = coord[0] * strides[0] + coord[1] * strides[1] + coord[2];
+        let mut gradient = [0.0_f32; 3];
+        for axis in 0..3 {
+            if coord[axis] + 1 < shape[axis] {
+                gradient[axis] = values[index + strides[axis]] - values[index];
+            }
+        }
+        let norm = (gradient.iter().map(|value| value * value).sum::<f32>() + 1e-8).sqrt();
+        normalized[index] = gradient.map(|value| value / norm);
+    });
+    let mut divergence = vec![0.0_f32; values.len()];
+    for_each_voxel(&shape, |coord| {
+        let index = coord[0] * strides[0] + coord[1] * strides[1] + coord[2];
+        divergence[index] = (0..3)
+            .map(|axis| {
+                let previous = if coord[axis] > 0 {
+                    normalized[index - strides[axis]][axis]
+                } else {
+                    0.0
+                };
+                normalized[index][axis] - previous
+            })
+            .sum();
+    });
+    divergence
+}
+
+/// Spectrum of the unit-sum PSF placed with its centre voxel at the origin of
+/// a periodic volume of `shape`.
+fn psf_transfer(psf: &DatasetF32, shape: [usize; 3], fft: &VolumeFft) -> Result<Vec<Complex<f32>>> {
+    let x_axis = axis_index(psf, AxisKind::X)?;
+    let y_axis = axis_index(psf, AxisKind::Y)?;
+    let z_axis = psf.axis_index(AxisKind::Z);
+    let psf_shape = [
+        z_axis.map_or(1, |axis| psf.shape()[axis]),
+        psf.shape()[y_axis],
+        psf.shape()[x_axis],
+    ];
+    if psf.data.len() != psf_shape.iter().product::<usize>() {
+        return Err(OpsError::UnsupportedLayout(
+            "input `psf` must only have X, Y and Z axes".to_string(),
+        ));
+    }
+    if psf_shape[1] > shape[1] || psf_shape[2] > shape[2] {
+        return Err(OpsError::UnsupportedLayout(format!(
+            "PSF is {}x{} but the image is only {}x{}",
+            psf_shape[2], psf_shape[1], shape[2], shape[1]
+        )));
+    }
+    // A 2D image uses the PSF's central (in-focus) plane.
+    let planes = if shape[0] == 1 {
+        psf_shape[0] / 2..psf_shape[0] / 2 + 1
+    } else if psf_shape[0] <= shape[0] {
+        0..psf_shape[0]
+    } else {
+        return Err(OpsError::UnsupportedLayout(format!(
+            "PSF has {} Z planes but the image only {}",
+            psf_shape[0], shape[0]
+        )));
+    };
+    let centre = [planes.len() / 2, psf_shape[1] / 2, psf_shape[2] / 2];
+
+    let mut padded = vec![Complex::new(0.0_f32, 0.0_f32); fft.len()];
+    let mut coord = vec![0; psf.shape().len()];
+    let mut total = 0.0_f32;
+    for (pz, z) in planes.enumerate() {
+        for y in 0..psf_shape[1] {
+            for x in 0..psf_shape[2] {
+                if let Some(axis) = z_axis {
+                    coord[axis] = z;
+                }
+                coord[y_axis] = y;
+                coord[x_axis] = x;
+                let value = psf.data[IxDyn(&coord)];
+                let target = [
+                    (pz + shape[0] - centre[0]) % shape[0],
+                    (y + shape[1] - centre[1]) % shape[1],
+                    (x + shape[2] - centre[2]) % shape[2],
+                ];
+                padded[(target[0] * shape[1] + target[1]) * shape[2] + target[2]].re += value;
+                total += value;
+            }
+        }
+    }
+    if total <= f32::EPSILON {
+        return Err(OpsError::InvalidParams(
+            "input `psf` must have a positive sum".to_string(),
+        ));
+    }
+    padded.iter_mut().for_each(|value| *value /= total);
+    fft.forward(&mut padded);
+    Ok(padded)
+}
+
+/// Forward and normalized inverse FFT of row-major (z, y, x) volumes.
+struct VolumeFft {
+    shape: [usize; 3],
+    forward: Vec<Arc<dyn Fft<f32>>>,
+    inverse: Vec<Arc<dyn Fft<f32>>>,
+}
+
+impl VolumeFft {
+    fn new(shape: [usize; 3]) -> Self {
+        let mut planner = FftPlanner::<f32>::new();
+        Self {
+            shape,
+            forward: shape
+                .iter()
+                .map(|size| planner.plan_fft_forward(*size))
+                .collect(),
+            inverse: shape
+                .iter()
+                .map(|size| planner.plan_fft_inverse(*size))
+                .collect(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    fn forward(&self, values: &mut [Complex<f32>]) {
+        self.transform(values, &self.forward);
+    }
+
+    fn inverse(&self, values: &mut [Complex<f32>]) {
+        self.transform(values, &self.inverse);
+        let normalization = self.len() as f32;
+        values.iter_mut().for_each(|value| *value /= normalization);
+    }
+
+    fn transform(&self, values: &mut [Complex<f32>], plans: &[Arc<dyn Fft<f32>>]) {
+        let strides = [self.shape[1] * self.shape[2], self.shape[2], 1];
+        let mut line = Vec::new();
+        for (axis, plan) in plans.iter().enumerate() {
+            let size = self.shape[axis];
+            if size < 2 {
+                continue;
+            }
+            line.resize(size, Complex::new(0.0, 0.0));
+            for start in 0..values.len() {
+                if !(start / strides[axis]).is_multiple_of(size) {
+                    continue;
+                }
+                for (index, value) in line.iter_mut().enumerate() {
+                    *value = values[start + index * strides[axis]];
+                }
+                plan.process(&mut line);
+                for (index, value) in line.iter().enumerate() {
+                    values[start + index * strides[axis]] = *value;
+                }
+            }
+        }
+    }
+}
+
+/// Visits every `[z, y, x]` of a volume in row-major order.
+fn for_each_voxel(shape: &[usize; 3], mut callback: impl FnMut([usize; 3])) {
+    for z in 0..shape[0] {
+        for y in 0..shape[1] {
+            for x in 0..shape[2] {
+                callback([z, y, x]);
+            }
+        }
+    }
+}
+
+/// `axes` holds the X, Y and optional Z axis indices.
+fn set_volume_coord(coord: &mut [usize], axes: &[usize], z: usize, y: usize, x: usize) {
+    coord[axes[0]] = x;
+    coord[axes[1]] = y;
+    if let Some(z_axis) = axes.get(2) {
+        coord[*z_axis] = z;
+    }
+}
+
+fn axis_index(dataset: &DatasetF32, axis: AxisKind) -> Result<usize> {
+    dataset
+        .axis_index(axis)
+        .ok_or_else(|| OpsError::UnsupportedLayout(format!("dataset has no {axis:?} axis")))
+}
+
+fn iterate_indices(shape: &[usize], mut callback: impl FnMut(&[usize])) {
+    if shape.is_empty() {
+        callback(&[]);
+        return;
+    }
+    let mut index = vec![0usize; shape.len()];
+    loop {
+        callback(&index);
+        let mut dim = shape.len();
+        while dim > 0 {
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+            if dim == 0 {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use crate::model::{AxisKind, Dataset, DatasetF32, PixelType};
+use ndarray::{ArrayD, IxDyn};
+use rayon::prelude::*;
+use serde_json::Value;
+
+use super::{
+    OpOutput, OpSchema, Operation, OpsError, ParamSpec, Result, get_optional_f32,
+    get_optional_usize,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageBilateralFilterOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageAnisotropicDiffusionOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageNonLocalMeansOp;
+
+fn param(name: &str, description: &str, kind: &str) -> ParamSpec {
+    ParamSpec {
+        name: name.to_string(),
+        description: description.to_string(),
+        required: false,
+        kind: kind.to_string(),
+    }
+}
+
+impl Operation for ImageBilateralFilterOp {
+    fn name(&self) -> &'static str {
+        "image.bilateral_filter"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Edge-preserving bilateral filter on each X/Y plane.".to_string(),
+            params: vec![
+                param(
+                    "sigma_spatial",
+                    "Spatial Gaussian standard deviation in pixels (default 2).",
+                    "float",
+                ),
+                param(
+                    "sigma_range",
+                    "Intensity Gaussian standard deviation (default twice the estimated noise level of each plane).",
+                    "float",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let sigma_spatial = positive(params, "sigma_spatial", 2.0)?;
+        let sigma_range = optional_positive(params, "sigma_range")?;
+        let radius = (3.0 * sigma_spatial).ceil() as isize;
+        let spatial = (-radius..=radius)
+            .flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)))
+            .map(|(dx, dy)| {
+                let distance = (dx * dx + dy * dy) as f32;
+                (
+                    dx,
+                    dy,
+                    (-distance / (2.0 * sigma_spatial * sigma_spatial)).exp(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        map_planes(dataset, |plane| {
+            let sigma_range = sigma_range.unwrap_or_else(|| 2.0 * plane.noise_level());
+            let range_scale = if sigma_range > 0.0 {
+                -1.0 / (2.0 * sigma_range * sigma_range)
+            } else {
+                f32::NEG_INFINITY
+            };
+            plane.map_pixels(|x, y, centre| {
+                let (mut total, mut weights) = (0.0, 0.0);
+                for (dx, dy, spatial_weight) in &spatial {
+                    let value = plane.clamped(x + dx, y + dy);
+                    let difference = value - centre;
+                    let weight = spatial_weight
+                        * if difference == 0.0 {
+                            1.0
+                        } else {
+                            (difference * difference * range_scale).exp()
+                        };
+                    total += weight * value;
+                    weights += weight;
+                }
+                total / weights
+            })
+        })
+    }
+}
+
+impl Operation for ImageAnisotropicDiffusionOp {
+    fn name(&self) -> &'static str {
+        "image.anisotropic_diffusion"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Perona-Malik anisotropic diffusion on each X/Y plane.".to_string(),
+            params: vec![
+                param(
+                    "iterations",
+                    "Number of diffusion steps (default 10).",
+                    "int",
+                ),
+                param(
+                    "kappa",
+                    "Gradient threshold above which edges stop diffusing (default twice the estimated noise level of each plane).",
+                    "float",
+                ),
+                param(
+                    "time_step",
+                    "Step size, at most 0.25 for stability (default 0.2).",
+                    "float",
+                ),
+                param(
+                    "conductance",
+                    "exponential (default, favours high-contrast edges) or quadratic (favours wide regions).",
+                    "string",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let iterations = get_optional_usize(params, "iterations", 10);
+        let kappa = optional_positive(params, "kappa")?;
+        let time_step = positive(params, "time_step", 0.2)?;
+        if time_step > 0.25 {
+            return Err(OpsError::InvalidParams(
+                "`time_step` must be <= 0.25".to_string(),
+            ));
+        }
+        let exponential = match params.get("conductance").and_then(Value::as_str) {
+            None => true,
+            Some(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "exponential" => true,
+                "quadratic" => false,
+                other => {
+                    return Err(OpsError::InvalidParams(format!(
+                        "unsupported `conductance` `{other}`; expected exponential or quadratic"
+                    )));
+                }
+            },
+        };
+
+        map_planes(dataset, |plane| {
+            let kappa = kappa.unwrap_or_else(|| 2.0 * plane.noise_level());
+            let conductance = |gradient: f32| {
+                if kappa <= 0.0 {
+                    return if gradient == 0.0 { 1.0 } else { 0.0 };
+                }
+                let ratio = (gradient / kappa).powi(2);
+                if exponential {
+                    (-ratio).exp()
+                } else {
+                    1.0 / (1.0 + ratio)
+                }
+            };
+            let mut values = plane.values.to_vec();
+            for _ in 0..iterations {
+                let current = Plane {
+                    values: &values,
+                    ..*plane
+                };
+                // Neumann boundaries: clamped neighbours have zero flux.
+                values = current.map_pixels(|x, y, centre| {
+                    let flux = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                        .iter()
+                        .map(|(dx, dy)| {
+                            let gradient = current.clamped(x + dx, y + dy) - centre;
+                            conductance(gradient.abs()) * gradient
+                        })
+                        .sum::<f32>();
+                    centre + time_step * flux
+                });
+            }
+            values
+        })
+    }
+}
+
+impl Operation for ImageNonLocalMeansOp {
+    fn name(&self) -> &'static str {
+        "image.non_local_means"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Patch-based non-local means denoising of each X/Y plane.".to_string(),
+            params: vec![
+                param(
+                    "patch_radius",
+                    "Radius of the compared patches in pixels (default 1, i.e. 3x3).",
+                    "int",
+                ),
+                param(
+                    "search_radius",
+                    "Radius of the window searched for similar patches (default 5).",
+                    "int",
+                ),
+                param(
+                    "sigma",
+                    "Noise standard deviation (default estimated per plane).",
+                    "float",
+                ),
+                param(
+                    "h",
+                    "Filtering strength (default 0.8 x sigma); larger values smooth more.",
+                    "float",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let patch_radius = get_optional_usize(params, "patch_radius", 1) as isize;
+        let search_radius = get_optional_usize(params, "search_radius", 5) as isize;
+        let sigma = optional_positive(params, "sigma")?;
+        let h = optional_positive(params, "h")?;
+        let patch_size = ((2 * patch_radius + 1) * (2 * patch_radius + 1)) as f32;
+
+        map_planes(dataset, |plane| {
+            let sigma = sigma.unwrap_or_else(|| plane.noise_level());
+            let h = h.unwrap_or(0.8 * sigma);
+            let offset = 2.0 * sigma * sigma;
+            plane.map_pixels(|x, y, _| {
+                let (mut total, mut weights) = (0.0, 0.0);
+                for sy in -search_radius..=search_radius {
+                    for sx in -search_radius..=search_radius {
+                        let mut distance = 0.0;
+                        for py in -patch_radius..=patch_radius {
+                            for px in -patch_radius..=patch_radius {
+                                let difference = plane.clamped(x + px, y + py)
+                                    - plane.clamped(x + sx + px, y + sy + py);
+                                distance += difference * difference;
+                            }
+                        }
+                        let excess = (distance / patch_size - offset).max(0.0);
+                        let weight = if excess == 0.0 {
+                            1.0
+                        } else if h > 0.0 {
+                            (-excess / (h * h)).exp()
+                        } else {
+                            0.0
+                        };
+                        total += weight * plane.clamped(x + sx, y + sy);
+                        weights += weight;
+                    }
+                }
+                total / weights
+            })
+        })
+    }
+}
+
+/// One X/Y plane in row-major order.
+#[derive(Debug, Clone, Copy)]
+struct Plane<'a> {
+    values: &'a [f32],
+    width: usize,
+    height: usize,
+}
+
+impl Plane<'_> {
+    /// Value at `(x, y)` with coordinates clamped to the plane.
+    fn clamped(&self, x: isize, y: isize) -> f32 {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.values[y * self.width + x]
+    }
+
+    /// Applies `filter` to every pixel in parallel, so a single plane still
+    /// uses every core.
+    fn map_pixels(&self, filter: impl Fn(isize, isize, f32) -> f32 + Sync) -> Vec<f32> {
+        self.values
+            .par_iter()
+            .enumerate()
+            .map(|(index, value)| {
+                filter(
+                    (index % self.width) as isize,
+                    (index / self.width) as isize,
+                    *value,
+                )
+            })
+            .collect()
+    }
+
+    /// Gaussian noise standard deviation from the Laplacian-difference
+    /// estimator of Immerkær (1996).
+    fn noise_level(&self) -> f32 {
+        if self.width < 3 || self.height < 3 {
+            return 0.0;
+        }
+        let mut total = 0.0_f64;
+        for y in 1..self.height as isize - 1 {
+            for x in 1..self.width as isize - 1 {
+                let at = |dx: isize, dy: isize| f64::from(self.clamped(x + dx, y + dy));
+                let response = at(-1, -1) + at(1, -1) + at(-1, 1) + at(1, 1)
+                    - 2.0 * (at(0, -1) + at(-1, 0) + at(1, 0) + at(0, 1))
+                    + 4.0 * at(0, 0);
+                total += response.abs();
+            }
+        }
+        let count = ((self.width - 2) * (self.height - 2)) as f64;
+        ((std::f64::consts::FRAC_PI_2).sqrt() * total / (6.0 * count)) as f32
+    }
+}
+
+/// Applies `filter` to every X/Y plane in parallel and returns an F32 dataset.
+fn map_planes(
+    dataset: &DatasetF32,
+    filter: impl Fn(&Plane<'_>) -> Vec<f32> + Sync,
+) -> Result<OpOutput> {
+    let x_axis = axis_index(dataset, AxisKind::X)?;
+    let y_axis = axis_index(dataset, AxisKind::Y)?;
+    let shape = dataset.shape().to_vec();
+    let (width, height) = (shape[x_axis], shape[y_axis]);
+    let mut outer_shape = shape.clone();
+    outer_shape[x_axis] = 1;
+    outer_shape[y_axis] = 1;
+
+    let mut planes = Vec::new();
+    iterate_indices(&outer_shape, |base| {
+        let mut coord = base.to_vec();
+        let mut values = Vec::with_capacity(width * height);
+        for y in 0..height {
+            coord[y_axis] = y;
+            for x in 0..width {
+                coord[x_axis] = x;
+                values.push(dataset.data[IxDyn(&coord)]);
+            }
+        }
+        planes.push((base.to_vec(), values));
+    });
+    let filtered = planes
+        .par_iter()
+        .map(|(_, values)| {
+            filter(&Plane {
+                values,
+                width,
+                height,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut output = ArrayD::<f32>::zeros(IxDyn(&shape));
+    for ((base, _), values) in planes.iter().zip(filtered) {
+        let mut coord = base.clone();
+        for (index, value) in values.into_iter().enumerate() {
+            coord[y_axis] = index / width;
+            coord[x_axis] = index % width;
+            output[IxDyn(&coord)] = value;
+        }
+    }
+    let mut metadata = dataset.metadata.clone();
+    metadata.pixel_type = PixelType::F32;
+    Ok(OpOutput::dataset_only(Dataset::new(output, metadata)?))
+}
+
+fn positive(params: &Value, key: &str, default: f32) -> Result<f32> {
+    let value = get_optional_f32(params, key, default);
+    if value.is_finite() && value > 0.0 {
+        Ok(value)
+    } else {
+        Err(OpsError::InvalidParams(format!("`{key}` must be > 0")))
+    }
+}
+
+fn optional_positive(params: &Value, key: &str) -> Result<Option<f32>> {
+    params
+        .get(key)
+        .map(|_| positive(params, key, 1.0))
+        .transpose()
+}
+
+fn axis_index(dataset: &DatasetF32, axis: AxisKind) -> Result<usize> {
+    dataset
+        .axis_index(axis)
+        .ok_or_else(|| OpsError::UnsupportedLayout(format!("dataset has no {axis:?} axis")))
+}
+
+fn iterate_indices(shape: &[usize], mut callback: impl FnMut(&[usize])) {
+    if shape.is_empty() {
+        callback(&[]);
+        return;
+    }
+    let mut index = vec![0usize; shape.len()];
+    loop {
+        callback(&index);
+        let mut dim = shape.len();
+        while dim > 0 {
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+            if dim == 0 {
+                return;
+            }
+        }
+    }
+}
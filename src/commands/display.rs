@@ -0,0 +1,209 @@
+use crate::model::{ChannelLut, CompositeMode, Dataset, DatasetF32, NAMED_LUTS};
+use serde_json::Value;
+
+use super::{OpOutput, OpSchema, Operation, OpsError, ParamSpec, Result};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageDisplaySetChannelOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageDisplaySetModeOp;
+
+impl Operation for ImageDisplaySetChannelOp {
+    fn name(&self) -> &'static str {
+        "image.display.set_channel"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Record the LUT, display range and visibility of a channel without changing pixels."
+                    .to_string(),
+            params: vec![
+                ParamSpec {
+                    name: "channel".to_string(),
+                    description: "Zero-based channel index. Applies to every channel when omitted."
+                        .to_string(),
+                    required: false,
+                    kind: "int".to_string(),
+                },
+                ParamSpec {
+                    name: "lut".to_string(),
+                    description: format!(
+                        "LUT name ({}) or an object with 256-entry `red`, `green` and `blue` arrays.",
+                        NAMED_LUTS.join(", ")
+                    ),
+                    required: false,
+                    kind: "string|object".to_string(),
+                },
+                ParamSpec {
+                    name: "min".to_string(),
+                    description: "Display minimum; requires `max`.".to_string(),
+                    required: false,
+                    kind: "float".to_string(),
+                },
+                ParamSpec {
+                    name: "max".to_string(),
+                    description: "Display maximum; requires `min`.".to_string(),
+                    required: false,
+                    kind: "float".to_string(),
+                },
+                ParamSpec {
+                    name: "visible".to_string(),
+                    description: "Whether the channel contributes to composite rendering."
+                        .to_string(),
+                    required: false,
+                    kind: "bool".to_string(),
+                },
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let channel_count = dataset.metadata.channel_count();
+        let channels = match params.get("channel") {
+            None | Some(Value::Null) => (0..channel_count).collect::<Vec<_>>(),
+            Some(value) => {
+                let channel = value.as_u64().ok_or_else(|| {
+                    OpsError::InvalidParams("`channel` must be a non-negative integer".to_string())
+                })? as usize;
+                if channel >= channel_count {
+                    return Err(OpsError::InvalidParams(format!(
+                        "`channel` {channel} is out of range for {channel_count} channel(s)"
+                    )));
+                }
+                vec![channel]
+            }
+        };
+        let lut = params.get("lut").map(parse_lut).transpose()?;
+        let min = optional_float(params, "min")?;
+        let max = optional_float(params, "max")?;
+        if min.is_some() != max.is_some() {
+            return Err(OpsError::InvalidParams(
+                "`min` and `max` must be provided together".to_string(),
+            ));
+        }
+        let visible = match params.get("visible") {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(value.as_bool().ok_or_else(|| {
+                OpsError::InvalidParams("`visible` must be a boolean".to_string())
+            })?),
+        };
+
+        let mut metadata = dataset.metadata.clone();
+        let display = metadata.display.get_or_insert_with(Default::default);
+        for channel in channels {
+            let entry = display.channel_mut(channel);
+            if let Some(lut) = &lut {
+                entry.lut = lut.clone();
+            }
+            if min.is_some() {
+                entry.min = min;
+                entry.max = max;
+            }
+            if let Some(visible) = visible {
+                entry.visible = visible;
+            }
+        }
+        display.validate(channel_count)?;
+        Ok(OpOutput::dataset_only(Dataset::new(
+            dataset.data.clone(),
+            metadata,
+        )?))
+    }
+}
+
+impl Operation for ImageDisplaySetModeOp {
+    fn name(&self) -> &'static str {
+        "image.display.set_mode"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Record how multichannel images are rendered without changing pixels."
+                .to_string(),
+            params: vec![ParamSpec {
+                name: "mode".to_string(),
+                description: "One of composite, color or grayscale.".to_string(),
+                required: true,
+                kind: "string".to_string(),
+            }],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let mode = params.get("mode").and_then(Value::as_str).ok_or_else(|| {
+            OpsError::InvalidParams("missing string parameter `mode`".to_string())
+        })?;
+        let mode = CompositeMode::parse(mode).ok_or_else(|| {
+            OpsError::InvalidParams(format!(
+                "unsupported `mode` `{mode}`; expected composite, color or grayscale"
+            ))
+        })?;
+        let mut metadata = dataset.metadata.clone();
+        metadata.display.get_or_insert_with(Default::default).mode = mode;
+        Ok(OpOutput::dataset_only(Dataset::new(
+            dataset.data.clone(),
+            metadata,
+        )?))
+    }
+}
+
+fn parse_lut(value: &Value) -> Result<ChannelLut> {
+    if let Some(name) = value.as_str() {
+        return ChannelLut::named(name).ok_or_else(|| {
+            OpsError::InvalidParams(format!(
+                "unknown LUT `{name}`; expected one of {}",
+                NAMED_LUTS.join(", ")
+            ))
+        });
+    }
+    let table = |component: &str| -> Result<Vec<u8>> {
+        value
+            .get(component)
+            .and_then(Value::as_array)
+            .ok_or_else(|| {
+                OpsError::InvalidParams(format!("custom `lut` is missing a `{component}` array"))
+            })?
+            .iter()
+            .map(|entry| {
+                entry
+                    .as_u64()
+                    .filter(|entry| *entry <= 255)
+                    .map(|entry| entry as u8)
+                    .ok_or_else(|| {
+                        OpsError::InvalidParams(format!(
+                            "custom `lut` `{component}` entries must be integers in 0..=255"
+                        ))
+                    })
+            })
+            .collect()
+    };
+    let lut = ChannelLut::Custom {
+        red: table("red")?,
+        green: table("green")?,
+        blue: table("blue")?,
+    };
+    lut.validate()?;
+    Ok(lut)
+}
+
+fn optional_float(params: &Value, key: &str) -> Result<Option<f32>> {
+    match params.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value
+            .as_f64()
+            .map(|value| Some(value as f32))
+            .ok_or_else(|| OpsError::InvalidParams(format!("`{key}` must be a number"))),
+    }
+}
@@ -0,0 +1,266 @@
+use crate::model::{AxisKind, Dataset, DatasetF32, DatasetKind, MAX_LABEL, PixelType};
+use ndarray::{ArrayD, ArrayViewMut1, Axis};
+use serde_json::Value;
+
+use super::{
+    OpOutput, OpSchema, Operation, OpsError, ParamSpec, Result, get_optional_bool, spatial_axes,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyDistanceTransformOp;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DistanceOutput {
+    Distance,
+    Labels,
+    Index,
+}
+
+impl DistanceOutput {
+    fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "distance" => Ok(Self::Distance),
+            "labels" => Ok(Self::Labels),
+            "index" => Ok(Self::Index),
+            other => Err(OpsError::InvalidParams(format!(
+                "unsupported `output` `{other}`; expected distance, labels or index"
+            ))),
+        }
+    }
+}
+
+impl Operation for MorphologyDistanceTransformOp {
+    fn name(&self) -> &'static str {
+        "morphology.distance_transform"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        let param = |name: &str, description: &str, kind: &str| ParamSpec {
+            name: name.to_string(),
+            description: description.to_string(),
+            required: false,
+            kind: kind.to_string(),
+        };
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Exact Euclidean distance transform over the spatial axes, using axis spacing."
+                    .to_string(),
+            params: vec![
+                param(
+                    "features",
+                    "Pixels distances are measured to: background (default for distance output) or foreground (default for labels and index output).",
+                    "string",
+                ),
+                param(
+                    "output",
+                    "distance (default), labels (label of the nearest foreground pixel, i.e. a Voronoi tessellation of the labels) or index (row-major index of the nearest feature pixel).",
+                    "string",
+                ),
+                param(
+                    "calibrated",
+                    "Measure in `Dim::spacing` units (default true); false uses pixels.",
+                    "bool",
+                ),
+                param(
+                    "stack_3d",
+                    "Include the Z axis when present (default true); false works per X/Y plane.",
+                    "bool",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let output = params
+            .get("output")
+            .and_then(Value::as_str)
+            .map(DistanceOutput::parse)
+            .transpose()?
+            .unwrap_or(DistanceOutput::Distance);
+        let foreground_features = match params.get("features").and_then(Value::as_str) {
+            None => output != DistanceOutput::Distance,
+            Some(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "background" => false,
+                "foreground" => true,
+                other => {
+                    return Err(OpsError::InvalidParams(format!(
+                        "unsupported `features` `{other}`; expected background or foreground"
+                    )));
+                }
+            },
+        };
+        if output == DistanceOutput::Labels && !foreground_features {
+            return Err(OpsError::InvalidParams(
+                "`output` labels requires foreground features".to_string(),
+            ));
+        }
+        if output == DistanceOutput::Index && dataset.data.len() > MAX_LABEL as usize {
+            return Err(OpsError::InvalidParams(format!(
+                "`output` index supports at most {MAX_LABEL} pixels"
+            )));
+        }
+        let labels = (output == DistanceOutput::Labels)
+            .then(|| dataset.to_labels())
+            .transpose()?;
+
+        let stack_3d = get_optional_bool(params, "stack_3d", true);
+        let calibrated = get_optional_bool(params, "calibrated", true);
+        let axes = spatial_axes(dataset)
+            .into_iter()
+            .filter(|axis| stack_3d || dataset.metadata.dims[*axis].axis != AxisKind::Z)
+            .collect::<Vec<_>>();
+        if axes.is_empty() {
+            return Err(OpsError::UnsupportedLayout(
+                "dataset has no spatial axes".to_string(),
+            ));
+        }
+        let spacing = |axis: usize| {
+            if calibrated {
+                f64::from(dataset.metadata.dims[axis].spacing.unwrap_or(1.0))
+            } else {
+                1.0
+            }
+        };
+
+        let (distances, nearest) = distance_transform(
+            &dataset
+                .data
+                .mapv(|value| (value > 0.5) == foreground_features),
+            &axes
+                .iter()
+                .map(|axis| (*axis, spacing(*axis)))
+                .collect::<Vec<_>>(),
+        );
+
+        let mut metadata = dataset.metadata.clone();
+        metadata.pixel_type = PixelType::F32;
+        let data = match output {
+            DistanceOutput::Distance => {
+                metadata.kind = DatasetKind::Intensity;
+                distances.mapv(|squared| squared.sqrt() as f32)
+            }
+            DistanceOutput::Labels => {
+                metadata.kind = DatasetKind::Label;
+                let labels = labels.expect("labels are read for label output");
+                let flat = labels.data.iter().copied().collect::<Vec<_>>();
+                nearest.mapv(|index| {
+                    if index == usize::MAX {
+                        0.0
+                    } else {
+                        flat[index] as f32
+                    }
+                })
+            }
+            DistanceOutput::Index => {
+                metadata.kind = DatasetKind::Intensity;
+                nearest.mapv(|index| {
+                    if index == usize::MAX {
+                        -1.0
+                    } else {
+                        index as f32
+                    }
+                })
+            }
+        };
+        Ok(OpOutput::dataset_only(Dataset::new(data, metadata)?))
+    }
+}
+
+/// Squared Euclidean distance from every pixel to the nearest `true` pixel
+/// along `axes` (each with its spacing), plus the row-major index of that
+/// pixel. Pixels whose volume has no feature keep `inf` and `usize::MAX`.
+///
+/// Separable exact transform (Felzenszwalb & Huttenlocher): a 1D lower
+/// envelope of parabolas is taken along each axis in turn.
+pub(super) fn distance_transform(
+    features: &ArrayD<bool>,
+    axes: &[(usize, f64)],
+) -> (ArrayD<f64>, ArrayD<usize>) {
+    let mut distances = features.mapv(|feature| if feature { 0.0 } else { f64::INFINITY });
+    let mut nearest = ArrayD::from_shape_vec(
+        features.raw_dim(),
+        features
+            .iter()
+            .enumerate()
+            .map(|(index, feature)| if *feature { index } else { usize::MAX })
+            .collect(),
+    )
+    .expect("shape is unchanged and valid");
+    let mut envelope = Envelope::default();
+    for (axis, spacing) in axes {
+        for (distance_lane, nearest_lane) in distances
+            .lanes_mut(Axis(*axis))
+            .into_iter()
+            .zip(nearest.lanes_mut(Axis(*axis)))
+        {
+            envelope.transform(distance_lane, nearest_lane, *spacing);
+        }
+    }
+    (distances, nearest)
+}
+
+/// Scratch buffers for the 1D lower envelope, reused across lanes.
+#[derive(Default)]
+struct Envelope {
+    values: Vec<f64>,
+    sources: Vec<usize>,
+    parabolas: Vec<usize>,
+    boundaries: Vec<f64>,
+}
+
+impl Envelope {
+    fn transform(
+        &mut self,
+        mut distances: ArrayViewMut1<f64>,
+        mut nearest: ArrayViewMut1<usize>,
+        spacing: f64,
+    ) {
+        self.values.clear();
+        self.values.extend(distances.iter().copied());
+        self.sources.clear();
+        self.sources.extend(nearest.iter().copied());
+        self.parabolas.clear();
+        self.boundaries.clear();
+
+        let position = |index: usize| index as f64 * spacing;
+        for (q, value) in self.values.iter().enumerate() {
+            if !value.is_finite() {
+                continue;
+            }
+            let mut boundary = f64::NEG_INFINITY;
+            while let Some(&v) = self.parabolas.last() {
+                let (xq, xv) = (position(q), position(v));
+                boundary = ((value + xq * xq) - (self.values[v] + xv * xv)) / (2.0 * (xq - xv));
+                if boundary <= *self.boundaries.last().expect("one boundary per parabola") {
+                    self.parabolas.pop();
+                    self.boundaries.pop();
+                    boundary = f64::NEG_INFINITY;
+                } else {
+                    break;
+                }
+            }
+            self.parabolas.push(q);
+            self.boundaries.push(boundary);
+        }
+        if self.parabolas.is_empty() {
+            return;
+        }
+
+        let mut k = 0;
+        for p in 0..self.values.len() {
+            let x = position(p);
+            while k + 1 < self.parabolas.len() && self.boundaries[k + 1] < x {
+                k += 1;
+            }
+            let q = self.parabolas[k];
+            let delta = x - position(q);
+            distances[p] = delta * delta + self.values[q];
+            nearest[p] = self.sources[q];
+        }
+    }
+}
@@ -0,0 +1,787 @@
+use crate::model::{AxisKind, Dataset, DatasetF32, PixelType};
+use ndarray::{ArrayD, IxDyn};
+use rayon::prelude::*;
+use serde_json::{Map, Value, json};
+
+use super::{
+    MeasurementTable, OpOutput, OpSchema, Operation, OpsError, ParamSpec, Result,
+    gaussian::{blur_axis, row_major_strides},
+    get_optional_bool, get_optional_f32, get_optional_usize,
+    util::gaussian_kernel,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageEdgesCannyOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageHoughLinesOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageHoughCirclesOp;
+
+fn param(name: &str, description: &str, kind: &str) -> ParamSpec {
+    ParamSpec {
+        name: name.to_string(),
+        description: description.to_string(),
+        required: false,
+        kind: kind.to_string(),
+    }
+}
+
+const RENDER_DESCRIPTION: &str =
+    "Return a 0/255 image with the detected shapes drawn instead of the input (default false).";
+
+impl Operation for ImageEdgesCannyOp {
+    fn name(&self) -> &'static str {
+        "image.edges.canny"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Canny edge detection on each X/Y plane; edges are 255, background 0."
+                .to_string(),
+            params: vec![
+                param(
+                    "sigma",
+                    "Gaussian smoothing in pixels before differentiation (default 1; 0 disables).",
+                    "float",
+                ),
+                param(
+                    "low_threshold",
+                    "Gradient magnitude that weak edges must reach to extend a strong edge (default 10% of the plane's largest gradient).",
+                    "float",
+                ),
+                param(
+                    "high_threshold",
+                    "Gradient magnitude that starts an edge (default 20% of the plane's largest gradient).",
+                    "float",
+                ),
+                param(
+                    "use_quantiles",
+                    "Read both thresholds as quantiles (0..1) of the plane's gradient magnitude, defaulting to 0.8 and 0.9 (default false).",
+                    "bool",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let sigma = get_optional_f32(params, "sigma", 1.0);
+        if !sigma.is_finite() || sigma < 0.0 {
+            return Err(OpsError::InvalidParams("`sigma` must be >= 0".to_string()));
+        }
+        let use_quantiles = get_optional_bool(params, "use_quantiles", false);
+        let low = optional_f32(params, "low_threshold")?;
+        let high = optional_f32(params, "high_threshold")?;
+        if use_quantiles
+            && [low, high]
+                .into_iter()
+                .flatten()
+                .any(|quantile| !(0.0..=1.0).contains(&quantile))
+        {
+            return Err(OpsError::InvalidParams(
+                "quantile thresholds must be within 0..1".to_string(),
+            ));
+        }
+        // Defaults are applied per plane, so the order is checked once both are known.
+        let ordered = |low: f32, high: f32| {
+            if low > high {
+                Err(OpsError::InvalidParams(format!(
+                    "`low_threshold` {low} must not exceed `high_threshold` {high}"
+                )))
+            } else {
+                Ok((low, high))
+            }
+        };
+
+        let planes = Planes::new(dataset)?;
+        let results = (0..planes.len())
+            .into_par_iter()
+            .map(|index| {
+                let mut plane = planes.read(dataset, index);
+                if sigma > 0.0 {
+                    plane = planes.smooth(&plane, sigma);
+                }
+                let (magnitude, directions) = planes.sobel(&plane);
+                let (low, high) = if use_quantiles {
+                    let (low, high) = ordered(low.unwrap_or(0.8), high.unwrap_or(0.9))?;
+                    let mut sorted = magnitude.clone();
+                    sorted.sort_by(f32::total_cmp);
+                    let at = |quantile: f32| {
+                        let last = sorted.len().saturating_sub(1);
+                        sorted
+                            .get((last as f32 * quantile).round() as usize)
+                            .copied()
+                            .unwrap_or(0.0)
+                    };
+                    (at(low), at(high))
+                } else {
+                    let largest = magnitude.iter().copied().fold(0.0_f32, f32::max);
+                    ordered(low.unwrap_or(0.1 * largest), high.unwrap_or(0.2 * largest))?
+                };
+                let thin = planes.suppress_non_maxima(&magnitude, &directions);
+                Ok((planes.hysteresis(&thin, low, high), low, high))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let rows = results
+            .iter()
+            .enumerate()
+            .map(|(index, (_, low, high))| {
+                let mut row = planes.row(dataset, index);
+                row.insert("low_threshold".to_string(), json!(low));
+                row.insert("high_threshold".to_string(), json!(high));
+                Value::Object(row)
+            })
+            .collect();
+        let edges = planes.assemble(dataset, results.into_iter().map(|(edges, ..)| edges))?;
+        Ok(OpOutput {
+            dataset: edges,
+            measurements: Some(table(rows)),
+        })
+    }
+}
+
+impl Operation for ImageHoughLinesOp {
+    fn name(&self) -> &'static str {
+        "image.hough.lines"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Straight-line Hough transform of an edge image; every pixel > 0 votes. Lines satisfy x cos(theta) + y sin(theta) = rho.".to_string(),
+            params: vec![
+                param(
+                    "angle_steps",
+                    "Number of angles sampled over [-90, 90) degrees (default 180).",
+                    "int",
+                ),
+                param(
+                    "threshold",
+                    "Minimum votes for a line (default half the largest vote count in the plane).",
+                    "int",
+                ),
+                param(
+                    "max_lines",
+                    "Maximum number of lines per plane (default 10).",
+                    "int",
+                ),
+                param(
+                    "min_distance",
+                    "Lines closer than this in rho (pixels) and `min_angle` are merged (default 9).",
+                    "float",
+                ),
+                param(
+                    "min_angle",
+                    "Lines closer than this in degrees and `min_distance` are merged (default 10).",
+                    "float",
+                ),
+                param("render", RENDER_DESCRIPTION, "bool"),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        reject_channels(dataset)?;
+        let angle_steps = get_optional_usize(params, "angle_steps", 180);
+        if angle_steps == 0 {
+            return Err(OpsError::InvalidParams(
+                "`angle_steps` must be >= 1".to_string(),
+            ));
+        }
+        let threshold = params
+            .get("threshold")
+            .map(|_| get_optional_usize(params, "threshold", 0));
+        let max_lines = get_optional_usize(params, "max_lines", 10);
+        let min_distance = non_negative(params, "min_distance", 9.0)?;
+        let min_angle = non_negative(params, "min_angle", 10.0)?;
+        let render = get_optional_bool(params, "render", false);
+
+        let planes = Planes::new(dataset)?;
+        let angles = (0..angle_steps)
+            .map(|step| (-90.0 + 180.0 * step as f32 / angle_steps as f32).to_radians())
+            .collect::<Vec<_>>();
+        let diagonal = (planes.width as f32).hypot(planes.height as f32).ceil() as isize;
+        let distances = (2 * diagonal + 1) as usize;
+
+        let detected = (0..planes.len())
+            .into_par_iter()
+            .map(|index| {
+                let plane = planes.read(dataset, index);
+                let mut votes = vec![0_u32; angles.len() * distances];
+                for (x, y) in planes.edge_points(&plane) {
+                    for (angle_index, angle) in angles.iter().enumerate() {
+                        let rho = (x as f32 * angle.cos() + y as f32 * angle.sin()).round()
+                            as isize
+                            + diagonal;
+                        votes[angle_index * distances + rho as usize] += 1;
+                    }
+                }
+                let largest = votes.iter().copied().max().unwrap_or(0);
+                let threshold = threshold.unwrap_or(largest.div_ceil(2) as usize).max(1) as u32;
+                let mut candidates = votes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, count)| **count >= threshold)
+                    .map(|(cell, count)| (cell, *count))
+                    .collect::<Vec<_>>();
+                candidates.sort_by(|left, right| right.1.cmp(&left.1).then(left.0.cmp(&right.0)));
+
+                let mut lines: Vec<Line> = Vec::new();
+                for (cell, count) in candidates {
+                    if lines.len() >= max_lines {
+                        break;
+                    }
+                    let line = Line {
+                        rho: (cell % distances) as f32 - diagonal as f32,
+                        theta: angles[cell / distances],
+                        votes: count,
+                    };
+                    if lines
+                        .iter()
+                        .all(|other| !line.is_near(other, min_distance, min_angle))
+                    {
+                        lines.push(line);
+                    }
+                }
+                lines
+            })
+            .collect::<Vec<_>>();
+
+        let mut rows = Vec::new();
+        for (index, lines) in detected.iter().enumerate() {
+            for line in lines {
+                let mut row = planes.row(dataset, index);
+                row.insert("rho".to_string(), json!(line.rho));
+                row.insert("theta".to_string(), json!(line.theta.to_degrees()));
+                row.insert("votes".to_string(), json!(line.votes));
+                rows.push(Value::Object(row));
+            }
+        }
+        let output = if render {
+            planes.assemble(
+                dataset,
+                detected.iter().map(|lines| {
+                    let mut canvas = vec![0.0; planes.width * planes.height];
+                    for line in lines {
+                        planes.draw_line(&mut canvas, line);
+                    }
+                    canvas
+                }),
+            )?
+        } else {
+            dataset.clone()
+        };
+        Ok(OpOutput {
+            dataset: output,
+            measurements: Some(table(rows)),
+        })
+    }
+}
+
+impl Operation for ImageHoughCirclesOp {
+    fn name(&self) -> &'static str {
+        "image.hough.circles"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Circle Hough transform of an edge image over a radius range; every pixel > 0 votes."
+                    .to_string(),
+            params: vec![
+                ParamSpec {
+                    name: "radius_min".to_string(),
+                    description: "Smallest radius searched, in pixels.".to_string(),
+                    required: true,
+                    kind: "float".to_string(),
+                },
+                param(
+                    "radius_max",
+                    "Largest radius searched, in pixels (default radius_min).",
+                    "float",
+                ),
+                param(
+                    "radius_step",
+                    "Radius increment in pixels (default 1).",
+                    "float",
+                ),
+                param(
+                    "threshold",
+                    "Minimum score, the fraction of a circle's perimeter covered by edges (default 0.4).",
+                    "float",
+                ),
+                param(
+                    "max_circles",
+                    "Maximum number of circles per plane (default 10).",
+                    "int",
+                ),
+                param(
+                    "min_distance",
+                    "Minimum distance in pixels between circle centres (default radius_min).",
+                    "float",
+                ),
+                param("render", RENDER_DESCRIPTION, "bool"),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        reject_channels(dataset)?;
+        let radius_min = params
+            .get("radius_min")
+            .and_then(Value::as_f64)
+            .map(|value| value as f32)
+            .ok_or_else(|| OpsError::InvalidParams("`radius_min` is required".to_string()))?;
+        if !radius_min.is_finite() || radius_min < 1.0 {
+            return Err(OpsError::InvalidParams(
+                "`radius_min` must be >= 1".to_string(),
+            ));
+        }
+        let radius_max = get_optional_f32(params, "radius_max", radius_min);
+        if !radius_max.is_finite() || radius_max < radius_min {
+            return Err(OpsError::InvalidParams(
+                "`radius_max` must be >= `radius_min`".to_string(),
+            ));
+        }
+        let radius_step = get_optional_f32(params, "radius_step", 1.0);
+        if !radius_step.is_finite() || radius_step <= 0.0 {
+            return Err(OpsError::InvalidParams(
+                "`radius_step` must be > 0".to_string(),
+            ));
+        }
+        let threshold = get_optional_f32(params, "threshold", 0.4);
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err(OpsError::InvalidParams(
+                "`threshold` must be within 0..1".to_string(),
+            ));
+        }
+        let max_circles = get_optional_usize(params, "max_circles", 10);
+        let min_distance = non_negative(params, "min_distance", radius_min)?;
+        let render = get_optional_bool(params, "render", false);
+
+        let steps = ((radius_max - radius_min) / radius_step + 1e-3).floor() as usize;
+        let radii = (0..=steps)
+            .map(|step| radius_min + step as f32 * radius_step)
+            .collect::<Vec<_>>();
+        let perimeters = radii
+            .iter()
+            .map(|radius| perimeter_offsets(*radius))
+            .collect::<Vec<_>>();
+
+        let planes = Planes::new(dataset)?;
+        let (width, height) = (planes.width, planes.height);
+        let detected = (0..planes.len())
+            .into_par_iter()
+            .map(|index| {
+                let plane = planes.read(dataset, index);
+                let points = planes.edge_points(&plane);
+                let mut candidates = Vec::new();
+                for (radius, offsets) in radii.iter().zip(&perimeters) {
+                    let mut votes = vec![0_u32; width * height];
+                    for (x, y) in &points {
+                        for (dx, dy) in offsets {
+                            let (cx, cy) = (*x as isize + dx, *y as isize + dy);
+                            if cx >= 0 && cy >= 0 && (cx as usize) < width && (cy as usize) < height
+                            {
+                                votes[cy as usize * width + cx as usize] += 1;
+                            }
+                        }
+                    }
+                    let total = offsets.len() as f32;
+                    candidates.extend(votes.into_iter().enumerate().filter_map(|(cell, count)| {
+                        let score = count as f32 / total;
+                        (count > 0 && score >= threshold).then_some(Circle {
+                            x: (cell % width) as f32,
+                            y: (cell / width) as f32,
+                            radius: *radius,
+                            score,
+                        })
+                    }));
+                }
+                candidates.sort_by(|left, right| right.score.total_cmp(&left.score));
+
+                let mut circles: Vec<Circle> = Vec::new();
+                for circle in candidates {
+                    if circles.len() >= max_circles {
+                        break;
+                    }
+                    if circles
+                        .iter()
+                        .all(|other| (circle.x - other.x).hypot(circle.y - other.y) >= min_distance)
+                    {
+                        circles.push(circle);
+                    }
+                }
+                circles
+            })
+            .collect::<Vec<_>>();
+
+        let mut rows = Vec::new();
+        for (index, circles) in detected.iter().enumerate() {
+            for circle in circles {
+                let mut row = planes.row(dataset, index);
+                row.insert("x".to_string(), json!(circle.x));
+                row.insert("y".to_string(), json!(circle.y));
+                row.insert("radius".to_string(), json!(circle.radius));
+                row.insert("score".to_string(), json!(circle.score));
+                rows.push(Value::Object(row));
+            }
+        }
+        let output = if render {
+            planes.assemble(
+                dataset,
+                detected.iter().map(|circles| {
+                    let mut canvas = vec![0.0; width * height];
+                    for circle in circles {
+                        for (dx, dy) in perimeter_offsets(circle.radius) {
+                            planes.plot(
+                                &mut canvas,
+                                circle.x as isize + dx,
+                                circle.y as isize + dy,
+                            );
+                        }
+                    }
+                    canvas
+                }),
+            )?
+        } else {
+            dataset.clone()
+        };
+        Ok(OpOutput {
+            dataset: output,
+            measurements: Some(table(rows)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Line {
+    rho: f32,
+    /// Angle of the line normal in radians.
+    theta: f32,
+    votes: u32,
+}
+
+impl Line {
+    /// Near in both rho and angle; angles wrap at ±90 degrees, where rho flips sign.
+    fn is_near(&self, other: &Self, min_distance: f32, min_angle: f32) -> bool {
+        let mut angle = (self.theta - other.theta).abs().to_degrees();
+        let mut rho = other.rho;
+        if angle > 90.0 {
+            angle = 180.0 - angle;
+            rho = -rho;
+        }
+        angle < min_angle && (self.rho - rho).abs() < min_distance
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Circle {
+    x: f32,
+    y: f32,
+    radius: f32,
+    score: f32,
+}
+
+/// Distinct integer offsets on a circle of `radius`.
+fn perimeter_offsets(radius: f32) -> Vec<(isize, isize)> {
+    let samples = ((std::f32::consts::TAU * radius).ceil() as usize * 2).max(8);
+    let mut offsets = (0..samples)
+        .map(|sample| {
+            let angle = std::f32::consts::TAU * sample as f32 / samples as f32;
+            (
+                (radius * angle.cos()).round() as isize,
+                (radius * angle.sin()).round() as isize,
+            )
+        })
+        .collect::<Vec<_>>();
+    offsets.sort_unstable();
+    offsets.dedup();
+    offsets
+}
+
+/// X/Y planes of a dataset in row-major order of the remaining axes.
+struct Planes {
+    x_axis: usize,
+    y_axis: usize,
+    width: usize,
+    height: usize,
+    bases: Vec<Vec<usize>>,
+}
+
+impl Planes {
+    fn new(dataset: &DatasetF32) -> Result<Self> {
+        let x_axis = axis_index(dataset, AxisKind::X)?;
+        let y_axis = axis_index(dataset, AxisKind::Y)?;
+        let shape = dataset.shape();
+        let mut outer_shape = shape.to_vec();
+        outer_shape[x_axis] = 1;
+        outer_shape[y_axis] = 1;
+        let mut bases = Vec::new();
+        iterate_indices(&outer_shape, |base| bases.push(base.to_vec()));
+        Ok(Self {
+            x_axis,
+            y_axis,
+            width: shape[x_axis],
+            height: shape[y_axis],
+            bases,
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.bases.len()
+    }
+
+    fn read(&self, dataset: &DatasetF32, index: usize) -> Vec<f32> {
+        let mut coord = self.bases[index].clone();
+        let mut values = Vec::with_capacity(self.width * self.height);
+        for y in 0..self.height {
+            coord[self.y_axis] = y;
+            for x in 0..self.width {
+                coord[self.x_axis] = x;
+                values.push(dataset.data[IxDyn(&coord)]);
+            }
+        }
+        values
+    }
+
+    /// Writes one 0/255 plane per base into a U8 dataset shaped like `dataset`.
+    fn assemble(
+        &self,
+        dataset: &DatasetF32,
+        planes: impl Iterator<Item = Vec<f32>>,
+    ) -> Result<DatasetF32> {
+        let mut output = ArrayD::<f32>::zeros(IxDyn(dataset.shape()));
+        for (base, values) in self.bases.iter().zip(planes) {
+            let mut coord = base.clone();
+            for (index, value) in values.into_iter().enumerate() {
+                coord[self.y_axis] = index / self.width;
+                coord[self.x_axis] = index % self.width;
+                output[IxDyn(&coord)] = value;
+            }
+        }
+        let mut metadata = dataset.metadata.clone();
+        metadata.pixel_type = PixelType::U8;
+        metadata.display = None;
+        Ok(Dataset::new(output, metadata)?)
+    }
+
+    /// Table row prefix naming the plane: its index and non-X/Y coordinates.
+    fn row(&self, dataset: &DatasetF32, index: usize) -> Map<String, Value> {
+        let mut row = Map::new();
+        row.insert("slice".to_string(), json!(index));
+        for (axis, dim) in dataset.metadata.dims.iter().enumerate() {
+            if axis != self.x_axis && axis != self.y_axis {
+                let code = dim.axis.code().to_ascii_lowercase();
+                row.insert(code.to_string(), json!(self.bases[index][axis]));
+            }
+        }
+        row
+    }
+
+    fn edge_points(&self, plane: &[f32]) -> Vec<(usize, usize)> {
+        plane
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| **value > 0.0)
+            .map(|(index, _)| (index % self.width, index / self.width))
+            .collect()
+    }
+
+    fn smooth(&self, plane: &[f32], sigma: f32) -> Vec<f32> {
+        let shape = [self.height, self.width];
+        let strides = row_major_strides(&shape);
+        let kernel = gaussian_kernel(sigma);
+        let radius = (kernel.len() / 2) as isize;
+        let rows = blur_axis(plane, &shape, &strides, 1, &kernel, radius);
+        blur_axis(&rows, &shape, &strides, 0, &kernel, radius)
+    }
+
+    fn clamped(&self, plane: &[f32], x: isize, y: isize) -> f32 {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        plane[y * self.width + x]
+    }
+
+    /// Sobel gradient magnitude and direction, quantized to 0 (horizontal
+    /// gradient), 1 (45 degrees), 2 (vertical) or 3 (135 degrees).
+    fn sobel(&self, plane: &[f32]) -> (Vec<f32>, Vec<u8>) {
+        let mut magnitude = Vec::with_capacity(plane.len());
+        let mut directions = Vec::with_capacity(plane.len());
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                let at = |dx: isize, dy: isize| self.clamped(plane, x + dx, y + dy);
+                let gx = at(1, -1) + 2.0 * at(1, 0) + at(1, 1)
+                    - at(-1, -1)
+                    - 2.0 * at(-1, 0)
+                    - at(-1, 1);
+                let gy = at(-1, 1) + 2.0 * at(0, 1) + at(1, 1)
+                    - at(-1, -1)
+                    - 2.0 * at(0, -1)
+                    - at(1, -1);
+                magnitude.push(gx.hypot(gy));
+                let angle = gy.atan2(gx).to_degrees().rem_euclid(180.0);
+                directions.push(((angle + 22.5) / 45.0).floor() as u8 % 4);
+            }
+        }
+        (magnitude, directions)
+    }
+
+    fn suppress_non_maxima(&self, magnitude: &[f32], directions: &[u8]) -> Vec<f32> {
+        let (width, height) = (self.width as isize, self.height as isize);
+        let at = |x: isize, y: isize| {
+            if x < 0 || y < 0 || x >= width || y >= height {
+                0.0
+            } else {
+                magnitude[(y * width + x) as usize]
+            }
+        };
+        (0..magnitude.len())
+            .map(|index| {
+                let (x, y) = ((index % self.width) as isize, (index / self.width) as isize);
+                let (dx, dy) = match directions[index] {
+                    0 => (1, 0),
+                    1 => (1, 1),
+                    2 => (0, 1),
+                    _ => (-1, 1),
+                };
+                let value = magnitude[index];
+                if value > at(x - dx, y - dy) && value >= at(x + dx, y + dy) {
+                    value
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    /// Keeps pixels at or above `low` that connect (8-connected) to a pixel at
+    /// or above `high`.
+    fn hysteresis(&self, thin: &[f32], low: f32, high: f32) -> Vec<f32> {
+        let mut edges = vec![0.0_f32; thin.len()];
+        let mut stack = thin
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| **value > 0.0 && **value >= high)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        for index in &stack {
+            edges[*index] = 255.0;
+        }
+        while let Some(index) = stack.pop() {
+            let (x, y) = ((index % self.width) as isize, (index / self.width) as isize);
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= self.width as isize || ny >= self.height as isize {
+                        continue;
+                    }
+                    let neighbour = ny as usize * self.width + nx as usize;
+                    if edges[neighbour] == 0.0 && thin[neighbour] > 0.0 && thin[neighbour] >= low {
+                        edges[neighbour] = 255.0;
+                        stack.push(neighbour);
+                    }
+                }
+            }
+        }
+        edges
+    }
+
+    fn plot(&self, canvas: &mut [f32], x: isize, y: isize) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            canvas[y as usize * self.width + x as usize] = 255.0;
+        }
+    }
+
+    /// Draws `line` across the plane, stepping along its longer extent.
+    fn draw_line(&self, canvas: &mut [f32], line: &Line) {
+        let (cos, sin) = (line.theta.cos(), line.theta.sin());
+        if sin.abs() >= cos.abs() {
+            for x in 0..self.width {
+                let y = (line.rho - x as f32 * cos) / sin;
+                self.plot(canvas, x as isize, y.round() as isize);
+            }
+        } else {
+            for y in 0..self.height {
+                let x = (line.rho - y as f32 * sin) / cos;
+                self.plot(canvas, x.round() as isize, y as isize);
+            }
+        }
+    }
+}
+
+fn table(rows: Vec<Value>) -> MeasurementTable {
+    let mut measurements = MeasurementTable::default();
+    measurements
+        .values
+        .insert("count".to_string(), json!(rows.len()));
+    measurements.values.insert("rows".to_string(), json!(rows));
+    measurements
+}
+
+fn reject_channels(dataset: &DatasetF32) -> Result<()> {
+    if dataset.metadata.channel_count() > 1 {
+        return Err(OpsError::UnsupportedLayout(
+            "Hough transforms need a single-channel edge image".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn optional_f32(params: &Value, key: &str) -> Result<Option<f32>> {
+    params
+        .get(key)
+        .map(|value| {
+            value
+                .as_f64()
+                .map(|value| value as f32)
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .ok_or_else(|| OpsError::InvalidParams(format!("`{key}` must be a number >= 0")))
+        })
+        .transpose()
+}
+
+fn non_negative(params: &Value, key: &str, default: f32) -> Result<f32> {
+    let value = get_optional_f32(params, key, default);
+    if value.is_finite() && value >= 0.0 {
+        Ok(value)
+    } else {
+        Err(OpsError::InvalidParams(format!("`{key}` must be >= 0")))
+    }
+}
+
+fn axis_index(dataset: &DatasetF32, axis: AxisKind) -> Result<usize> {
+    dataset
+        .axis_index(axis)
+        .ok_or_else(|| OpsError::UnsupportedLayout(format!("missing {axis:?} axis")))
+}
+
+fn iterate_indices(shape: &[usize], mut callback: impl FnMut(&[usize])) {
+    if shape.is_empty() {
+        callback(&[]);
+        return;
+    }
+    if shape.contains(&0) {
+        return;
+    }
+    let mut index = vec![0usize; shape.len()];
+    loop {
+        callback(&index);
+        let mut dim = shape.len();
+        while dim > 0 {
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+            if dim == 0 {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,752 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::model::{AxisKind, Dataset, DatasetF32, Metadata};
+use ndarray::{ArrayD, IxDyn};
+use serde_json::Value;
+
+use super::calculator::broadcast;
+use super::{
+    INPUT_PARAM_KIND, OpInputs, OpOutput, OpSchema, Operation, OpsError, ParamSpec, Result,
+    gaussian::row_major_strides, get_optional_bool, get_optional_usize, get_required_f32,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct VolumeShape {
+    pub(super) width: usize,
+    pub(super) height: usize,
+    pub(super) depth: usize,
+}
+
+impl VolumeShape {
+    pub(super) fn len(self) -> usize {
+        self.width * self.height * self.depth
+    }
+
+    /// Indices of the in-bounds neighbours of `index`.
+    pub(super) fn neighbours<'a>(
+        self,
+        index: usize,
+        offsets: &'a [(isize, isize, isize)],
+    ) -> impl Iterator<Item = usize> + 'a {
+        let x = index % self.width;
+        let y = (index / self.width) % self.height;
+        let z = index / (self.width * self.height);
+        offsets.iter().filter_map(move |(dx, dy, dz)| {
+            let nx = x.checked_add_signed(*dx).filter(|nx| *nx < self.width)?;
+            let ny = y.checked_add_signed(*dy).filter(|ny| *ny < self.height)?;
+            let nz = z.checked_add_signed(*dz).filter(|nz| *nz < self.depth)?;
+            Some(nx + self.width * (ny + self.height * nz))
+        })
+    }
+}
+
+/// Orders pixels by value, then by insertion so equal values are processed first-in first-out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Priority(pub(super) f32, pub(super) u64);
+
+impl Eq for Priority {}
+
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Priority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+pub(super) fn connectivity_offsets(
+    connectivity: usize,
+    volumetric: bool,
+) -> Result<Vec<(isize, isize, isize)>> {
+    let max_nonzero = match (connectivity, volumetric) {
+        (4, false) | (6, true) => 1,
+        (8, false) | (18, true) => 2,
+        (26, true) => 3,
+        _ => {
+            return Err(OpsError::InvalidParams(
+                "`connectivity` must be 4 or 8 in 2D, or 6, 18 or 26 in 3D".to_string(),
+            ));
+        }
+    };
+    let z_range = if volumetric { -1..=1 } else { 0..=0 };
+    let mut offsets = Vec::new();
+    for dz in z_range {
+        for dy in -1_isize..=1 {
+            for dx in -1_isize..=1 {
+                let nonzero = [dx, dy, dz].iter().filter(|value| **value != 0).count();
+                if nonzero > 0 && nonzero <= max_nonzero {
+                    offsets.push((dx, dy, dz));
+                }
+            }
+        }
+    }
+    Ok(offsets)
+}
+
+/// Labels each plateau with no lower neighbour, numbered from 1 in raster order.
+pub(super) fn regional_minima(
+    values: &[f32],
+    volume: VolumeShape,
+    offsets: &[(isize, isize, isize)],
+) -> Vec<i64> {
+    let mut labels = vec![0_i64; values.len()];
+    let mut visited = vec![false; values.len()];
+    let mut next_label = 1;
+    for start in 0..values.len() {
+        if visited[start] || !values[start].is_finite() {
+            continue;
+        }
+        let level = values[start];
+        let mut plateau = Vec::new();
+        let mut is_minimum = true;
+        let mut queue = VecDeque::from([start]);
+        visited[start] = true;
+        while let Some(index) = queue.pop_front() {
+            plateau.push(index);
+            for neighbour in volume.neighbours(index, offsets) {
+                let value = values[neighbour];
+                if value < level {
+                    is_minimum = false;
+                } else if value == level && !visited[neighbour] {
+                    visited[neighbour] = true;
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        if is_minimum {
+            for index in plateau {
+                labels[index] = next_label;
+            }
+            next_label += 1;
+        }
+    }
+    labels
+}
+
+/// Grayscale reconstruction by dilation of `marker` under `mask`, using a
+/// priority queue so each pixel settles once in order of decreasing value.
+pub(super) fn reconstruct_by_dilation(
+    marker: &[f32],
+    mask: &[f32],
+    volume: VolumeShape,
+    offsets: &[(isize, isize, isize)],
+) -> Vec<f32> {
+    let mut reconstructed = marker
+        .iter()
+        .zip(mask)
+        .map(|(marker, mask)| marker.min(*mask))
+        .collect::<Vec<_>>();
+    let mut heap = (0..reconstructed.len())
+        .filter(|index| reconstructed[*index].is_finite())
+        .map(|index| (Priority(reconstructed[index], index as u64), index))
+        .collect::<BinaryHeap<_>>();
+    while let Some((Priority(level, _), index)) = heap.pop() {
+        if level < reconstructed[index] {
+            continue;
+        }
+        for neighbour in volume.neighbours(index, offsets) {
+            let candidate = level.min(mask[neighbour]);
+            if candidate > reconstructed[neighbour] {
+                reconstructed[neighbour] = candidate;
+                heap.push((Priority(candidate, neighbour as u64), neighbour));
+            }
+        }
+    }
+    reconstructed
+}
+
+/// Grayscale reconstruction by erosion of `marker` above `mask`.
+pub(super) fn reconstruct_by_erosion(
+    marker: &[f32],
+    mask: &[f32],
+    volume: VolumeShape,
+    offsets: &[(isize, isize, isize)],
+) -> Vec<f32> {
+    let negate = |values: &[f32]| values.iter().map(|value| -value).collect::<Vec<_>>();
+    negate(&reconstruct_by_dilation(
+        &negate(marker),
+        &negate(mask),
+        volume,
+        offsets,
+    ))
+}
+
+/// Suppresses minima shallower than `h`.
+pub(super) fn h_minima_transform(
+    values: &[f32],
+    h: f32,
+    volume: VolumeShape,
+    offsets: &[(isize, isize, isize)],
+) -> Vec<f32> {
+    let raised = values.iter().map(|value| value + h).collect::<Vec<_>>();
+    reconstruct_by_erosion(&raised, values, volume, offsets)
+}
+
+/// Suppresses maxima lower than `h`.
+fn h_maxima_transform(
+    values: &[f32],
+    h: f32,
+    volume: VolumeShape,
+    offsets: &[(isize, isize, isize)],
+) -> Vec<f32> {
+    let lowered = values.iter().map(|value| value - h).collect::<Vec<_>>();
+    reconstruct_by_dilation(&lowered, values, volume, offsets)
+}
+
+fn regional_maxima(
+    values: &[f32],
+    volume: VolumeShape,
+    offsets: &[(isize, isize, isize)],
+) -> Vec<i64> {
+    let negated = values.iter().map(|value| -value).collect::<Vec<_>>();
+    regional_minima(&negated, volume, offsets)
+}
+
+fn as_mask(labels: Vec<i64>) -> Vec<f32> {
+    labels
+        .into_iter()
+        .map(|label| if label > 0 { 1.0 } else { 0.0 })
+        .collect()
+}
+
+/// X/Y (and optionally Z) volumes of a dataset, enumerated over the remaining axes.
+struct Volumes {
+    shape: VolumeShape,
+    x_axis: usize,
+    y_axis: usize,
+    z_axis: Option<usize>,
+    outer_axes: Vec<usize>,
+    offsets: Vec<(isize, isize, isize)>,
+}
+
+impl Volumes {
+    /// Reads `stack_3d` and `connectivity`; with `skip_channel`, the channel axis
+    /// is left for the caller to index.
+    fn new(dataset: &DatasetF32, params: &Value, skip_channel: bool) -> Result<Self> {
+        let x_axis = axis_index(dataset, AxisKind::X)?;
+        let y_axis = axis_index(dataset, AxisKind::Y)?;
+        let z_axis = get_optional_bool(params, "stack_3d", true)
+            .then(|| dataset.axis_index(AxisKind::Z))
+            .flatten();
+        let channel_axis = skip_channel
+            .then(|| dataset.axis_index(AxisKind::Channel))
+            .flatten();
+        let offsets = connectivity_offsets(
+            get_optional_usize(params, "connectivity", if z_axis.is_some() { 6 } else { 4 }),
+            z_axis.is_some(),
+        )?;
+        let shape = dataset.shape();
+        let volume_axes = [Some(x_axis), Some(y_axis), z_axis, channel_axis];
+        Ok(Self {
+            shape: VolumeShape {
+                width: shape[x_axis],
+                height: shape[y_axis],
+                depth: z_axis.map(|axis| shape[axis]).unwrap_or(1),
+            },
+            x_axis,
+            y_axis,
+            z_axis,
+            outer_axes: (0..shape.len())
+                .filter(|axis| !volume_axes.contains(&Some(*axis)))
+                .collect(),
+            offsets,
+        })
+    }
+
+    /// Calls `callback` with the flat offset of every voxel of each volume, in
+    /// z, y, x order, once per row-major layout in `strides`.
+    fn for_each<const N: usize>(
+        &self,
+        dataset: &DatasetF32,
+        strides: [&[usize]; N],
+        mut callback: impl FnMut([&[usize]; N]),
+    ) {
+        let shape = dataset.shape();
+        let outer_shape = self
+            .outer_axes
+            .iter()
+            .map(|axis| shape[*axis])
+            .collect::<Vec<_>>();
+        // Offsets inside a volume are the same for every volume; only the base moves.
+        let local = strides.map(|strides| {
+            let mut offsets = Vec::with_capacity(self.shape.len());
+            let z_stride = self.z_axis.map(|axis| strides[axis]).unwrap_or(0);
+            for z in 0..self.shape.depth {
+                for y in 0..self.shape.height {
+                    let row = z * z_stride + y * strides[self.y_axis];
+                    offsets.extend((0..self.shape.width).map(|x| row + x * strides[self.x_axis]));
+                }
+            }
+            offsets
+        });
+        let mut positions = local.clone();
+        iterate_indices(&outer_shape, |outer_coord| {
+            for ((positions, local), strides) in positions.iter_mut().zip(&local).zip(strides) {
+                let base = self
+                    .outer_axes
+                    .iter()
+                    .zip(outer_coord)
+                    .map(|(axis, index)| index * strides[*axis])
+                    .sum::<usize>();
+                for (position, offset) in positions.iter_mut().zip(local) {
+                    *position = base + offset;
+                }
+            }
+            callback(positions.each_ref().map(Vec::as_slice));
+        });
+    }
+
+    /// Applies `transform` to every volume, channels included, keeping the layout.
+    fn map(
+        &self,
+        dataset: &DatasetF32,
+        transform: impl Fn(&[f32], VolumeShape, &[(isize, isize, isize)]) -> Vec<f32>,
+    ) -> ArrayD<f32> {
+        let mut output = dataset.data.as_standard_layout().into_owned();
+        let strides = row_major_strides(dataset.shape());
+        let data = output
+            .as_slice_mut()
+            .expect("standard layout arrays are contiguous");
+        // Volumes never overlap, so each one is read and rewritten in place.
+        self.for_each(dataset, [&strides], |[positions]| {
+            let values = positions
+                .iter()
+                .map(|position| data[*position])
+                .collect::<Vec<_>>();
+            let transformed = transform(&values, self.shape, &self.offsets);
+            for (position, value) in positions.iter().zip(transformed) {
+                data[*position] = value;
+            }
+        });
+        output
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExtremaKind {
+    RegionalMaxima,
+    RegionalMinima,
+    HMaxima,
+    HMinima,
+    ExtendedMaxima,
+    ExtendedMinima,
+}
+
+impl ExtremaKind {
+    fn uses_h(self) -> bool {
+        !matches!(self, Self::RegionalMaxima | Self::RegionalMinima)
+    }
+
+    fn apply(
+        self,
+        values: &[f32],
+        h: f32,
+        volume: VolumeShape,
+        offsets: &[(isize, isize, isize)],
+    ) -> Vec<f32> {
+        match self {
+            Self::RegionalMaxima => as_mask(regional_maxima(values, volume, offsets)),
+            Self::RegionalMinima => as_mask(regional_minima(values, volume, offsets)),
+            Self::HMaxima => h_maxima_transform(values, h, volume, offsets),
+            Self::HMinima => h_minima_transform(values, h, volume, offsets),
+            Self::ExtendedMaxima => as_mask(regional_maxima(
+                &h_maxima_transform(values, h, volume, offsets),
+                volume,
+                offsets,
+            )),
+            Self::ExtendedMinima => as_mask(regional_minima(
+                &h_minima_transform(values, h, volume, offsets),
+                volume,
+                offsets,
+            )),
+        }
+    }
+}
+
+fn extrema_params(uses_h: bool) -> Vec<ParamSpec> {
+    let mut params = Vec::new();
+    if uses_h {
+        params.push(ParamSpec {
+            name: "h".to_string(),
+            description: "Dynamic: minimum height of the extrema that are kept.".to_string(),
+            required: true,
+            kind: "float".to_string(),
+        });
+    }
+    params.extend([
+        ParamSpec {
+            name: "connectivity".to_string(),
+            description: "4 (default) or 8 in 2D; 6 (default), 18 or 26 in 3D.".to_string(),
+            required: false,
+            kind: "int".to_string(),
+        },
+        ParamSpec {
+            name: "stack_3d".to_string(),
+            description:
+                "Process X/Y/Z volumes instead of single slices when a Z axis exists (default true)."
+                    .to_string(),
+            required: false,
+            kind: "bool".to_string(),
+        },
+    ]);
+    params
+}
+
+fn execute_extrema(dataset: &DatasetF32, params: &Value, kind: ExtremaKind) -> Result<OpOutput> {
+    let h = if kind.uses_h() {
+        let h = get_required_f32(params, "h")?;
+        if !h.is_finite() || h < 0.0 {
+            return Err(OpsError::InvalidParams(
+                "`h` must be finite and >= 0".to_string(),
+            ));
+        }
+        h
+    } else {
+        0.0
+    };
+    let volumes = Volumes::new(dataset, params, false)?;
+    let output = volumes.map(dataset, |values, volume, offsets| {
+        kind.apply(values, h, volume, offsets)
+    });
+    Ok(OpOutput::dataset_only(Dataset::new(
+        output,
+        dataset.metadata.clone(),
+    )?))
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyRegionalMaximaOp;
+
+impl Operation for MorphologyRegionalMaximaOp {
+    fn name(&self) -> &'static str {
+        "morphology.regional_maxima"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Binary mask of plateaus with no higher neighbour.".to_string(),
+            params: extrema_params(false),
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        execute_extrema(dataset, params, ExtremaKind::RegionalMaxima)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyRegionalMinimaOp;
+
+impl Operation for MorphologyRegionalMinimaOp {
+    fn name(&self) -> &'static str {
+        "morphology.regional_minima"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Binary mask of plateaus with no lower neighbour.".to_string(),
+            params: extrema_params(false),
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        execute_extrema(dataset, params, ExtremaKind::RegionalMinima)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyHMaximaOp;
+
+impl Operation for MorphologyHMaximaOp {
+    fn name(&self) -> &'static str {
+        "morphology.h_maxima"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "H-maxima transform: flattens maxima whose dynamic is below h."
+                .to_string(),
+            params: extrema_params(true),
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        execute_extrema(dataset, params, ExtremaKind::HMaxima)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyHMinimaOp;
+
+impl Operation for MorphologyHMinimaOp {
+    fn name(&self) -> &'static str {
+        "morphology.h_minima"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "H-minima transform: fills minima whose dynamic is below h.".to_string(),
+            params: extrema_params(true),
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        execute_extrema(dataset, params, ExtremaKind::HMinima)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyExtendedMaximaOp;
+
+impl Operation for MorphologyExtendedMaximaOp {
+    fn name(&self) -> &'static str {
+        "morphology.extended_maxima"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Binary mask of the regional maxima of the h-maxima transform."
+                .to_string(),
+            params: extrema_params(true),
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        execute_extrema(dataset, params, ExtremaKind::ExtendedMaxima)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyExtendedMinimaOp;
+
+impl Operation for MorphologyExtendedMinimaOp {
+    fn name(&self) -> &'static str {
+        "morphology.extended_minima"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Binary mask of the regional minima of the h-minima transform."
+                .to_string(),
+            params: extrema_params(true),
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        execute_extrema(dataset, params, ExtremaKind::ExtendedMinima)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyImposeMinimaOp;
+
+/// Where `morphology.impose_minima` reads its markers from.
+enum Markers {
+    /// A separate marker image, read through `indices` (one per pixel of the dataset).
+    Input {
+        values: Vec<f32>,
+        indices: Vec<usize>,
+    },
+    Channel(usize),
+}
+
+impl Operation for MorphologyImposeMinimaOp {
+    fn name(&self) -> &'static str {
+        "morphology.impose_minima"
+    }
+
+    fn schema(&self) -> OpSchema {
+        let mut params = vec![
+            ParamSpec {
+                name: "channel".to_string(),
+                description: "Channel holding the image to modify (default 0).".to_string(),
+                required: false,
+                kind: "int".to_string(),
+            },
+            ParamSpec {
+                name: "markers".to_string(),
+                description: "Marker image whose pixels > 0.5 become the only regional minima; takes precedence over `marker_channel`.".to_string(),
+                required: false,
+                kind: INPUT_PARAM_KIND.to_string(),
+            },
+            ParamSpec {
+                name: "marker_channel".to_string(),
+                description: "Channel whose pixels > 0.5 become the only regional minima, used when no `markers` image is given."
+                    .to_string(),
+                required: false,
+                kind: "int".to_string(),
+            },
+        ];
+        params.extend(extrema_params(false));
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Minima imposition: raise the image so its only regional minima lie on the markers."
+                    .to_string(),
+            params,
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        let channel_axis = dataset.axis_index(AxisKind::Channel);
+        let channel_count = dataset.metadata.channel_count();
+        let channel = get_optional_usize(params, "channel", 0);
+        let marker_channel = params
+            .get("marker_channel")
+            .and_then(Value::as_u64)
+            .map(|value| value as usize);
+        for (key, value) in [
+            ("channel", Some(channel)),
+            ("marker_channel", marker_channel),
+        ] {
+            if let Some(value) = value
+                && value >= channel_count
+            {
+                return Err(OpsError::InvalidParams(format!(
+                    "`{key}` {value} is out of range for {channel_count} channel(s)"
+                )));
+            }
+        }
+        let markers = match (inputs.get("markers"), marker_channel) {
+            (Some(markers), _) => Markers::Input {
+                indices: broadcast(dataset, markers, "markers")?,
+                values: markers.data.iter().copied().collect(),
+            },
+            (None, Some(marker_channel)) => Markers::Channel(marker_channel),
+            (None, None) => {
+                return Err(OpsError::InvalidParams(
+                    "a `markers` input or `marker_channel` is required".to_string(),
+                ));
+            }
+        };
+
+        let volumes = Volumes::new(dataset, params, true)?;
+        let output_axes = (0..dataset.ndim())
+            .filter(|axis| Some(*axis) != channel_axis)
+            .collect::<Vec<_>>();
+        let output_shape = output_axes
+            .iter()
+            .map(|axis| dataset.shape()[*axis])
+            .collect::<Vec<_>>();
+        let mut output = ArrayD::<f32>::zeros(IxDyn(&output_shape));
+        let input = dataset.data.as_standard_layout();
+        let input = input
+            .as_slice()
+            .expect("standard layout arrays are contiguous");
+        let strides = row_major_strides(dataset.shape());
+        // The output has no channel axis; a zero stride lets it share the volume walk.
+        let mut output_strides = row_major_strides(&output_shape);
+        if let Some(channel_axis) = channel_axis {
+            output_strides.insert(channel_axis, 0);
+        }
+        let channel_offset =
+            |channel: usize| channel_axis.map_or(0, |axis| channel * strides[axis]);
+        let imposed_values = output
+            .as_slice_mut()
+            .expect("standard layout arrays are contiguous");
+        volumes.for_each(
+            dataset,
+            [&strides, &output_strides],
+            |[positions, output_positions]| {
+                let read = |channel: usize| {
+                    positions
+                        .iter()
+                        .map(|position| input[position + channel_offset(channel)])
+                        .collect::<Vec<_>>()
+                };
+                let values = read(channel);
+                let markers = match &markers {
+                    Markers::Input { values, indices } => positions
+                        .iter()
+                        .map(|position| values[indices[position + channel_offset(channel)]])
+                        .collect::<Vec<_>>(),
+                    Markers::Channel(marker_channel) => read(*marker_channel),
+                };
+                // Markers sit strictly below every other value; elsewhere the marker
+                // image is +inf, so the reconstruction only keeps minima on markers.
+                let floor = values
+                    .iter()
+                    .copied()
+                    .filter(|value| value.is_finite())
+                    .fold(f32::INFINITY, f32::min)
+                    - 1.0;
+                let marker = markers
+                    .iter()
+                    .map(|value| if *value > 0.5 { floor } else { f32::INFINITY })
+                    .collect::<Vec<_>>();
+                let mask = values
+                    .iter()
+                    .zip(&marker)
+                    .map(|(value, marker)| value.min(*marker))
+                    .collect::<Vec<_>>();
+                let imposed =
+                    reconstruct_by_erosion(&marker, &mask, volumes.shape, &volumes.offsets);
+                for (position, value) in output_positions.iter().zip(imposed) {
+                    imposed_values[*position] = value;
+                }
+            },
+        );
+
+        let mut metadata = Metadata {
+            dims: output_axes
+                .iter()
+                .map(|axis| dataset.metadata.dims[*axis].clone())
+                .collect(),
+            ..dataset.metadata.clone()
+        };
+        if channel_axis.is_some() {
+            metadata.channel_names.clear();
+            metadata.display = None;
+        }
+        Ok(OpOutput::dataset_only(Dataset::new(output, metadata)?))
+    }
+}
+
+fn axis_index(dataset: &DatasetF32, axis: AxisKind) -> Result<usize> {
+    dataset
+        .axis_index(axis)
+        .ok_or_else(|| OpsError::UnsupportedLayout(format!("dataset has no {axis:?} axis")))
+}
+
+fn iterate_indices(shape: &[usize], mut callback: impl FnMut(&[usize])) {
+    if shape.is_empty() {
+        callback(&[]);
+        return;
+    }
+    let mut index = vec![0usize; shape.len()];
+    loop {
+        callback(&index);
+        let mut dim = shape.len();
+        while dim > 0 {
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+            if dim == 0 {
+                return;
+            }
+        }
+    }
+}
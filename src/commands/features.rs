@@ -0,0 +1,865 @@
+use crate::model::{AxisKind, Dataset, DatasetF32, Dim, PixelType};
+use ndarray::{ArrayD, IxDyn};
+use serde_json::Value;
+
+use super::{
+    OpOutput, OpSchema, Operation, OpsError, ParamSpec, Result,
+    gaussian::{blur_axis, row_major_strides},
+    get_optional_bool, get_optional_f32,
+    util::gaussian_derivative_kernel,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct FeaturesLaplacianOfGaussianOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FeaturesDifferenceOfGaussiansOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FeaturesGradientMagnitudeOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FeaturesHessianOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FeaturesStructureTensorOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FeaturesFrangiOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FeaturesSatoOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct FeaturesMeijeringOp;
+
+fn param(name: &str, description: &str, kind: &str) -> ParamSpec {
+    ParamSpec {
+        name: name.to_string(),
+        description: description.to_string(),
+        required: false,
+        kind: kind.to_string(),
+    }
+}
+
+/// Parameters shared by every feature filter.
+fn scale_params(sigma_description: &str) -> Vec<ParamSpec> {
+    let mut params = vec![param("sigma", sigma_description, "float")];
+    params.extend(axis_params());
+    params
+}
+
+fn axis_params() -> Vec<ParamSpec> {
+    vec![
+        param(
+            "calibrated",
+            "Read sigma in `Dim::spacing` units and differentiate per calibrated unit (default true).",
+            "bool",
+        ),
+        param(
+            "stack_3d",
+            "Include the Z axis when present (default true); false works per X/Y plane.",
+            "bool",
+        ),
+    ]
+}
+
+/// Parameters shared by the multi-scale ridge filters.
+fn ridge_params() -> Vec<ParamSpec> {
+    let mut params = vec![
+        param(
+            "sigmas",
+            "Gaussian scales to search (default [1, 2, 3]); the strongest response is kept.",
+            "array",
+        ),
+        param(
+            "polarity",
+            "bright (default) for bright ridges on a dark background, or dark.",
+            "string",
+        ),
+        param(
+            "return_scale",
+            "Append a `scale` channel with the sigma of the strongest response, 0 where none responds (default false).",
+            "bool",
+        ),
+    ];
+    params.extend(axis_params());
+    params
+}
+
+const SIGMA_DESCRIPTION: &str =
+    "Gaussian scale (default 1); a number or an [x, y, z] array for anisotropic scales.";
+
+impl Operation for FeaturesLaplacianOfGaussianOp {
+    fn name(&self) -> &'static str {
+        "features.log"
+    }
+
+    fn schema(&self) -> OpSchema {
+        let mut params = scale_params(SIGMA_DESCRIPTION);
+        params.push(param(
+            "normalize",
+            "Multiply by sigma² so responses compare across scales (default false).",
+            "bool",
+        ));
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Laplacian of Gaussian over X/Y (and Z); bright spots give negative responses."
+                    .to_string(),
+            params,
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let scale = Scale::read(dataset, params, "sigma", 1.0)?;
+        let normalize = get_optional_bool(params, "normalize", false);
+        let mut laplacian = vec![0.0_f32; dataset.data.len()];
+        for index in 0..scale.axes.len() {
+            let mut orders = vec![0; scale.axes.len()];
+            orders[index] = 2;
+            let weight = if normalize {
+                scale.axes[index].sigma.powi(2)
+            } else {
+                1.0
+            };
+            for (total, value) in laplacian.iter_mut().zip(scale.derivative(&orders)) {
+                *total += weight * value;
+            }
+        }
+        Ok(OpOutput::dataset_only(float_dataset(dataset, laplacian)?))
+    }
+}
+
+impl Operation for FeaturesDifferenceOfGaussiansOp {
+    fn name(&self) -> &'static str {
+        "features.dog"
+    }
+
+    fn schema(&self) -> OpSchema {
+        let mut params =
+            scale_params("Smaller Gaussian scale (default 1); a number or an [x, y, z] array.");
+        params.push(param(
+            "sigma2",
+            "Larger Gaussian scale (default 1.6 x sigma); a number or an [x, y, z] array.",
+            "float",
+        ));
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Difference of Gaussians: blur(sigma) - blur(sigma2); bright spots give positive responses."
+                .to_string(),
+            params,
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let fine = Scale::read(dataset, params, "sigma", 1.0)?;
+        let coarse = match params.get("sigma2") {
+            Some(_) => Scale::read(dataset, params, "sigma2", 1.6)?,
+            None => fine.scaled(1.6),
+        };
+        let orders = vec![0; fine.axes.len()];
+        let difference = fine
+            .derivative(&orders)
+            .into_iter()
+            .zip(coarse.derivative(&orders))
+            .map(|(fine, coarse)| fine - coarse)
+            .collect();
+        Ok(OpOutput::dataset_only(float_dataset(dataset, difference)?))
+    }
+}
+
+impl Operation for FeaturesGradientMagnitudeOp {
+    fn name(&self) -> &'static str {
+        "features.gradient_magnitude"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Gaussian gradient magnitude over X/Y (and Z).".to_string(),
+            params: scale_params(SIGMA_DESCRIPTION),
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let scale = Scale::read(dataset, params, "sigma", 1.0)?;
+        let mut squared = vec![0.0_f32; dataset.data.len()];
+        for gradient in scale.gradient() {
+            for (total, value) in squared.iter_mut().zip(gradient) {
+                *total += value * value;
+            }
+        }
+        let magnitude = squared.into_iter().map(f32::sqrt).collect();
+        Ok(OpOutput::dataset_only(float_dataset(dataset, magnitude)?))
+    }
+}
+
+impl Operation for FeaturesHessianOp {
+    fn name(&self) -> &'static str {
+        "features.hessian"
+    }
+
+    fn schema(&self) -> OpSchema {
+        let mut params = scale_params(SIGMA_DESCRIPTION);
+        params.push(param(
+            "normalize",
+            "Multiply second derivatives by the product of their sigmas (default false).",
+            "bool",
+        ));
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Eigenvalues of the Gaussian Hessian as channels, largest first."
+                .to_string(),
+            params,
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let scale = Scale::read(dataset, params, "sigma", 1.0)?;
+        reject_channels(dataset)?;
+        let normalize = get_optional_bool(params, "normalize", false);
+        let eigenvalues = scale.hessian_eigenvalues(normalize);
+        let channels = eigenvalues
+            .into_iter()
+            .enumerate()
+            .map(|(index, values)| (format!("eigenvalue {}", index + 1), values))
+            .collect();
+        Ok(OpOutput::dataset_only(channel_dataset(dataset, channels)?))
+    }
+}
+
+impl Operation for FeaturesStructureTensorOp {
+    fn name(&self) -> &'static str {
+        "features.structure_tensor"
+    }
+
+    fn schema(&self) -> OpSchema {
+        let mut params =
+            scale_params("Derivative scale (default 1); a number or an [x, y, z] array.");
+        params.push(param(
+            "integration_sigma",
+            "Scale the gradient products are averaged over (default 2 x sigma); a number or an [x, y, z] array.",
+            "float",
+        ));
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Structure-tensor eigenvalues (largest first) and coherency as channels."
+                .to_string(),
+            params,
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let scale = Scale::read(dataset, params, "sigma", 1.0)?;
+        reject_channels(dataset)?;
+        let integration = match params.get("integration_sigma") {
+            Some(_) => Scale::read(dataset, params, "integration_sigma", 2.0)?,
+            None => scale.scaled(2.0),
+        };
+        let rank = scale.axes.len();
+        let gradient = scale.gradient();
+        let smoothing = vec![0; rank];
+        let mut tensor = Vec::with_capacity(rank * (rank + 1) / 2);
+        for row in 0..rank {
+            for column in row..rank {
+                let product = gradient[row]
+                    .iter()
+                    .zip(&gradient[column])
+                    .map(|(a, b)| a * b)
+                    .collect::<Vec<_>>();
+                tensor.push(integration.filter(&product, &smoothing));
+            }
+        }
+        let eigenvalues = symmetric_eigenvalues(&tensor, rank, dataset.data.len());
+        let (largest, smallest) = (&eigenvalues[0], &eigenvalues[rank - 1]);
+        let coherency = largest
+            .iter()
+            .zip(smallest)
+            .map(|(largest, smallest)| {
+                let sum = largest + smallest;
+                if sum > f32::EPSILON {
+                    (largest - smallest) / sum
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let mut channels = eigenvalues
+            .into_iter()
+            .enumerate()
+            .map(|(index, values)| (format!("eigenvalue {}", index + 1), values))
+            .collect::<Vec<_>>();
+        channels.push(("coherency".to_string(), coherency));
+        Ok(OpOutput::dataset_only(channel_dataset(dataset, channels)?))
+    }
+}
+
+impl Operation for FeaturesFrangiOp {
+    fn name(&self) -> &'static str {
+        "features.frangi"
+    }
+
+    fn schema(&self) -> OpSchema {
+        let mut params = ridge_params();
+        params.extend([
+            param(
+                "alpha",
+                "Sensitivity to plate-like versus line-like structures in 3D (default 0.5).",
+                "float",
+            ),
+            param(
+                "beta",
+                "Sensitivity to blob-like structures (default 0.5).",
+                "float",
+            ),
+            param(
+                "gamma",
+                "Sensitivity to overall second-order structure (default half the largest Hessian norm at each scale).",
+                "float",
+            ),
+        ]);
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Frangi vesselness from scale-normalized Hessian eigenvalues.".to_string(),
+            params,
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let ridges = Ridges::read(dataset, params)?;
+        let alpha = positive(params, "alpha", 0.5)?;
+        let beta = positive(params, "beta", 0.5)?;
+        let gamma = match params.get("gamma") {
+            Some(_) => Some(positive(params, "gamma", 1.0)?),
+            None => None,
+        };
+        ridges.execute(dataset, "vesselness", |eigenvalues| {
+            let rank = eigenvalues.len();
+            let len = eigenvalues[0].len();
+            let norms = (0..len)
+                .map(|index| {
+                    eigenvalues
+                        .iter()
+                        .map(|values| values[index].powi(2))
+                        .sum::<f32>()
+                        .sqrt()
+                })
+                .collect::<Vec<_>>();
+            let gamma = gamma.unwrap_or_else(|| {
+                let largest = norms.iter().copied().fold(0.0_f32, f32::max);
+                if largest > 0.0 { largest / 2.0 } else { 1.0 }
+            });
+            let mut sorted = vec![0.0_f32; rank];
+            (0..len)
+                .map(|index| {
+                    for (target, values) in sorted.iter_mut().zip(eigenvalues) {
+                        *target = values[index];
+                    }
+                    sorted.sort_by(|a, b| a.abs().total_cmp(&b.abs()));
+                    let across = sorted[1..]
+                        .iter()
+                        .map(|value| value.max(1e-10))
+                        .collect::<Vec<_>>();
+                    let (plate, blob) = if rank == 2 {
+                        (1.0, sorted[0].abs() / across[0])
+                    } else {
+                        let ratio = across[0] / across[1];
+                        (
+                            1.0 - (-ratio * ratio / (2.0 * alpha * alpha)).exp(),
+                            sorted[0].abs() / (across[0] * across[1]).sqrt(),
+                        )
+                    };
+                    let structure = norms[index];
+                    plate
+                        * (-blob * blob / (2.0 * beta * beta)).exp()
+                        * (1.0 - (-structure * structure / (2.0 * gamma * gamma)).exp())
+                })
+                .collect()
+        })
+    }
+}
+
+impl Operation for FeaturesSatoOp {
+    fn name(&self) -> &'static str {
+        "features.sato"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Sato tubeness: geometric mean of the cross-sectional Hessian eigenvalues."
+                    .to_string(),
+            params: ridge_params(),
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let ridges = Ridges::read(dataset, params)?;
+        ridges.execute(dataset, "tubeness", |eigenvalues| {
+            let across = &eigenvalues[..eigenvalues.len() - 1];
+            let exponent = 1.0 / across.len() as f32;
+            (0..eigenvalues[0].len())
+                .map(|index| {
+                    across
+                        .iter()
+                        .map(|values| values[index].max(0.0))
+                        .product::<f32>()
+                        .powf(exponent)
+                })
+                .collect()
+        })
+    }
+}
+
+impl Operation for FeaturesMeijeringOp {
+    fn name(&self) -> &'static str {
+        "features.meijering"
+    }
+
+    fn schema(&self) -> OpSchema {
+        let mut params = ridge_params();
+        params.push(param(
+            "alpha",
+            "Weight of the other eigenvalues in each modified eigenvalue (default -1/(dimensions + 1)).",
+            "float",
+        ));
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Meijering neuriteness from modified Hessian eigenvalues, scaled to 1 per scale."
+                    .to_string(),
+            params,
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let ridges = Ridges::read(dataset, params)?;
+        let alpha = params
+            .get("alpha")
+            .map(|_| get_optional_f32(params, "alpha", 0.0))
+            .unwrap_or(-1.0 / (ridges.base.axes.len() + 1) as f32);
+        if !alpha.is_finite() {
+            return Err(OpsError::InvalidParams(
+                "`alpha` must be finite".to_string(),
+            ));
+        }
+        ridges.execute(dataset, "neuriteness", |eigenvalues| {
+            let mut responses = (0..eigenvalues[0].len())
+                .map(|index| {
+                    let total = eigenvalues.iter().map(|values| values[index]).sum::<f32>();
+                    eigenvalues
+                        .iter()
+                        .map(|values| values[index] + alpha * (total - values[index]))
+                        .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+                        .unwrap_or(0.0)
+                        .max(0.0)
+                })
+                .collect::<Vec<_>>();
+            let largest = responses.iter().copied().fold(0.0_f32, f32::max);
+            if largest > 0.0 {
+                responses.iter_mut().for_each(|value| *value /= largest);
+            }
+            responses
+        })
+    }
+}
+
+/// Multi-scale driver shared by the ridge filters. Eigenvalues handed to the
+/// response are those of the scale-normalized Hessian, largest first, with
+/// the sign flipped for bright ridges so that a ridge's cross-section always
+/// has large positive eigenvalues.
+struct Ridges {
+    base: Scale,
+    sigmas: Vec<f32>,
+    bright: bool,
+    return_scale: bool,
+}
+
+impl Ridges {
+    fn read(dataset: &DatasetF32, params: &Value) -> Result<Self> {
+        reject_channels(dataset)?;
+        let sigmas = match params.get("sigmas") {
+            None => vec![1.0, 2.0, 3.0],
+            Some(Value::Array(values)) => values
+                .iter()
+                .map(|value| value.as_f64().map(|value| value as f32))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| {
+                    OpsError::InvalidParams("`sigmas` values must be numbers".to_string())
+                })?,
+            Some(value) => vec![value.as_f64().ok_or_else(|| {
+                OpsError::InvalidParams("`sigmas` must be a number or an array".to_string())
+            })? as f32],
+        };
+        if sigmas.is_empty()
+            || sigmas
+                .iter()
+                .any(|sigma| !sigma.is_finite() || *sigma <= 0.0)
+        {
+            return Err(OpsError::InvalidParams(
+                "`sigmas` must be a non-empty list of values > 0".to_string(),
+            ));
+        }
+        let bright = match params.get("polarity").and_then(Value::as_str) {
+            None => true,
+            Some(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "bright" => true,
+                "dark" => false,
+                other => {
+                    return Err(OpsError::InvalidParams(format!(
+                        "unsupported `polarity` `{other}`; expected bright or dark"
+                    )));
+                }
+            },
+        };
+        Ok(Self {
+            base: Scale::isotropic(dataset, params, 1.0)?,
+            sigmas,
+            bright,
+            return_scale: get_optional_bool(params, "return_scale", false),
+        })
+    }
+
+    fn execute(
+        &self,
+        dataset: &DatasetF32,
+        name: &str,
+        response: impl Fn(&[Vec<f32>]) -> Vec<f32>,
+    ) -> Result<OpOutput> {
+        let len = dataset.data.len();
+        let mut best = vec![0.0_f32; len];
+        let mut best_scale = vec![0.0_f32; len];
+        for sigma in &self.sigmas {
+            let mut eigenvalues = self.base.scaled(*sigma).hessian_eigenvalues(true);
+            if self.bright {
+                eigenvalues.reverse();
+                for values in &mut eigenvalues {
+                    values.iter_mut().for_each(|value| *value = -*value);
+                }
+            }
+            for ((value, scale), candidate) in best
+                .iter_mut()
+                .zip(&mut best_scale)
+                .zip(response(&eigenvalues))
+            {
+                if candidate > *value {
+                    *value = candidate;
+                    *scale = *sigma;
+                }
+            }
+        }
+        let output = if self.return_scale {
+            channel_dataset(
+                dataset,
+                vec![(name.to_string(), best), ("scale".to_string(), best_scale)],
+            )?
+        } else {
+            float_dataset(dataset, best)?
+        };
+        Ok(OpOutput::dataset_only(output))
+    }
+}
+
+fn positive(params: &Value, key: &str, default: f32) -> Result<f32> {
+    let value = get_optional_f32(params, key, default);
+    if value.is_finite() && value > 0.0 {
+        Ok(value)
+    } else {
+        Err(OpsError::InvalidParams(format!("`{key}` must be > 0")))
+    }
+}
+
+/// Per-axis Gaussian scale over the differentiated spatial axes.
+#[derive(Debug, Clone, Copy)]
+struct AxisScale {
+    axis: usize,
+    /// Scale in derivative units: calibrated units when calibrated, else pixels.
+    sigma: f32,
+    /// Pixel size in derivative units.
+    spacing: f32,
+}
+
+/// Dataset values in row-major order with the axes to filter along.
+struct Scale {
+    values: Vec<f32>,
+    shape: Vec<usize>,
+    strides: Vec<usize>,
+    axes: Vec<AxisScale>,
+}
+
+impl Scale {
+    fn read(dataset: &DatasetF32, params: &Value, key: &str, default: f32) -> Result<Self> {
+        let kinds = Self::axis_kinds(dataset, params);
+        let sigmas = match params.get(key) {
+            Some(Value::Array(values)) => {
+                if values.len() < kinds.len() {
+                    return Err(OpsError::InvalidParams(format!(
+                        "`{key}` needs one value per axis ({})",
+                        kinds.len()
+                    )));
+                }
+                values
+                    .iter()
+                    .map(|value| value.as_f64().map(|value| value as f32))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| {
+                        OpsError::InvalidParams(format!("`{key}` values must be numbers"))
+                    })?
+            }
+            _ => vec![get_optional_f32(params, key, default); kinds.len()],
+        };
+        if sigmas
+            .iter()
+            .any(|sigma| !sigma.is_finite() || *sigma <= 0.0)
+        {
+            return Err(OpsError::InvalidParams(format!("`{key}` must be > 0")));
+        }
+
+        Self::with_sigmas(dataset, params, &kinds, sigmas)
+    }
+
+    /// The same scale along every filtered axis.
+    fn isotropic(dataset: &DatasetF32, params: &Value, sigma: f32) -> Result<Self> {
+        let kinds = Self::axis_kinds(dataset, params);
+        let sigmas = vec![sigma; kinds.len()];
+        Self::with_sigmas(dataset, params, &kinds, sigmas)
+    }
+
+    fn axis_kinds(dataset: &DatasetF32, params: &Value) -> Vec<AxisKind> {
+        let stack_3d = get_optional_bool(params, "stack_3d", true);
+        let mut kinds = vec![AxisKind::X, AxisKind::Y];
+        if stack_3d && dataset.axis_index(AxisKind::Z).is_some() {
+            kinds.push(AxisKind::Z);
+        }
+        kinds
+    }
+
+    fn with_sigmas(
+        dataset: &DatasetF32,
+        params: &Value,
+        kinds: &[AxisKind],
+        sigmas: Vec<f32>,
+    ) -> Result<Self> {
+        let calibrated = get_optional_bool(params, "calibrated", true);
+        let axes = kinds
+            .iter()
+            .zip(sigmas)
+            .map(|(kind, sigma)| {
+                let axis = dataset.axis_index(*kind).ok_or_else(|| {
+                    OpsError::UnsupportedLayout(format!("dataset has no {kind:?} axis"))
+                })?;
+                let spacing = if calibrated {
+                    dataset.metadata.dims[axis].spacing.unwrap_or(1.0)
+                } else {
+                    1.0
+                };
+                Ok(AxisScale {
+                    axis,
+                    sigma,
+                    spacing,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let shape = dataset.shape().to_vec();
+        Ok(Self {
+            values: dataset.data.iter().copied().collect(),
+            strides: row_major_strides(&shape),
+            shape,
+            axes,
+        })
+    }
+
+    fn scaled(&self, factor: f32) -> Self {
+        Self {
+            values: self.values.clone(),
+            shape: self.shape.clone(),
+            strides: self.strides.clone(),
+            axes: self
+                .axes
+                .iter()
+                .map(|scale| AxisScale {
+                    sigma: scale.sigma * factor,
+                    ..*scale
+                })
+                .collect(),
+        }
+    }
+
+    /// Gaussian derivative of the dataset with `orders[i]` along `axes[i]`.
+    fn derivative(&self, orders: &[usize]) -> Vec<f32> {
+        self.filter(&self.values, orders)
+    }
+
+    fn filter(&self, values: &[f32], orders: &[usize]) -> Vec<f32> {
+        let mut current = values.to_vec();
+        for (scale, order) in self.axes.iter().zip(orders) {
+            if self.shape[scale.axis] <= 1 {
+                if *order > 0 {
+                    return vec![0.0; values.len()];
+                }
+                continue;
+            }
+            let kernel = gaussian_derivative_kernel(scale.sigma / scale.spacing, *order)
+                .into_iter()
+                .map(|weight| weight / scale.spacing.powi(*order as i32))
+                .collect::<Vec<_>>();
+            let radius = (kernel.len() / 2) as isize;
+            current = blur_axis(
+                &current,
+                &self.shape,
+                &self.strides,
+                scale.axis,
+                &kernel,
+                radius,
+            );
+        }
+        current
+    }
+
+    /// Eigenvalues of the Gaussian Hessian, largest first; `normalize`
+    /// multiplies each second derivative by the product of its sigmas.
+    fn hessian_eigenvalues(&self, normalize: bool) -> Vec<Vec<f32>> {
+        let rank = self.axes.len();
+        let mut tensor = Vec::with_capacity(rank * (rank + 1) / 2);
+        for row in 0..rank {
+            for column in row..rank {
+                let mut orders = vec![0; rank];
+                orders[row] += 1;
+                orders[column] += 1;
+                let weight = if normalize {
+                    self.axes[row].sigma * self.axes[column].sigma
+                } else {
+                    1.0
+                };
+                tensor.push(
+                    self.derivative(&orders)
+                        .into_iter()
+                        .map(|value| weight * value)
+                        .collect::<Vec<_>>(),
+                );
+            }
+        }
+        symmetric_eigenvalues(&tensor, rank, self.values.len())
+    }
+
+    fn gradient(&self) -> Vec<Vec<f32>> {
+        (0..self.axes.len())
+            .map(|index| {
+                let mut orders = vec![0; self.axes.len()];
+                orders[index] = 1;
+                self.derivative(&orders)
+            })
+            .collect()
+    }
+}
+
+fn reject_channels(dataset: &DatasetF32) -> Result<()> {
+    if dataset.metadata.channel_count() > 1 {
+        return Err(OpsError::UnsupportedLayout(
+            "tensor features need a single-channel image".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Eigenvalues, largest first, of symmetric `rank` x `rank` tensors stored as
+/// upper-triangle component images in row order (xx, xy, [xz,] yy, [yz, zz]).
+fn symmetric_eigenvalues(tensor: &[Vec<f32>], rank: usize, len: usize) -> Vec<Vec<f32>> {
+    let mut eigenvalues = vec![Vec::with_capacity(len); rank];
+    let mut components = vec![0.0_f64; tensor.len()];
+    for index in 0..len {
+        for (value, image) in components.iter_mut().zip(tensor) {
+            *value = f64::from(image[index]);
+        }
+        let component = |k: usize| components[k];
+        let mut values = if rank == 2 {
+            let (xx, xy, yy) = (component(0), component(1), component(2));
+            let mean = (xx + yy) / 2.0;
+            let radius = ((xx - yy) / 2.0).hypot(xy);
+            vec![mean + radius, mean - radius]
+        } else {
+            let (xx, xy, xz, yy, yz, zz) = (
+                component(0),
+                component(1),
+                component(2),
+                component(3),
+                component(4),
+                component(5),
+            );
+            symmetric_3x3_eigenvalues([[xx, xy, xz], [xy, yy, yz], [xz, yz, zz]]).to_vec()
+        };
+        values.sort_by(|a, b| b.total_cmp(a));
+        for (target, value) in eigenvalues.iter_mut().zip(values) {
+            target.push(value as f32);
+        }
+    }
+    eigenvalues
+}
+
+/// Closed-form eigenvalues of a symmetric 3x3 matrix (Smith, 1961).
+fn symmetric_3x3_eigenvalues(matrix: [[f64; 3]; 3]) -> [f64; 3] {
+    let off_diagonal = matrix[0][1].powi(2) + matrix[0][2].powi(2) + matrix[1][2].powi(2);
+    if off_diagonal <= f64::EPSILON * 1e-3 {
+        return [matrix[0][0], matrix[1][1], matrix[2][2]];
+    }
+    let q = (matrix[0][0] + matrix[1][1] + matrix[2][2]) / 3.0;
+    let p = ((0..3).map(|i| (matrix[i][i] - q).powi(2)).sum::<f64>() + 2.0 * off_diagonal) / 6.0;
+    let p = p.sqrt();
+    let b = |i: usize, j: usize| (matrix[i][j] - if i == j { q } else { 0.0 }) / p;
+    let determinant = b(0, 0) * (b(1, 1) * b(2, 2) - b(1, 2) * b(2, 1))
+        - b(0, 1) * (b(1, 0) * b(2, 2) - b(1, 2) * b(2, 0))
+        + b(0, 2) * (b(1, 0) * b(2, 1) - b(1, 1) * b(2, 0));
+    let phi = (determinant / 2.0).clamp(-1.0, 1.0).acos() / 3.0;
+    let largest = q + 2.0 * p * phi.cos();
+    let smallest = q + 2.0 * p * (phi + 2.0 * std::f64::consts::PI / 3.0).cos();
+    [largest, 3.0 * q - largest - smallest, smallest]
+}
+
+fn float_dataset(dataset: &DatasetF32, values: Vec<f32>) -> Result<DatasetF32> {
+    let data = ArrayD::from_shape_vec(IxDyn(dataset.shape()), values)
+        .map_err(|_| OpsError::UnsupportedLayout("failed to build feature image".to_string()))?;
+    let mut metadata = dataset.metadata.clone();
+    metadata.pixel_type = PixelType::F32;
+    metadata.display = None;
+    Ok(Dataset::new(data, metadata)?)
+}
+
+/// Output with one channel per feature image; a size-1 channel axis is
+/// reused, otherwise a channel axis is appended.
+fn channel_dataset(dataset: &DatasetF32, channels: Vec<(String, Vec<f32>)>) -> Result<DatasetF32> {
+    let strides = row_major_strides(dataset.shape());
+    let mut metadata = dataset.metadata.clone();
+    let channel_axis = match dataset.axis_index(AxisKind::Channel) {
+        Some(axis) => {
+            metadata.dims[axis].size = channels.len();
+            axis
+        }
+        None => {
+            metadata
+                .dims
+                .push(Dim::new(AxisKind::Channel, channels.len()));
+            metadata.dims.len() - 1
+        }
+    };
+    let shape = metadata.dims.iter().map(|dim| dim.size).collect::<Vec<_>>();
+    let data = ArrayD::from_shape_fn(IxDyn(&shape), |index| {
+        let offset = strides
+            .iter()
+            .enumerate()
+            .map(|(axis, stride)| {
+                if axis == channel_axis {
+                    0
+                } else {
+                    index[axis] * stride
+                }
+            })
+            .sum::<usize>();
+        channels[index[channel_axis]].1[offset]
+    });
+    metadata.channel_names = channels.into_iter().map(|(name, _)| name).collect();
+    metadata.pixel_type = PixelType::F32;
+    metadata.display = None;
+    Ok(Dataset::new(data, metadata)?)
+}
@@ -0,0 +1,995 @@
+use std::sync::Arc;
+
+use crate::model::{AxisKind, Dataset, DatasetF32, Dim, PixelType};
+use ndarray::{ArrayD, IxDyn};
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use serde_json::{Map, Value, json};
+
+use super::{
+    INPUT_PARAM_KIND, MeasurementTable, OpInputs, OpOutput, OpSchema, Operation, OpsError,
+    ParamSpec, Result, get_optional_bool, get_optional_f32,
+};
+
+/// `Metadata::extras` key marking a complex spectrum made by `image.fft_forward`.
+const FFT_EXTRAS_KEY: &str = "fft";
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageFftForwardOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageFftInverseOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageFftCustomFilterOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageFftConvolveOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageFftDeconvolveOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageFftCorrelateOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImagePhaseCorrelationOp;
+
+fn param(name: &str, description: &str, kind: &str) -> ParamSpec {
+    ParamSpec {
+        name: name.to_string(),
+        description: description.to_string(),
+        required: false,
+        kind: kind.to_string(),
+    }
+}
+
+fn input_param(name: &str, description: &str) -> ParamSpec {
+    ParamSpec {
+        name: name.to_string(),
+        description: description.to_string(),
+        required: true,
+        kind: INPUT_PARAM_KIND.to_string(),
+    }
+}
+
+impl Operation for ImageFftForwardOp {
+    fn name(&self) -> &'static str {
+        "image.fft_forward"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Complex FFT of each X/Y plane, stored as interleaved real and imaginary channels."
+                    .to_string(),
+            params: vec![param(
+                "centered",
+                "Place the zero frequency at (width/2, height/2) like the power spectrum (default true).",
+                "bool",
+            )],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        if dataset.metadata.extras.contains_key(FFT_EXTRAS_KEY) {
+            return Err(OpsError::UnsupportedLayout(
+                "dataset is already a complex spectrum".to_string(),
+            ));
+        }
+        let centered = get_optional_bool(params, "centered", true);
+        let planes = Planes::new(dataset)?;
+        let fft = PlaneFft::new(planes.width, planes.height);
+
+        let channel_count = dataset.metadata.channel_count();
+        let mut metadata = dataset.metadata.clone();
+        let channel_axis = match dataset.axis_index(AxisKind::Channel) {
+            Some(axis) => {
+                metadata.dims[axis].size = channel_count * 2;
+                axis
+            }
+            None => {
+                metadata.dims.push(Dim::new(AxisKind::Channel, 2));
+                metadata.dims.len() - 1
+            }
+        };
+        let shape = metadata.dims.iter().map(|dim| dim.size).collect::<Vec<_>>();
+        let mut output = ArrayD::<f32>::zeros(IxDyn(&shape));
+
+        planes.for_each(|coord| {
+            let mut spectrum = to_complex(&planes.read(dataset, coord));
+            fft.forward(&mut spectrum);
+            if centered {
+                spectrum = planes.swap_quadrants(&spectrum);
+            }
+            let channel = dataset
+                .axis_index(AxisKind::Channel)
+                .map_or(0, |axis| coord[axis]);
+            let mut output_coord = coord.to_vec();
+            output_coord.resize(shape.len(), 0);
+            for (part, component) in [(0, false), (1, true)] {
+                output_coord[channel_axis] = channel * 2 + part;
+                planes.write(
+                    &mut output,
+                    &output_coord,
+                    spectrum
+                        .iter()
+                        .map(|value| if component { value.im } else { value.re }),
+                );
+            }
+        });
+
+        metadata.extras.insert(
+            FFT_EXTRAS_KEY.to_string(),
+            json!({
+                "centered": centered,
+                "channel_axis_added": dataset.axis_index(AxisKind::Channel).is_none(),
+                "channel_names": dataset.metadata.channel_names,
+                "pixel_type": dataset.metadata.pixel_type,
+            }),
+        );
+        metadata.channel_names = if channel_count == 1 && dataset.metadata.channel_names.is_empty()
+        {
+            vec!["real".to_string(), "imaginary".to_string()]
+        } else {
+            (0..channel_count)
+                .flat_map(|channel| {
+                    let name = dataset
+                        .metadata
+                        .channel_names
+                        .get(channel)
+                        .cloned()
+                        .unwrap_or_else(|| format!("C{}", channel + 1));
+                    [format!("{name} real"), format!("{name} imaginary")]
+                })
+                .collect()
+        };
+        metadata.pixel_type = PixelType::F32;
+        metadata.display = None;
+        Ok(OpOutput::dataset_only(Dataset::new(output, metadata)?))
+    }
+}
+
+impl Operation for ImageFftInverseOp {
+    fn name(&self) -> &'static str {
+        "image.fft_inverse"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Transform a complex spectrum from image.fft_forward back to a real image."
+                    .to_string(),
+            params: vec![],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, _params: &Value) -> Result<OpOutput> {
+        let spectrum = Spectrum::read(dataset)?;
+        let planes = Planes::new(dataset)?;
+        let fft = PlaneFft::new(planes.width, planes.height);
+        let channel_axis = spectrum.channel_axis;
+
+        let mut metadata = dataset.metadata.clone();
+        if spectrum.channel_axis_added {
+            metadata.dims.remove(channel_axis);
+        } else {
+            metadata.dims[channel_axis].size /= 2;
+        }
+        let shape = metadata.dims.iter().map(|dim| dim.size).collect::<Vec<_>>();
+        let mut output = ArrayD::<f32>::zeros(IxDyn(&shape));
+
+        planes.for_each(|coord| {
+            if coord[channel_axis] % 2 == 1 {
+                return;
+            }
+            let mut values = spectrum.complex_plane(&planes, coord);
+            if spectrum.centered {
+                values = planes.unswap_quadrants(&values);
+            }
+            fft.inverse(&mut values);
+            let mut output_coord = coord.to_vec();
+            if spectrum.channel_axis_added {
+                output_coord.remove(channel_axis);
+            } else {
+                output_coord[channel_axis] /= 2;
+            }
+            planes.write(
+                &mut output,
+                &output_coord,
+                values.iter().map(|value| value.re),
+            );
+        });
+
+        metadata.extras.remove(FFT_EXTRAS_KEY);
+        metadata.channel_names = spectrum.channel_names;
+        metadata.pixel_type = PixelType::F32;
+        Ok(OpOutput::dataset_only(Dataset::new(output, metadata)?))
+    }
+}
+
+impl Operation for ImageFftCustomFilterOp {
+    fn name(&self) -> &'static str {
+        "image.fft_custom_filter"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Multiply the spectrum of each X/Y plane by a filter image (FFT Custom Filter)."
+                    .to_string(),
+            params: vec![input_param(
+                "filter",
+                "X/Y filter plane in the centered power-spectrum layout; 0 blocks, the filter maximum passes.",
+            )],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        _params: &Value,
+    ) -> Result<OpOutput> {
+        let planes = Planes::new(dataset)?;
+        let mut filter = single_plane(inputs.require("filter")?, "filter", &planes)?;
+        let max = filter.iter().copied().fold(0.0_f32, f32::max);
+        if max > 1.0 {
+            filter.iter_mut().for_each(|value| *value /= max);
+        }
+        let centered_filter = planes.unswap_quadrants(&to_complex(&filter));
+
+        // A complex spectrum is filtered in place; a real image goes through
+        // forward and inverse transforms.
+        if let Ok(spectrum) = Spectrum::read(dataset) {
+            let filter = if spectrum.centered {
+                to_complex(&filter)
+            } else {
+                centered_filter
+            };
+            let mut output = dataset.data.clone();
+            planes.for_each(|coord| {
+                if coord[spectrum.channel_axis] % 2 == 1 {
+                    return;
+                }
+                let values = spectrum.complex_plane(&planes, coord);
+                let filtered = values
+                    .iter()
+                    .zip(&filter)
+                    .map(|(value, factor)| value * factor.re)
+                    .collect::<Vec<_>>();
+                spectrum.write_complex_plane(&planes, &mut output, coord, &filtered);
+            });
+            return Ok(OpOutput::dataset_only(Dataset::new(
+                output,
+                dataset.metadata.clone(),
+            )?));
+        }
+
+        let fft = PlaneFft::new(planes.width, planes.height);
+        let output = planes.map_dataset(dataset, |values| {
+            let mut spectrum = to_complex(&values);
+            fft.forward(&mut spectrum);
+            for (value, factor) in spectrum.iter_mut().zip(&centered_filter) {
+                *value *= factor.re;
+            }
+            fft.inverse(&mut spectrum);
+            spectrum.iter().map(|value| value.re).collect()
+        });
+        let mut metadata = dataset.metadata.clone();
+        metadata.pixel_type = PixelType::F32;
+        Ok(OpOutput::dataset_only(Dataset::new(output, metadata)?))
+    }
+}
+
+impl Operation for ImageFftConvolveOp {
+    fn name(&self) -> &'static str {
+        "image.fft_convolve"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Convolve each X/Y plane with a kernel image via the FFT (periodic boundaries)."
+                    .to_string(),
+            params: vec![
+                input_param(
+                    "kernel",
+                    "X/Y kernel plane no larger than the image; its centre pixel is the origin.",
+                ),
+                param(
+                    "normalize",
+                    "Scale the kernel to unit sum (default true).",
+                    "bool",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        let planes = Planes::new(dataset)?;
+        let fft = PlaneFft::new(planes.width, planes.height);
+        let kernel = kernel_spectrum(
+            inputs.require("kernel")?,
+            &planes,
+            &fft,
+            get_optional_bool(params, "normalize", true),
+        )?;
+        let output = planes.map_dataset(dataset, |values| {
+            let mut spectrum = to_complex(&values);
+            fft.forward(&mut spectrum);
+            for (value, factor) in spectrum.iter_mut().zip(&kernel) {
+                *value *= factor;
+            }
+            fft.inverse(&mut spectrum);
+            spectrum.iter().map(|value| value.re).collect()
+        });
+        let mut metadata = dataset.metadata.clone();
+        metadata.pixel_type = PixelType::F32;
+        Ok(OpOutput::dataset_only(Dataset::new(output, metadata)?))
+    }
+}
+
+impl Operation for ImageFftDeconvolveOp {
+    fn name(&self) -> &'static str {
+        "image.fft_deconvolve"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Regularized inverse filter: divide each X/Y plane's spectrum by a kernel's spectrum."
+                    .to_string(),
+            params: vec![
+                input_param(
+                    "kernel",
+                    "X/Y kernel (PSF) plane no larger than the image; its centre pixel is the origin.",
+                ),
+                param(
+                    "regularization",
+                    "Wiener/Tikhonov term relative to the kernel's peak power (default 0.001; 0 divides directly).",
+                    "float",
+                ),
+                param(
+                    "normalize",
+                    "Scale the kernel to unit sum (default true).",
+                    "bool",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        let regularization = get_optional_f32(params, "regularization", 1e-3);
+        if !regularization.is_finite() || regularization < 0.0 {
+            return Err(OpsError::InvalidParams(
+                "`regularization` must be finite and >= 0".to_string(),
+            ));
+        }
+        let planes = Planes::new(dataset)?;
+        let fft = PlaneFft::new(planes.width, planes.height);
+        let kernel = kernel_spectrum(
+            inputs.require("kernel")?,
+            &planes,
+            &fft,
+            get_optional_bool(params, "normalize", true),
+        )?;
+        let peak = kernel
+            .iter()
+            .map(|value| value.norm_sqr())
+            .fold(0.0_f32, f32::max);
+        let epsilon = regularization * peak;
+        let output = planes.map_dataset(dataset, |values| {
+            let mut spectrum = to_complex(&values);
+            fft.forward(&mut spectrum);
+            for (value, factor) in spectrum.iter_mut().zip(&kernel) {
+                let power = factor.norm_sqr() + epsilon;
+                *value = if power > 0.0 {
+                    *value * factor.conj() / power
+                } else {
+                    Complex::new(0.0, 0.0)
+                };
+            }
+            fft.inverse(&mut spectrum);
+            spectrum.iter().map(|value| value.re).collect()
+        });
+        let mut metadata = dataset.metadata.clone();
+        metadata.pixel_type = PixelType::F32;
+        Ok(OpOutput::dataset_only(Dataset::new(output, metadata)?))
+    }
+}
+
+impl Operation for ImageFftCorrelateOp {
+    fn name(&self) -> &'static str {
+        "image.fft_correlate"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Cross-correlate each X/Y plane with a reference plane, or autocorrelate it, via the FFT."
+                .to_string(),
+            params: vec![
+                ParamSpec {
+                    required: false,
+                    ..input_param(
+                        "reference",
+                        "X/Y reference plane of the same size; omit for autocorrelation.",
+                    )
+                },
+                param(
+                    "normalize",
+                    "Subtract the means and scale to Pearson coefficients in -1..1 (default true).",
+                    "bool",
+                ),
+                param(
+                    "centered",
+                    "Place zero shift at (width/2, height/2) (default true); a peak right of centre means the plane is shifted right of the reference.",
+                    "bool",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        let normalize = get_optional_bool(params, "normalize", true);
+        let centered = get_optional_bool(params, "centered", true);
+        let planes = Planes::new(dataset)?;
+        let fft = PlaneFft::new(planes.width, planes.height);
+        let reference = inputs
+            .get("reference")
+            .map(|reference| {
+                let values = single_plane(reference, "reference", &planes)?;
+                Ok::<_, OpsError>(Correland::new(&values, &fft, normalize))
+            })
+            .transpose()?;
+
+        let output = planes.map_dataset(dataset, |values| {
+            let plane = Correland::new(&values, &fft, normalize);
+            let reference = reference.as_ref().unwrap_or(&plane);
+            let mut product = plane
+                .spectrum
+                .iter()
+                .zip(&reference.spectrum)
+                .map(|(value, reference)| value * reference.conj())
+                .collect::<Vec<_>>();
+            fft.inverse(&mut product);
+            let scale = plane.norm * reference.norm;
+            let correlation = product
+                .iter()
+                .map(|value| if scale > 0.0 { value.re / scale } else { 0.0 })
+                .collect::<Vec<_>>();
+            if centered {
+                planes.swap_quadrants(&correlation)
+            } else {
+                correlation
+            }
+        });
+        let mut metadata = dataset.metadata.clone();
+        metadata.pixel_type = PixelType::F32;
+        metadata.display = None;
+        Ok(OpOutput::dataset_only(Dataset::new(output, metadata)?))
+    }
+}
+
+impl Operation for ImagePhaseCorrelationOp {
+    fn name(&self) -> &'static str {
+        "image.phase_correlation"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Estimate the subpixel X/Y translation of each plane relative to a reference plane."
+                    .to_string(),
+            params: vec![input_param(
+                "reference",
+                "X/Y reference plane of the same size.",
+            )],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        _params: &Value,
+    ) -> Result<OpOutput> {
+        let planes = Planes::new(dataset)?;
+        let reference = single_plane(inputs.require("reference")?, "reference", &planes)?;
+        let estimator = PhaseCorrelation::new(&reference, planes.width, planes.height);
+
+        let mut rows = Vec::new();
+        planes.for_each(|coord| {
+            let shift = estimator.estimate(&planes.read(dataset, coord));
+            let mut row = Map::new();
+            for (axis, dim) in dataset.metadata.dims.iter().enumerate() {
+                if axis != planes.x_axis && axis != planes.y_axis {
+                    let code = dim.axis.code().to_ascii_lowercase();
+                    row.insert(code.to_string(), json!(coord[axis]));
+                }
+            }
+            row.insert("x".to_string(), json!(shift.x));
+            row.insert("y".to_string(), json!(shift.y));
+            row.insert("peak".to_string(), json!(shift.peak));
+            rows.push(Value::Object(row));
+        });
+
+        let mut measurements = MeasurementTable::default();
+        measurements
+            .values
+            .insert("count".to_string(), json!(rows.len()));
+        measurements.values.insert("rows".to_string(), json!(rows));
+        Ok(OpOutput {
+            dataset: dataset.clone(),
+            measurements: Some(measurements),
+        })
+    }
+}
+
+/// Layout of a complex spectrum read from `Metadata::extras`.
+struct Spectrum<'a> {
+    dataset: &'a DatasetF32,
+    channel_axis: usize,
+    centered: bool,
+    channel_axis_added: bool,
+    channel_names: Vec<String>,
+}
+
+impl<'a> Spectrum<'a> {
+    fn read(dataset: &'a DatasetF32) -> Result<Self> {
+        let info = dataset.metadata.extras.get(FFT_EXTRAS_KEY).ok_or_else(|| {
+            OpsError::UnsupportedLayout(
+                "dataset is not a complex spectrum from image.fft_forward".to_string(),
+            )
+        })?;
+        let channel_axis = dataset
+            .axis_index(AxisKind::Channel)
+            .filter(|axis| dataset.shape()[*axis].is_multiple_of(2))
+            .ok_or_else(|| {
+                OpsError::UnsupportedLayout(
+                    "complex spectra need paired real/imaginary channels".to_string(),
+                )
+            })?;
+        Ok(Self {
+            dataset,
+            channel_axis,
+            centered: info["centered"].as_bool().unwrap_or(true),
+            channel_axis_added: info["channel_axis_added"].as_bool().unwrap_or(false),
+            channel_names: serde_json::from_value(info["channel_names"].clone())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// The complex plane whose real part is at `coord` (an even channel).
+    fn complex_plane(&self, planes: &Planes, coord: &[usize]) -> Vec<Complex<f32>> {
+        let mut imaginary_coord = coord.to_vec();
+        imaginary_coord[self.channel_axis] += 1;
+        planes
+            .read(self.dataset, coord)
+            .into_iter()
+            .zip(planes.read(self.dataset, &imaginary_coord))
+            .map(|(re, im)| Complex::new(re, im))
+            .collect()
+    }
+
+    fn write_complex_plane(
+        &self,
+        planes: &Planes,
+        output: &mut ArrayD<f32>,
+        coord: &[usize],
+        values: &[Complex<f32>],
+    ) {
+        planes.write(output, coord, values.iter().map(|value| value.re));
+        let mut imaginary_coord = coord.to_vec();
+        imaginary_coord[self.channel_axis] += 1;
+        planes.write(
+            output,
+            &imaginary_coord,
+            values.iter().map(|value| value.im),
+        );
+    }
+}
+
+/// X/Y plane access for a dataset; every other axis indexes a separate plane.
+struct Planes {
+    x_axis: usize,
+    y_axis: usize,
+    width: usize,
+    height: usize,
+    shape: Vec<usize>,
+}
+
+impl Planes {
+    fn new(dataset: &DatasetF32) -> Result<Self> {
+        let x_axis = axis_index(dataset, AxisKind::X)?;
+        let y_axis = axis_index(dataset, AxisKind::Y)?;
+        let shape = dataset.shape().to_vec();
+        Ok(Self {
+            x_axis,
+            y_axis,
+            width: shape[x_axis],
+            height: shape[y_axis],
+            shape,
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Calls `callback` with the base coordinate (x = y = 0) of every plane.
+    fn for_each(&self, callback: impl FnMut(&[usize])) {
+        let mut outer_shape = self.shape.clone();
+        outer_shape[self.x_axis] = 1;
+        outer_shape[self.y_axis] = 1;
+        iterate_indices(&outer_shape, callback);
+    }
+
+    fn read(&self, dataset: &DatasetF32, base: &[usize]) -> Vec<f32> {
+        let mut coord = base.to_vec();
+        let mut values = Vec::with_capacity(self.len());
+        for y in 0..self.height {
+            coord[self.y_axis] = y;
+            for x in 0..self.width {
+                coord[self.x_axis] = x;
+                values.push(dataset.data[IxDyn(&coord)]);
+            }
+        }
+        values
+    }
+
+    fn write(&self, output: &mut ArrayD<f32>, base: &[usize], values: impl Iterator<Item = f32>) {
+        let mut coord = base.to_vec();
+        for (index, value) in values.enumerate() {
+            coord[self.y_axis] = index / self.width;
+            coord[self.x_axis] = index % self.width;
+            output[IxDyn(&coord)] = value;
+        }
+    }
+
+    /// Applies `transform` to every plane of `dataset`, which must have this layout.
+    fn map_dataset(
+        &self,
+        dataset: &DatasetF32,
+        mut transform: impl FnMut(Vec<f32>) -> Vec<f32>,
+    ) -> ArrayD<f32> {
+        let mut output = ArrayD::<f32>::zeros(IxDyn(&self.shape));
+        self.for_each(|coord| {
+            let values = transform(self.read(dataset, coord));
+            self.write(&mut output, coord, values.into_iter());
+        });
+        output
+    }
+
+    /// Moves the zero frequency from index 0 to (width/2, height/2).
+    fn swap_quadrants<T: Copy>(&self, values: &[T]) -> Vec<T> {
+        self.shift(values, self.width / 2, self.height / 2)
+    }
+
+    /// Inverse of [`Self::swap_quadrants`], also for odd sizes.
+    fn unswap_quadrants<T: Copy>(&self, values: &[T]) -> Vec<T> {
+        self.shift(values, self.width.div_ceil(2), self.height.div_ceil(2))
+    }
+
+    fn shift<T: Copy>(&self, values: &[T], dx: usize, dy: usize) -> Vec<T> {
+        let mut shifted = values.to_vec();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let target = (y + dy) % self.height * self.width + (x + dx) % self.width;
+                shifted[target] = values[y * self.width + x];
+            }
+        }
+        shifted
+    }
+}
+
+/// Forward and normalized inverse 2D FFT of row-major `width * height` planes.
+struct PlaneFft {
+    width: usize,
+    height: usize,
+    row_forward: Arc<dyn Fft<f32>>,
+    column_forward: Arc<dyn Fft<f32>>,
+    row_inverse: Arc<dyn Fft<f32>>,
+    column_inverse: Arc<dyn Fft<f32>>,
+}
+
+impl PlaneFft {
+    fn new(width: usize, height: usize) -> Self {
+        let mut planner = FftPlanner::<f32>::new();
+        Self {
+            width,
+            height,
+            row_forward: planner.plan_fft_forward(width),
+            column_forward: planner.plan_fft_forward(height),
+            row_inverse: planner.plan_fft_inverse(width),
+            column_inverse: planner.plan_fft_inverse(height),
+        }
+    }
+
+    fn forward(&self, values: &mut [Complex<f32>]) {
+        self.transform(values, &self.row_forward, &self.column_forward);
+    }
+
+    fn inverse(&self, values: &mut [Complex<f32>]) {
+        self.transform(values, &self.row_inverse, &self.column_inverse);
+        let normalization = (self.width * self.height) as f32;
+        values.iter_mut().for_each(|value| *value /= normalization);
+    }
+
+    fn transform(
+        &self,
+        values: &mut [Complex<f32>],
+        rows: &Arc<dyn Fft<f32>>,
+        columns: &Arc<dyn Fft<f32>>,
+    ) {
+        for row in values.chunks_exact_mut(self.width) {
+            rows.process(row);
+        }
+        let mut column = vec![Complex::new(0.0_f32, 0.0_f32); self.height];
+        for x in 0..self.width {
+            for y in 0..self.height {
+                column[y] = values[y * self.width + x];
+            }
+            columns.process(&mut column);
+            for y in 0..self.height {
+                values[y * self.width + x] = column[y];
+            }
+        }
+    }
+}
+
+/// Spectrum of one plane for correlation, with the norm that turns the
+/// correlation into Pearson coefficients (1 when not normalizing).
+struct Correland {
+    spectrum: Vec<Complex<f32>>,
+    norm: f32,
+}
+
+impl Correland {
+    fn new(values: &[f32], fft: &PlaneFft, normalize: bool) -> Self {
+        let mut spectrum = to_complex(values);
+        let mut norm = 1.0;
+        if normalize {
+            let mean = values.iter().sum::<f32>() / values.len() as f32;
+            spectrum.iter_mut().for_each(|value| value.re -= mean);
+            norm = spectrum
+                .iter()
+                .map(|value| value.re * value.re)
+                .sum::<f32>()
+                .sqrt();
+        }
+        fft.forward(&mut spectrum);
+        Self { spectrum, norm }
+    }
+}
+
+/// Translation of a plane relative to a reference, in pixels; positive `x`
+/// and `y` mean the content moved right and down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Translation {
+    pub x: f32,
+    pub y: f32,
+    /// Height of the phase-correlation peak (1 for a pure circular shift).
+    pub peak: f32,
+}
+
+/// Phase-correlation translation estimator against a fixed reference plane.
+///
+/// Planes are mean-subtracted; the peak is refined to subpixel precision
+/// with a parabola through its neighbours along each axis.
+pub(super) struct PhaseCorrelation {
+    fft: PlaneFft,
+    reference: Vec<Complex<f32>>,
+}
+
+impl PhaseCorrelation {
+    pub(super) fn new(reference: &[f32], width: usize, height: usize) -> Self {
+        let fft = PlaneFft::new(width, height);
+        let reference = mean_subtracted_spectrum(&fft, reference);
+        Self { fft, reference }
+    }
+
+    pub(super) fn estimate(&self, values: &[f32]) -> Translation {
+        let (width, height) = (self.fft.width, self.fft.height);
+        let mut cross_power = mean_subtracted_spectrum(&self.fft, values)
+            .iter()
+            .zip(&self.reference)
+            .map(|(value, reference)| {
+                let product = value * reference.conj();
+                let magnitude = product.norm();
+                if magnitude > f32::EPSILON {
+                    product / magnitude
+                } else {
+                    Complex::new(0.0, 0.0)
+                }
+            })
+            .collect::<Vec<_>>();
+        self.fft.inverse(&mut cross_power);
+        let surface = cross_power.iter().map(|value| value.re).collect::<Vec<_>>();
+
+        let (peak_index, peak) = surface.iter().copied().enumerate().fold(
+            (0, f32::NEG_INFINITY),
+            |best, (index, value)| {
+                if value > best.1 { (index, value) } else { best }
+            },
+        );
+        let (px, py) = (peak_index % width, peak_index / width);
+        let at = |x: usize, y: usize| surface[y * width + x];
+        let dx = parabolic_offset(
+            at((px + width - 1) % width, py),
+            peak,
+            at((px + 1) % width, py),
+        );
+        let dy = parabolic_offset(
+            at(px, (py + height - 1) % height),
+            peak,
+            at(px, (py + 1) % height),
+        );
+        let wrap = |position: usize, size: usize| {
+            if position > size / 2 {
+                position as f32 - size as f32
+            } else {
+                position as f32
+            }
+        };
+        Translation {
+            x: wrap(px, width) + if width > 2 { dx } else { 0.0 },
+            y: wrap(py, height) + if height > 2 { dy } else { 0.0 },
+            peak,
+        }
+    }
+}
+
+fn mean_subtracted_spectrum(fft: &PlaneFft, values: &[f32]) -> Vec<Complex<f32>> {
+    let mean = values.iter().sum::<f32>() / values.len() as f32;
+    let mut spectrum = values
+        .iter()
+        .map(|value| Complex::new(value - mean, 0.0))
+        .collect::<Vec<_>>();
+    fft.forward(&mut spectrum);
+    spectrum
+}
+
+/// Vertex offset in -0.5..0.5 of the parabola through three samples.
+fn parabolic_offset(before: f32, peak: f32, after: f32) -> f32 {
+    let curvature = before - 2.0 * peak + after;
+    if curvature < 0.0 {
+        (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    }
+}
+
+fn to_complex(values: &[f32]) -> Vec<Complex<f32>> {
+    values
+        .iter()
+        .map(|value| Complex::new(*value, 0.0))
+        .collect()
+}
+
+/// The first X/Y plane of `input`, which must match the X/Y size of `planes`
+/// and have size 1 along every other axis.
+fn single_plane(input: &DatasetF32, name: &str, planes: &Planes) -> Result<Vec<f32>> {
+    let input_planes = Planes::new(input)?;
+    if input_planes.width != planes.width || input_planes.height != planes.height {
+        return Err(OpsError::UnsupportedLayout(format!(
+            "input `{name}` is {}x{} but the image is {}x{}",
+            input_planes.width, input_planes.height, planes.width, planes.height
+        )));
+    }
+    if input.data.len() != input_planes.len() {
+        return Err(OpsError::UnsupportedLayout(format!(
+            "input `{name}` must be a single X/Y plane"
+        )));
+    }
+    Ok(input_planes.read(input, &vec![0; input_planes.shape.len()]))
+}
+
+/// Spectrum of a single-plane kernel placed with its centre pixel at the
+/// origin of an image-sized periodic plane.
+fn kernel_spectrum(
+    kernel: &DatasetF32,
+    planes: &Planes,
+    fft: &PlaneFft,
+    normalize: bool,
+) -> Result<Vec<Complex<f32>>> {
+    let kernel_planes = Planes::new(kernel)?;
+    if kernel.data.len() != kernel_planes.len() {
+        return Err(OpsError::UnsupportedLayout(
+            "input `kernel` must be a single X/Y plane".to_string(),
+        ));
+    }
+    if kernel_planes.width > planes.width || kernel_planes.height > planes.height {
+        return Err(OpsError::UnsupportedLayout(format!(
+            "kernel is {}x{} but the image is only {}x{}",
+            kernel_planes.width, kernel_planes.height, planes.width, planes.height
+        )));
+    }
+    let values = kernel_planes.read(kernel, &vec![0; kernel_planes.shape.len()]);
+    let sum = values.iter().sum::<f32>();
+    let scale = if normalize && sum.abs() > f32::EPSILON {
+        1.0 / sum
+    } else {
+        1.0
+    };
+
+    let (centre_x, centre_y) = (kernel_planes.width / 2, kernel_planes.height / 2);
+    let mut padded = vec![Complex::new(0.0_f32, 0.0_f32); planes.len()];
+    for (index, value) in values.iter().enumerate() {
+        let x = (index % kernel_planes.width + planes.width - centre_x) % planes.width;
+        let y = (index / kernel_planes.width + planes.height - centre_y) % planes.height;
+        padded[y * planes.width + x].re = value * scale;
+    }
+    fft.forward(&mut padded);
+    Ok(padded)
+}
+
+fn axis_index(dataset: &DatasetF32, axis: AxisKind) -> Result<usize> {
+    dataset
+        .axis_index(axis)
+        .ok_or_else(|| OpsError::UnsupportedLayout(format!("dataset has no {axis:?} axis")))
+}
+
+fn iterate_indices(shape: &[usize], mut callback: impl FnMut(&[usize])) {
+    if shape.is_empty() {
+        callback(&[]);
+        return;
+    }
+    let mut index = vec![0usize; shape.len()];
+    loop {
+        callback(&index);
+        let mut dim = shape.len();
+        while dim > 0 {
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+            if dim == 0 {
+                return;
+            }
+        }
+    }
+}
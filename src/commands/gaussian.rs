@@ -69,7 +69,7 @@ impl Operation for GaussianBlurOp {
     }
 }
 
-fn blur_axis(
+pub(super) fn blur_axis(
     input: &[f32],
     shape: &[usize],
     strides: &[usize],
@@ -129,7 +129,7 @@ fn lane_base_offset(lane_index: usize, axis: usize, shape: &[usize], strides: &[
     base
 }
 
-fn row_major_strides(shape: &[usize]) -> Vec<usize> {
+pub(super) fn row_major_strides(shape: &[usize]) -> Vec<usize> {
     let mut strides = vec![1usize; shape.len()];
     if shape.len() < 2 {
         return strides;
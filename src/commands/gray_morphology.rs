@@ -0,0 +1,648 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::model::{AxisKind, Dataset, DatasetF32};
+use rayon::prelude::*;
+use serde_json::Value;
+
+use super::{
+    OpOutput, OpSchema, Operation, OpsError, ParamSpec, Result, gaussian::row_major_strides,
+    get_optional_usize,
+};
+
+/// Flat structuring element as `[dx, dy, dz]` offsets around the origin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StructuringElement {
+    offsets: Vec<[isize; 3]>,
+}
+
+impl StructuringElement {
+    fn from_params(params: &Value) -> Result<Self> {
+        let radius = get_optional_usize(params, "radius", 1) as isize;
+        let shape = params
+            .get("shape")
+            .and_then(Value::as_str)
+            .unwrap_or("disk")
+            .to_ascii_lowercase();
+        let offsets = match shape.as_str() {
+            // Disks and balls use the ImageJ `r * r + 1` extent, so radius 1 is a 3x3 square.
+            "disk" => Self::filtered(radius, 0, |dx, dy, _| {
+                dx * dx + dy * dy <= radius * radius + 1
+            }),
+            "ball" => Self::filtered(radius, radius, |dx, dy, dz| {
+                dx * dx + dy * dy + dz * dz <= radius * radius + 1
+            }),
+            "square" => Self::filtered(radius, 0, |_, _, _| true),
+            "diamond" => Self::filtered(radius, 0, |dx, dy, _| dx.abs() + dy.abs() <= radius),
+            "octagon" => Self::octagon(radius),
+            "line" => Self::line(params)?,
+            "custom" => Self::custom(params)?,
+            other => {
+                return Err(OpsError::InvalidParams(format!(
+                    "unsupported structuring element `{other}`; expected disk, square, diamond, line, octagon, ball, or custom"
+                )));
+            }
+        };
+        Ok(Self { offsets })
+    }
+
+    fn filtered(
+        radius: isize,
+        z_radius: isize,
+        include: impl Fn(isize, isize, isize) -> bool,
+    ) -> Vec<[isize; 3]> {
+        let mut offsets = Vec::new();
+        for dz in -z_radius..=z_radius {
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    if include(dx, dy, dz) {
+                        offsets.push([dx, dy, dz]);
+                    }
+                }
+            }
+        }
+        offsets
+    }
+
+    /// Alternating 3x3 square and cross dilations, which give a regular octagon.
+    fn octagon(radius: isize) -> Vec<[isize; 3]> {
+        let square = Self::filtered(1, 0, |_, _, _| true);
+        let cross = Self::filtered(1, 0, |dx, dy, _| dx.abs() + dy.abs() <= 1);
+        let mut offsets = BTreeSet::from([[0_isize; 3]]);
+        for step in 0..radius {
+            let unit = if step % 2 == 0 { &square } else { &cross };
+            offsets = offsets
+                .iter()
+                .flat_map(|offset| {
+                    unit.iter()
+                        .map(move |step| [offset[0] + step[0], offset[1] + step[1], 0])
+                })
+                .collect();
+        }
+        offsets.into_iter().collect()
+    }
+
+    fn line(params: &Value) -> Result<Vec<[isize; 3]>> {
+        let length = get_optional_usize(params, "length", 3);
+        if length == 0 {
+            return Err(OpsError::InvalidParams(
+                "`length` must be at least 1".to_string(),
+            ));
+        }
+        let angle = params
+            .get("angle")
+            .and_then(Value::as_f64)
+            .unwrap_or(0.0)
+            .to_radians();
+        // Angles are counter-clockwise on screen, where Y points down.
+        let (dx, dy) = (angle.cos(), -angle.sin());
+        let centre = (length as f64 - 1.0) / 2.0;
+        let offsets = (0..length)
+            .map(|step| {
+                let t = step as f64 - centre;
+                [(t * dx).round() as isize, (t * dy).round() as isize, 0]
+            })
+            .collect::<BTreeSet<_>>();
+        Ok(offsets.into_iter().collect())
+    }
+
+    /// A 2D (`[y][x]`) or 3D (`[z][y][x]`) array of odd size whose non-zero
+    /// entries belong to the element; the centre entry is the origin.
+    fn custom(params: &Value) -> Result<Vec<[isize; 3]>> {
+        let invalid = || {
+            OpsError::InvalidParams(
+                "`kernel` must be a rectangular 2D or 3D array of numbers with odd sizes"
+                    .to_string(),
+            )
+        };
+        let kernel = params
+            .get("kernel")
+            .and_then(Value::as_array)
+            .ok_or_else(invalid)?;
+        let is_3d = kernel
+            .first()
+            .and_then(Value::as_array)
+            .and_then(|row| row.first())
+            .is_some_and(Value::is_array);
+        let planes = if is_3d {
+            kernel.iter().collect::<Vec<_>>()
+        } else {
+            vec![params.get("kernel").expect("kernel is present")]
+        };
+
+        let mut offsets = Vec::new();
+        let mut plane_shape = None;
+        let z_centre = (planes.len() / 2) as isize;
+        for (z, plane) in planes.iter().enumerate() {
+            let rows = plane.as_array().ok_or_else(invalid)?;
+            let width = rows
+                .first()
+                .and_then(Value::as_array)
+                .map(Vec::len)
+                .ok_or_else(invalid)?;
+            if *plane_shape.get_or_insert((rows.len(), width)) != (rows.len(), width) {
+                return Err(invalid());
+            }
+            let (y_centre, x_centre) = ((rows.len() / 2) as isize, (width / 2) as isize);
+            for (y, row) in rows.iter().enumerate() {
+                let row = row.as_array().filter(|row| row.len() == width);
+                for (x, value) in row.ok_or_else(invalid)?.iter().enumerate() {
+                    if value.as_f64().ok_or_else(invalid)? != 0.0 {
+                        offsets.push([
+                            x as isize - x_centre,
+                            y as isize - y_centre,
+                            z as isize - z_centre,
+                        ]);
+                    }
+                }
+            }
+        }
+        let (height, width) = plane_shape.ok_or_else(invalid)?;
+        if planes.len() % 2 == 0 || height % 2 == 0 || width % 2 == 0 {
+            return Err(invalid());
+        }
+        if offsets.is_empty() {
+            return Err(OpsError::InvalidParams(
+                "`kernel` must contain at least one non-zero entry".to_string(),
+            ));
+        }
+        Ok(offsets)
+    }
+
+    fn is_volumetric(&self) -> bool {
+        self.offsets.iter().any(|offset| offset[2] != 0)
+    }
+
+    fn reflected(&self) -> Self {
+        Self {
+            offsets: self
+                .offsets
+                .iter()
+                .map(|offset| offset.map(|value| -value))
+                .collect(),
+        }
+    }
+
+    fn extent(&self) -> [usize; 3] {
+        let mut extent = [0_usize; 3];
+        for offset in &self.offsets {
+            for axis in 0..3 {
+                extent[axis] = extent[axis].max(offset[axis].unsigned_abs());
+            }
+        }
+        extent
+    }
+
+    /// Decomposes the element into runs of consecutive X offsets, keyed by
+    /// `(dy, dz)`, so each run can be evaluated with a 1-D running extremum.
+    fn runs(&self) -> Vec<(isize, isize, isize, usize)> {
+        let mut rows = BTreeMap::<(isize, isize), Vec<isize>>::new();
+        for [dx, dy, dz] in &self.offsets {
+            rows.entry((*dy, *dz)).or_default().push(*dx);
+        }
+        let mut runs = Vec::new();
+        for ((dy, dz), mut xs) in rows {
+            xs.sort_unstable();
+            xs.dedup();
+            let mut start = xs[0];
+            let mut length = 1;
+            for pair in xs.windows(2) {
+                if pair[1] == pair[0] + 1 {
+                    length += 1;
+                } else {
+                    runs.push((start, dy, dz, length));
+                    start = pair[1];
+                    length = 1;
+                }
+            }
+            runs.push((start, dy, dz, length));
+        }
+        runs
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Extremum {
+    Min,
+    Max,
+}
+
+impl Extremum {
+    fn identity(self) -> f32 {
+        match self {
+            Self::Min => f32::INFINITY,
+            Self::Max => f32::NEG_INFINITY,
+        }
+    }
+
+    fn pick(self, left: f32, right: f32) -> f32 {
+        match self {
+            Self::Min => left.min(right),
+            Self::Max => left.max(right),
+        }
+    }
+}
+
+/// Running extremum over windows `values[i..i + length]` using the van Herk /
+/// Gil-Werman algorithm, which costs three comparisons per sample for any length.
+fn window_extremum(values: &[f32], length: usize, extremum: Extremum) -> Vec<f32> {
+    let len = values.len();
+    if length <= 1 {
+        return values.to_vec();
+    }
+    let mut prefix = values.to_vec();
+    let mut suffix = values.to_vec();
+    for index in 1..len {
+        if index % length != 0 {
+            prefix[index] = extremum.pick(prefix[index - 1], values[index]);
+        }
+    }
+    for index in (0..len.saturating_sub(1)).rev() {
+        if (index + 1) % length != 0 {
+            suffix[index] = extremum.pick(suffix[index + 1], values[index]);
+        }
+    }
+    // Windows that run past the end are truncated to the remaining samples.
+    let mut output = values.to_vec();
+    let mut tail = extremum.identity();
+    for index in (0..len).rev() {
+        output[index] = match prefix.get(index + length - 1) {
+            Some(end) => extremum.pick(suffix[index], *end),
+            None => {
+                tail = extremum.pick(tail, values[index]);
+                tail
+            }
+        };
+    }
+    output
+}
+
+/// Flat erosion (`Min`) or dilation (`Max`) of a `width * height * depth`
+/// volume stored in X-fastest order. Pixels outside the volume are ignored.
+fn extremum_filter(
+    values: &[f32],
+    shape: [usize; 3],
+    element: &StructuringElement,
+    extremum: Extremum,
+) -> Vec<f32> {
+    let element = match extremum {
+        Extremum::Min => element.clone(),
+        Extremum::Max => element.reflected(),
+    };
+    let extent = element.extent();
+    let padded = [0, 1, 2].map(|axis| shape[axis] + 2 * extent[axis]);
+    let mut buffer = vec![extremum.identity(); padded.iter().product()];
+    for z in 0..shape[2] {
+        for y in 0..shape[1] {
+            let source = shape[0] * (y + shape[1] * z);
+            let target = extent[0] + padded[0] * (y + extent[1] + padded[1] * (z + extent[2]));
+            buffer[target..target + shape[0]].copy_from_slice(&values[source..source + shape[0]]);
+        }
+    }
+
+    let runs = element.runs();
+    let lengths = runs
+        .iter()
+        .map(|run| run.3)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let windows = lengths
+        .par_iter()
+        .map(|length| {
+            buffer
+                .chunks(padded[0])
+                .flat_map(|row| window_extremum(row, *length, extremum))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let runs = runs
+        .into_iter()
+        .map(|(dx, dy, dz, length)| {
+            let window = lengths
+                .binary_search(&length)
+                .expect("length was collected");
+            (dx, dy, dz, window)
+        })
+        .collect::<Vec<_>>();
+
+    (0..values.len())
+        .into_par_iter()
+        .map(|index| {
+            let x = (index % shape[0]) as isize + extent[0] as isize;
+            let y = ((index / shape[0]) % shape[1]) as isize + extent[1] as isize;
+            let z = (index / (shape[0] * shape[1])) as isize + extent[2] as isize;
+            runs.iter()
+                .fold(extremum.identity(), |acc, (dx, dy, dz, window)| {
+                    let position = (x + dx) as usize
+                        + padded[0] * ((y + dy) as usize + padded[1] * (z + dz) as usize);
+                    extremum.pick(acc, windows[*window][position])
+                })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GrayMorphologyKind {
+    Erode,
+    Dilate,
+    Open,
+    Close,
+    WhiteTopHat,
+    BlackTopHat,
+    Gradient,
+}
+
+fn gray_morphology_volume(
+    values: &[f32],
+    shape: [usize; 3],
+    element: &StructuringElement,
+    kind: GrayMorphologyKind,
+) -> Vec<f32> {
+    let erode = |values: &[f32]| extremum_filter(values, shape, element, Extremum::Min);
+    let dilate = |values: &[f32]| extremum_filter(values, shape, element, Extremum::Max);
+    let difference = |left: Vec<f32>, right: &[f32]| {
+        left.into_iter()
+            .zip(right)
+            .map(|(left, right)| left - right)
+            .collect()
+    };
+    match kind {
+        GrayMorphologyKind::Erode => erode(values),
+        GrayMorphologyKind::Dilate => dilate(values),
+        GrayMorphologyKind::Open => dilate(&erode(values)),
+        GrayMorphologyKind::Close => erode(&dilate(values)),
+        GrayMorphologyKind::WhiteTopHat => difference(values.to_vec(), &dilate(&erode(values))),
+        GrayMorphologyKind::BlackTopHat => difference(erode(&dilate(values)), values),
+        GrayMorphologyKind::Gradient => difference(dilate(values), &erode(values)),
+    }
+}
+
+fn execute_gray_morphology(
+    dataset: &DatasetF32,
+    params: &Value,
+    kind: GrayMorphologyKind,
+) -> Result<OpOutput> {
+    let element = StructuringElement::from_params(params)?;
+    let x_axis = axis_index(dataset, AxisKind::X)?;
+    let y_axis = axis_index(dataset, AxisKind::Y)?;
+    let z_axis = if element.is_volumetric() {
+        dataset.axis_index(AxisKind::Z)
+    } else {
+        None
+    };
+    let shape = dataset.shape().to_vec();
+    let volume = [
+        shape[x_axis],
+        shape[y_axis],
+        z_axis.map(|axis| shape[axis]).unwrap_or(1),
+    ];
+    let volume_axes = [Some(x_axis), Some(y_axis), z_axis];
+    let outer_axes = (0..shape.len())
+        .filter(|axis| !volume_axes.contains(&Some(*axis)))
+        .collect::<Vec<_>>();
+    let outer_shape = outer_axes
+        .iter()
+        .map(|axis| shape[*axis])
+        .collect::<Vec<_>>();
+
+    // Voxel offsets inside a volume are shared by every volume; only the base moves.
+    let strides = row_major_strides(&shape);
+    let z_stride = z_axis.map(|axis| strides[axis]).unwrap_or(0);
+    let mut local = Vec::with_capacity(volume.iter().product());
+    for z in 0..volume[2] {
+        for y in 0..volume[1] {
+            let row = z * z_stride + y * strides[y_axis];
+            local.extend((0..volume[0]).map(|x| row + x * strides[x_axis]));
+        }
+    }
+
+    let mut output = dataset.data.as_standard_layout().into_owned();
+    let data = output
+        .as_slice_mut()
+        .expect("standard layout arrays are contiguous");
+    iterate_indices(&outer_shape, |outer_coord| {
+        let base = outer_axes
+            .iter()
+            .zip(outer_coord)
+            .map(|(axis, index)| index * strides[*axis])
+            .sum::<usize>();
+        let values = local
+            .iter()
+            .map(|offset| data[base + offset])
+            .collect::<Vec<_>>();
+        let filtered = gray_morphology_volume(&values, volume, &element, kind);
+        for (offset, value) in local.iter().zip(filtered) {
+            data[base + offset] = value;
+        }
+    });
+
+    Ok(OpOutput::dataset_only(Dataset::new(
+        output,
+        dataset.metadata.clone(),
+    )?))
+}
+
+fn gray_morphology_schema(name: &str, description: &str) -> OpSchema {
+    let param = |name: &str, description: &str, kind: &str| ParamSpec {
+        name: name.to_string(),
+        description: description.to_string(),
+        required: false,
+        kind: kind.to_string(),
+    };
+    OpSchema {
+        name: name.to_string(),
+        description: description.to_string(),
+        params: vec![
+            param(
+                "shape",
+                "Structuring element: disk (default), square, diamond, line, octagon, ball, or custom.",
+                "string",
+            ),
+            param(
+                "radius",
+                "Element radius in pixels for disk, square, diamond, octagon and ball.",
+                "int",
+            ),
+            param("length", "Line length in pixels.", "int"),
+            param(
+                "angle",
+                "Line angle in degrees, counter-clockwise from the X axis.",
+                "float",
+            ),
+            param(
+                "kernel",
+                "Custom element as a 2D [y][x] or 3D [z][y][x] array of odd size; non-zero entries are included.",
+                "array",
+            ),
+        ],
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyGrayErodeOp;
+
+impl Operation for MorphologyGrayErodeOp {
+    fn name(&self) -> &'static str {
+        "morphology.gray.erode"
+    }
+
+    fn schema(&self) -> OpSchema {
+        gray_morphology_schema(
+            self.name(),
+            "Grayscale erosion: minimum over the structuring element.",
+        )
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        execute_gray_morphology(dataset, params, GrayMorphologyKind::Erode)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyGrayDilateOp;
+
+impl Operation for MorphologyGrayDilateOp {
+    fn name(&self) -> &'static str {
+        "morphology.gray.dilate"
+    }
+
+    fn schema(&self) -> OpSchema {
+        gray_morphology_schema(
+            self.name(),
+            "Grayscale dilation: maximum over the reflected structuring element.",
+        )
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        execute_gray_morphology(dataset, params, GrayMorphologyKind::Dilate)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyGrayOpenOp;
+
+impl Operation for MorphologyGrayOpenOp {
+    fn name(&self) -> &'static str {
+        "morphology.gray.open"
+    }
+
+    fn schema(&self) -> OpSchema {
+        gray_morphology_schema(
+            self.name(),
+            "Grayscale opening: erosion followed by dilation.",
+        )
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        execute_gray_morphology(dataset, params, GrayMorphologyKind::Open)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyGrayCloseOp;
+
+impl Operation for MorphologyGrayCloseOp {
+    fn name(&self) -> &'static str {
+        "morphology.gray.close"
+    }
+
+    fn schema(&self) -> OpSchema {
+        gray_morphology_schema(
+            self.name(),
+            "Grayscale closing: dilation followed by erosion.",
+        )
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        execute_gray_morphology(dataset, params, GrayMorphologyKind::Close)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyGrayWhiteTopHatOp;
+
+impl Operation for MorphologyGrayWhiteTopHatOp {
+    fn name(&self) -> &'static str {
+        "morphology.gray.white_tophat"
+    }
+
+    fn schema(&self) -> OpSchema {
+        gray_morphology_schema(
+            self.name(),
+            "White top-hat: the image minus its opening, keeping bright details smaller than the element.",
+        )
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        execute_gray_morphology(dataset, params, GrayMorphologyKind::WhiteTopHat)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyGrayBlackTopHatOp;
+
+impl Operation for MorphologyGrayBlackTopHatOp {
+    fn name(&self) -> &'static str {
+        "morphology.gray.black_tophat"
+    }
+
+    fn schema(&self) -> OpSchema {
+        gray_morphology_schema(
+            self.name(),
+            "Black top-hat: the closing minus the image, keeping dark details smaller than the element.",
+        )
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        execute_gray_morphology(dataset, params, GrayMorphologyKind::BlackTopHat)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyGrayGradientOp;
+
+impl Operation for MorphologyGrayGradientOp {
+    fn name(&self) -> &'static str {
+        "morphology.gray.gradient"
+    }
+
+    fn schema(&self) -> OpSchema {
+        gray_morphology_schema(
+            self.name(),
+            "Morphological gradient: dilation minus erosion.",
+        )
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        execute_gray_morphology(dataset, params, GrayMorphologyKind::Gradient)
+    }
+}
+
+fn axis_index(dataset: &DatasetF32, axis: AxisKind) -> Result<usize> {
+    dataset
+        .axis_index(axis)
+        .ok_or_else(|| OpsError::UnsupportedLayout(format!("dataset has no {axis:?} axis")))
+}
+
+fn iterate_indices(shape: &[usize], mut callback: impl FnMut(&[usize])) {
+    if shape.is_empty() {
+        callback(&[]);
+        return;
+    }
+    let mut index = vec![0usize; shape.len()];
+    loop {
+        callback(&index);
+        let mut dim = shape.len();
+        while dim > 0 {
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+            if dim == 0 {
+                return;
+            }
+        }
+    }
+}
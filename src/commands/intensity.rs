@@ -53,6 +53,7 @@ impl Operation for IntensityNormalizeOp {
     }
 
     fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        reject_label_input(self.name(), dataset)?;
         let (source_min, source_max) = dataset.min_max().unwrap_or((0.0, 1.0));
         let min = params
             .get("min")
@@ -111,6 +112,7 @@ impl Operation for IntensityEnhanceContrastOp {
     }
 
     fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        reject_label_input(self.name(), dataset)?;
         let saturated = get_optional_f32(params, "saturated_percent", 0.35).clamp(0.0, 100.0);
         let normalize = params
             .get("normalize")
@@ -131,6 +133,12 @@ impl Operation for IntensityEnhanceContrastOp {
             metadata
                 .extras
                 .insert("display_max".to_string(), json!(high));
+            let display = metadata.display.get_or_insert_with(Default::default);
+            for channel in 0..dataset.metadata.channel_count() {
+                let entry = display.channel_mut(channel);
+                entry.min = Some(low);
+                entry.max = Some(high);
+            }
             return Ok(OpOutput::dataset_only(Dataset::new(
                 dataset.data.clone(),
                 metadata,
@@ -182,6 +190,7 @@ impl Operation for IntensityInvertOp {
     }
 
     fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        reject_label_input(self.name(), dataset)?;
         let (min, max) = match dataset.metadata.pixel_type {
             PixelType::U8 => (0.0, 255.0),
             PixelType::U16 => (0.0, 65_535.0),
@@ -239,6 +248,7 @@ impl Operation for IntensityMathOp {
     }
 
     fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        reject_label_input(self.name(), dataset)?;
         let Some(operation) = params.get("operation").and_then(Value::as_str) else {
             return Err(OpsError::InvalidParams(
                 "`operation` is required".to_string(),
@@ -310,6 +320,7 @@ impl Operation for IntensityNaNBackgroundOp {
     }
 
     fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        reject_label_input(self.name(), dataset)?;
         if dataset.metadata.pixel_type != PixelType::F32 {
             return Err(OpsError::UnsupportedLayout(
                 "NaN Background requires f32 pixel metadata".to_string(),
@@ -501,6 +512,16 @@ fn get_required_bitmask(params: &Value) -> Result<u32> {
     ))
 }
 
+/// Intensity arithmetic would corrupt object ids, so label images are refused.
+pub(super) fn reject_label_input(op: &str, dataset: &DatasetF32) -> Result<()> {
+    if dataset.is_label() {
+        return Err(OpsError::UnsupportedLayout(format!(
+            "`{op}` does not apply to label images"
+        )));
+    }
+    Ok(())
+}
+
 fn execute_bitwise_math(
     dataset: &DatasetF32,
     operation: MathOperation,
@@ -578,6 +599,7 @@ impl Operation for IntensityWindowOp {
     }
 
     fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        reject_label_input(self.name(), dataset)?;
         let low = get_required_f32(params, "low")?;
         let high = get_required_f32(params, "high")?;
         if high <= low {
@@ -671,7 +693,7 @@ fn window_sample(value: f32, low: f32, high: f32, output_max: f32) -> f32 {
     scale_unit_to_pixel_type(((value - low) / (high - low)).clamp(0.0, 1.0), output_max)
 }
 
-fn pixel_type_max(pixel_type: PixelType) -> f32 {
+pub(super) fn pixel_type_max(pixel_type: PixelType) -> f32 {
     match pixel_type {
         PixelType::U8 => 255.0,
         PixelType::U16 => 65_535.0,
@@ -0,0 +1,708 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use crate::model::{AxisKind, Dataset, DatasetF32, DatasetKind, Metadata};
+use ndarray::{ArrayD, Axis, IxDyn};
+use serde_json::{Value, json};
+
+use super::calculator::broadcast_values;
+use super::distance::distance_transform;
+use super::{
+    INPUT_PARAM_KIND, MeasurementTable, OpInputs, OpOutput, OpSchema, Operation, OpsError,
+    ParamSpec, Result, get_optional_bool, get_optional_f32, get_optional_usize, spatial_axes,
+};
+
+/// A label image read from one channel of a dataset, stored flat in row-major order.
+struct LabelImage {
+    labels: Vec<u32>,
+    shape: Vec<usize>,
+    strides: Vec<usize>,
+    spatial: Vec<usize>,
+    metadata: Metadata,
+}
+
+impl LabelImage {
+    /// Reads the ids in channel `channel` (default 0) and drops the channel axis.
+    fn read(dataset: &DatasetF32, params: &Value) -> Result<Self> {
+        let (data, metadata) = channel_plane(dataset, get_optional_usize(params, "channel", 0))?;
+        let labels = Dataset::new(data, metadata.clone())?.to_labels()?;
+        let shape = labels.shape().to_vec();
+        let spatial = spatial_axes(&Dataset::new(
+            ArrayD::<f32>::zeros(IxDyn(&shape)),
+            metadata.clone(),
+        )?);
+        if spatial.is_empty() {
+            return Err(OpsError::UnsupportedLayout(
+                "dataset has no spatial axes".to_string(),
+            ));
+        }
+        let mut strides = vec![1_usize; shape.len()];
+        for axis in (0..shape.len().saturating_sub(1)).rev() {
+            strides[axis] = strides[axis + 1] * shape[axis + 1];
+        }
+        Ok(Self {
+            labels: labels.data.iter().copied().collect(),
+            shape,
+            strides,
+            spatial,
+            metadata,
+        })
+    }
+
+    fn coordinate(&self, index: usize, axis: usize) -> usize {
+        (index / self.strides[axis]) % self.shape[axis]
+    }
+
+    /// Spatial neighbours of `index`: face neighbours only, or the full
+    /// 3^n - 1 neighbourhood when `full`.
+    fn neighbours(&self, index: usize, full: bool) -> Vec<usize> {
+        let mut neighbours = Vec::new();
+        let rank = self.spatial.len();
+        for code in 0..3_usize.pow(rank as u32) {
+            let mut offsets = Vec::with_capacity(rank);
+            let mut remainder = code;
+            for _ in 0..rank {
+                offsets.push(remainder as isize % 3 - 1);
+                remainder /= 3;
+            }
+            let nonzero = offsets.iter().filter(|offset| **offset != 0).count();
+            if nonzero == 0 || (!full && nonzero > 1) {
+                continue;
+            }
+            let mut neighbour = index as isize;
+            let mut inside = true;
+            for (axis, offset) in self.spatial.iter().zip(offsets) {
+                let position = self.coordinate(index, *axis) as isize + offset;
+                if position < 0 || position >= self.shape[*axis] as isize {
+                    inside = false;
+                    break;
+                }
+                neighbour += offset * self.strides[*axis] as isize;
+            }
+            if inside {
+                neighbours.push(neighbour as usize);
+            }
+        }
+        neighbours
+    }
+
+    fn touches_border(&self, index: usize, axes: &[usize]) -> bool {
+        axes.iter().any(|axis| {
+            let position = self.coordinate(index, *axis);
+            position == 0 || position + 1 == self.shape[*axis]
+        })
+    }
+
+    fn counts(&self) -> BTreeMap<u32, usize> {
+        let mut counts = BTreeMap::new();
+        for label in self.labels.iter().filter(|label| **label > 0) {
+            *counts.entry(*label).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    fn retain(&mut self, keep: impl Fn(u32) -> bool) {
+        for label in &mut self.labels {
+            if *label > 0 && !keep(*label) {
+                *label = 0;
+            }
+        }
+    }
+
+    fn into_output(self) -> Result<OpOutput> {
+        let count = self.counts().len();
+        let data = ArrayD::from_shape_vec(IxDyn(&self.shape), self.labels)
+            .expect("label buffer matches its shape");
+        let dataset = Dataset::new(data, self.metadata)?.to_label_dataset()?;
+        let mut measurements = MeasurementTable::default();
+        measurements
+            .values
+            .insert("count".to_string(), json!(count));
+        Ok(OpOutput {
+            dataset,
+            measurements: Some(measurements),
+        })
+    }
+}
+
+/// One channel of `dataset` with the channel axis removed.
+pub(super) fn channel_plane(
+    dataset: &DatasetF32,
+    channel: usize,
+) -> Result<(ArrayD<f32>, Metadata)> {
+    let Some(channel_axis) = dataset.axis_index(AxisKind::Channel) else {
+        if channel > 0 {
+            return Err(OpsError::InvalidParams(format!(
+                "`channel` {channel} is out of range for 1 channel(s)"
+            )));
+        }
+        return Ok((dataset.data.clone(), dataset.metadata.clone()));
+    };
+    let channel_count = dataset.shape()[channel_axis];
+    if channel >= channel_count {
+        return Err(OpsError::InvalidParams(format!(
+            "`channel` {channel} is out of range for {channel_count} channel(s)"
+        )));
+    }
+    let mut metadata = dataset.metadata.clone();
+    metadata.dims.remove(channel_axis);
+    metadata.channel_names.clear();
+    metadata.display = None;
+    Ok((
+        dataset
+            .data
+            .index_axis(Axis(channel_axis), channel)
+            .to_owned(),
+        metadata,
+    ))
+}
+
+/// The `intensity` input, or else the `intensity_channel` of `dataset`, laid out
+/// like the label plane read from `channel`.
+pub(super) fn intensity_plane(
+    dataset: &DatasetF32,
+    inputs: &OpInputs<'_>,
+    params: &Value,
+    channel: usize,
+) -> Result<Option<ArrayD<f32>>> {
+    if let Some(image) = inputs.get("intensity") {
+        let data = broadcast_values(dataset, image, "intensity")?;
+        return Ok(Some(match dataset.axis_index(AxisKind::Channel) {
+            Some(axis) => data.index_axis(Axis(axis), channel).to_owned(),
+            None => data,
+        }));
+    }
+    params
+        .get("intensity_channel")
+        .and_then(Value::as_u64)
+        .map(|channel| channel_plane(dataset, channel as usize).map(|(data, _)| data))
+        .transpose()
+}
+
+fn label_param(name: &str, description: &str, kind: &str) -> ParamSpec {
+    ParamSpec {
+        name: name.to_string(),
+        description: description.to_string(),
+        required: false,
+        kind: kind.to_string(),
+    }
+}
+
+fn channel_param() -> ParamSpec {
+    label_param(
+        "channel",
+        "Channel holding the labels (default 0); the output drops the channel axis.",
+        "int",
+    )
+}
+
+fn label_list(value: &Value, key: &str) -> Result<Vec<u32>> {
+    value
+        .as_array()
+        .ok_or_else(|| OpsError::InvalidParams(format!("`{key}` must be an array of label ids")))?
+        .iter()
+        .map(|label| {
+            label
+                .as_u64()
+                .filter(|label| *label > 0)
+                .map(|label| label as u32)
+                .ok_or_else(|| {
+                    OpsError::InvalidParams(format!("`{key}` must contain positive label ids"))
+                })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LabelsRemoveOp;
+
+impl Operation for LabelsRemoveOp {
+    fn name(&self) -> &'static str {
+        "labels.remove"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Remove labels by size, mean intensity or border contact.".to_string(),
+            params: vec![
+                channel_param(),
+                label_param("min_size", "Minimum label size in pixels.", "int"),
+                label_param("max_size", "Maximum label size in pixels.", "int"),
+                label_param(
+                    "exclude_edges",
+                    "Remove labels touching the X/Y border.",
+                    "bool",
+                ),
+                label_param(
+                    "intensity",
+                    "Image whose mean under each label is compared with min_mean/max_mean; takes precedence over `intensity_channel`.",
+                    INPUT_PARAM_KIND,
+                ),
+                label_param(
+                    "intensity_channel",
+                    "Channel whose mean under each label is compared with min_mean/max_mean.",
+                    "int",
+                ),
+                label_param("min_mean", "Minimum mean intensity.", "float"),
+                label_param("max_mean", "Maximum mean intensity.", "float"),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        let mut image = LabelImage::read(dataset, params)?;
+        let min_size = get_optional_usize(params, "min_size", 0);
+        let max_size = get_optional_usize(params, "max_size", usize::MAX);
+        let counts = image.counts();
+        let mut removed = counts
+            .iter()
+            .filter(|(_, size)| **size < min_size || **size > max_size)
+            .map(|(label, _)| *label)
+            .collect::<BTreeSet<_>>();
+
+        if get_optional_bool(params, "exclude_edges", false) {
+            let border_axes = [AxisKind::X, AxisKind::Y]
+                .into_iter()
+                .filter_map(|axis| image.metadata.dims.iter().position(|dim| dim.axis == axis))
+                .collect::<Vec<_>>();
+            for (index, label) in image.labels.iter().enumerate() {
+                if *label > 0 && image.touches_border(index, &border_axes) {
+                    removed.insert(*label);
+                }
+            }
+        }
+
+        let channel = get_optional_usize(params, "channel", 0);
+        if let Some(intensities) = intensity_plane(dataset, inputs, params, channel)? {
+            let min_mean = get_optional_f32(params, "min_mean", f32::NEG_INFINITY);
+            let max_mean = get_optional_f32(params, "max_mean", f32::INFINITY);
+            let mut sums = HashMap::<u32, f64>::new();
+            for (label, value) in image.labels.iter().zip(intensities.iter()) {
+                if *label > 0 {
+                    *sums.entry(*label).or_insert(0.0) += f64::from(*value);
+                }
+            }
+            for (label, sum) in sums {
+                let mean = sum / counts[&label] as f64;
+                if mean < f64::from(min_mean) || mean > f64::from(max_mean) {
+                    removed.insert(label);
+                }
+            }
+        } else if params.get("min_mean").is_some() || params.get("max_mean").is_some() {
+            return Err(OpsError::InvalidParams(
+                "`min_mean`/`max_mean` require `intensity` or `intensity_channel`".to_string(),
+            ));
+        }
+
+        image.retain(|label| !removed.contains(&label));
+        image.into_output()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LabelsRelabelOp;
+
+impl Operation for LabelsRelabelOp {
+    fn name(&self) -> &'static str {
+        "labels.relabel"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Renumber labels 1..n in raster order of first appearance.".to_string(),
+            params: vec![channel_param()],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let mut image = LabelImage::read(dataset, params)?;
+        let mut mapping = HashMap::new();
+        for label in &mut image.labels {
+            if *label > 0 {
+                let next = mapping.len() as u32 + 1;
+                *label = *mapping.entry(*label).or_insert(next);
+            }
+        }
+        image.into_output()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LabelsDilateOp;
+
+impl Operation for LabelsDilateOp {
+    fn name(&self) -> &'static str {
+        "labels.dilate"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Grow labels into the background by up to `radius` pixels without merging them."
+                    .to_string(),
+            params: vec![
+                channel_param(),
+                label_param("radius", "Euclidean growth radius in pixels.", "float"),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let mut image = LabelImage::read(dataset, params)?;
+        let radius = get_optional_f32(params, "radius", 1.0);
+        if !radius.is_finite() || radius < 0.0 {
+            return Err(OpsError::InvalidParams(
+                "`radius` must be finite and >= 0".to_string(),
+            ));
+        }
+        let radius_squared = f64::from(radius) * f64::from(radius);
+
+        // Each background pixel within `radius` of a label joins the label of
+        // its nearest labelled pixel, found by the exact Euclidean transform.
+        let features = ArrayD::from_shape_vec(
+            IxDyn(&image.shape),
+            image.labels.iter().map(|label| *label > 0).collect(),
+        )
+        .expect("one label per pixel");
+        let axes = image
+            .spatial
+            .iter()
+            .map(|axis| (*axis, 1.0))
+            .collect::<Vec<_>>();
+        let (distances, nearest) = distance_transform(&features, &axes);
+        let labels = image.labels.clone();
+        for ((label, distance), nearest) in image.labels.iter_mut().zip(&distances).zip(&nearest) {
+            if *label == 0 && *distance <= radius_squared {
+                *label = labels[*nearest];
+            }
+        }
+        image.into_output()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LabelsBoundariesOp;
+
+impl Operation for LabelsBoundariesOp {
+    fn name(&self) -> &'static str {
+        "labels.boundaries"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Keep only label pixels that touch another label or the background (inner outlines)."
+                    .to_string(),
+            params: vec![
+                channel_param(),
+                label_param(
+                    "binary",
+                    "Return a 0/1 boundary mask instead of labelled outlines.",
+                    "bool",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let mut image = LabelImage::read(dataset, params)?;
+        let outlines = (0..image.labels.len())
+            .map(|index| {
+                let label = image.labels[index];
+                let boundary = label > 0
+                    && image
+                        .neighbours(index, false)
+                        .into_iter()
+                        .any(|neighbour| image.labels[neighbour] != label);
+                if boundary { label } else { 0 }
+            })
+            .collect::<Vec<_>>();
+        image.labels = outlines;
+        if !get_optional_bool(params, "binary", false) {
+            return image.into_output();
+        }
+        let data = ArrayD::from_shape_vec(
+            IxDyn(&image.shape),
+            image
+                .labels
+                .iter()
+                .map(|label| if *label > 0 { 1.0 } else { 0.0 })
+                .collect(),
+        )
+        .expect("label buffer matches its shape");
+        let mut metadata = image.metadata;
+        metadata.kind = DatasetKind::Intensity;
+        Ok(OpOutput::dataset_only(Dataset::new(data, metadata)?))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LabelsMergeOp;
+
+impl Operation for LabelsMergeOp {
+    fn name(&self) -> &'static str {
+        "labels.merge"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Merge each group of labels into the group's smallest id.".to_string(),
+            params: vec![
+                channel_param(),
+                ParamSpec {
+                    name: "groups".to_string(),
+                    description:
+                        "Label groups, e.g. [[1, 2], [5, 6, 7]]; a flat list is one group."
+                            .to_string(),
+                    required: true,
+                    kind: "array".to_string(),
+                },
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let groups = params
+            .get("groups")
+            .and_then(Value::as_array)
+            .ok_or_else(|| OpsError::InvalidParams("`groups` is required".to_string()))?;
+        let groups = if groups.iter().all(Value::is_array) {
+            groups
+                .iter()
+                .map(|group| label_list(group, "groups"))
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            vec![label_list(&params["groups"], "groups")?]
+        };
+        let mut mapping = HashMap::new();
+        for group in groups {
+            let Some(target) = group.iter().copied().min() else {
+                continue;
+            };
+            for label in group {
+                mapping.insert(label, target);
+            }
+        }
+
+        let mut image = LabelImage::read(dataset, params)?;
+        for label in &mut image.labels {
+            if let Some(target) = mapping.get(label) {
+                *label = *target;
+            }
+        }
+        image.into_output()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LabelsFillHolesOp;
+
+impl Operation for LabelsFillHolesOp {
+    fn name(&self) -> &'static str {
+        "labels.fill_holes"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Fill background regions that are enclosed by a single label with that label."
+                    .to_string(),
+            params: vec![channel_param()],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let mut image = LabelImage::read(dataset, params)?;
+        let spatial = image.spatial.clone();
+        let mut visited = vec![false; image.labels.len()];
+        for start in 0..image.labels.len() {
+            if image.labels[start] != 0 || visited[start] {
+                continue;
+            }
+            visited[start] = true;
+            let mut region = Vec::new();
+            let mut surrounding = BTreeSet::new();
+            let mut touches_border = false;
+            let mut queue = VecDeque::from([start]);
+            while let Some(index) = queue.pop_front() {
+                region.push(index);
+                touches_border |= image.touches_border(index, &spatial);
+                for neighbour in image.neighbours(index, false) {
+                    match image.labels[neighbour] {
+                        0 if !visited[neighbour] => {
+                            visited[neighbour] = true;
+                            queue.push_back(neighbour);
+                        }
+                        0 => {}
+                        label => {
+                            surrounding.insert(label);
+                        }
+                    }
+                }
+            }
+            if !touches_border && surrounding.len() == 1 {
+                let label = *surrounding.first().expect("one surrounding label");
+                for index in region {
+                    image.labels[index] = label;
+                }
+            }
+        }
+        image.into_output()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LabelsKeepLargestOp;
+
+impl Operation for LabelsKeepLargestOp {
+    fn name(&self) -> &'static str {
+        "labels.keep_largest"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Keep only the label with the most pixels.".to_string(),
+            params: vec![channel_param()],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let mut image = LabelImage::read(dataset, params)?;
+        // Ties go to the smallest id.
+        let largest = image
+            .counts()
+            .into_iter()
+            .max_by(|(left_label, left), (right_label, right)| {
+                left.cmp(right).then(right_label.cmp(left_label))
+            })
+            .map(|(label, _)| label);
+        image.retain(|label| Some(label) == largest);
+        image.into_output()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LabelsCropOp;
+
+impl Operation for LabelsCropOp {
+    fn name(&self) -> &'static str {
+        "labels.crop"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Crop the spatial axes to one label's bounding box, keeping only that label."
+                    .to_string(),
+            params: vec![
+                channel_param(),
+                ParamSpec {
+                    name: "label".to_string(),
+                    description: "Label id to crop to.".to_string(),
+                    required: true,
+                    kind: "int".to_string(),
+                },
+                label_param(
+                    "padding",
+                    "Extra pixels kept around the bounding box (default 0).",
+                    "int",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let target = params
+            .get("label")
+            .and_then(Value::as_u64)
+            .filter(|label| *label > 0)
+            .ok_or_else(|| OpsError::InvalidParams("`label` must be a positive id".to_string()))?
+            as u32;
+        let padding = get_optional_usize(params, "padding", 0);
+        let mut image = LabelImage::read(dataset, params)?;
+
+        let mut bounds = image
+            .spatial
+            .iter()
+            .map(|axis| (*axis, usize::MAX, 0_usize))
+            .collect::<Vec<_>>();
+        let mut found = false;
+        for (index, label) in image.labels.iter().enumerate() {
+            if *label != target {
+                continue;
+            }
+            found = true;
+            for (axis, min, max) in &mut bounds {
+                let position = image.coordinate(index, *axis);
+                *min = (*min).min(position);
+                *max = (*max).max(position);
+            }
+        }
+        if !found {
+            return Err(OpsError::InvalidParams(format!(
+                "label {target} does not occur in the image"
+            )));
+        }
+
+        image.retain(|label| label == target);
+        let mut data = ArrayD::from_shape_vec(IxDyn(&image.shape), image.labels)
+            .expect("label buffer matches its shape");
+        let mut metadata = image.metadata;
+        for (axis, min, max) in bounds {
+            let start = min.saturating_sub(padding);
+            let end = (max + padding + 1).min(image.shape[axis]);
+            data = data.slice_axis(Axis(axis), (start..end).into()).to_owned();
+            metadata.dims[axis].size = end - start;
+        }
+        let dataset = Dataset::new(data, metadata)?.to_label_dataset()?;
+        Ok(OpOutput::dataset_only(dataset))
+    }
+}
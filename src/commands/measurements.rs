@@ -24,6 +24,10 @@ impl Operation for MeasurementsSummaryOp {
         "measurements.summary"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -111,6 +115,10 @@ impl Operation for MeasurementsHistogramOp {
         "measurements.histogram"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -165,6 +173,10 @@ impl Operation for MeasurementsProfileOp {
         "measurements.profile"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -244,6 +256,10 @@ impl Operation for ImageStackZProfileOp {
         "image.stack.z_profile"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -303,6 +319,10 @@ impl Operation for ImageStackStatisticsOp {
         "image.stack.statistics"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
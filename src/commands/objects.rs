@@ -0,0 +1,586 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::f64::consts::PI;
+
+use crate::model::{AxisKind, Dataset, DatasetF32, DatasetKind, Metadata, PixelType};
+use ndarray::{ArrayD, IxDyn};
+use serde_json::{Map, Value, json};
+
+use super::calculator::broadcast_values;
+use super::params::{get_optional_bool, get_optional_usize};
+use super::{
+    INPUT_PARAM_KIND, MeasurementTable, OpInputs, OpOutput, OpSchema, Operation, OpsError,
+    ParamSpec, Result,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct AnalyzeObjects3dOp;
+
+#[derive(Debug, Clone, Copy)]
+struct VolumeShape {
+    width: usize,
+    height: usize,
+    depth: usize,
+}
+
+impl VolumeShape {
+    fn len(self) -> usize {
+        self.width * self.height * self.depth
+    }
+
+    fn index(self, x: usize, y: usize, z: usize) -> usize {
+        x + self.width * (y + self.height * z)
+    }
+
+    fn coordinates(self, index: usize) -> (usize, usize, usize) {
+        (
+            index % self.width,
+            (index / self.width) % self.height,
+            index / (self.width * self.height),
+        )
+    }
+}
+
+impl Operation for AnalyzeObjects3dOp {
+    fn name(&self) -> &'static str {
+        "analyze.objects_3d"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        let param = |name: &str, description: &str, kind: &str| ParamSpec {
+            name: name.to_string(),
+            description: description.to_string(),
+            required: false,
+            kind: kind.to_string(),
+        };
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Label 3D objects in each X/Y/Z volume and measure them like the 3D Objects Counter."
+                    .to_string(),
+            params: vec![
+                param(
+                    "lower",
+                    "Lower threshold; foreground is lower <= value <= upper. Defaults to value > 0.5. Label images are measured as-is.",
+                    "float",
+                ),
+                param("upper", "Upper threshold; requires `lower`.", "float"),
+                param("connectivity", "6, 18 or 26 (default).", "int"),
+                param("min_size", "Minimum object size in voxels.", "int"),
+                param("max_size", "Maximum object size in voxels.", "int"),
+                param(
+                    "exclude_edges",
+                    "Discard objects touching the X/Y border.",
+                    "bool",
+                ),
+                param(
+                    "channel",
+                    "Channel that defines the objects (default 0).",
+                    "int",
+                ),
+                param(
+                    "intensity",
+                    "Image whose intensities are measured; takes precedence over `redirect_channel`.",
+                    INPUT_PARAM_KIND,
+                ),
+                param(
+                    "redirect_channel",
+                    "Channel whose intensities are measured; defaults to `channel`.",
+                    "int",
+                ),
+                param(
+                    "output",
+                    "Output image: labels (default) or none.",
+                    "string",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        let x_axis = axis_index(dataset, AxisKind::X)?;
+        let y_axis = axis_index(dataset, AxisKind::Y)?;
+        let z_axis = dataset.axis_index(AxisKind::Z);
+        let channel_axis = dataset.axis_index(AxisKind::Channel);
+        let channel_count = dataset.metadata.channel_count();
+        let channel = get_optional_usize(params, "channel", 0);
+        let redirect_channel = get_optional_usize(params, "redirect_channel", channel);
+        for (key, value) in [("channel", channel), ("redirect_channel", redirect_channel)] {
+            if value >= channel_count {
+                return Err(OpsError::InvalidParams(format!(
+                    "`{key}` {value} is out of range for {channel_count} channel(s)"
+                )));
+            }
+        }
+        let intensity = inputs
+            .get("intensity")
+            .map(|image| broadcast_values(dataset, image, "intensity"))
+            .transpose()?;
+        let threshold = match (
+            optional_f64(params, "lower")?,
+            optional_f64(params, "upper")?,
+        ) {
+            (None, None) => None,
+            (Some(lower), upper) => Some((lower, upper.unwrap_or(f64::INFINITY))),
+            (None, Some(_)) => {
+                return Err(OpsError::InvalidParams(
+                    "`upper` requires `lower`".to_string(),
+                ));
+            }
+        };
+        let offsets = connectivity_offsets(get_optional_usize(params, "connectivity", 26))?;
+        let min_size = get_optional_usize(params, "min_size", 0);
+        let max_size = get_optional_usize(params, "max_size", usize::MAX);
+        if min_size > max_size {
+            return Err(OpsError::InvalidParams(
+                "`min_size` must not exceed `max_size`".to_string(),
+            ));
+        }
+        let exclude_edges = get_optional_bool(params, "exclude_edges", false);
+        let write_labels = match params.get("output").and_then(Value::as_str) {
+            None | Some("labels") => true,
+            Some("none") => false,
+            Some(other) => {
+                return Err(OpsError::InvalidParams(format!(
+                    "unsupported `output` `{other}`; expected labels or none"
+                )));
+            }
+        };
+
+        let shape = dataset.shape().to_vec();
+        let volume = VolumeShape {
+            width: shape[x_axis],
+            height: shape[y_axis],
+            depth: z_axis.map(|axis| shape[axis]).unwrap_or(1),
+        };
+        let spacing = |axis: Option<usize>| {
+            axis.and_then(|axis| dataset.metadata.dims[axis].spacing)
+                .map(f64::from)
+                .unwrap_or(1.0)
+        };
+        let spacing = [
+            spacing(Some(x_axis)),
+            spacing(Some(y_axis)),
+            spacing(z_axis),
+        ];
+        let volume_axes = [Some(x_axis), Some(y_axis), z_axis];
+        let outer_axes = (0..shape.len())
+            .filter(|axis| !volume_axes.contains(&Some(*axis)) && Some(*axis) != channel_axis)
+            .collect::<Vec<_>>();
+        let outer_shape = outer_axes
+            .iter()
+            .map(|axis| shape[*axis])
+            .collect::<Vec<_>>();
+        let output_axes = (0..shape.len())
+            .filter(|axis| Some(*axis) != channel_axis)
+            .collect::<Vec<_>>();
+        let mut output_data = write_labels.then(|| {
+            ArrayD::<f32>::zeros(IxDyn(
+                &output_axes
+                    .iter()
+                    .map(|axis| shape[*axis])
+                    .collect::<Vec<_>>(),
+            ))
+        });
+
+        let mut rows = Vec::new();
+        let mut next_label = 1_u32;
+        iterate_indices(&outer_shape, |outer_coord| {
+            let mut coord = vec![0_usize; shape.len()];
+            for (axis, index) in outer_axes.iter().zip(outer_coord) {
+                coord[*axis] = *index;
+            }
+            let mut read_volume = |data: &ArrayD<f32>, channel: usize| {
+                if let Some(channel_axis) = channel_axis {
+                    coord[channel_axis] = channel;
+                }
+                let mut values = Vec::with_capacity(volume.len());
+                for z in 0..volume.depth {
+                    if let Some(z_axis) = z_axis {
+                        coord[z_axis] = z;
+                    }
+                    for y in 0..volume.height {
+                        coord[y_axis] = y;
+                        for x in 0..volume.width {
+                            coord[x_axis] = x;
+                            values.push(data[IxDyn(&coord)]);
+                        }
+                    }
+                }
+                values
+            };
+            let source = read_volume(&dataset.data, channel);
+            let intensities = match intensity.as_ref() {
+                Some(intensity) => read_volume(intensity, channel),
+                None => read_volume(&dataset.data, redirect_channel),
+            };
+
+            let objects = if dataset.is_label() && threshold.is_none() {
+                objects_from_labels(&source)
+            } else {
+                let mask = source
+                    .iter()
+                    .map(|value| match threshold {
+                        Some((lower, upper)) => {
+                            f64::from(*value) >= lower && f64::from(*value) <= upper
+                        }
+                        None => *value > 0.5,
+                    })
+                    .collect::<Vec<_>>();
+                label_objects(&mask, volume, &offsets)
+            };
+            // One owner id per voxel (0 for background) so surface faces are tested without
+            // allocating a membership buffer for every object.
+            let mut owner = vec![0_u32; volume.len()];
+            for (id, voxels) in (1_u32..).zip(&objects) {
+                for index in voxels {
+                    owner[*index] = id;
+                }
+            }
+
+            for (id, voxels) in (1_u32..).zip(objects) {
+                if voxels.len() < min_size || voxels.len() > max_size {
+                    continue;
+                }
+                let stats = measure_object(&voxels, id, &owner, &intensities, volume, spacing);
+                let [min_x, min_y, _, max_x, max_y, _] = stats.bounds;
+                if exclude_edges
+                    && (min_x == 0
+                        || min_y == 0
+                        || max_x + 1 == volume.width
+                        || max_y + 1 == volume.height)
+                {
+                    continue;
+                }
+                let label = next_label;
+                next_label += 1;
+                if let Some(output_data) = output_data.as_mut() {
+                    let mut output_coord = output_axes
+                        .iter()
+                        .map(|axis| coord[*axis])
+                        .collect::<Vec<_>>();
+                    let position =
+                        |axis: usize| output_axes.iter().position(|candidate| *candidate == axis);
+                    for index in &voxels {
+                        let (x, y, z) = volume.coordinates(*index);
+                        output_coord[position(x_axis).expect("x axis kept")] = x;
+                        output_coord[position(y_axis).expect("y axis kept")] = y;
+                        if let Some(z_position) = z_axis.and_then(position) {
+                            output_coord[z_position] = z;
+                        }
+                        output_data[IxDyn(&output_coord)] = label as f32;
+                    }
+                }
+
+                let mut row = Map::new();
+                row.insert("label".to_string(), json!(label));
+                for (axis, index) in outer_axes.iter().zip(outer_coord) {
+                    let code = dataset.metadata.dims[*axis]
+                        .axis
+                        .code()
+                        .to_ascii_lowercase();
+                    row.insert(code.to_string(), json!(index));
+                }
+                stats.write_row(&mut row);
+                rows.push(Value::Object(row));
+            }
+        });
+
+        let mut measurements = MeasurementTable::default();
+        measurements
+            .values
+            .insert("count".to_string(), json!(rows.len()));
+        measurements.values.insert("rows".to_string(), json!(rows));
+        if let Some(unit) = common_unit(dataset, &volume_axes) {
+            measurements
+                .values
+                .insert("volume_unit".to_string(), json!(format!("{unit}^3")));
+        }
+
+        let output_dataset = match output_data {
+            None => dataset.clone(),
+            Some(data) => {
+                let mut metadata = Metadata {
+                    dims: output_axes
+                        .iter()
+                        .map(|axis| dataset.metadata.dims[*axis].clone())
+                        .collect(),
+                    ..dataset.metadata.clone()
+                };
+                if channel_axis.is_some() {
+                    metadata.channel_names.clear();
+                    metadata.display = None;
+                }
+                metadata.kind = DatasetKind::Label;
+                metadata.pixel_type = PixelType::F32;
+                Dataset::new(data, metadata)?
+            }
+        };
+        Ok(OpOutput {
+            dataset: output_dataset,
+            measurements: Some(measurements),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ObjectStats {
+    voxels: usize,
+    volume: f64,
+    surface_area: f64,
+    sphericity: f64,
+    centroid: [f64; 3],
+    bounds: [usize; 6],
+    mean: f64,
+    std_dev: f64,
+    min: f32,
+    max: f32,
+    integrated_density: f64,
+}
+
+impl ObjectStats {
+    fn write_row(&self, row: &mut Map<String, Value>) {
+        let [min_x, min_y, min_z, max_x, max_y, max_z] = self.bounds;
+        row.insert("voxels".to_string(), json!(self.voxels));
+        row.insert("volume".to_string(), json!(self.volume));
+        row.insert("surface_area".to_string(), json!(self.surface_area));
+        row.insert("sphericity".to_string(), json!(self.sphericity));
+        row.insert("centroid_x".to_string(), json!(self.centroid[0]));
+        row.insert("centroid_y".to_string(), json!(self.centroid[1]));
+        row.insert("centroid_z".to_string(), json!(self.centroid[2]));
+        row.insert(
+            "bbox".to_string(),
+            json!([
+                min_x,
+                min_y,
+                min_z,
+                max_x - min_x + 1,
+                max_y - min_y + 1,
+                max_z - min_z + 1
+            ]),
+        );
+        row.insert("mean".to_string(), json!(self.mean));
+        row.insert("std_dev".to_string(), json!(self.std_dev));
+        row.insert("min".to_string(), json!(self.min));
+        row.insert("max".to_string(), json!(self.max));
+        row.insert(
+            "integrated_density".to_string(),
+            json!(self.integrated_density),
+        );
+    }
+}
+
+fn connectivity_offsets(connectivity: usize) -> Result<Vec<(isize, isize, isize)>> {
+    let max_nonzero = match connectivity {
+        6 => 1,
+        18 => 2,
+        26 => 3,
+        _ => {
+            return Err(OpsError::InvalidParams(
+                "`connectivity` must be 6, 18 or 26".to_string(),
+            ));
+        }
+    };
+    let mut offsets = Vec::new();
+    for dz in -1_isize..=1 {
+        for dy in -1_isize..=1 {
+            for dx in -1_isize..=1 {
+                let nonzero = [dx, dy, dz].iter().filter(|value| **value != 0).count();
+                if nonzero > 0 && nonzero <= max_nonzero {
+                    offsets.push((dx, dy, dz));
+                }
+            }
+        }
+    }
+    Ok(offsets)
+}
+
+/// Voxel index lists of the connected foreground objects in raster order.
+fn label_objects(
+    mask: &[bool],
+    volume: VolumeShape,
+    offsets: &[(isize, isize, isize)],
+) -> Vec<Vec<usize>> {
+    let mut visited = vec![false; mask.len()];
+    let mut objects = Vec::new();
+    for start in 0..mask.len() {
+        if !mask[start] || visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut voxels = Vec::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(index) = queue.pop_front() {
+            voxels.push(index);
+            let (x, y, z) = volume.coordinates(index);
+            for (dx, dy, dz) in offsets {
+                let (Some(nx), Some(ny), Some(nz)) = (
+                    x.checked_add_signed(*dx),
+                    y.checked_add_signed(*dy),
+                    z.checked_add_signed(*dz),
+                ) else {
+                    continue;
+                };
+                if nx >= volume.width || ny >= volume.height || nz >= volume.depth {
+                    continue;
+                }
+                let neighbour = volume.index(nx, ny, nz);
+                if mask[neighbour] && !visited[neighbour] {
+                    visited[neighbour] = true;
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        objects.push(voxels);
+    }
+    objects
+}
+
+/// Voxel index lists per non-zero label id, in ascending id order.
+fn objects_from_labels(labels: &[f32]) -> Vec<Vec<usize>> {
+    let mut objects = BTreeMap::<u32, Vec<usize>>::new();
+    for (index, value) in labels.iter().enumerate() {
+        if *value >= 1.0 {
+            objects.entry(*value as u32).or_default().push(index);
+        }
+    }
+    objects.into_values().collect()
+}
+
+/// Statistics for the object `id`, whose voxels are marked with `id` in `owner`.
+fn measure_object(
+    voxels: &[usize],
+    id: u32,
+    owner: &[u32],
+    intensities: &[f32],
+    volume: VolumeShape,
+    spacing: [f64; 3],
+) -> ObjectStats {
+    let face_areas = [
+        spacing[1] * spacing[2],
+        spacing[0] * spacing[2],
+        spacing[0] * spacing[1],
+    ];
+    let mut surface_area = 0.0_f64;
+    let mut centroid = [0.0_f64; 3];
+    let mut bounds = [usize::MAX, usize::MAX, usize::MAX, 0, 0, 0];
+    let mut sum = 0.0_f64;
+    let mut sum2 = 0.0_f64;
+    let mut min = f32::INFINITY;
+    let mut max = f32::NEG_INFINITY;
+    for index in voxels {
+        let (x, y, z) = volume.coordinates(*index);
+        let position = [x, y, z];
+        let extent = [volume.width, volume.height, volume.depth];
+        for axis in 0..3 {
+            centroid[axis] += (position[axis] as f64 + 0.5) * spacing[axis];
+            bounds[axis] = bounds[axis].min(position[axis]);
+            bounds[axis + 3] = bounds[axis + 3].max(position[axis]);
+            // Each face shared with a non-member voxel, or the volume border, is exposed.
+            for step in [-1_isize, 1] {
+                let exposed = match position[axis].checked_add_signed(step) {
+                    Some(next) if next < extent[axis] => {
+                        let mut neighbour = position;
+                        neighbour[axis] = next;
+                        owner[volume.index(neighbour[0], neighbour[1], neighbour[2])] != id
+                    }
+                    _ => true,
+                };
+                if exposed {
+                    surface_area += face_areas[axis];
+                }
+            }
+        }
+        let value = intensities[*index];
+        sum += f64::from(value);
+        sum2 += f64::from(value) * f64::from(value);
+        min = min.min(value);
+        max = max.max(value);
+    }
+
+    let count = voxels.len() as f64;
+    let object_volume = count * spacing.iter().product::<f64>();
+    let mean = sum / count;
+    let variance = if voxels.len() > 1 {
+        ((sum2 - sum * sum / count) / (count - 1.0)).max(0.0)
+    } else {
+        0.0
+    };
+    ObjectStats {
+        voxels: voxels.len(),
+        volume: object_volume,
+        surface_area,
+        sphericity: if surface_area > 0.0 {
+            PI.cbrt() * (6.0 * object_volume).powf(2.0 / 3.0) / surface_area
+        } else {
+            0.0
+        },
+        centroid: centroid.map(|value| value / count),
+        bounds,
+        mean,
+        std_dev: variance.sqrt(),
+        min,
+        max,
+        integrated_density: sum * spacing.iter().product::<f64>(),
+    }
+}
+
+fn common_unit(dataset: &DatasetF32, axes: &[Option<usize>]) -> Option<String> {
+    let mut units = axes
+        .iter()
+        .flatten()
+        .map(|axis| dataset.metadata.dims[*axis].unit.as_deref());
+    let first = units.next()??;
+    (first != "pixel" && units.all(|unit| unit == Some(first))).then(|| first.to_string())
+}
+
+fn optional_f64(params: &Value, key: &str) -> Result<Option<f64>> {
+    match params.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value
+            .as_f64()
+            .map(Some)
+            .ok_or_else(|| OpsError::InvalidParams(format!("`{key}` must be a number"))),
+    }
+}
+
+fn axis_index(dataset: &DatasetF32, axis: AxisKind) -> Result<usize> {
+    dataset
+        .axis_index(axis)
+        .ok_or_else(|| OpsError::UnsupportedLayout(format!("dataset has no {axis:?} axis")))
+}
+
+fn iterate_indices(shape: &[usize], mut callback: impl FnMut(&[usize])) {
+    if shape.is_empty() {
+        callback(&[]);
+        return;
+    }
+    let mut index = vec![0usize; shape.len()];
+    loop {
+        callback(&index);
+        let mut dim = shape.len();
+        while dim > 0 {
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+            if dim == 0 {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,843 @@
+use std::collections::VecDeque;
+use std::f64::consts::{PI, SQRT_2};
+
+use crate::model::{AxisKind, Dataset, DatasetF32, DatasetKind, Metadata, PixelType};
+use ndarray::{ArrayD, IxDyn};
+use serde_json::{Map, Value, json};
+
+use super::calculator::broadcast_values;
+use super::params::{get_optional_bool, get_optional_f32, get_optional_usize};
+use super::{
+    INPUT_PARAM_KIND, MeasurementTable, OpInputs, OpOutput, OpSchema, Operation, OpsError,
+    ParamSpec, Result,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct AnalyzeParticlesOp;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParticleOutput {
+    Nothing,
+    Labels,
+    Masks,
+    Outlines,
+}
+
+impl ParticleOutput {
+    fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "none" | "nothing" => Ok(Self::Nothing),
+            "labels" | "count_masks" => Ok(Self::Labels),
+            "masks" => Ok(Self::Masks),
+            "outlines" => Ok(Self::Outlines),
+            other => Err(OpsError::InvalidParams(format!(
+                "unsupported `output` `{other}`; expected none, labels, masks or outlines"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ParticleOptions {
+    min_size: f64,
+    max_size: f64,
+    min_circularity: f64,
+    max_circularity: f64,
+    exclude_edges: bool,
+    include_holes: bool,
+    eight_connected: bool,
+    pixel_width: f64,
+    pixel_height: f64,
+}
+
+impl Operation for AnalyzeParticlesOp {
+    fn name(&self) -> &'static str {
+        "analyze.particles"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        let param = |name: &str, description: &str, kind: &str| ParamSpec {
+            name: name.to_string(),
+            description: description.to_string(),
+            required: false,
+            kind: kind.to_string(),
+        };
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Count and measure particles in each X/Y plane of a binary or thresholded image."
+                    .to_string(),
+            params: vec![
+                param(
+                    "lower",
+                    "Lower threshold; foreground is lower <= value <= upper. Defaults to value > 0.5.",
+                    "float",
+                ),
+                param("upper", "Upper threshold; requires `lower`.", "float"),
+                param(
+                    "min_size",
+                    "Minimum particle area in calibrated units (pixels with `pixel_units`).",
+                    "float",
+                ),
+                param(
+                    "max_size",
+                    "Maximum particle area; unbounded by default.",
+                    "float",
+                ),
+                param("min_circularity", "Minimum circularity in 0..=1.", "float"),
+                param("max_circularity", "Maximum circularity in 0..=1.", "float"),
+                param("pixel_units", "Interpret size limits in pixels.", "bool"),
+                param(
+                    "exclude_edges",
+                    "Discard particles touching the image border.",
+                    "bool",
+                ),
+                param(
+                    "include_holes",
+                    "Measure particles with interior holes filled.",
+                    "bool",
+                ),
+                param("connectivity", "4 or 8 (default).", "int"),
+                param(
+                    "channel",
+                    "Channel that defines the particles (default 0).",
+                    "int",
+                ),
+                param(
+                    "intensity",
+                    "Image whose intensities are measured; takes precedence over `redirect_channel`.",
+                    INPUT_PARAM_KIND,
+                ),
+                param(
+                    "redirect_channel",
+                    "Channel whose intensities are measured; defaults to `channel`.",
+                    "int",
+                ),
+                param(
+                    "output",
+                    "Output image: none (default), labels, masks or outlines.",
+                    "string",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        let x_axis = axis_index(dataset, AxisKind::X)?;
+        let y_axis = axis_index(dataset, AxisKind::Y)?;
+        let channel_axis = dataset.axis_index(AxisKind::Channel);
+        let channel_count = dataset.metadata.channel_count();
+        let channel = get_optional_usize(params, "channel", 0);
+        let redirect_channel = get_optional_usize(params, "redirect_channel", channel);
+        for (key, value) in [("channel", channel), ("redirect_channel", redirect_channel)] {
+            if value >= channel_count {
+                return Err(OpsError::InvalidParams(format!(
+                    "`{key}` {value} is out of range for {channel_count} channel(s)"
+                )));
+            }
+        }
+        let intensity = inputs
+            .get("intensity")
+            .map(|image| broadcast_values(dataset, image, "intensity"))
+            .transpose()?;
+        let threshold = match (
+            optional_f64(params, "lower")?,
+            optional_f64(params, "upper")?,
+        ) {
+            (None, None) => None,
+            (Some(lower), upper) => Some((lower, upper.unwrap_or(f64::INFINITY))),
+            (None, Some(_)) => {
+                return Err(OpsError::InvalidParams(
+                    "`upper` requires `lower`".to_string(),
+                ));
+            }
+        };
+        let output = params
+            .get("output")
+            .and_then(Value::as_str)
+            .map(ParticleOutput::parse)
+            .transpose()?
+            .unwrap_or(ParticleOutput::Nothing);
+        let connectivity = get_optional_usize(params, "connectivity", 8);
+        if connectivity != 4 && connectivity != 8 {
+            return Err(OpsError::InvalidParams(
+                "`connectivity` must be 4 or 8".to_string(),
+            ));
+        }
+
+        let pixel_width = f64::from(dataset.metadata.dims[x_axis].spacing.unwrap_or(1.0));
+        let pixel_height = f64::from(dataset.metadata.dims[y_axis].spacing.unwrap_or(1.0));
+        let size_scale = if get_optional_bool(params, "pixel_units", false) {
+            pixel_width * pixel_height
+        } else {
+            1.0
+        };
+        let options = ParticleOptions {
+            min_size: f64::from(get_optional_f32(params, "min_size", 0.0)) * size_scale,
+            max_size: optional_f64(params, "max_size")?.unwrap_or(f64::INFINITY) * size_scale,
+            min_circularity: f64::from(get_optional_f32(params, "min_circularity", 0.0)),
+            max_circularity: f64::from(get_optional_f32(params, "max_circularity", 1.0)),
+            exclude_edges: get_optional_bool(params, "exclude_edges", false),
+            include_holes: get_optional_bool(params, "include_holes", false),
+            eight_connected: connectivity == 8,
+            pixel_width,
+            pixel_height,
+        };
+        if options.min_size > options.max_size || options.min_circularity > options.max_circularity
+        {
+            return Err(OpsError::InvalidParams(
+                "size and circularity minimums must not exceed their maximums".to_string(),
+            ));
+        }
+
+        let shape = dataset.shape().to_vec();
+        let width = shape[x_axis];
+        let height = shape[y_axis];
+        let plane_axes = (0..shape.len())
+            .filter(|axis| *axis != x_axis && *axis != y_axis && Some(*axis) != channel_axis)
+            .collect::<Vec<_>>();
+        let plane_shape = plane_axes
+            .iter()
+            .map(|axis| shape[*axis])
+            .collect::<Vec<_>>();
+        let output_axes = (0..shape.len())
+            .filter(|axis| Some(*axis) != channel_axis)
+            .collect::<Vec<_>>();
+        let output_position = |axis: usize| {
+            output_axes
+                .iter()
+                .position(|candidate| *candidate == axis)
+                .expect("spatial axes are kept in the output")
+        };
+        let (x_position, y_position) = (output_position(x_axis), output_position(y_axis));
+        let mut output_data = (output != ParticleOutput::Nothing).then(|| {
+            ArrayD::<f32>::zeros(IxDyn(
+                &output_axes
+                    .iter()
+                    .map(|axis| shape[*axis])
+                    .collect::<Vec<_>>(),
+            ))
+        });
+
+        let mut rows = Vec::new();
+        let mut next_label = 1_u32;
+        let mut total_area = 0.0_f64;
+        let mut plane_count = 0_usize;
+        iterate_indices(&plane_shape, |plane_coord| {
+            plane_count += 1;
+            let mut coord = vec![0_usize; shape.len()];
+            for (axis, index) in plane_axes.iter().zip(plane_coord) {
+                coord[*axis] = *index;
+            }
+            let mut read_plane = |data: &ArrayD<f32>, channel: usize| {
+                if let Some(channel_axis) = channel_axis {
+                    coord[channel_axis] = channel;
+                }
+                let mut values = Vec::with_capacity(width * height);
+                for y in 0..height {
+                    for x in 0..width {
+                        coord[y_axis] = y;
+                        coord[x_axis] = x;
+                        values.push(data[IxDyn(&coord)]);
+                    }
+                }
+                values
+            };
+            let mask = read_plane(&dataset.data, channel)
+                .into_iter()
+                .map(|value| match threshold {
+                    Some((lower, upper)) => f64::from(value) >= lower && f64::from(value) <= upper,
+                    None => value > 0.5,
+                })
+                .collect::<Vec<_>>();
+            let intensities = match intensity.as_ref() {
+                Some(intensity) => read_plane(intensity, channel),
+                None => read_plane(&dataset.data, redirect_channel),
+            };
+
+            let particles = analyze_plane(&mask, &intensities, width, height, &options);
+            for particle in particles {
+                let label = next_label;
+                next_label += 1;
+                total_area += particle.area;
+                if let Some(output_data) = output_data.as_mut() {
+                    let mut output_coord = vec![0_usize; output_axes.len()];
+                    for (position, axis) in output_axes.iter().enumerate() {
+                        output_coord[position] = coord[*axis];
+                    }
+                    let pixels = match output {
+                        ParticleOutput::Outlines => &particle.outline,
+                        _ => &particle.pixels,
+                    };
+                    for (x, y) in pixels {
+                        output_coord[x_position] = *x;
+                        output_coord[y_position] = *y;
+                        output_data[IxDyn(&output_coord)] = match output {
+                            ParticleOutput::Labels => label as f32,
+                            _ => 1.0,
+                        };
+                    }
+                }
+
+                let mut row = Map::new();
+                row.insert("label".to_string(), json!(label));
+                for (axis, index) in plane_axes.iter().zip(plane_coord) {
+                    let code = dataset.metadata.dims[*axis]
+                        .axis
+                        .code()
+                        .to_ascii_lowercase();
+                    row.insert(code.to_string(), json!(index));
+                }
+                particle.write_row(&mut row);
+                rows.push(Value::Object(row));
+            }
+        });
+
+        let count = rows.len();
+        let image_area = (width * height * plane_count) as f64 * pixel_width * pixel_height;
+        let mut measurements = MeasurementTable::default();
+        measurements
+            .values
+            .insert("count".to_string(), json!(count));
+        measurements
+            .values
+            .insert("total_area".to_string(), json!(total_area));
+        measurements.values.insert(
+            "average_size".to_string(),
+            json!(if count == 0 {
+                0.0
+            } else {
+                total_area / count as f64
+            }),
+        );
+        measurements.values.insert(
+            "area_fraction".to_string(),
+            json!(if image_area > 0.0 {
+                100.0 * total_area / image_area
+            } else {
+                0.0
+            }),
+        );
+        measurements.values.insert("rows".to_string(), json!(rows));
+
+        let output_dataset = match output_data {
+            None => dataset.clone(),
+            Some(data) => {
+                let mut metadata = Metadata {
+                    dims: output_axes
+                        .iter()
+                        .map(|axis| dataset.metadata.dims[*axis].clone())
+                        .collect(),
+                    ..dataset.metadata.clone()
+                };
+                if channel_axis.is_some() {
+                    metadata.channel_names.clear();
+                    metadata.display = None;
+                }
+                if output == ParticleOutput::Labels {
+                    metadata.kind = DatasetKind::Label;
+                    metadata.pixel_type = PixelType::F32;
+                } else {
+                    metadata.kind = DatasetKind::Intensity;
+                    metadata.pixel_type = PixelType::U8;
+                }
+                Dataset::new(data, metadata)?
+            }
+        };
+        Ok(OpOutput {
+            dataset: output_dataset,
+            measurements: Some(measurements),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ParticleStats {
+    pixels: Vec<(usize, usize)>,
+    outline: Vec<(usize, usize)>,
+    bbox: [usize; 4],
+    area: f64,
+    perimeter: f64,
+    centroid: (f64, f64),
+    circularity: f64,
+    solidity: f64,
+    feret: f64,
+    feret_angle: f64,
+    min_feret: f64,
+    mean: f64,
+    min: f32,
+    max: f32,
+    integrated_density: f64,
+}
+
+impl ParticleStats {
+    fn write_row(&self, row: &mut Map<String, Value>) {
+        row.insert("area".to_string(), json!(self.area));
+        row.insert("perimeter".to_string(), json!(self.perimeter));
+        row.insert("centroid_x".to_string(), json!(self.centroid.0));
+        row.insert("centroid_y".to_string(), json!(self.centroid.1));
+        row.insert("bbox".to_string(), json!(self.bbox));
+        row.insert("circularity".to_string(), json!(self.circularity));
+        row.insert("solidity".to_string(), json!(self.solidity));
+        row.insert("feret".to_string(), json!(self.feret));
+        row.insert("feret_angle".to_string(), json!(self.feret_angle));
+        row.insert("min_feret".to_string(), json!(self.min_feret));
+        row.insert("mean".to_string(), json!(self.mean));
+        row.insert("min".to_string(), json!(self.min));
+        row.insert("max".to_string(), json!(self.max));
+        row.insert(
+            "integrated_density".to_string(),
+            json!(self.integrated_density),
+        );
+    }
+}
+
+/// Labels, traces and measures the particles of one plane in raster order,
+/// returning only the particles that pass the filters.
+fn analyze_plane(
+    mask: &[bool],
+    intensities: &[f32],
+    width: usize,
+    height: usize,
+    options: &ParticleOptions,
+) -> Vec<ParticleStats> {
+    let mut labels = vec![0_u32; mask.len()];
+    let mut components = Vec::new();
+    for start in 0..mask.len() {
+        if !mask[start] || labels[start] != 0 {
+            continue;
+        }
+        let label = components.len() as u32 + 1;
+        labels[start] = label;
+        let mut pixels = Vec::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(index) = queue.pop_front() {
+            let (x, y) = (index % width, index / width);
+            pixels.push((x, y));
+            for (nx, ny) in neighbours(x, y, width, height, options.eight_connected) {
+                let neighbour = nx + ny * width;
+                if mask[neighbour] && labels[neighbour] == 0 {
+                    labels[neighbour] = label;
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        components.push(pixels);
+    }
+
+    let pixel_area = options.pixel_width * options.pixel_height;
+    let mut particles = Vec::new();
+    for (component, mut pixels) in components.into_iter().enumerate() {
+        let label = component as u32 + 1;
+        let (start_x, start_y) = pixels[0];
+        // Particles swallowed by an earlier particle's filled holes are skipped.
+        if labels[start_x + start_y * width] != label {
+            continue;
+        }
+        let mut bbox = [start_x, start_y, start_x, start_y];
+        for (x, y) in &pixels {
+            bbox = [
+                bbox[0].min(*x),
+                bbox[1].min(*y),
+                bbox[2].max(*x),
+                bbox[3].max(*y),
+            ];
+        }
+        if options.exclude_edges
+            && (bbox[0] == 0 || bbox[1] == 0 || bbox[2] + 1 == width || bbox[3] + 1 == height)
+        {
+            continue;
+        }
+
+        let polygon = trace_outline(
+            &labels,
+            width,
+            height,
+            label,
+            (start_x, start_y),
+            options.eight_connected,
+        );
+        if options.include_holes {
+            for hole in interior_holes(&labels, width, label, bbox, options.eight_connected) {
+                labels[hole] = label;
+                pixels.push((hole % width, hole / width));
+            }
+        }
+
+        let area = pixels.len() as f64 * pixel_area;
+        let perimeter = traced_perimeter(&polygon, options.pixel_width, options.pixel_height);
+        let circularity = if perimeter > 0.0 {
+            (4.0 * PI * area / (perimeter * perimeter)).min(1.0)
+        } else {
+            0.0
+        };
+        if area < options.min_size
+            || area > options.max_size
+            || circularity < options.min_circularity
+            || circularity > options.max_circularity
+        {
+            continue;
+        }
+
+        let hull = convex_hull(&polygon)
+            .into_iter()
+            .map(|(x, y)| {
+                (
+                    x as f64 * options.pixel_width,
+                    y as f64 * options.pixel_height,
+                )
+            })
+            .collect::<Vec<_>>();
+        let hull_area = polygon_area(&hull);
+        let (feret, feret_angle, min_feret) = feret_diameters(&hull);
+
+        let mut sum = 0.0_f64;
+        let mut min = f32::INFINITY;
+        let mut max = f32::NEG_INFINITY;
+        let mut centroid = (0.0_f64, 0.0_f64);
+        for (x, y) in &pixels {
+            let value = intensities[x + y * width];
+            sum += f64::from(value);
+            min = min.min(value);
+            max = max.max(value);
+            centroid.0 += (*x as f64 + 0.5) * options.pixel_width;
+            centroid.1 += (*y as f64 + 0.5) * options.pixel_height;
+        }
+        let count = pixels.len() as f64;
+        let outline = pixels
+            .iter()
+            .copied()
+            .filter(|(x, y)| {
+                *x == 0
+                    || *y == 0
+                    || *x + 1 == width
+                    || *y + 1 == height
+                    || neighbours(*x, *y, width, height, false)
+                        .any(|(nx, ny)| labels[nx + ny * width] != label)
+            })
+            .collect();
+
+        particles.push(ParticleStats {
+            pixels,
+            outline,
+            bbox: [
+                bbox[0],
+                bbox[1],
+                bbox[2] - bbox[0] + 1,
+                bbox[3] - bbox[1] + 1,
+            ],
+            area,
+            perimeter,
+            centroid: (centroid.0 / count, centroid.1 / count),
+            circularity,
+            solidity: if hull_area > 0.0 {
+                (area / hull_area).min(1.0)
+            } else {
+                1.0
+            },
+            feret,
+            feret_angle,
+            min_feret,
+            mean: sum / count,
+            min,
+            max,
+            integrated_density: sum / count * area,
+        });
+    }
+    particles
+}
+
+fn neighbours(
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    eight_connected: bool,
+) -> impl Iterator<Item = (usize, usize)> {
+    const OFFSETS: [(isize, isize); 8] = [
+        (1, 0),
+        (-1, 0),
+        (0, 1),
+        (0, -1),
+        (1, 1),
+        (1, -1),
+        (-1, 1),
+        (-1, -1),
+    ];
+    let count = if eight_connected { 8 } else { 4 };
+    OFFSETS[..count].iter().filter_map(move |(dx, dy)| {
+        let nx = x.checked_add_signed(*dx)?;
+        let ny = y.checked_add_signed(*dy)?;
+        (nx < width && ny < height).then_some((nx, ny))
+    })
+}
+
+/// Follows the outer boundary along pixel edges, keeping the particle on the
+/// right, and returns the polygon corners. `start` must be the particle's
+/// first pixel in raster order.
+fn trace_outline(
+    labels: &[u32],
+    width: usize,
+    height: usize,
+    label: u32,
+    start: (usize, usize),
+    eight_connected: bool,
+) -> Vec<(i64, i64)> {
+    const STEPS: [(i64, i64); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let inside = |x: i64, y: i64| {
+        x >= 0
+            && y >= 0
+            && (x as usize) < width
+            && (y as usize) < height
+            && labels[x as usize + y as usize * width] == label
+    };
+    // Pixels ahead-left and ahead-right of vertex (x, y) when heading east, south, west, north.
+    let ahead = |x: i64, y: i64, direction: usize| match direction {
+        0 => ((x, y - 1), (x, y)),
+        1 => ((x, y), (x - 1, y)),
+        2 => ((x - 1, y), (x - 1, y - 1)),
+        _ => ((x - 1, y - 1), (x, y - 1)),
+    };
+
+    let start = (start.0 as i64, start.1 as i64);
+    let mut vertex = start;
+    let mut direction = 3;
+    let mut corners = Vec::new();
+    loop {
+        let ((left_x, left_y), (right_x, right_y)) = ahead(vertex.0, vertex.1, direction);
+        let left = inside(left_x, left_y);
+        let right = inside(right_x, right_y);
+        let next = if left && (eight_connected || right) {
+            (direction + 3) % 4
+        } else if right {
+            direction
+        } else {
+            (direction + 1) % 4
+        };
+        if next != direction {
+            corners.push(vertex);
+        }
+        direction = next;
+        vertex = (vertex.0 + STEPS[direction].0, vertex.1 + STEPS[direction].1);
+        if vertex == start {
+            return corners;
+        }
+    }
+}
+
+/// Background pixels inside `bbox` that cannot reach the box border.
+fn interior_holes(
+    labels: &[u32],
+    width: usize,
+    label: u32,
+    bbox: [usize; 4],
+    eight_connected: bool,
+) -> Vec<usize> {
+    let [min_x, min_y, max_x, max_y] = bbox;
+    let box_width = max_x - min_x + 1;
+    let box_height = max_y - min_y + 1;
+    let local = |x: usize, y: usize| (x - min_x) + (y - min_y) * box_width;
+    let mut exterior = vec![false; box_width * box_height];
+    let mut stack = Vec::new();
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            let border = x == min_x || x == max_x || y == min_y || y == max_y;
+            if border && labels[x + y * width] != label {
+                exterior[local(x, y)] = true;
+                stack.push((x, y));
+            }
+        }
+    }
+    while let Some((x, y)) = stack.pop() {
+        // Background is traced with the connectivity complementary to the particle's.
+        for (nx, ny) in neighbours(
+            x - min_x,
+            y - min_y,
+            box_width,
+            box_height,
+            !eight_connected,
+        ) {
+            let (nx, ny) = (nx + min_x, ny + min_y);
+            if !exterior[local(nx, ny)] && labels[nx + ny * width] != label {
+                exterior[local(nx, ny)] = true;
+                stack.push((nx, ny));
+            }
+        }
+    }
+    let mut holes = Vec::new();
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            if !exterior[local(x, y)] && labels[x + y * width] != label {
+                holes.push(x + y * width);
+            }
+        }
+    }
+    holes
+}
+
+/// ImageJ's traced perimeter: edge lengths with each corner pair cut diagonally.
+fn traced_perimeter(polygon: &[(i64, i64)], pixel_width: f64, pixel_height: f64) -> f64 {
+    let count = polygon.len();
+    if count == 0 {
+        return 0.0;
+    }
+    let mut sum_dx = 0_i64;
+    let mut sum_dy = 0_i64;
+    let mut corners = 0_usize;
+    let mut corner = false;
+    let (mut dx1, mut dy1) = (
+        polygon[0].0 - polygon[count - 1].0,
+        polygon[0].1 - polygon[count - 1].1,
+    );
+    let mut side1 = dx1.abs() + dy1.abs();
+    for index in 0..count {
+        let next = polygon[(index + 1) % count];
+        let (dx2, dy2) = (next.0 - polygon[index].0, next.1 - polygon[index].1);
+        sum_dx += dx1.abs();
+        sum_dy += dy1.abs();
+        if side1 > 1 || !corner {
+            corner = true;
+            corners += 1;
+        } else {
+            corner = false;
+        }
+        (dx1, dy1) = (dx2, dy2);
+        side1 = dx2.abs() + dy2.abs();
+    }
+    sum_dx as f64 * pixel_width + sum_dy as f64 * pixel_height
+        - corners as f64 * (2.0 - SQRT_2) * (pixel_width + pixel_height) / 2.0
+}
+
+pub(super) fn convex_hull(points: &[(i64, i64)]) -> Vec<(i64, i64)> {
+    let mut points = points.to_vec();
+    points.sort_unstable();
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    let cross = |o: (i64, i64), a: (i64, i64), b: (i64, i64)| {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+    let mut hull: Vec<(i64, i64)> = Vec::with_capacity(points.len() * 2);
+    for pass in 0..2 {
+        let start = hull.len();
+        let ordered: Box<dyn Iterator<Item = &(i64, i64)>> = if pass == 0 {
+            Box::new(points.iter())
+        } else {
+            Box::new(points.iter().rev())
+        };
+        for point in ordered {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], *point) <= 0
+            {
+                hull.pop();
+            }
+            hull.push(*point);
+        }
+        hull.pop();
+    }
+    hull
+}
+
+pub(super) fn polygon_area(points: &[(f64, f64)]) -> f64 {
+    let count = points.len();
+    (0..count)
+        .map(|index| {
+            let (x1, y1) = points[index];
+            let (x2, y2) = points[(index + 1) % count];
+            x1 * y2 - x2 * y1
+        })
+        .sum::<f64>()
+        .abs()
+        / 2.0
+}
+
+/// Maximum caliper diameter with its angle (degrees, counter-clockwise from
+/// the x axis with y pointing up) and the minimum caliper width.
+pub(super) fn feret_diameters(hull: &[(f64, f64)]) -> (f64, f64, f64) {
+    let mut feret = 0.0_f64;
+    let mut feret_angle = 0.0_f64;
+    for (index, a) in hull.iter().enumerate() {
+        for b in &hull[index + 1..] {
+            let distance = (b.0 - a.0).hypot(b.1 - a.1);
+            if distance > feret {
+                feret = distance;
+                let (left, right) = if a.0 <= b.0 { (a, b) } else { (b, a) };
+                feret_angle = (left.1 - right.1).atan2(right.0 - left.0).to_degrees();
+                if feret_angle < 0.0 {
+                    feret_angle += 180.0;
+                }
+            }
+        }
+    }
+    let mut min_feret = if hull.len() < 3 { feret } else { f64::INFINITY };
+    for index in 0..hull.len() {
+        if hull.len() < 3 {
+            break;
+        }
+        let a = hull[index];
+        let b = hull[(index + 1) % hull.len()];
+        let length = (b.0 - a.0).hypot(b.1 - a.1);
+        if length == 0.0 {
+            continue;
+        }
+        let width = hull
+            .iter()
+            .map(|p| ((b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)).abs() / length)
+            .fold(0.0_f64, f64::max);
+        min_feret = min_feret.min(width);
+    }
+    (feret, feret_angle, min_feret)
+}
+
+fn optional_f64(params: &Value, key: &str) -> Result<Option<f64>> {
+    match params.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value
+            .as_f64()
+            .map(Some)
+            .ok_or_else(|| OpsError::InvalidParams(format!("`{key}` must be a number"))),
+    }
+}
+
+fn axis_index(dataset: &DatasetF32, axis: AxisKind) -> Result<usize> {
+    dataset
+        .axis_index(axis)
+        .ok_or_else(|| OpsError::UnsupportedLayout(format!("dataset has no {axis:?} axis")))
+}
+
+fn iterate_indices(shape: &[usize], mut callback: impl FnMut(&[usize])) {
+    if shape.is_empty() {
+        callback(&[]);
+        return;
+    }
+    let mut index = vec![0usize; shape.len()];
+    loop {
+        callback(&index);
+        let mut dim = shape.len();
+        while dim > 0 {
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+            if dim == 0 {
+                return;
+            }
+        }
+    }
+}
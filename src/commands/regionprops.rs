@@ -0,0 +1,459 @@
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+use crate::model::{AxisKind, Dataset, DatasetF32, Metadata};
+use ndarray::IxDyn;
+use serde_json::{Map, Value, json};
+
+use super::labels::{channel_plane, intensity_plane};
+use super::particles::{convex_hull, feret_diameters, polygon_area};
+use super::{
+    INPUT_PARAM_KIND, MeasurementTable, OpInputs, OpOutput, OpSchema, Operation, OpsError,
+    ParamSpec, Result, get_optional_usize,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct MeasurementsRegionPropsOp;
+
+impl Operation for MeasurementsRegionPropsOp {
+    fn name(&self) -> &'static str {
+        "measurements.regionprops"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        let param = |name: &str, description: &str, kind: &str| ParamSpec {
+            name: name.to_string(),
+            description: description.to_string(),
+            required: false,
+            kind: kind.to_string(),
+        };
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Measure shape and intensity properties of every label in each X/Y plane."
+                .to_string(),
+            params: vec![
+                param(
+                    "channel",
+                    "Channel holding the label image (default 0).",
+                    "int",
+                ),
+                param(
+                    "intensity",
+                    "Image whose intensities are measured under each label; takes precedence over `intensity_channel`.",
+                    INPUT_PARAM_KIND,
+                ),
+                param(
+                    "intensity_channel",
+                    "Channel whose intensities are measured under each label; intensity columns are omitted without it.",
+                    "int",
+                ),
+                param(
+                    "connectivity",
+                    "Foreground connectivity for the Euler number: 4 or 8 (default).",
+                    "int",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        let channel = get_optional_usize(params, "channel", 0);
+        let (labels, metadata) = channel_plane(dataset, channel)?;
+        let labels = Dataset::new(labels, metadata.clone())?.to_labels()?.data;
+        let intensities = intensity_plane(dataset, inputs, params, channel)?;
+        let eight_connected = match get_optional_usize(params, "connectivity", 8) {
+            4 => false,
+            8 => true,
+            _ => {
+                return Err(OpsError::InvalidParams(
+                    "`connectivity` must be 4 or 8".to_string(),
+                ));
+            }
+        };
+
+        let x_axis = axis_index(&metadata, AxisKind::X)?;
+        let y_axis = axis_index(&metadata, AxisKind::Y)?;
+        let spacing = |axis: usize| f64::from(metadata.dims[axis].spacing.unwrap_or(1.0));
+        let calibration = Calibration {
+            pixel_width: spacing(x_axis),
+            pixel_height: spacing(y_axis),
+        };
+        let shape = labels.shape().to_vec();
+        let (width, height) = (shape[x_axis], shape[y_axis]);
+        let plane_axes = (0..shape.len())
+            .filter(|axis| *axis != x_axis && *axis != y_axis)
+            .collect::<Vec<_>>();
+        let plane_shape = plane_axes
+            .iter()
+            .map(|axis| shape[*axis])
+            .collect::<Vec<_>>();
+
+        let mut rows = Vec::new();
+        iterate_indices(&plane_shape, |plane_coord| {
+            let mut coord = vec![0_usize; shape.len()];
+            for (axis, index) in plane_axes.iter().zip(plane_coord) {
+                coord[*axis] = *index;
+            }
+            let mut plane_labels = Vec::with_capacity(width * height);
+            let mut plane_values = intensities
+                .as_ref()
+                .map(|_| Vec::with_capacity(width * height));
+            for y in 0..height {
+                for x in 0..width {
+                    coord[y_axis] = y;
+                    coord[x_axis] = x;
+                    plane_labels.push(labels[IxDyn(&coord)]);
+                    if let (Some(values), Some(intensities)) =
+                        (plane_values.as_mut(), intensities.as_ref())
+                    {
+                        values.push(intensities[IxDyn(&coord)]);
+                    }
+                }
+            }
+
+            let plane = Plane {
+                labels: &plane_labels,
+                width,
+                height,
+            };
+            let mut regions = BTreeMap::<u32, Vec<(usize, usize)>>::new();
+            for (index, label) in plane_labels.iter().enumerate() {
+                if *label > 0 {
+                    regions
+                        .entry(*label)
+                        .or_default()
+                        .push((index % width, index / width));
+                }
+            }
+            for (label, pixels) in regions {
+                let mut row = Map::new();
+                row.insert("label".to_string(), json!(label));
+                for (axis, index) in plane_axes.iter().zip(plane_coord) {
+                    let code = metadata.dims[*axis].axis.code().to_ascii_lowercase();
+                    row.insert(code.to_string(), json!(index));
+                }
+                plane.write_shape(label, &pixels, calibration, eight_connected, &mut row);
+                if let Some(values) = plane_values.as_ref() {
+                    write_intensity(values, width, &pixels, calibration, &mut row);
+                }
+                rows.push(Value::Object(row));
+            }
+        });
+
+        let mut measurements = MeasurementTable::default();
+        measurements
+            .values
+            .insert("count".to_string(), json!(rows.len()));
+        measurements.values.insert("rows".to_string(), json!(rows));
+        if let Some(unit) = common_unit(&metadata, &[x_axis, y_axis]) {
+            measurements
+                .values
+                .insert("area_unit".to_string(), json!(format!("{unit}^2")));
+        }
+        Ok(OpOutput {
+            dataset: dataset.clone(),
+            measurements: Some(measurements),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Calibration {
+    pixel_width: f64,
+    pixel_height: f64,
+}
+
+impl Calibration {
+    /// Calibrated centre of pixel `(x, y)`.
+    fn centre(&self, x: usize, y: usize) -> (f64, f64) {
+        (
+            (x as f64 + 0.5) * self.pixel_width,
+            (y as f64 + 0.5) * self.pixel_height,
+        )
+    }
+}
+
+struct Plane<'a> {
+    labels: &'a [u32],
+    width: usize,
+    height: usize,
+}
+
+impl Plane<'_> {
+    fn is(&self, label: u32, x: isize, y: isize) -> bool {
+        x >= 0
+            && y >= 0
+            && (x as usize) < self.width
+            && (y as usize) < self.height
+            && self.labels[x as usize + y as usize * self.width] == label
+    }
+
+    fn write_shape(
+        &self,
+        label: u32,
+        pixels: &[(usize, usize)],
+        calibration: Calibration,
+        eight_connected: bool,
+        row: &mut Map<String, Value>,
+    ) {
+        let Calibration {
+            pixel_width,
+            pixel_height,
+        } = calibration;
+        let count = pixels.len() as f64;
+        let area = count * pixel_width * pixel_height;
+
+        let mut bbox = [usize::MAX, usize::MAX, 0, 0];
+        let mut centroid = (0.0_f64, 0.0_f64);
+        for (x, y) in pixels {
+            bbox = [
+                bbox[0].min(*x),
+                bbox[1].min(*y),
+                bbox[2].max(*x),
+                bbox[3].max(*y),
+            ];
+            let (cx, cy) = calibration.centre(*x, *y);
+            centroid.0 += cx;
+            centroid.1 += cy;
+        }
+        centroid = (centroid.0 / count, centroid.1 / count);
+
+        // Central second moments with y pointing up; each pixel contributes
+        // its own extent (1/12 of its squared size) as in ImageJ's ellipse fit.
+        let (mut mu20, mut mu02, mut mu11) = (0.0_f64, 0.0_f64, 0.0_f64);
+        for (x, y) in pixels {
+            let (cx, cy) = calibration.centre(*x, *y);
+            let (dx, dy) = (cx - centroid.0, centroid.1 - cy);
+            mu20 += dx * dx;
+            mu02 += dy * dy;
+            mu11 += dx * dy;
+        }
+        mu20 = mu20 / count + pixel_width * pixel_width / 12.0;
+        mu02 = mu02 / count + pixel_height * pixel_height / 12.0;
+        mu11 /= count;
+        let half_trace = (mu20 + mu02) / 2.0;
+        let spread = (((mu20 - mu02) / 2.0).powi(2) + mu11 * mu11).sqrt();
+        let (major_variance, minor_variance) =
+            (half_trace + spread, (half_trace - spread).max(0.0));
+        let mut ellipse_angle = (0.5 * (2.0 * mu11).atan2(mu20 - mu02)).to_degrees();
+        if ellipse_angle < 0.0 {
+            ellipse_angle += 180.0;
+        }
+        let eccentricity = if major_variance > 0.0 {
+            (1.0 - minor_variance / major_variance).max(0.0).sqrt()
+        } else {
+            0.0
+        };
+
+        let corners = pixels
+            .iter()
+            .flat_map(|(x, y)| {
+                let (x, y) = (*x as i64, *y as i64);
+                [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)]
+            })
+            .collect::<Vec<_>>();
+        let hull = convex_hull(&corners)
+            .into_iter()
+            .map(|(x, y)| (x as f64 * pixel_width, y as f64 * pixel_height))
+            .collect::<Vec<_>>();
+        let convex_area = polygon_area(&hull);
+        let (feret, feret_angle, min_feret) = feret_diameters(&hull);
+
+        row.insert("area".to_string(), json!(area));
+        row.insert(
+            "perimeter".to_string(),
+            json!(self.crofton_perimeter(label, pixels, calibration)),
+        );
+        row.insert("centroid_x".to_string(), json!(centroid.0));
+        row.insert("centroid_y".to_string(), json!(centroid.1));
+        row.insert(
+            "bbox".to_string(),
+            json!([
+                bbox[0],
+                bbox[1],
+                bbox[2] - bbox[0] + 1,
+                bbox[3] - bbox[1] + 1
+            ]),
+        );
+        row.insert("major".to_string(), json!(4.0 * major_variance.sqrt()));
+        row.insert("minor".to_string(), json!(4.0 * minor_variance.sqrt()));
+        row.insert("ellipse_angle".to_string(), json!(ellipse_angle));
+        row.insert("eccentricity".to_string(), json!(eccentricity));
+        row.insert("feret".to_string(), json!(feret));
+        row.insert("feret_angle".to_string(), json!(feret_angle));
+        row.insert("min_feret".to_string(), json!(min_feret));
+        row.insert("convex_area".to_string(), json!(convex_area));
+        row.insert(
+            "solidity".to_string(),
+            json!(if convex_area > 0.0 {
+                (area / convex_area).min(1.0)
+            } else {
+                1.0
+            }),
+        );
+        row.insert(
+            "euler_number".to_string(),
+            json!(self.euler_number(label, bbox, eight_connected)),
+        );
+    }
+
+    /// Cauchy–Crofton perimeter from boundary crossings along horizontal,
+    /// vertical and both diagonal line families through pixel centres.
+    fn crofton_perimeter(
+        &self,
+        label: u32,
+        pixels: &[(usize, usize)],
+        calibration: Calibration,
+    ) -> f64 {
+        let Calibration {
+            pixel_width,
+            pixel_height,
+        } = calibration;
+        let mut crossings = [0_usize; 4];
+        for (x, y) in pixels {
+            let (x, y) = (*x as isize, *y as isize);
+            for (family, (dx, dy)) in [(1, 0), (0, 1), (1, 1), (1, -1)].into_iter().enumerate() {
+                crossings[family] += usize::from(!self.is(label, x + dx, y + dy))
+                    + usize::from(!self.is(label, x - dx, y - dy));
+            }
+        }
+        // Perpendicular spacing between neighbouring lines of each family.
+        let diagonal_spacing = pixel_width * pixel_height / pixel_width.hypot(pixel_height);
+        let weighted = crossings[0] as f64 * pixel_height
+            + crossings[1] as f64 * pixel_width
+            + (crossings[2] + crossings[3]) as f64 * diagonal_spacing;
+        PI / 8.0 * weighted
+    }
+
+    /// Euler number (objects minus holes) from 2x2 bit-quad counts.
+    fn euler_number(&self, label: u32, bbox: [usize; 4], eight_connected: bool) -> i64 {
+        let (mut single, mut triple, mut diagonal) = (0_i64, 0_i64, 0_i64);
+        for y in bbox[1] as isize - 1..=bbox[3] as isize {
+            for x in bbox[0] as isize - 1..=bbox[2] as isize {
+                let quad = [
+                    self.is(label, x, y),
+                    self.is(label, x + 1, y),
+                    self.is(label, x, y + 1),
+                    self.is(label, x + 1, y + 1),
+                ];
+                match quad.iter().filter(|set| **set).count() {
+                    1 => single += 1,
+                    3 => triple += 1,
+                    2 if quad[0] == quad[3] => diagonal += 1,
+                    _ => {}
+                }
+            }
+        }
+        if eight_connected {
+            (single - triple - 2 * diagonal) / 4
+        } else {
+            (single - triple + 2 * diagonal) / 4
+        }
+    }
+}
+
+fn write_intensity(
+    values: &[f32],
+    width: usize,
+    pixels: &[(usize, usize)],
+    calibration: Calibration,
+    row: &mut Map<String, Value>,
+) {
+    let count = pixels.len() as f64;
+    let mut sum = 0.0_f64;
+    let mut min = f32::INFINITY;
+    let mut max = f32::NEG_INFINITY;
+    let mut weighted = (0.0_f64, 0.0_f64);
+    for (x, y) in pixels {
+        let value = values[x + y * width];
+        sum += f64::from(value);
+        min = min.min(value);
+        max = max.max(value);
+        let (cx, cy) = calibration.centre(*x, *y);
+        weighted.0 += cx * f64::from(value);
+        weighted.1 += cy * f64::from(value);
+    }
+    let mean = sum / count;
+    let variance = if pixels.len() > 1 {
+        pixels
+            .iter()
+            .map(|(x, y)| (f64::from(values[x + y * width]) - mean).powi(2))
+            .sum::<f64>()
+            / (count - 1.0)
+    } else {
+        0.0
+    };
+    // Falls back to the geometric centroid when the intensities sum to zero.
+    let weighted_centroid = if sum != 0.0 {
+        (weighted.0 / sum, weighted.1 / sum)
+    } else {
+        (
+            row["centroid_x"].as_f64().unwrap_or(0.0),
+            row["centroid_y"].as_f64().unwrap_or(0.0),
+        )
+    };
+    let area = count * calibration.pixel_width * calibration.pixel_height;
+    row.insert(
+        "weighted_centroid_x".to_string(),
+        json!(weighted_centroid.0),
+    );
+    row.insert(
+        "weighted_centroid_y".to_string(),
+        json!(weighted_centroid.1),
+    );
+    row.insert("mean".to_string(), json!(mean));
+    row.insert("std_dev".to_string(), json!(variance.sqrt()));
+    row.insert("min".to_string(), json!(min));
+    row.insert("max".to_string(), json!(max));
+    row.insert("integrated_density".to_string(), json!(mean * area));
+}
+
+fn common_unit(metadata: &Metadata, axes: &[usize]) -> Option<String> {
+    let mut units = axes.iter().map(|axis| metadata.dims[*axis].unit.as_deref());
+    let first = units.next()??;
+    (first != "pixel" && units.all(|unit| unit == Some(first))).then(|| first.to_string())
+}
+
+fn axis_index(metadata: &Metadata, axis: AxisKind) -> Result<usize> {
+    metadata
+        .dims
+        .iter()
+        .position(|dim| dim.axis == axis)
+        .ok_or_else(|| OpsError::UnsupportedLayout(format!("dataset has no {axis:?} axis")))
+}
+
+fn iterate_indices(shape: &[usize], mut callback: impl FnMut(&[usize])) {
+    if shape.is_empty() {
+        callback(&[]);
+        return;
+    }
+    let mut index = vec![0usize; shape.len()];
+    loop {
+        callback(&index);
+        let mut dim = shape.len();
+        while dim > 0 {
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+            if dim == 0 {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,913 @@
+use crate::model::{AxisKind, Dataset, DatasetF32};
+use ndarray::{ArrayD, Axis, Dimension, IxDyn};
+use serde_json::{Map, Value, json};
+
+use super::{
+    MeasurementTable, OpOutput, OpSchema, Operation, OpsError, ParamSpec, Result,
+    fft::{PhaseCorrelation, Translation},
+    get_optional_bool, get_optional_f32, get_optional_usize,
+    transform::{TranslateInterpolation, bilinear_sample_or_fill, sample_or_fill, translate_xy},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageRegisterTranslationOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageRegisterStackOp;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImageRegisterApplyOp;
+
+/// What each slice is aligned to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reference {
+    Slice(usize),
+    Previous,
+    Mean,
+}
+
+fn param(name: &str, description: &str, kind: &str) -> ParamSpec {
+    ParamSpec {
+        name: name.to_string(),
+        description: description.to_string(),
+        required: false,
+        kind: kind.to_string(),
+    }
+}
+
+impl Reference {
+    fn parse(value: &Value) -> Result<Self> {
+        if let Some(index) = value.as_u64() {
+            return Ok(Self::Slice(index as usize));
+        }
+        match value
+            .as_str()
+            .map(|text| text.trim().to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("first") => Ok(Self::Slice(0)),
+            Some("previous") => Ok(Self::Previous),
+            Some("mean") => Ok(Self::Mean),
+            _ => Err(OpsError::InvalidParams(format!(
+                "unsupported `reference` {value}; expected first, previous, mean or a slice index"
+            ))),
+        }
+    }
+}
+
+impl Operation for ImageRegisterTranslationOp {
+    fn name(&self) -> &'static str {
+        "image.register.translation"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Align the X/Y planes of a stack by phase-correlation translation (drift correction)."
+                .to_string(),
+            params: vec![
+                param(
+                    "axis",
+                    "Axis to register along: t or z (default t when it has more than one sample, else z).",
+                    "string",
+                ),
+                param(
+                    "reference",
+                    "first (default), previous (each slice to its neighbour, shifts accumulate), mean, or a slice index.",
+                    "string",
+                ),
+                param(
+                    "channel",
+                    "Channel the shifts are estimated on (default 0); all channels are moved.",
+                    "int",
+                ),
+                param(
+                    "apply",
+                    "Translate the slices (default true); false only reports the shifts.",
+                    "bool",
+                ),
+                param(
+                    "interpolation",
+                    "nearest or bilinear (default bilinear).",
+                    "string",
+                ),
+                param("fill", "Fill value for uncovered regions.", "float"),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let stack = RegistrationStack::read(dataset, params)?;
+        let reference = read_reference(params, stack.slice_count(), true)?;
+        let interpolation = read_interpolation(dataset, params)?;
+        let fill = get_optional_f32(params, "fill", 0.0);
+        let (width, height) = (stack.width, stack.height);
+        let signals = &stack.signals;
+        let (axis, slice_count) = (stack.axis, stack.slice_count());
+
+        let shifts = match reference {
+            Reference::Slice(index) => {
+                let estimator = PhaseCorrelation::new(&signals[index], width, height);
+                signals
+                    .iter()
+                    .map(|signal| estimator.estimate(signal))
+                    .collect::<Vec<_>>()
+            }
+            Reference::Mean => {
+                let mut mean = vec![0.0_f32; width * height];
+                for signal in signals {
+                    for (total, value) in mean.iter_mut().zip(signal) {
+                        *total += value / slice_count as f32;
+                    }
+                }
+                let estimator = PhaseCorrelation::new(&mean, width, height);
+                signals
+                    .iter()
+                    .map(|signal| estimator.estimate(signal))
+                    .collect()
+            }
+            Reference::Previous => {
+                let mut shifts = vec![Translation {
+                    x: 0.0,
+                    y: 0.0,
+                    peak: 1.0,
+                }];
+                for pair in signals.windows(2) {
+                    let previous = shifts[shifts.len() - 1];
+                    let shift = PhaseCorrelation::new(&pair[0], width, height).estimate(&pair[1]);
+                    shifts.push(Translation {
+                        x: previous.x + shift.x,
+                        y: previous.y + shift.y,
+                        peak: shift.peak,
+                    });
+                }
+                shifts
+            }
+        };
+
+        let apply = get_optional_bool(params, "apply", true);
+        let mut output = dataset.clone();
+        let mut slice_metadata = dataset.metadata.clone();
+        slice_metadata.dims.remove(axis);
+        let mut rows = Vec::with_capacity(slice_count);
+        for (index, shift) in shifts.iter().enumerate() {
+            // Undo the measured displacement.
+            let (x, y) = (-shift.x, -shift.y);
+            if apply && (x != 0.0 || y != 0.0) {
+                let slice = Dataset::new(
+                    dataset.data.index_axis(Axis(axis), index).to_owned(),
+                    slice_metadata.clone(),
+                )?;
+                let moved = translate_xy(&slice, x, y, fill, interpolation)?;
+                output
+                    .data
+                    .index_axis_mut(Axis(axis), index)
+                    .assign(&moved.data);
+            }
+            let mut row = Map::new();
+            row.insert(stack.code.clone(), json!(index));
+            row.insert("x".to_string(), json!(x));
+            row.insert("y".to_string(), json!(y));
+            row.insert("peak".to_string(), json!(shift.peak));
+            rows.push(Value::Object(row));
+        }
+        Ok(OpOutput {
+            dataset: output,
+            measurements: Some(table(rows)),
+        })
+    }
+}
+
+impl Operation for ImageRegisterStackOp {
+    fn name(&self) -> &'static str {
+        "image.register.stack"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Intensity-based translation, rigid or affine alignment of the X/Y planes of a stack (StackReg-style)."
+                .to_string(),
+            params: vec![
+                param(
+                    "transform",
+                    "translation, rigid (default) or affine.",
+                    "string",
+                ),
+                param(
+                    "axis",
+                    "Axis to register along: t or z (default t when it has more than one sample, else z).",
+                    "string",
+                ),
+                param(
+                    "reference",
+                    "first (default), previous (each slice to its neighbour, transforms compose) or a slice index.",
+                    "string",
+                ),
+                param(
+                    "channel",
+                    "Channel the transforms are estimated on (default 0); all channels are moved.",
+                    "int",
+                ),
+                param(
+                    "levels",
+                    "Pyramid levels (default: halve until the smaller side is below 64 pixels, at most 4).",
+                    "int",
+                ),
+                param(
+                    "iterations",
+                    "Maximum least-squares iterations per pyramid level (default 50).",
+                    "int",
+                ),
+                param(
+                    "apply",
+                    "Warp the slices (default true); false only reports the transforms.",
+                    "bool",
+                ),
+                param(
+                    "interpolation",
+                    "nearest or bilinear (default bilinear).",
+                    "string",
+                ),
+                param("fill", "Fill value for uncovered regions.", "float"),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let model = params
+            .get("transform")
+            .and_then(Value::as_str)
+            .map(Model::parse)
+            .transpose()?
+            .unwrap_or(Model::Rigid);
+        let stack = RegistrationStack::read(dataset, params)?;
+        let reference = read_reference(params, stack.slice_count(), false)?;
+        let interpolation = read_interpolation(dataset, params)?;
+        let fill = get_optional_f32(params, "fill", 0.0);
+        let iterations = get_optional_usize(params, "iterations", 50);
+        let (width, height) = (stack.width, stack.height);
+        let levels = match params.get("levels") {
+            Some(_) => get_optional_usize(params, "levels", 1).max(1),
+            None => {
+                let mut levels = 1;
+                while levels < 4 && width.min(height) >> levels >= 64 {
+                    levels += 1;
+                }
+                levels
+            }
+        };
+        let aligner = |reference: &[f32], moving: &[f32]| {
+            Aligner::new(model, reference, moving, width, height, levels).align(iterations)
+        };
+
+        let signals = &stack.signals;
+        let matrices = match reference {
+            Reference::Slice(anchor) => signals
+                .iter()
+                .enumerate()
+                .map(|(index, signal)| {
+                    if index == anchor {
+                        Affine::IDENTITY
+                    } else {
+                        aligner(&signals[anchor], signal)
+                    }
+                })
+                .collect::<Vec<_>>(),
+            Reference::Previous => {
+                let mut matrices = vec![Affine::IDENTITY];
+                for pair in signals.windows(2) {
+                    let previous = matrices[matrices.len() - 1];
+                    matrices.push(aligner(&pair[0], &pair[1]).after(&previous));
+                }
+                matrices
+            }
+            Reference::Mean => unreachable!("mean reference is rejected for stack registration"),
+        };
+
+        let output = if get_optional_bool(params, "apply", true) {
+            warp_stack(dataset, stack.axis, &matrices, fill, interpolation)?
+        } else {
+            dataset.clone()
+        };
+        let rows = matrices
+            .iter()
+            .enumerate()
+            .map(|(index, matrix)| {
+                let mut row = Map::new();
+                row.insert(stack.code.clone(), json!(index));
+                row.insert("matrix".to_string(), json!(matrix.rows()));
+                Value::Object(row)
+            })
+            .collect();
+        let mut measurements = table(rows);
+        measurements
+            .values
+            .insert("transform".to_string(), json!(model.name()));
+        Ok(OpOutput {
+            dataset: output,
+            measurements: Some(measurements),
+        })
+    }
+}
+
+impl Operation for ImageRegisterApplyOp {
+    fn name(&self) -> &'static str {
+        "image.register.apply"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Warp the X/Y planes of a stack with transforms exported by image.register.stack."
+                    .to_string(),
+            params: vec![
+                ParamSpec {
+                    required: true,
+                    ..param(
+                        "transforms",
+                        "Rows with a 2x3 `matrix` mapping output to source pixel coordinates, indexed by the axis code (e.g. `t`) or by position.",
+                        "array",
+                    )
+                },
+                param(
+                    "axis",
+                    "Axis the transforms run along: t or z (default t when it has more than one sample, else z).",
+                    "string",
+                ),
+                param(
+                    "interpolation",
+                    "nearest or bilinear (default bilinear).",
+                    "string",
+                ),
+                param("fill", "Fill value for uncovered regions.", "float"),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let axis = registration_axis(dataset, params)?;
+        let code = axis_code(dataset, axis);
+        let rows = params
+            .get("transforms")
+            .and_then(Value::as_array)
+            .ok_or_else(|| OpsError::InvalidParams("`transforms` must be an array".to_string()))?;
+        let slice_count = dataset.shape()[axis];
+        let mut matrices = vec![Affine::IDENTITY; slice_count];
+        for (position, row) in rows.iter().enumerate() {
+            let index = match row.get(&code) {
+                Some(index) => index.as_u64().map(|index| index as usize).ok_or_else(|| {
+                    OpsError::InvalidParams(format!("`{code}` must be a slice index"))
+                })?,
+                None => position,
+            };
+            if index >= slice_count {
+                return Err(OpsError::InvalidParams(format!(
+                    "transform for slice {index} is out of range for {slice_count} slices"
+                )));
+            }
+            matrices[index] = Affine::from_value(row.get("matrix").unwrap_or(row))?;
+        }
+        let interpolation = read_interpolation(dataset, params)?;
+        let fill = get_optional_f32(params, "fill", 0.0);
+        Ok(OpOutput::dataset_only(warp_stack(
+            dataset,
+            axis,
+            &matrices,
+            fill,
+            interpolation,
+        )?))
+    }
+}
+
+/// Registration signal of each slice along the registration axis: the chosen
+/// channel, summed over every other non-X/Y axis.
+struct RegistrationStack {
+    axis: usize,
+    code: String,
+    width: usize,
+    height: usize,
+    signals: Vec<Vec<f32>>,
+}
+
+impl RegistrationStack {
+    fn read(dataset: &DatasetF32, params: &Value) -> Result<Self> {
+        let x_axis = axis_index(dataset, AxisKind::X)?;
+        let y_axis = axis_index(dataset, AxisKind::Y)?;
+        let axis = registration_axis(dataset, params)?;
+        let channel = get_optional_usize(params, "channel", 0);
+        let channel_axis = dataset.axis_index(AxisKind::Channel);
+        if channel_axis.map_or(channel > 0, |axis| channel >= dataset.shape()[axis]) {
+            return Err(OpsError::InvalidParams(format!(
+                "`channel` {channel} is out of range"
+            )));
+        }
+
+        let (width, height) = (dataset.shape()[x_axis], dataset.shape()[y_axis]);
+        let mut signals = vec![vec![0.0_f32; width * height]; dataset.shape()[axis]];
+        for (index, value) in dataset.data.indexed_iter() {
+            if channel_axis.is_some_and(|axis| index[axis] != channel) {
+                continue;
+            }
+            signals[index[axis]][index[y_axis] * width + index[x_axis]] += value;
+        }
+        Ok(Self {
+            axis,
+            code: axis_code(dataset, axis),
+            width,
+            height,
+            signals,
+        })
+    }
+
+    fn slice_count(&self) -> usize {
+        self.signals.len()
+    }
+}
+
+fn registration_axis(dataset: &DatasetF32, params: &Value) -> Result<usize> {
+    match params.get("axis").and_then(Value::as_str) {
+        Some(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "t" | "time" => axis_index(dataset, AxisKind::Time),
+            "z" => axis_index(dataset, AxisKind::Z),
+            other => Err(OpsError::InvalidParams(format!(
+                "unsupported `axis` `{other}`; expected t or z"
+            ))),
+        },
+        None => dataset
+            .axis_index(AxisKind::Time)
+            .filter(|axis| dataset.shape()[*axis] > 1)
+            .or_else(|| dataset.axis_index(AxisKind::Z))
+            .ok_or_else(|| OpsError::UnsupportedLayout("dataset has no T or Z axis".to_string())),
+    }
+}
+
+fn axis_code(dataset: &DatasetF32, axis: usize) -> String {
+    dataset.metadata.dims[axis]
+        .axis
+        .code()
+        .to_ascii_lowercase()
+        .to_string()
+}
+
+fn read_reference(params: &Value, slice_count: usize, allow_mean: bool) -> Result<Reference> {
+    let reference = params
+        .get("reference")
+        .map(Reference::parse)
+        .transpose()?
+        .unwrap_or(Reference::Slice(0));
+    match reference {
+        Reference::Slice(index) if index >= slice_count => Err(OpsError::InvalidParams(format!(
+            "`reference` slice {index} is out of range for {slice_count} slices"
+        ))),
+        Reference::Mean if !allow_mean => Err(OpsError::InvalidParams(
+            "`reference` mean is only supported for translation registration".to_string(),
+        )),
+        reference => Ok(reference),
+    }
+}
+
+fn read_interpolation(dataset: &DatasetF32, params: &Value) -> Result<TranslateInterpolation> {
+    Ok(params
+        .get("interpolation")
+        .and_then(Value::as_str)
+        .map(TranslateInterpolation::parse)
+        .transpose()?
+        .unwrap_or(TranslateInterpolation::Bilinear)
+        .for_dataset(dataset))
+}
+
+fn table(rows: Vec<Value>) -> MeasurementTable {
+    let mut measurements = MeasurementTable::default();
+    measurements
+        .values
+        .insert("count".to_string(), json!(rows.len()));
+    measurements.values.insert("rows".to_string(), json!(rows));
+    measurements
+}
+
+/// Resamples every slice along `axis` so output pixel `(x, y)` reads source
+/// pixel `matrices[slice].apply(x, y)`.
+fn warp_stack(
+    dataset: &DatasetF32,
+    axis: usize,
+    matrices: &[Affine],
+    fill: f32,
+    interpolation: TranslateInterpolation,
+) -> Result<DatasetF32> {
+    let x_axis = axis_index(dataset, AxisKind::X)?;
+    let y_axis = axis_index(dataset, AxisKind::Y)?;
+    let values = dataset
+        .data
+        .indexed_iter()
+        .map(|(index, value)| {
+            let coord = index.slice();
+            let matrix = matrices[coord[axis]];
+            if matrix == Affine::IDENTITY {
+                return *value;
+            }
+            let (x, y) = matrix.apply(coord[x_axis] as f64, coord[y_axis] as f64);
+            let (x, y) = (x as f32, y as f32);
+            match interpolation {
+                TranslateInterpolation::Nearest => {
+                    sample_or_fill(dataset, coord, x_axis, y_axis, x.round(), y.round(), fill)
+                }
+                TranslateInterpolation::Bilinear => {
+                    bilinear_sample_or_fill(dataset, coord, x_axis, y_axis, x, y, fill)
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    let data = ArrayD::from_shape_vec(IxDyn(dataset.shape()), values).map_err(|_| {
+        OpsError::UnsupportedLayout("failed to build registered dataset".to_string())
+    })?;
+    Ok(Dataset::new(data, dataset.metadata.clone())?)
+}
+
+/// 2x3 affine map from output to source pixel coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Affine([[f64; 3]; 2]);
+
+impl Affine {
+    const IDENTITY: Self = Self([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+
+    fn from_value(value: &Value) -> Result<Self> {
+        let invalid = || {
+            OpsError::InvalidParams("a transform `matrix` must be 2 rows of 3 numbers".to_string())
+        };
+        let rows = value
+            .as_array()
+            .filter(|rows| rows.len() == 2)
+            .ok_or_else(invalid)?;
+        let mut matrix = [[0.0; 3]; 2];
+        for (target, row) in matrix.iter_mut().zip(rows) {
+            let row = row
+                .as_array()
+                .filter(|row| row.len() == 3)
+                .ok_or_else(invalid)?;
+            for (entry, value) in target.iter_mut().zip(row) {
+                *entry = value
+                    .as_f64()
+                    .filter(|value| value.is_finite())
+                    .ok_or_else(invalid)?;
+            }
+        }
+        Ok(Self(matrix))
+    }
+
+    fn rows(&self) -> [[f64; 3]; 2] {
+        self.0
+    }
+
+    fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let [a, b] = self.0;
+        (a[0] * x + a[1] * y + a[2], b[0] * x + b[1] * y + b[2])
+    }
+
+    /// The map `x -> self(first(x))`.
+    fn after(&self, first: &Self) -> Self {
+        let [a, b] = self.0;
+        let [p, q] = first.0;
+        let compose = |row: [f64; 3]| {
+            [
+                row[0] * p[0] + row[1] * q[0],
+                row[0] * p[1] + row[1] * q[1],
+                row[0] * p[2] + row[1] * q[2] + row[2],
+            ]
+        };
+        Self([compose(a), compose(b)])
+    }
+
+    /// Converts a map between coordinates centred on `centre` to pixel coordinates.
+    fn uncentred(&self, centre: (f64, f64)) -> Self {
+        let [mut a, mut b] = self.0;
+        let (x, y) = self.apply(-centre.0, -centre.1);
+        a[2] = x + centre.0;
+        b[2] = y + centre.1;
+        Self([a, b])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Model {
+    Translation,
+    Rigid,
+    Affine,
+}
+
+impl Model {
+    fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "translation" => Ok(Self::Translation),
+            "rigid" | "rigid_body" => Ok(Self::Rigid),
+            "affine" => Ok(Self::Affine),
+            other => Err(OpsError::InvalidParams(format!(
+                "unsupported `transform` `{other}`; expected translation, rigid or affine"
+            ))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Translation => "translation",
+            Self::Rigid => "rigid",
+            Self::Affine => "affine",
+        }
+    }
+
+    /// Identity parameters: `[tx, ty]`, `[angle, tx, ty]` or the affine matrix
+    /// entries `[a, b, c, d, tx, ty]`.
+    fn identity(self) -> Vec<f64> {
+        match self {
+            Self::Translation => vec![0.0, 0.0],
+            Self::Rigid => vec![0.0, 0.0, 0.0],
+            Self::Affine => vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+        }
+    }
+
+    /// Index of `tx` in the parameter vector; `ty` follows it.
+    fn translation_index(self) -> usize {
+        match self {
+            Self::Translation => 0,
+            Self::Rigid => 1,
+            Self::Affine => 4,
+        }
+    }
+
+    fn matrix(self, params: &[f64]) -> Affine {
+        match self {
+            Self::Translation => Affine([[1.0, 0.0, params[0]], [0.0, 1.0, params[1]]]),
+            Self::Rigid => {
+                let (sin, cos) = params[0].sin_cos();
+                Affine([[cos, -sin, params[1]], [sin, cos, params[2]]])
+            }
+            Self::Affine => Affine([
+                [params[0], params[1], params[4]],
+                [params[2], params[3], params[5]],
+            ]),
+        }
+    }
+
+    /// Derivatives of the mapped `x` and `y` with respect to each parameter at `(x, y)`.
+    fn jacobian(self, params: &[f64], x: f64, y: f64) -> ([f64; 6], [f64; 6]) {
+        match self {
+            Self::Translation => (
+                [1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0, 0.0, 0.0],
+            ),
+            Self::Rigid => {
+                let (sin, cos) = params[0].sin_cos();
+                (
+                    [-sin * x - cos * y, 1.0, 0.0, 0.0, 0.0, 0.0],
+                    [cos * x - sin * y, 0.0, 1.0, 0.0, 0.0, 0.0],
+                )
+            }
+            Self::Affine => ([x, y, 0.0, 0.0, 1.0, 0.0], [0.0, 0.0, x, y, 0.0, 1.0]),
+        }
+    }
+}
+
+/// One resolution of the registration pyramid.
+struct Level {
+    width: usize,
+    height: usize,
+    reference: Vec<f32>,
+    moving: Vec<f32>,
+    gradient_x: Vec<f32>,
+    gradient_y: Vec<f32>,
+}
+
+impl Level {
+    fn new(reference: Vec<f32>, moving: Vec<f32>, width: usize, height: usize) -> Self {
+        let at = |x: usize, y: usize| moving[y * width + x];
+        let mut gradient_x = vec![0.0; width * height];
+        let mut gradient_y = vec![0.0; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                let (left, right) = (x.saturating_sub(1), (x + 1).min(width - 1));
+                let (up, down) = (y.saturating_sub(1), (y + 1).min(height - 1));
+                if right > left {
+                    gradient_x[y * width + x] =
+                        (at(right, y) - at(left, y)) / (right - left) as f32;
+                }
+                if down > up {
+                    gradient_y[y * width + x] = (at(x, down) - at(x, up)) / (down - up) as f32;
+                }
+            }
+        }
+        Self {
+            width,
+            height,
+            reference,
+            moving,
+            gradient_x,
+            gradient_y,
+        }
+    }
+
+    /// Half-resolution level from 2x2 block means.
+    fn downsample(&self) -> Self {
+        let (width, height) = (self.width / 2, self.height / 2);
+        let reduce = |values: &[f32]| {
+            let mut reduced = Vec::with_capacity(width * height);
+            for y in 0..height {
+                for x in 0..width {
+                    let at = |dx: usize, dy: usize| values[(2 * y + dy) * self.width + 2 * x + dx];
+                    reduced.push((at(0, 0) + at(1, 0) + at(0, 1) + at(1, 1)) / 4.0);
+                }
+            }
+            reduced
+        };
+        Self::new(reduce(&self.reference), reduce(&self.moving), width, height)
+    }
+
+    fn centre(&self) -> (f64, f64) {
+        (
+            (self.width as f64 - 1.0) / 2.0,
+            (self.height as f64 - 1.0) / 2.0,
+        )
+    }
+
+    /// Bilinear sample at a position inside the plane.
+    fn sample(&self, values: &[f32], x: f64, y: f64) -> f64 {
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+        let at = |x: usize, y: usize| f64::from(values[y * self.width + x]);
+        let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+        let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    /// Gauss-Newton minimization of the squared intensity difference between
+    /// the reference and the warped moving plane, in centred coordinates.
+    fn refine(&self, model: Model, params: &mut [f64], iterations: usize) {
+        let count = params.len();
+        let (cx, cy) = self.centre();
+        let corners = [(-cx, -cy), (cx, -cy), (-cx, cy), (cx, cy)];
+        for _ in 0..iterations {
+            let matrix = model.matrix(params);
+            let mut hessian = vec![vec![0.0_f64; count]; count];
+            let mut gradient = vec![0.0_f64; count];
+            let mut samples = 0;
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    let (xc, yc) = (x as f64 - cx, y as f64 - cy);
+                    let (sx, sy) = matrix.apply(xc, yc);
+                    let (px, py) = (sx + cx, sy + cy);
+                    if px < 0.0
+                        || py < 0.0
+                        || px > (self.width - 1) as f64
+                        || py > (self.height - 1) as f64
+                    {
+                        continue;
+                    }
+                    let residual = self.sample(&self.moving, px, py)
+                        - f64::from(self.reference[y * self.width + x]);
+                    let gx = self.sample(&self.gradient_x, px, py);
+                    let gy = self.sample(&self.gradient_y, px, py);
+                    let (jx, jy) = model.jacobian(params, xc, yc);
+                    let steepest = (0..count)
+                        .map(|index| gx * jx[index] + gy * jy[index])
+                        .collect::<Vec<_>>();
+                    for row in 0..count {
+                        gradient[row] += steepest[row] * residual;
+                        for column in 0..count {
+                            hessian[row][column] += steepest[row] * steepest[column];
+                        }
+                    }
+                    samples += 1;
+                }
+            }
+            if samples < count * 4 {
+                return;
+            }
+            let Some(step) = solve(hessian, gradient.iter().map(|value| -value).collect()) else {
+                return;
+            };
+            for (param, delta) in params.iter_mut().zip(&step) {
+                *param += delta;
+            }
+            let updated = model.matrix(params);
+            let moved = corners
+                .iter()
+                .map(|(x, y)| {
+                    let (before, after) = (matrix.apply(*x, *y), updated.apply(*x, *y));
+                    (after.0 - before.0).hypot(after.1 - before.1)
+                })
+                .fold(0.0_f64, f64::max);
+            if moved < 1e-3 {
+                return;
+            }
+        }
+    }
+}
+
+/// Pyramid registration of a moving plane onto a reference plane.
+struct Aligner {
+    model: Model,
+    /// Finest level first.
+    levels: Vec<Level>,
+}
+
+impl Aligner {
+    fn new(
+        model: Model,
+        reference: &[f32],
+        moving: &[f32],
+        width: usize,
+        height: usize,
+        levels: usize,
+    ) -> Self {
+        let mut pyramid = vec![Level::new(
+            reference.to_vec(),
+            moving.to_vec(),
+            width,
+            height,
+        )];
+        while pyramid.len() < levels {
+            let coarser = pyramid[pyramid.len() - 1].downsample();
+            if coarser.width < 4 || coarser.height < 4 {
+                break;
+            }
+            pyramid.push(coarser);
+        }
+        Self {
+            model,
+            levels: pyramid,
+        }
+    }
+
+    /// The map from reference to moving pixel coordinates, starting from the
+    /// phase-correlation translation.
+    fn align(&self, iterations: usize) -> Affine {
+        let finest = &self.levels[0];
+        let shift = PhaseCorrelation::new(&finest.reference, finest.width, finest.height)
+            .estimate(&finest.moving);
+        let mut params = self.model.identity();
+        let translation = self.model.translation_index();
+        let scale = f64::from(1_u32 << (self.levels.len() - 1));
+        params[translation] = f64::from(shift.x) / scale;
+        params[translation + 1] = f64::from(shift.y) / scale;
+        for (index, level) in self.levels.iter().enumerate().rev() {
+            level.refine(self.model, &mut params, iterations);
+            if index > 0 {
+                params[translation] *= 2.0;
+                params[translation + 1] *= 2.0;
+            }
+        }
+        self.model.matrix(&params).uncentred(finest.centre())
+    }
+}
+
+/// Solves `matrix * x = rhs` by Gaussian elimination with partial pivoting.
+fn solve(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Option<Vec<f64>> {
+    let count = rhs.len();
+    for column in 0..count {
+        let pivot = (column..count).max_by(|a, b| {
+            matrix[*a][column]
+                .abs()
+                .total_cmp(&matrix[*b][column].abs())
+        })?;
+        if matrix[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        rhs.swap(column, pivot);
+        for row in column + 1..count {
+            let factor = matrix[row][column] / matrix[column][column];
+            let pivot_row = matrix[column].clone();
+            for (entry, pivot) in matrix[row].iter_mut().zip(&pivot_row).skip(column) {
+                *entry -= factor * pivot;
+            }
+            rhs[row] -= factor * rhs[column];
+        }
+    }
+    let mut solution = vec![0.0; count];
+    for row in (0..count).rev() {
+        let sum = (row + 1..count)
+            .map(|k| matrix[row][k] * solution[k])
+            .sum::<f64>();
+        solution[row] = (rhs[row] - sum) / matrix[row][row];
+    }
+    Some(solution)
+}
+
+fn axis_index(dataset: &DatasetF32, axis: AxisKind) -> Result<usize> {
+    dataset
+        .axis_index(axis)
+        .ok_or_else(|| OpsError::UnsupportedLayout(format!("dataset has no {axis:?} axis")))
+}
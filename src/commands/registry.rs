@@ -1,29 +1,46 @@
 use std::collections::HashMap;
 use std::sync::{Arc, OnceLock};
 
-use crate::model::DatasetF32;
+use crate::model::{DatasetF32, DatasetKind};
 use serde_json::Value;
 
 use super::{
-    ComponentsLabelOp, GaussianBlurOp, ImageBinOp, ImageCalibrateOp, ImageCanvasResizeOp,
-    ImageColorThresholdOp, ImageConvertOp, ImageConvolveOp, ImageCoordinatesOp, ImageCropOp,
-    ImageFftBandpassOp, ImageFftPowerSpectrumOp, ImageFindEdgesOp, ImageFindMaximaOp, ImageFlipOp,
-    ImageHyperstackReduceDimensionalityOp, ImageHyperstackSubsetOp, ImageHyperstackToStackOp,
-    ImageMedianFilterOp, ImageRankFilter3dOp, ImageRankFilterOp, ImageRemoveNaNsOp,
+    AnalyzeObjects3dOp, AnalyzeParticlesOp, ComponentsLabelOp, FeaturesDifferenceOfGaussiansOp,
+    FeaturesFrangiOp, FeaturesGradientMagnitudeOp, FeaturesHessianOp,
+    FeaturesLaplacianOfGaussianOp, FeaturesMeijeringOp, FeaturesSatoOp, FeaturesStructureTensorOp,
+    GaussianBlurOp, ImageAnisotropicDiffusionOp, ImageApplyMaskOp, ImageAxesAssignOp,
+    ImageAxesPermuteOp, ImageBilateralFilterOp, ImageBinOp, ImageCalculatorOp, ImageCalibrateOp,
+    ImageCanvasResizeOp, ImageColorThresholdOp, ImageConvertOp, ImageConvolveOp,
+    ImageCoordinatesOp, ImageCropOp, ImageDeconvolveRichardsonLucyOp, ImageDisplaySetChannelOp,
+    ImageDisplaySetModeOp, ImageEdgesCannyOp, ImageFftBandpassOp, ImageFftConvolveOp,
+    ImageFftCorrelateOp, ImageFftCustomFilterOp, ImageFftDeconvolveOp, ImageFftForwardOp,
+    ImageFftInverseOp, ImageFftPowerSpectrumOp, ImageFindEdgesOp, ImageFindMaximaOp, ImageFlipOp,
+    ImageHoughCirclesOp, ImageHoughLinesOp, ImageHyperstackReduceDimensionalityOp,
+    ImageHyperstackSubsetOp, ImageHyperstackToStackOp, ImageMedianFilterOp, ImageNonLocalMeansOp,
+    ImagePhaseCorrelationOp, ImagePsfGenerateOp, ImageRankFilter3dOp, ImageRankFilterOp,
+    ImageRegisterApplyOp, ImageRegisterStackOp, ImageRegisterTranslationOp, ImageRemoveNaNsOp,
     ImageRemoveOutliersOp, ImageResizeOp, ImageRotate90Op, ImageRotateOp, ImageScaleOp,
     ImageSetScaleOp, ImageShadowDemoOp, ImageShadowOp, ImageSharpenOp, ImageStackAddSliceOp,
     ImageStackDeleteSliceOp, ImageStackGroupedZProjectOp, ImageStackMontageOp,
     ImageStackMontageToStackOp, ImageStackReduceOp, ImageStackResliceOp, ImageStackStatisticsOp,
     ImageStackSubstackOp, ImageStackToHyperstackOp, ImageStackZProfileOp, ImageStackZProjectOp,
     ImageSubtractBackgroundOp, ImageSurfacePlotOp, ImageSwapQuadrantsOp, ImageTranslateOp,
-    ImageUnsharpMaskOp, IntensityEnhanceContrastOp, IntensityInvertOp, IntensityMathOp,
-    IntensityNaNBackgroundOp, IntensityNormalizeOp, IntensityWindowOp, MeasurementsHistogramOp,
-    MeasurementsProfileOp, MeasurementsSummaryOp, MorphologyBinaryMedianOp, MorphologyCloseOp,
-    MorphologyDilateOp, MorphologyDistanceMapOp, MorphologyErodeOp, MorphologyFillHolesOp,
-    MorphologyOpenOp, MorphologyOutlineOp, MorphologySkeletonizeOp, MorphologyUltimatePointsOp,
-    MorphologyVoronoiOp, MorphologyWatershedOp, NoiseGaussianOp, NoiseSaltAndPepperOp, OpOutput,
-    OpSchema, Operation, OpsError, Result, ThresholdFixedOp, ThresholdMakeBinaryOp,
-    ThresholdOtsuOp,
+    ImageUnsharpMaskOp, IntensityBleachCorrectionOp, IntensityClaheOp, IntensityEnhanceContrastOp,
+    IntensityHistogramMatchOp, IntensityInvertOp, IntensityMathOp, IntensityNaNBackgroundOp,
+    IntensityNormalizeOp, IntensityWindowOp, LabelsBoundariesOp, LabelsCropOp, LabelsDilateOp,
+    LabelsFillHolesOp, LabelsKeepLargestOp, LabelsMergeOp, LabelsRelabelOp, LabelsRemoveOp,
+    MeasurementsHistogramOp, MeasurementsProfileOp, MeasurementsRegionPropsOp,
+    MeasurementsSummaryOp, MorphologyBinaryMedianOp, MorphologyCloseOp, MorphologyDilateOp,
+    MorphologyDistanceMapOp, MorphologyDistanceTransformOp, MorphologyErodeOp,
+    MorphologyExtendedMaximaOp, MorphologyExtendedMinimaOp, MorphologyFillHolesOp,
+    MorphologyGrayBlackTopHatOp, MorphologyGrayCloseOp, MorphologyGrayDilateOp,
+    MorphologyGrayErodeOp, MorphologyGrayGradientOp, MorphologyGrayOpenOp,
+    MorphologyGrayWhiteTopHatOp, MorphologyHMaximaOp, MorphologyHMinimaOp,
+    MorphologyImposeMinimaOp, MorphologyMarkerWatershedOp, MorphologyOpenOp, MorphologyOutlineOp,
+    MorphologyRegionalMaximaOp, MorphologyRegionalMinimaOp, MorphologySkeletonizeOp,
+    MorphologyUltimatePointsOp, MorphologyVoronoiOp, MorphologyWatershedOp, NoiseGaussianOp,
+    NoiseSaltAndPepperOp, OpInputs, OpOutput, OpSchema, Operation, OpsError, Result,
+    ThresholdFixedOp, ThresholdLocalOp, ThresholdMakeBinaryOp, ThresholdOtsuOp,
 };
 #[cfg(feature = "morpholib")]
 use super::{
@@ -59,9 +76,44 @@ fn registry() -> &'static Registry {
         register(&mut map, ImageScaleOp);
         register(&mut map, ImageCanvasResizeOp);
         register(&mut map, ImageCropOp);
+        register(&mut map, ImageCalculatorOp);
+        register(&mut map, ImageApplyMaskOp);
+        register(&mut map, ImageFftForwardOp);
+        register(&mut map, ImageFftInverseOp);
+        register(&mut map, ImageFftCustomFilterOp);
+        register(&mut map, ImageFftConvolveOp);
+        register(&mut map, ImageFftDeconvolveOp);
+        register(&mut map, ImageFftCorrelateOp);
+        register(&mut map, ImagePhaseCorrelationOp);
+        register(&mut map, ImageRegisterTranslationOp);
+        register(&mut map, ImageRegisterStackOp);
+        register(&mut map, ImageRegisterApplyOp);
+        register(&mut map, ImageDeconvolveRichardsonLucyOp);
+        register(&mut map, ImagePsfGenerateOp);
+        register(&mut map, FeaturesLaplacianOfGaussianOp);
+        register(&mut map, FeaturesDifferenceOfGaussiansOp);
+        register(&mut map, FeaturesGradientMagnitudeOp);
+        register(&mut map, FeaturesHessianOp);
+        register(&mut map, FeaturesStructureTensorOp);
+        register(&mut map, ImageBilateralFilterOp);
+        register(&mut map, ImageAnisotropicDiffusionOp);
+        register(&mut map, ImageNonLocalMeansOp);
+        register(&mut map, IntensityClaheOp);
+        register(&mut map, IntensityHistogramMatchOp);
+        register(&mut map, IntensityBleachCorrectionOp);
+        register(&mut map, FeaturesFrangiOp);
+        register(&mut map, FeaturesSatoOp);
+        register(&mut map, FeaturesMeijeringOp);
+        register(&mut map, ImageEdgesCannyOp);
+        register(&mut map, ImageHoughLinesOp);
+        register(&mut map, ImageHoughCirclesOp);
         register(&mut map, ImageCoordinatesOp);
         register(&mut map, ImageSetScaleOp);
         register(&mut map, ImageCalibrateOp);
+        register(&mut map, ImageDisplaySetChannelOp);
+        register(&mut map, ImageDisplaySetModeOp);
+        register(&mut map, ImageAxesPermuteOp);
+        register(&mut map, ImageAxesAssignOp);
         register(&mut map, ImageStackAddSliceOp);
         register(&mut map, ImageStackDeleteSliceOp);
         register(&mut map, ImageStackZProjectOp);
@@ -100,25 +152,53 @@ fn registry() -> &'static Registry {
         register(&mut map, ImageFftBandpassOp);
         register(&mut map, ImageSurfacePlotOp);
         register(&mut map, ThresholdFixedOp);
+        register(&mut map, ThresholdLocalOp);
         register(&mut map, ThresholdMakeBinaryOp);
         register(&mut map, ThresholdOtsuOp);
         register(&mut map, MeasurementsHistogramOp);
         register(&mut map, MeasurementsProfileOp);
+        register(&mut map, MeasurementsRegionPropsOp);
         register(&mut map, MorphologyErodeOp);
         register(&mut map, MorphologyDilateOp);
         register(&mut map, MorphologyOpenOp);
         register(&mut map, MorphologyCloseOp);
         register(&mut map, MorphologyBinaryMedianOp);
         register(&mut map, MorphologyDistanceMapOp);
+        register(&mut map, MorphologyDistanceTransformOp);
         register(&mut map, MorphologyUltimatePointsOp);
         register(&mut map, MorphologyWatershedOp);
+        register(&mut map, MorphologyMarkerWatershedOp);
+        register(&mut map, MorphologyRegionalMaximaOp);
+        register(&mut map, MorphologyRegionalMinimaOp);
+        register(&mut map, MorphologyHMaximaOp);
+        register(&mut map, MorphologyHMinimaOp);
+        register(&mut map, MorphologyExtendedMaximaOp);
+        register(&mut map, MorphologyExtendedMinimaOp);
+        register(&mut map, MorphologyImposeMinimaOp);
         register(&mut map, MorphologyVoronoiOp);
         register(&mut map, MorphologyFillHolesOp);
         register(&mut map, MorphologyOutlineOp);
         register(&mut map, MorphologySkeletonizeOp);
+        register(&mut map, MorphologyGrayErodeOp);
+        register(&mut map, MorphologyGrayDilateOp);
+        register(&mut map, MorphologyGrayOpenOp);
+        register(&mut map, MorphologyGrayCloseOp);
+        register(&mut map, MorphologyGrayWhiteTopHatOp);
+        register(&mut map, MorphologyGrayBlackTopHatOp);
+        register(&mut map, MorphologyGrayGradientOp);
         register(&mut map, NoiseGaussianOp);
         register(&mut map, NoiseSaltAndPepperOp);
         register(&mut map, ComponentsLabelOp);
+        register(&mut map, LabelsRemoveOp);
+        register(&mut map, LabelsRelabelOp);
+        register(&mut map, LabelsDilateOp);
+        register(&mut map, LabelsBoundariesOp);
+        register(&mut map, LabelsMergeOp);
+        register(&mut map, LabelsFillHolesOp);
+        register(&mut map, LabelsKeepLargestOp);
+        register(&mut map, LabelsCropOp);
+        register(&mut map, AnalyzeParticlesOp);
+        register(&mut map, AnalyzeObjects3dOp);
         #[cfg(feature = "morpholib")]
         register(&mut map, MorpholibjChamferDistanceOp);
         #[cfg(feature = "morpholib")]
@@ -155,20 +235,36 @@ pub fn list_operations() -> Vec<OpSchema> {
 }
 
 pub fn execute_operation(name: &str, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
-    let op = registry()
-        .get(name)
-        .ok_or_else(|| OpsError::UnknownOperation(name.to_string()))?;
-    op.execute(dataset, params)
+    execute_operation_with_inputs(name, dataset, &OpInputs::new(), params)
+}
+
+pub fn execute_operation_with_inputs(
+    name: &str,
+    dataset: &DatasetF32,
+    inputs: &OpInputs<'_>,
+    params: &Value,
+) -> Result<OpOutput> {
+    execute_operation_with_registry(registry(), name, dataset, inputs, params)
 }
 
 pub fn execute_operation_with_registry(
     registry: &HashMap<&'static str, Arc<dyn Operation>>,
     name: &str,
     dataset: &DatasetF32,
+    inputs: &OpInputs<'_>,
     params: &Value,
 ) -> Result<OpOutput> {
     let op = registry
         .get(name)
         .ok_or_else(|| OpsError::UnknownOperation(name.to_string()))?;
-    op.execute(dataset, params)
+    let mut output = op.execute_with_inputs(dataset, inputs, params)?;
+    if !op.keeps_label_kind() {
+        output.dataset.metadata.kind = DatasetKind::Intensity;
+    }
+    // Per-channel display settings are indexed by channel, so they only carry
+    // over while the channel axis keeps its length.
+    if output.dataset.metadata.channel_count() != dataset.metadata.channel_count() {
+        output.dataset.metadata.display = None;
+    }
+    Ok(output)
 }
@@ -1,8 +1,14 @@
+use std::collections::BTreeMap;
+
 use crate::model::DatasetF32;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use super::Result;
+use super::{OpsError, Result};
+
+/// `ParamSpec::kind` of a named secondary input; the dataset is passed through
+/// [`OpInputs`] under the param's name rather than in the params object.
+pub const INPUT_PARAM_KIND: &str = "image";
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ParamSpec {
@@ -39,8 +45,71 @@ impl OpOutput {
     }
 }
 
+/// Secondary datasets of a multi-input operation, keyed by input name.
+#[derive(Debug, Clone, Default)]
+pub struct OpInputs<'a> {
+    datasets: BTreeMap<String, &'a DatasetF32>,
+}
+
+impl<'a> OpInputs<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, name: impl Into<String>, dataset: &'a DatasetF32) -> Self {
+        self.insert(name, dataset);
+        self
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, dataset: &'a DatasetF32) {
+        self.datasets.insert(name.into(), dataset);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&'a DatasetF32> {
+        self.datasets.get(name).copied()
+    }
+
+    pub fn require(&self, name: &str) -> Result<&'a DatasetF32> {
+        self.get(name)
+            .ok_or_else(|| OpsError::InvalidParams(format!("missing input image `{name}`")))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.datasets.keys().map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.datasets.is_empty()
+    }
+}
+
 pub trait Operation: Send + Sync {
     fn name(&self) -> &'static str;
     fn schema(&self) -> OpSchema;
     fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput>;
+
+    /// Runs with named secondary inputs, declared in the schema as params of
+    /// kind [`INPUT_PARAM_KIND`]. Single-input operations reject any input.
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        if let Some(name) = inputs.names().next() {
+            return Err(OpsError::InvalidParams(format!(
+                "`{}` takes no input image `{name}`",
+                self.name()
+            )));
+        }
+        self.execute(dataset, params)
+    }
+
+    /// Whether the output keeps `Metadata::kind`. Only operations that carry
+    /// label ids through unchanged (axes, crops, nearest-neighbour resampling,
+    /// `labels.*`) or that set the kind themselves opt in; every other output
+    /// is reset to an intensity image.
+    fn keeps_label_kind(&self) -> bool {
+        false
+    }
 }
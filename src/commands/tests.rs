@@ -1,9 +1,16 @@
+use std::f64::consts::{PI, SQRT_2};
+
 use ndarray::{Array, IxDyn};
 use serde_json::json;
 
-use crate::model::{AxisKind, Dataset, Dim, Metadata, PixelType};
+use crate::model::{
+    AxisKind, ChannelDisplay, ChannelLut, CompositeMode, Dataset, DatasetKind, Dim, Metadata,
+    PixelType,
+};
 
-use super::{execute_operation, list_operations};
+use super::{
+    OpInputs, OpOutput, execute_operation, execute_operation_with_inputs, list_operations,
+};
 
 fn test_dataset(values: Vec<f32>, shape: (usize, usize)) -> Dataset<f32> {
     let data = Array::from_shape_vec(shape, values)
@@ -39,6 +46,10 @@ fn contains_required_operations() {
     assert!(names.contains(&"image.coordinates".to_string()));
     assert!(names.contains(&"image.set_scale".to_string()));
     assert!(names.contains(&"image.calibrate".to_string()));
+    assert!(names.contains(&"image.display.set_channel".to_string()));
+    assert!(names.contains(&"image.display.set_mode".to_string()));
+    assert!(names.contains(&"image.axes.permute".to_string()));
+    assert!(names.contains(&"image.axes.assign".to_string()));
     assert!(names.contains(&"image.bin".to_string()));
     assert!(names.contains(&"image.flip".to_string()));
     assert!(names.contains(&"image.median_filter".to_string()));
@@ -229,6 +240,8 @@ fn intensity_enhance_contrast_can_record_display_range_without_changing_pixels()
         output.dataset.metadata.extras.get("display_max"),
         Some(&json!(3.0))
     );
+    let display = output.dataset.metadata.display.expect("display settings");
+    assert_eq!(display.channels[0].range(), Some((0.0, 3.0)));
 }
 
 #[test]
@@ -4290,206 +4303,2134 @@ fn connected_components_reports_regions() {
         .and_then(|value| value.as_u64())
         .expect("count");
     assert_eq!(count, 2);
+    assert_eq!(output.dataset.metadata.kind, DatasetKind::Label);
+    assert_eq!(output.dataset.data[IxDyn(&[2, 2])], 2.0);
 }
 
 #[test]
-fn morphology_erode_honors_iterations() {
+fn label_images_resample_with_nearest_neighbour_and_refuse_intensity_ops() {
     let dataset = test_dataset(
         vec![
-            0.0, 0.0, 0.0, 0.0, 0.0, //
-            0.0, 1.0, 1.0, 1.0, 0.0, //
-            0.0, 1.0, 1.0, 1.0, 0.0, //
-            0.0, 1.0, 1.0, 1.0, 0.0, //
-            0.0, 0.0, 0.0, 0.0, 0.0,
+            1.0, 1.0, 5.0, 5.0, //
+            1.0, 1.0, 5.0, 5.0, //
+            9.0, 9.0, 0.0, 0.0, //
+            9.0, 9.0, 0.0, 0.0, //
         ],
-        (5, 5),
-    );
-
-    let once = execute_operation(
-        "morphology.erode",
-        &dataset,
-        &json!({
-            "iterations": 1
-        }),
+        (4, 4),
     )
-    .expect("erode once");
-    assert_eq!(once.dataset.data[IxDyn(&[2, 2])], 1.0);
+    .to_labels()
+    .expect("labels")
+    .to_label_dataset()
+    .expect("label dataset");
 
-    let twice = execute_operation(
-        "morphology.erode",
-        &dataset,
-        &json!({
-            "iterations": 2
-        }),
-    )
-    .expect("erode twice");
-    assert_eq!(twice.dataset.data[IxDyn(&[2, 2])], 0.0);
+    for (op, params) in [
+        ("image.resize", json!({"width": 3, "height": 3})),
+        ("image.scale", json!({"x_scale": 1.5, "y_scale": 1.5})),
+        ("image.rotate", json!({"angle": 30.0})),
+        ("image.translate", json!({"x": 0.5, "y": 0.5})),
+    ] {
+        let output = execute_operation(op, &dataset, &params).expect(op);
+        assert!(output.dataset.is_label(), "{op} dropped the label kind");
+        assert!(
+            output
+                .dataset
+                .data
+                .iter()
+                .all(|value| [0.0, 1.0, 5.0, 9.0].contains(value)),
+            "{op} blended label ids"
+        );
+    }
+
+    for (op, params) in [
+        ("intensity.invert", json!({})),
+        ("intensity.math", json!({"operation": "add", "value": 1.0})),
+        ("intensity.normalize", json!({})),
+    ] {
+        assert!(execute_operation(op, &dataset, &params).is_err(), "{op}");
+    }
 }
 
 #[test]
-fn morphology_erode_rejects_zero_iterations() {
-    let dataset = test_dataset(vec![1.0; 9], (3, 3));
-    let error = execute_operation(
-        "morphology.erode",
-        &dataset,
-        &json!({
-            "iterations": 0
-        }),
+fn filtering_a_label_image_yields_an_intensity_dataset() {
+    let dataset = test_dataset(
+        vec![
+            1.0, 1.0, 5.0, 5.0, //
+            1.0, 1.0, 5.0, 5.0, //
+            9.0, 9.0, 0.0, 0.0, //
+            9.0, 9.0, 0.0, 0.0, //
+        ],
+        (4, 4),
     )
-    .expect_err("zero iterations should fail");
-
-    assert!(error.to_string().contains("iterations"));
+    .to_labels()
+    .expect("labels")
+    .to_label_dataset()
+    .expect("label dataset");
+
+    for (op, params) in [
+        ("gaussian.blur", json!({"sigma": 1.0})),
+        ("features.log", json!({})),
+        ("image.bilateral_filter", json!({})),
+        ("threshold.otsu", json!({})),
+        ("labels.boundaries", json!({"binary": true})),
+    ] {
+        let output = execute_operation(op, &dataset, &params).expect(op);
+        assert_eq!(output.dataset.metadata.kind, DatasetKind::Intensity, "{op}");
+    }
+    for (op, params) in [
+        (
+            "image.crop",
+            json!({"x": 0, "y": 0, "width": 2, "height": 2}),
+        ),
+        ("image.flip", json!({"axis": "x"})),
+        ("labels.boundaries", json!({})),
+    ] {
+        let output = execute_operation(op, &dataset, &params).expect(op);
+        assert!(output.dataset.is_label(), "{op}");
+    }
 }
 
 #[test]
-fn morphology_erode_honors_neighbor_count() {
-    let dataset = test_dataset(
+fn analyze_particles_measures_shapes_and_applies_filters() {
+    let mut dataset = test_dataset(
         vec![
-            0.0, 1.0, 1.0, //
-            1.0, 1.0, 1.0, //
-            1.0, 1.0, 1.0,
+            0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 5.0, //
+            0.0, 10.0, 10.0, 20.0, 0.0, 0.0, 0.0, //
+            0.0, 10.0, 0.0, 10.0, 0.0, 0.0, 0.0, //
+            0.0, 10.0, 10.0, 10.0, 0.0, 0.0, 0.0, //
+            0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, //
         ],
-        (3, 3),
+        (5, 7),
     );
+    let rows = |output: &OpOutput| {
+        output
+            .measurements
+            .as_ref()
+            .and_then(|table| table.values.get("rows"))
+            .and_then(|rows| rows.as_array())
+            .cloned()
+            .expect("rows")
+    };
 
-    let count_one = execute_operation(
-        "morphology.erode",
+    let output = execute_operation("analyze.particles", &dataset, &json!({"lower": 5.0}))
+        .expect("particles");
+    assert_eq!(output.dataset.data, dataset.data);
+    let measured = rows(&output);
+    assert_eq!(measured.len(), 2);
+    assert_eq!(measured[0]["area"], json!(1.0));
+    assert!((measured[0]["perimeter"].as_f64().expect("perimeter") - 2.0 * SQRT_2).abs() < 1e-9);
+    let ring = &measured[1];
+    assert_eq!(ring["label"], json!(2));
+    assert_eq!(ring["area"], json!(8.0));
+    assert_eq!(ring["bbox"], json!([1, 1, 3, 3]));
+    assert_eq!(ring["centroid_x"], json!(2.5));
+    assert_eq!(ring["mean"], json!(11.25));
+    assert_eq!(ring["max"], json!(20.0));
+    let perimeter = ring["perimeter"].as_f64().expect("perimeter");
+    assert!((perimeter - (12.0 - 4.0 * (2.0 - SQRT_2))).abs() < 1e-9);
+    assert!((ring["solidity"].as_f64().expect("solidity") - 8.0 / 9.0).abs() < 1e-9);
+    assert!((ring["feret"].as_f64().expect("feret") - 18.0_f64.sqrt()).abs() < 1e-9);
+    assert!((ring["min_feret"].as_f64().expect("min feret") - 3.0).abs() < 1e-9);
+
+    let filled = execute_operation(
+        "analyze.particles",
+        &dataset,
+        &json!({"lower": 5.0, "exclude_edges": true, "include_holes": true, "output": "labels"}),
+    )
+    .expect("filled particles");
+    let measured = rows(&filled);
+    assert_eq!(measured.len(), 1);
+    assert_eq!(measured[0]["area"], json!(9.0));
+    assert_eq!(measured[0]["solidity"], json!(1.0));
+    assert!(filled.dataset.is_label());
+    assert_eq!(filled.dataset.data[IxDyn(&[2, 2])], 1.0);
+    assert_eq!(filled.dataset.data[IxDyn(&[0, 6])], 0.0);
+
+    let outlines = execute_operation(
+        "analyze.particles",
+        &dataset,
+        &json!({"lower": 5.0, "min_size": 2.0, "output": "outlines"}),
+    )
+    .expect("outlines");
+    assert_eq!(rows(&outlines).len(), 1);
+    assert_eq!(outlines.dataset.data[IxDyn(&[1, 1])], 1.0);
+    assert_eq!(outlines.dataset.data[IxDyn(&[0, 6])], 0.0);
+
+    dataset.metadata.dims[0].spacing = Some(0.5);
+    dataset.metadata.dims[1].spacing = Some(0.5);
+    let calibrated = execute_operation(
+        "analyze.particles",
         &dataset,
-        &json!({
-            "count": 1
-        }),
+        &json!({"lower": 5.0, "min_size": 2.0, "pixel_units": true}),
     )
-    .expect("erode count one");
-    assert_eq!(count_one.dataset.data[IxDyn(&[1, 1])], 0.0);
+    .expect("calibrated");
+    let measured = rows(&calibrated);
+    assert_eq!(measured.len(), 1);
+    assert_eq!(measured[0]["area"], json!(2.0));
 
-    let count_two = execute_operation(
-        "morphology.erode",
-        &dataset,
-        &json!({
-            "count": 2
-        }),
-    )
-    .expect("erode count two");
-    assert_eq!(count_two.dataset.data[IxDyn(&[1, 1])], 1.0);
+    assert!(execute_operation("analyze.particles", &dataset, &json!({"connectivity": 6})).is_err());
+    assert!(execute_operation("analyze.particles", &dataset, &json!({"channel": 1})).is_err());
 }
 
 #[test]
-fn morphology_dilate_honors_neighbor_count() {
-    let dataset = test_dataset(
-        vec![
-            0.0, 1.0, 0.0, //
-            0.0, 0.0, 0.0, //
-            0.0, 0.0, 0.0,
+fn analyze_particles_honors_connectivity_and_redirect_channel() {
+    let mut values = vec![0.0_f32; 3 * 3 * 2];
+    for (y, x) in [(0, 0), (1, 1)] {
+        values[(y * 3 + x) * 2] = 1.0;
+        values[(y * 3 + x) * 2 + 1] = 7.0 + x as f32;
+    }
+    let data = Array::from_shape_vec(IxDyn(&[3, 3, 2]), values).expect("shape");
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Y, 3),
+            Dim::new(AxisKind::X, 3),
+            Dim::new(AxisKind::Channel, 2),
         ],
-        (3, 3),
-    );
+        ..Metadata::default()
+    };
+    let dataset = Dataset::new(data, metadata).expect("dataset");
 
-    let count_one = execute_operation(
-        "morphology.dilate",
+    let count = |params: serde_json::Value| {
+        let output = execute_operation("analyze.particles", &dataset, &params).expect("particles");
+        output.measurements.expect("measurements").values["count"].clone()
+    };
+    assert_eq!(count(json!({})), json!(1));
+    assert_eq!(count(json!({"connectivity": 4})), json!(2));
+
+    let output = execute_operation(
+        "analyze.particles",
         &dataset,
-        &json!({
-            "count": 1
-        }),
+        &json!({"redirect_channel": 1, "output": "masks"}),
     )
-    .expect("dilate count one");
-    assert_eq!(count_one.dataset.data[IxDyn(&[1, 1])], 1.0);
+    .expect("redirect");
+    let table = output.measurements.expect("measurements");
+    assert_eq!(table.values["rows"][0]["mean"], json!(7.5));
+    assert_eq!(output.dataset.shape(), &[3, 3]);
+    assert_eq!(output.dataset.data[IxDyn(&[1, 1])], 1.0);
+}
 
-    let count_two = execute_operation(
-        "morphology.dilate",
+#[test]
+fn analyze_objects_3d_measures_calibrated_volumes() {
+    let mut data = Array::zeros(IxDyn(&[5, 5, 4]));
+    for y in 1..3 {
+        for x in 1..3 {
+            for z in 1..3 {
+                data[IxDyn(&[y, x, z])] = 1.0;
+            }
+        }
+    }
+    data[IxDyn(&[3, 3, 3])] = 1.0;
+    let mut dims = vec![
+        Dim::new(AxisKind::Y, 5),
+        Dim::new(AxisKind::X, 5),
+        Dim::new(AxisKind::Z, 4),
+    ];
+    for (dim, spacing) in dims.iter_mut().zip([1.0, 1.0, 2.0]) {
+        dim.spacing = Some(spacing);
+        dim.unit = Some("um".to_string());
+    }
+    let metadata = Metadata {
+        dims,
+        ..Metadata::default()
+    };
+    let dataset = Dataset::new(data, metadata).expect("dataset");
+
+    let run = |params: serde_json::Value| {
+        execute_operation("analyze.objects_3d", &dataset, &params).expect("objects")
+    };
+    let joined = run(json!({}));
+    let table = joined.measurements.expect("measurements");
+    assert_eq!(table.values["count"], json!(1));
+    assert_eq!(table.values["rows"][0]["voxels"], json!(9));
+    assert_eq!(table.values["volume_unit"], json!("um^3"));
+    assert!(joined.dataset.is_label());
+    assert_eq!(joined.dataset.data[IxDyn(&[3, 3, 3])], 1.0);
+
+    let separated = run(json!({"connectivity": 6, "min_size": 2}));
+    let table = separated.measurements.expect("measurements");
+    assert_eq!(table.values["count"], json!(1));
+    let row = &table.values["rows"][0];
+    assert_eq!(row["voxels"], json!(8));
+    assert_eq!(row["volume"], json!(16.0));
+    assert_eq!(row["surface_area"], json!(40.0));
+    assert_eq!(row["centroid_x"], json!(2.0));
+    assert_eq!(row["centroid_z"], json!(4.0));
+    assert_eq!(row["bbox"], json!([1, 1, 1, 2, 2, 2]));
+    let expected = PI.cbrt() * 96.0_f64.powf(2.0 / 3.0) / 40.0;
+    assert!((row["sphericity"].as_f64().expect("sphericity") - expected).abs() < 1e-9);
+    assert_eq!(separated.dataset.data[IxDyn(&[3, 3, 3])], 0.0);
+
+    assert!(
+        execute_operation("analyze.objects_3d", &dataset, &json!({"connectivity": 8})).is_err()
+    );
+}
+
+#[test]
+fn analyze_objects_3d_reads_redirect_channel_and_label_input() {
+    let mut values = vec![0.0_f32; 4 * 4 * 2];
+    for (y, x, label) in [(0, 0, 2.0), (0, 1, 2.0), (2, 2, 5.0)] {
+        values[(y * 4 + x) * 2] = label;
+        values[(y * 4 + x) * 2 + 1] = 10.0 * label + x as f32;
+    }
+    let data = Array::from_shape_vec(IxDyn(&[4, 4, 2]), values).expect("shape");
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Y, 4),
+            Dim::new(AxisKind::X, 4),
+            Dim::new(AxisKind::Channel, 2),
+        ],
+        kind: DatasetKind::Label,
+        ..Metadata::default()
+    };
+    let dataset = Dataset::new(data, metadata).expect("dataset");
+
+    let output = execute_operation(
+        "analyze.objects_3d",
         &dataset,
-        &json!({
-            "count": 2
-        }),
+        &json!({"redirect_channel": 1, "exclude_edges": true}),
     )
-    .expect("dilate count two");
-    assert_eq!(count_two.dataset.data[IxDyn(&[1, 1])], 0.0);
+    .expect("objects");
+    let table = output.measurements.expect("measurements");
+    assert_eq!(table.values["count"], json!(1));
+    let row = &table.values["rows"][0];
+    assert_eq!(row["mean"], json!(52.0));
+    assert_eq!(row["centroid_x"], json!(2.5));
+    assert_eq!(row["centroid_z"], json!(0.5));
+    assert_eq!(output.dataset.shape(), &[4, 4]);
+    assert_eq!(output.dataset.data[IxDyn(&[2, 2])], 1.0);
+    assert!(!table.values.contains_key("volume_unit"));
 }
 
 #[test]
-fn morphology_erode_rejects_zero_count() {
-    let dataset = test_dataset(vec![1.0; 9], (3, 3));
+fn gray_morphology_matches_brute_force_structuring_elements() {
+    let (height, width) = (9, 11);
+    let values = (0..height * width)
+        .map(|index| ((index * 37 + 11) % 23) as f32)
+        .collect::<Vec<_>>();
+    let dataset = test_dataset(values.clone(), (height, width));
+    let brute_force = |offsets: &[(isize, isize)], minimum: bool| {
+        let mut expected = Vec::new();
+        for y in 0..height as isize {
+            for x in 0..width as isize {
+                let neighbours = offsets.iter().filter_map(|(dx, dy)| {
+                    let (nx, ny) = if minimum {
+                        (x + dx, y + dy)
+                    } else {
+                        (x - dx, y - dy)
+                    };
+                    ((0..width as isize).contains(&nx) && (0..height as isize).contains(&ny))
+                        .then(|| values[ny as usize * width + nx as usize])
+                });
+                expected.push(if minimum {
+                    neighbours.fold(f32::INFINITY, f32::min)
+                } else {
+                    neighbours.fold(f32::NEG_INFINITY, f32::max)
+                });
+            }
+        }
+        expected
+    };
+
+    let mut diamond = Vec::new();
+    for dy in -3_isize..=3 {
+        for dx in -3_isize..=3 {
+            if dx.abs() + dy.abs() <= 3 {
+                diamond.push((dx, dy));
+            }
+        }
+    }
+    let cases = [
+        (json!({"shape": "diamond", "radius": 3}), diamond),
+        (
+            json!({"shape": "line", "length": 5, "angle": 90.0}),
+            vec![(0, -2), (0, -1), (0, 0), (0, 1), (0, 2)],
+        ),
+        (
+            json!({"shape": "custom", "kernel": [[0, 0, 0], [0, 1, 1], [1, 0, 0]]}),
+            vec![(0, 0), (1, 0), (-1, 1)],
+        ),
+    ];
+    for (params, offsets) in cases {
+        for (op, minimum) in [
+            ("morphology.gray.erode", true),
+            ("morphology.gray.dilate", false),
+        ] {
+            let output = execute_operation(op, &dataset, &params).expect("gray morphology");
+            assert_eq!(
+                output.dataset.data.iter().copied().collect::<Vec<_>>(),
+                brute_force(&offsets, minimum),
+                "{op} {params}"
+            );
+        }
+    }
+
     let error = execute_operation(
-        "morphology.erode",
+        "morphology.gray.erode",
         &dataset,
-        &json!({
-            "count": 0
-        }),
+        &json!({"shape": "custom", "kernel": [[1, 1]]}),
     )
-    .expect_err("zero count should fail");
-
-    assert!(error.to_string().contains("count"));
+    .expect_err("even kernel");
+    assert!(error.to_string().contains("odd"));
 }
 
 #[test]
-fn morphology_fill_holes_fills_enclosed_background() {
-    let dataset = test_dataset(
-        vec![
-            0.0, 0.0, 0.0, 0.0, 0.0, //
-            0.0, 1.0, 1.0, 1.0, 0.0, //
-            0.0, 1.0, 0.0, 1.0, 0.0, //
-            0.0, 1.0, 1.0, 1.0, 0.0, //
-            0.0, 0.0, 0.0, 0.0, 0.0,
-        ],
-        (5, 5),
+fn gray_morphology_derived_filters_isolate_small_details() {
+    let mut values = vec![0.0_f32; 7 * 7];
+    for (index, value) in values.iter_mut().enumerate() {
+        *value = (index % 7) as f32;
+    }
+    values[3 * 7 + 3] += 10.0;
+    let dataset = test_dataset(values, (7, 7));
+    let run = |op: &str, params: serde_json::Value| {
+        execute_operation(op, &dataset, &params)
+            .expect("gray morphology")
+            .dataset
+    };
+
+    let top_hat = run(
+        "morphology.gray.white_tophat",
+        json!({"shape": "square", "radius": 1}),
     );
+    assert_eq!(top_hat.data[IxDyn(&[3, 3])], 10.0);
+    assert_eq!(top_hat.data[IxDyn(&[1, 1])], 0.0);
 
-    let output =
-        execute_operation("morphology.fill_holes", &dataset, &json!({})).expect("fill holes");
+    let black_top_hat = run(
+        "morphology.gray.black_tophat",
+        json!({"shape": "octagon", "radius": 2}),
+    );
+    assert!(black_top_hat.data.iter().all(|value| *value >= 0.0));
 
-    assert_eq!(output.dataset.data[IxDyn(&[2, 2])], 1.0);
-    assert_eq!(output.dataset.data[IxDyn(&[0, 0])], 0.0);
-    assert_eq!(output.dataset.data[IxDyn(&[4, 4])], 0.0);
-}
+    let gradient = run("morphology.gray.gradient", json!({"shape": "square"}));
+    assert_eq!(gradient.data[IxDyn(&[1, 1])], 2.0);
 
-#[test]
-fn morphology_fill_holes_processes_xy_planes_independently() {
-    let data = Array::from_shape_vec(
-        (3, 3, 2),
-        vec![
-            1.0, 1.0, 1.0, 1.0, 1.0, 1.0, //
-            1.0, 0.0, 0.0, 0.0, 1.0, 1.0, //
-            1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
-        ],
-    )
-    .expect("shape")
-    .into_dyn();
+    let mut volume = Array::zeros(IxDyn(&[3, 3, 3]));
+    volume[IxDyn(&[1, 1, 0])] = 5.0;
     let metadata = Metadata {
         dims: vec![
             Dim::new(AxisKind::Y, 3),
             Dim::new(AxisKind::X, 3),
-            Dim::new(AxisKind::Z, 2),
+            Dim::new(AxisKind::Z, 3),
+        ],
+        ..Metadata::default()
+    };
+    let volume = Dataset::new(volume, metadata).expect("dataset");
+    let dilated = execute_operation(
+        "morphology.gray.dilate",
+        &volume,
+        &json!({"shape": "ball", "radius": 1}),
+    )
+    .expect("ball dilation");
+    assert_eq!(dilated.dataset.data[IxDyn(&[1, 1, 1])], 5.0);
+    assert_eq!(dilated.dataset.data[IxDyn(&[1, 1, 2])], 0.0);
+    let flat = execute_operation(
+        "morphology.gray.dilate",
+        &volume,
+        &json!({"shape": "disk", "radius": 1}),
+    )
+    .expect("disk dilation");
+    assert_eq!(flat.dataset.data[IxDyn(&[1, 1, 1])], 0.0);
+}
+
+#[test]
+fn marker_watershed_floods_from_marker_channel_with_dams_and_mask() {
+    let profile = [2.0, 1.0, 0.0, 1.0, 2.0, 1.0, 0.0, 1.0, 2.0];
+    let data = Array::from_shape_fn(IxDyn(&[5, 9, 3]), |index| {
+        let (y, x, channel) = (index[0], index[1], index[2]);
+        match channel {
+            0 => profile[x],
+            1 if y == 2 && x == 2 => 1.0,
+            1 if y == 2 && x == 6 => 2.0,
+            1 => 0.0,
+            _ => {
+                if x < 8 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    });
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Y, 5),
+            Dim::new(AxisKind::X, 9),
+            Dim::new(AxisKind::Channel, 3),
         ],
-        pixel_type: PixelType::F32,
         ..Metadata::default()
     };
     let dataset = Dataset::new(data, metadata).expect("dataset");
+    let row = |output: &OpOutput| {
+        (0..9)
+            .map(|x| output.dataset.data[IxDyn(&[0, x])])
+            .collect::<Vec<_>>()
+    };
 
-    let output =
-        execute_operation("morphology.fill_holes", &dataset, &json!({})).expect("fill holes");
+    let dammed = execute_operation(
+        "morphology.marker_watershed",
+        &dataset,
+        &json!({"marker_channel": 1, "mask_channel": 2, "dams": true}),
+    )
+    .expect("watershed");
+    assert!(dammed.dataset.is_label());
+    assert_eq!(dammed.dataset.shape(), &[5, 9]);
+    assert_eq!(
+        row(&dammed),
+        vec![1.0, 1.0, 1.0, 1.0, 0.0, 2.0, 2.0, 2.0, 0.0]
+    );
+    assert_eq!(
+        dammed.measurements.expect("count").values["count"],
+        json!(2)
+    );
 
-    assert_eq!(output.dataset.data[IxDyn(&[1, 1, 0])], 1.0);
-    assert_eq!(output.dataset.data[IxDyn(&[1, 1, 1])], 0.0);
+    let filled = execute_operation(
+        "morphology.marker_watershed",
+        &dataset,
+        &json!({"marker_channel": 1}),
+    )
+    .expect("watershed");
+    assert!(row(&filled).iter().all(|label| *label > 0.0));
 }
 
 #[test]
-fn morphology_outline_keeps_boundary_foreground() {
-    let dataset = test_dataset(vec![1.0; 9], (3, 3));
-
-    let output = execute_operation("morphology.outline", &dataset, &json!({})).expect("outline");
+fn marker_watershed_reads_seeds_from_a_markers_input() {
+    let profile = [2.0, 1.0, 0.0, 1.0, 2.0, 1.0, 0.0, 1.0, 2.0];
+    let image = test_dataset((0..27).map(|index| profile[index % 9]).collect(), (3, 9));
+    let mut seeds = vec![0.0; 27];
+    seeds[9 + 2] = 4.0;
+    seeds[9 + 6] = 7.0;
+    let mut markers = test_dataset(seeds, (3, 9));
+    markers.metadata.kind = DatasetKind::Label;
 
+    let output = execute_operation_with_inputs(
+        "morphology.marker_watershed",
+        &image,
+        &OpInputs::new().with("markers", &markers),
+        &json!({"dams": true}),
+    )
+    .expect("watershed");
+    assert!(output.dataset.is_label());
     assert_eq!(
-        output.dataset.data.iter().copied().collect::<Vec<_>>(),
-        vec![
-            1.0, 1.0, 1.0, //
-            1.0, 0.0, 1.0, //
-            1.0, 1.0, 1.0,
-        ]
+        (0..9)
+            .map(|x| output.dataset.data[IxDyn(&[1, x])])
+            .collect::<Vec<_>>(),
+        vec![4.0, 4.0, 4.0, 4.0, 0.0, 7.0, 7.0, 7.0, 7.0]
+    );
+    assert_eq!(
+        output.measurements.expect("count").values["count"],
+        json!(2)
+    );
+
+    let mismatched = test_dataset(vec![0.0; 4], (2, 2));
+    assert!(
+        execute_operation_with_inputs(
+            "morphology.marker_watershed",
+            &image,
+            &OpInputs::new().with("markers", &mismatched),
+            &json!({}),
+        )
+        .is_err()
     );
 }
 
 #[test]
-fn morphology_outline_processes_xy_planes_independently() {
-    let data = Array::from_shape_vec(
+fn marker_watershed_seeds_from_h_minima() {
+    let dataset = test_dataset(vec![0.0, 5.0, 4.0, 4.5, 5.0, 1.0, 5.0], (1, 7));
+    let count = |params: serde_json::Value| {
+        execute_operation("morphology.marker_watershed", &dataset, &params)
+            .expect("watershed")
+            .measurements
+            .expect("measurements")
+            .values["count"]
+            .clone()
+    };
+    assert_eq!(count(json!({})), json!(3));
+    assert_eq!(count(json!({"h": 1.0})), json!(2));
+    assert!(
+        execute_operation(
+            "morphology.marker_watershed",
+            &dataset,
+            &json!({"connectivity": 6})
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn regional_and_extended_extrema_follow_dynamics() {
+    let dataset = test_dataset(vec![1.0, 5.0, 4.0, 4.5, 3.0, 8.0, 8.0, 2.0], (1, 8));
+    let run = |op: &str, params: serde_json::Value| {
+        execute_operation(op, &dataset, &params)
+            .expect("extrema")
+            .dataset
+            .data
+            .iter()
+            .copied()
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        run("morphology.regional_maxima", json!({})),
+        vec![0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0]
+    );
+    assert_eq!(
+        run("morphology.regional_minima", json!({})),
+        vec![1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0]
+    );
+    assert_eq!(
+        run("morphology.extended_maxima", json!({"h": 1.0})),
+        vec![0.0, 1.0, 1.0, 1.0, 0.0, 1.0, 1.0, 0.0]
+    );
+    assert_eq!(
+        run("morphology.h_maxima", json!({"h": 1.0})),
+        vec![1.0, 4.0, 4.0, 4.0, 3.0, 7.0, 7.0, 2.0]
+    );
+    let h_minima = run("morphology.h_minima", json!({"h": 1.5}));
+    assert_eq!(h_minima[2], 4.5);
+    assert!(
+        execute_operation("morphology.h_maxima", &dataset, &json!({})).is_err(),
+        "h is required"
+    );
+
+    let data = Array::from_shape_vec(IxDyn(&[1, 1, 3]), vec![1.0, 2.0, 1.0]).expect("shape");
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Y, 1),
+            Dim::new(AxisKind::X, 1),
+            Dim::new(AxisKind::Z, 3),
+        ],
+        ..Metadata::default()
+    };
+    let stack = Dataset::new(data, metadata).expect("dataset");
+    let maxima = |params: serde_json::Value| {
+        execute_operation("morphology.regional_maxima", &stack, &params)
+            .expect("maxima")
+            .dataset
+            .data
+            .iter()
+            .copied()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(maxima(json!({})), vec![0.0, 1.0, 0.0]);
+    assert_eq!(maxima(json!({"stack_3d": false})), vec![1.0, 1.0, 1.0]);
+}
+
+#[test]
+fn impose_minima_keeps_only_marked_basins() {
+    let values = [3.0, 1.0, 3.0, 0.0, 3.0, 2.0, 3.0];
+    let data = Array::from_shape_fn(IxDyn(&[1, 7, 2]), |index| match index[2] {
+        0 => values[index[1]],
+        _ => {
+            if index[1] == 5 {
+                1.0
+            } else {
+                0.0
+            }
+        }
+    });
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Y, 1),
+            Dim::new(AxisKind::X, 7),
+            Dim::new(AxisKind::Channel, 2),
+        ],
+        ..Metadata::default()
+    };
+    let dataset = Dataset::new(data, metadata).expect("dataset");
+
+    let output = execute_operation(
+        "morphology.impose_minima",
+        &dataset,
+        &json!({"marker_channel": 1}),
+    )
+    .expect("impose minima");
+    assert_eq!(output.dataset.shape(), &[1, 7]);
+    assert_eq!(
+        output.dataset.data.iter().copied().collect::<Vec<_>>(),
+        vec![3.0, 3.0, 3.0, 3.0, 3.0, -1.0, 3.0]
+    );
+}
+
+#[test]
+fn impose_minima_reads_markers_input_on_an_image_without_channels() {
+    let image = test_dataset(vec![3.0, 1.0, 3.0, 0.0, 3.0, 2.0, 3.0], (1, 7));
+    let markers = test_dataset(vec![0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0], (1, 7));
+
+    let output = execute_operation_with_inputs(
+        "morphology.impose_minima",
+        &image,
+        &OpInputs::new().with("markers", &markers),
+        &json!({}),
+    )
+    .expect("impose minima");
+    assert_eq!(output.dataset.shape(), &[1, 7]);
+    assert_eq!(
+        output.dataset.data.iter().copied().collect::<Vec<_>>(),
+        vec![3.0, 3.0, 3.0, 3.0, 3.0, -1.0, 3.0]
+    );
+
+    let error = execute_operation("morphology.impose_minima", &image, &json!({}))
+        .expect_err("markers are required");
+    assert!(error.to_string().contains("markers"));
+}
+
+#[test]
+fn label_removal_relabel_and_keep_largest() {
+    let dataset = test_dataset(
+        vec![
+            4.0, 4.0, 0.0, 0.0, 0.0, //
+            0.0, 0.0, 0.0, 7.0, 0.0, //
+            0.0, 9.0, 9.0, 9.0, 0.0, //
+            0.0, 9.0, 9.0, 0.0, 0.0, //
+        ],
+        (4, 5),
+    );
+    let values = |name: &str, params: serde_json::Value| {
+        let output = execute_operation(name, &dataset, &params).expect(name);
+        assert_eq!(output.dataset.metadata.kind, DatasetKind::Label);
+        output.dataset.data.iter().copied().collect::<Vec<_>>()
+    };
+
+    let removed = values(
+        "labels.remove",
+        json!({"min_size": 2, "exclude_edges": true}),
+    );
+    assert!(removed.iter().all(|value| *value == 0.0));
+    let removed = values("labels.remove", json!({"min_size": 2}));
+    assert!(!removed.contains(&7.0) && removed.contains(&4.0) && removed.contains(&9.0));
+
+    let relabelled = values("labels.relabel", json!({}));
+    assert_eq!(&relabelled[..2], &[1.0, 1.0]);
+    assert_eq!(relabelled[8], 2.0);
+    assert_eq!(relabelled[11], 3.0);
+
+    let largest = values("labels.keep_largest", json!({}));
+    assert_eq!(largest.iter().filter(|value| **value == 9.0).count(), 5);
+    assert!(largest.iter().all(|value| *value == 0.0 || *value == 9.0));
+}
+
+#[test]
+fn label_dilate_fill_merge_and_crop() {
+    let dataset = test_dataset(
+        vec![
+            1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 2.0, //
+            1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 2.0, //
+            1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 2.0, //
+        ],
+        (3, 7),
+    );
+    let run = |name: &str, params: serde_json::Value| {
+        execute_operation(name, &dataset, &params)
+            .expect(name)
+            .dataset
+            .data
+            .iter()
+            .copied()
+            .collect::<Vec<_>>()
+    };
+
+    let dilated = run("labels.dilate", json!({"radius": 1.5}));
+    assert_eq!(&dilated[..7], &[1.0, 1.0, 1.0, 1.0, 0.0, 2.0, 2.0]);
+    assert_eq!(dilated[8], 1.0);
+
+    // (2, 2) is two chessboard steps from both seeds but Euclidean-closer to label 2.
+    let mut seeds = test_dataset(vec![0.0; 15], (3, 5));
+    seeds.data[IxDyn(&[0, 0])] = 1.0;
+    seeds.data[IxDyn(&[2, 4])] = 2.0;
+    seeds.metadata.kind = DatasetKind::Label;
+    let grown = execute_operation("labels.dilate", &seeds, &json!({"radius": 3.0}))
+        .expect("dilate")
+        .dataset;
+    assert_eq!(grown.data[IxDyn(&[2, 2])], 2.0);
+    assert_eq!(grown.data[IxDyn(&[0, 2])], 1.0);
+
+    let filled = run("labels.fill_holes", json!({}));
+    assert_eq!(filled[8], 1.0);
+    assert_eq!(filled[10], 0.0);
+
+    let boundaries = run("labels.boundaries", json!({"binary": true}));
+    assert_eq!(&boundaries[..7], &[0.0, 1.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+
+    let merged =
+        execute_operation("labels.merge", &dataset, &json!({"groups": [2, 1]})).expect("merge");
+    let count = merged
+        .measurements
+        .as_ref()
+        .and_then(|table| table.values.get("count"))
+        .and_then(|value| value.as_u64());
+    assert_eq!(count, Some(1));
+    assert_eq!(merged.dataset.data[IxDyn(&[0, 6])], 1.0);
+
+    let cropped = execute_operation("labels.crop", &dataset, &json!({"label": 2, "padding": 1}))
+        .expect("crop");
+    assert_eq!(cropped.dataset.shape(), &[3, 2]);
+    assert_eq!(cropped.dataset.metadata.dims[1].size, 2);
+    assert_eq!(
+        cropped.dataset.data.iter().copied().collect::<Vec<_>>(),
+        vec![0.0, 2.0, 0.0, 2.0, 0.0, 2.0]
+    );
+    assert!(execute_operation("labels.crop", &dataset, &json!({"label": 5})).is_err());
+}
+
+#[test]
+fn regionprops_measures_shape_topology_and_intensity() {
+    let data = Array::from_shape_fn(IxDyn(&[6, 10, 2]), |index| {
+        let (y, x) = (index[0], index[1]);
+        match index[2] {
+            0 if y < 4 && x < 4 && (y, x) != (1, 1) => 1.0,
+            0 if y == 5 && (3..9).contains(&x) => 2.0,
+            0 => 0.0,
+            _ => x as f32,
+        }
+    });
+    let mut x_dim = Dim::new(AxisKind::X, 10);
+    x_dim.spacing = Some(0.5);
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Y, 6),
+            x_dim,
+            Dim::new(AxisKind::Channel, 2),
+        ],
+        ..Metadata::default()
+    };
+    let dataset = Dataset::new(data, metadata).expect("dataset");
+
+    let output = execute_operation(
+        "measurements.regionprops",
+        &dataset,
+        &json!({"intensity_channel": 1}),
+    )
+    .expect("regionprops");
+    let table = output.measurements.expect("measurements");
+    assert_eq!(table.values["count"], json!(2));
+    let rows = table.values["rows"].as_array().expect("rows");
+    let value = |row: usize, key: &str| rows[row][key].as_f64().expect(key);
+
+    assert_eq!(rows[0]["label"], json!(1));
+    assert!((value(0, "area") - 7.5).abs() < 1e-9);
+    assert_eq!(rows[0]["euler_number"], json!(0));
+    assert_eq!(rows[0]["bbox"], json!([0, 0, 4, 4]));
+    assert!((value(0, "convex_area") - 8.0).abs() < 1e-9);
+    assert!((value(0, "solidity") - 15.0 / 16.0).abs() < 1e-9);
+    assert!((value(0, "feret") - 2.0_f64.hypot(4.0)).abs() < 1e-9);
+    assert!((value(0, "min_feret") - 2.0).abs() < 1e-9);
+    assert!(value(0, "perimeter") > 6.0 && value(0, "perimeter") < 14.0);
+
+    assert_eq!(rows[1]["euler_number"], json!(1));
+    assert!((value(1, "centroid_x") - 3.0).abs() < 1e-9);
+    assert!((value(1, "centroid_y") - 5.5).abs() < 1e-9);
+    assert!(value(1, "ellipse_angle").abs() < 1e-9);
+    assert!(value(1, "major") > value(1, "minor"));
+    assert!(value(1, "eccentricity") > 0.9);
+    assert!((value(1, "mean") - 5.5).abs() < 1e-9);
+    assert_eq!(value(1, "min"), 3.0);
+    assert_eq!(value(1, "max"), 8.0);
+    assert!(value(1, "weighted_centroid_x") > value(1, "centroid_x"));
+
+    let shape_only =
+        execute_operation("measurements.regionprops", &dataset, &json!({})).expect("shape only");
+    let rows = shape_only.measurements.expect("measurements").values["rows"].clone();
+    assert!(rows[0].get("mean").is_none());
+    assert_eq!(
+        execute_operation(
+            "measurements.regionprops",
+            &dataset,
+            &json!({"connectivity": 4})
+        )
+        .expect("4-connected")
+        .measurements
+        .expect("measurements")
+        .values["rows"][0]["euler_number"],
+        json!(0)
+    );
+}
+
+#[test]
+fn measurement_ops_read_intensities_from_a_named_input() {
+    let mut ids = vec![0.0; 16];
+    for index in [0, 1] {
+        ids[index] = 1.0;
+    }
+    for index in [10, 11] {
+        ids[index] = 2.0;
+    }
+    let mut labels = test_dataset(ids, (4, 4));
+    labels.metadata.kind = DatasetKind::Label;
+    let intensity = test_dataset(
+        (0..16)
+            .map(|index| (10 * (index / 4) + index % 4) as f32)
+            .collect(),
+        (4, 4),
+    );
+    let means = |op: &str| {
+        let output = execute_operation_with_inputs(
+            op,
+            &labels,
+            &OpInputs::new().with("intensity", &intensity),
+            &json!({}),
+        )
+        .expect(op);
+        output.measurements.expect("measurements").values["rows"]
+            .as_array()
+            .expect("rows")
+            .iter()
+            .map(|row| row["mean"].as_f64().expect("mean"))
+            .collect::<Vec<_>>()
+    };
+    for op in [
+        "analyze.particles",
+        "analyze.objects_3d",
+        "measurements.regionprops",
+    ] {
+        assert_eq!(means(op), vec![0.5, 22.5], "{op}");
+    }
+
+    let kept = execute_operation_with_inputs(
+        "labels.remove",
+        &labels,
+        &OpInputs::new().with("intensity", &intensity),
+        &json!({"min_mean": 10.0}),
+    )
+    .expect("remove");
+    assert_eq!(kept.dataset.data[IxDyn(&[0, 0])], 0.0);
+    assert_eq!(kept.dataset.data[IxDyn(&[2, 2])], 2.0);
+}
+
+#[test]
+fn distance_transform_is_exact_with_anisotropic_spacing() {
+    let shape = [3, 5, 6];
+    let foreground =
+        |z: usize, y: usize, x: usize| !((z, y, x) == (0, 1, 1) || (z, y, x) == (2, 4, 5));
+    let data = Array::from_shape_fn(IxDyn(&shape), |index| {
+        if foreground(index[0], index[1], index[2]) {
+            1.0
+        } else {
+            0.0
+        }
+    });
+    let spacings = [2.0_f32, 0.5, 0.25];
+    let metadata = Metadata {
+        dims: [AxisKind::Z, AxisKind::Y, AxisKind::X]
+            .into_iter()
+            .zip(shape)
+            .zip(spacings)
+            .map(|((axis, size), spacing)| {
+                let mut dim = Dim::new(axis, size);
+                dim.spacing = Some(spacing);
+                dim
+            })
+            .collect(),
+        ..Metadata::default()
+    };
+    let dataset = Dataset::new(data, metadata).expect("dataset");
+
+    let output = execute_operation("morphology.distance_transform", &dataset, &json!({}))
+        .expect("distance transform");
+    for z in 0..shape[0] {
+        for y in 0..shape[1] {
+            for x in 0..shape[2] {
+                let expected = [(0.0_f32, 1.0_f32, 1.0_f32), (2.0, 4.0, 5.0)]
+                    .iter()
+                    .map(|(bz, by, bx)| {
+                        ((z as f32 - bz) * spacings[0])
+                            .hypot((y as f32 - by) * spacings[1])
+                            .hypot((x as f32 - bx) * spacings[2])
+                    })
+                    .fold(f32::INFINITY, f32::min);
+                let actual = output.dataset.data[IxDyn(&[z, y, x])];
+                assert!(
+                    (actual - expected).abs() < 1e-5,
+                    "{z} {y} {x}: {actual} vs {expected}"
+                );
+            }
+        }
+    }
+
+    let per_plane = execute_operation(
+        "morphology.distance_transform",
+        &dataset,
+        &json!({"stack_3d": false, "calibrated": false}),
+    )
+    .expect("per plane");
+    assert_eq!(per_plane.dataset.data[IxDyn(&[0, 1, 3])], 2.0);
+    assert!(per_plane.dataset.data[IxDyn(&[1, 0, 0])].is_infinite());
+}
+
+#[test]
+fn distance_transform_returns_voronoi_labels_and_nearest_index() {
+    let dataset = test_dataset(
+        vec![
+            3.0, 0.0, 0.0, 0.0, 0.0, 0.0, //
+            0.0, 0.0, 0.0, 0.0, 0.0, 7.0, //
+        ],
+        (2, 6),
+    );
+    let labels = execute_operation(
+        "morphology.distance_transform",
+        &dataset,
+        &json!({"output": "labels"}),
+    )
+    .expect("labels");
+    assert_eq!(labels.dataset.metadata.kind, DatasetKind::Label);
+    assert_eq!(
+        labels.dataset.data.iter().copied().collect::<Vec<_>>(),
+        vec![3.0, 3.0, 3.0, 7.0, 7.0, 7.0, 3.0, 3.0, 3.0, 7.0, 7.0, 7.0]
+    );
+
+    let index = execute_operation(
+        "morphology.distance_transform",
+        &dataset,
+        &json!({"output": "index"}),
+    )
+    .expect("index");
+    assert_eq!(index.dataset.data[IxDyn(&[1, 4])], 11.0);
+    assert_eq!(index.dataset.data[IxDyn(&[1, 0])], 0.0);
+
+    let rejected = execute_operation(
+        "morphology.distance_transform",
+        &dataset,
+        &json!({"output": "labels", "features": "background"}),
+    );
+    assert!(rejected.is_err());
+}
+
+#[test]
+fn image_calculator_broadcasts_a_plane_over_a_stack() {
+    let data = Array::from_shape_vec(IxDyn(&[2, 1, 3]), vec![10.0, 200.0, 6.0, 20.0, 100.0, 0.0])
+        .expect("shape");
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Z, 2),
+            Dim::new(AxisKind::Y, 1),
+            Dim::new(AxisKind::X, 3),
+        ],
+        pixel_type: PixelType::U8,
+        ..Metadata::default()
+    };
+    let stack = Dataset::new(data, metadata).expect("stack");
+    let mut plane = test_dataset(vec![5.0, 100.0, 0.0], (1, 3));
+    plane.metadata.pixel_type = PixelType::U8;
+    let inputs = OpInputs::new().with("image2", &plane);
+    let run = |params: serde_json::Value| {
+        let output = execute_operation_with_inputs("image.calculator", &stack, &inputs, &params)
+            .expect("calculator");
+        (
+            output.dataset.metadata.pixel_type,
+            output.dataset.data.iter().copied().collect::<Vec<_>>(),
+        )
+    };
+
+    assert_eq!(
+        run(json!({"operation": "add"})),
+        (PixelType::U8, vec![15.0, 255.0, 6.0, 25.0, 200.0, 0.0])
+    );
+    assert_eq!(
+        run(json!({"operation": "divide"})).1,
+        vec![2.0, 2.0, 255.0, 4.0, 1.0, 255.0]
+    );
+    let (pixel_type, divided) = run(json!({"operation": "divide", "float_result": true}));
+    assert_eq!(pixel_type, PixelType::F32);
+    assert!(divided[2].is_infinite() && divided[5].is_nan());
+    assert_eq!(
+        run(json!({"operation": "and"})).1,
+        vec![0.0, 64.0, 0.0, 4.0, 100.0, 0.0]
+    );
+    assert_eq!(
+        run(json!({"operation": "difference"})).1,
+        vec![5.0, 100.0, 6.0, 15.0, 0.0, 0.0]
+    );
+
+    assert!(execute_operation("image.calculator", &stack, &json!({"operation": "add"})).is_err());
+    let mismatched = test_dataset(vec![1.0, 2.0], (1, 2));
+    assert!(
+        execute_operation_with_inputs(
+            "image.calculator",
+            &stack,
+            &OpInputs::new().with("image2", &mismatched),
+            &json!({"operation": "add"}),
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn image_apply_mask_clears_pixels_outside_the_mask() {
+    let dataset = test_dataset(vec![1.0, 2.0, 3.0, 4.0], (2, 2));
+    let mask = test_dataset(vec![0.0, 255.0, 1.0, 0.0], (2, 2));
+    let inputs = OpInputs::new().with("mask", &mask);
+    let output = execute_operation_with_inputs(
+        "image.apply_mask",
+        &dataset,
+        &inputs,
+        &json!({"background": "NaN"}),
+    )
+    .expect("apply mask");
+    let values = output.dataset.data.iter().copied().collect::<Vec<_>>();
+    assert!(values[0].is_nan() && values[3].is_nan());
+    assert_eq!(&values[1..3], &[2.0, 3.0]);
+
+    assert!(
+        execute_operation_with_inputs("intensity.invert", &dataset, &inputs, &json!({}),).is_err()
+    );
+}
+
+#[test]
+fn fft_forward_inverse_round_trips_and_custom_filter_removes_stripes() {
+    let values = (0..64)
+        .map(|index| 10.0 + (std::f32::consts::PI * (index % 8) as f32 / 2.0).cos())
+        .collect::<Vec<_>>();
+    let image = test_dataset(values.clone(), (8, 8));
+
+    let spectrum = execute_operation("image.fft_forward", &image, &json!({})).expect("forward");
+    assert_eq!(spectrum.dataset.shape(), &[8, 8, 2]);
+    assert_eq!(
+        spectrum.dataset.metadata.channel_names,
+        vec!["real", "imaginary"]
+    );
+    assert!((spectrum.dataset.data[[4, 4, 0]] - 640.0).abs() < 1e-2);
+    assert!((spectrum.dataset.data[[4, 2, 0]] - 32.0).abs() < 1e-2);
+
+    let restored =
+        execute_operation("image.fft_inverse", &spectrum.dataset, &json!({})).expect("inverse");
+    assert_eq!(restored.dataset.shape(), &[8, 8]);
+    assert!(restored.dataset.metadata.channel_names.is_empty());
+    for (restored, original) in restored.dataset.data.iter().zip(&values) {
+        assert!((restored - original).abs() < 1e-4);
+    }
+
+    let mut filter = vec![255.0; 64];
+    filter[4 * 8 + 2] = 0.0;
+    filter[4 * 8 + 6] = 0.0;
+    let filter = test_dataset(filter, (8, 8));
+    let inputs = OpInputs::new().with("filter", &filter);
+    let filtered =
+        execute_operation_with_inputs("image.fft_custom_filter", &image, &inputs, &json!({}))
+            .expect("filter image");
+    assert!(
+        filtered
+            .dataset
+            .data
+            .iter()
+            .all(|value| (value - 10.0).abs() < 1e-4)
+    );
+
+    let filtered_spectrum = execute_operation_with_inputs(
+        "image.fft_custom_filter",
+        &spectrum.dataset,
+        &inputs,
+        &json!({}),
+    )
+    .expect("filter spectrum");
+    assert_eq!(filtered_spectrum.dataset.data[[4, 2, 0]], 0.0);
+    let restored = execute_operation("image.fft_inverse", &filtered_spectrum.dataset, &json!({}))
+        .expect("inverse filtered");
+    assert!(
+        restored
+            .dataset
+            .data
+            .iter()
+            .all(|value| (value - 10.0).abs() < 1e-4)
+    );
+}
+
+#[test]
+fn fft_convolve_and_deconvolve_with_a_kernel_image() {
+    let mut impulse = vec![0.0; 64];
+    impulse[3 * 8 + 3] = 1.0;
+    let image = test_dataset(impulse.clone(), (8, 8));
+    let kernel = test_dataset(vec![0.0, 1.0, 0.0, 1.0, 6.0, 1.0, 0.0, 1.0, 0.0], (3, 3));
+    let inputs = OpInputs::new().with("kernel", &kernel);
+
+    let blurred = execute_operation_with_inputs("image.fft_convolve", &image, &inputs, &json!({}))
+        .expect("convolve");
+    assert!((blurred.dataset.data[[3, 3]] - 0.6).abs() < 1e-5);
+    assert!((blurred.dataset.data[[2, 3]] - 0.1).abs() < 1e-5);
+    assert!((blurred.dataset.data[[3, 4]] - 0.1).abs() < 1e-5);
+    assert!(blurred.dataset.data[[2, 2]].abs() < 1e-5);
+
+    let restored = execute_operation_with_inputs(
+        "image.fft_deconvolve",
+        &blurred.dataset,
+        &inputs,
+        &json!({"regularization": 0.0}),
+    )
+    .expect("deconvolve");
+    for (restored, original) in restored.dataset.data.iter().zip(&impulse) {
+        assert!((restored - original).abs() < 1e-4);
+    }
+
+    assert!(execute_operation("image.fft_convolve", &image, &json!({})).is_err());
+    let large = test_dataset(vec![1.0; 90], (9, 10));
+    assert!(
+        execute_operation_with_inputs(
+            "image.fft_convolve",
+            &image,
+            &OpInputs::new().with("kernel", &large),
+            &json!({}),
+        )
+        .is_err()
+    );
+}
+
+fn gaussian_spot(size: usize, centre: (f32, f32)) -> Vec<f32> {
+    (0..size * size)
+        .map(|index| {
+            let dx = (index % size) as f32 - centre.0;
+            let dy = (index / size) as f32 - centre.1;
+            100.0 * (-(dx * dx + dy * dy) / 8.0).exp()
+        })
+        .collect()
+}
+
+#[test]
+fn fft_correlate_and_phase_correlation_find_the_shift() {
+    let reference = test_dataset(gaussian_spot(32, (14.0, 16.0)), (32, 32));
+    let moved = test_dataset(gaussian_spot(32, (17.0, 14.0)), (32, 32));
+
+    let auto = execute_operation("image.fft_correlate", &reference, &json!({})).expect("auto");
+    assert!((auto.dataset.data[[16, 16]] - 1.0).abs() < 1e-4);
+    assert!(auto.dataset.data.iter().all(|value| *value <= 1.0 + 1e-4));
+
+    let inputs = OpInputs::new().with("reference", &reference);
+    let cross = execute_operation_with_inputs("image.fft_correlate", &moved, &inputs, &json!({}))
+        .expect("cross");
+    let peak = cross
+        .dataset
+        .data
+        .indexed_iter()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(index, _)| (index[1], index[0]))
+        .expect("peak");
+    assert_eq!(peak, (19, 14));
+
+    let phase =
+        execute_operation_with_inputs("image.phase_correlation", &moved, &inputs, &json!({}))
+            .expect("phase correlation");
+    let row = &phase.measurements.expect("table").values["rows"][0];
+    assert!((row["x"].as_f64().expect("x") - 3.0).abs() < 0.1);
+    assert!((row["y"].as_f64().expect("y") + 2.0).abs() < 0.1);
+
+    let subpixel = test_dataset(gaussian_spot(32, (15.5, 16.0)), (32, 32));
+    let phase =
+        execute_operation_with_inputs("image.phase_correlation", &subpixel, &inputs, &json!({}))
+            .expect("subpixel");
+    let row = &phase.measurements.expect("table").values["rows"][0];
+    assert!((row["x"].as_f64().expect("x") - 1.5).abs() < 0.25);
+}
+
+#[test]
+fn image_register_translation_aligns_a_drifting_time_series() {
+    let centres = [(14.0, 16.0), (16.0, 15.0), (19.0, 13.0)];
+    let values = centres
+        .iter()
+        .flat_map(|centre| gaussian_spot(32, *centre))
+        .collect::<Vec<_>>();
+    let data = Array::from_shape_vec(IxDyn(&[3, 32, 32]), values).expect("shape");
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Time, 3),
+            Dim::new(AxisKind::Y, 32),
+            Dim::new(AxisKind::X, 32),
+        ],
+        pixel_type: PixelType::F32,
+        ..Metadata::default()
+    };
+    let series = Dataset::new(data, metadata).expect("series");
+
+    for reference in [json!("first"), json!("previous"), json!(0)] {
+        let output = execute_operation(
+            "image.register.translation",
+            &series,
+            &json!({"reference": reference}),
+        )
+        .expect("register");
+        let rows = output.measurements.expect("table").values["rows"].clone();
+        assert_eq!(rows[2]["t"], json!(2));
+        for (row, centre) in rows.as_array().expect("rows").iter().zip(centres) {
+            assert!((row["x"].as_f64().expect("x") + f64::from(centre.0) - 14.0).abs() < 0.1);
+            assert!((row["y"].as_f64().expect("y") + f64::from(centre.1) - 16.0).abs() < 0.1);
+        }
+        for t in 0..3 {
+            assert!((output.dataset.data[[t, 16, 14]] - 100.0).abs() < 1.0);
+        }
+    }
+
+    let report = execute_operation(
+        "image.register.translation",
+        &series,
+        &json!({"apply": false}),
+    )
+    .expect("report only");
+    assert_eq!(report.dataset.data, series.data);
+    assert!(
+        execute_operation(
+            "image.register.translation",
+            &series,
+            &json!({"reference": 3})
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn image_register_stack_recovers_rotation_and_reapplies_transforms() {
+    let blobs = [
+        (20.0, 22.0, 3.0),
+        (41.0, 18.0, 4.0),
+        (30.0, 42.0, 5.0),
+        (46.0, 40.0, 2.5),
+    ];
+    let scene = |x: f32, y: f32| {
+        blobs
+            .iter()
+            .map(|(bx, by, sigma)| {
+                100.0 * (-((x - bx).powi(2) + (y - by).powi(2)) / (2.0 * sigma * sigma)).exp()
+            })
+            .sum::<f32>()
+    };
+    // Slice 1 samples the scene rotated by 6 degrees about the centre and shifted.
+    let (sin, cos) = 6.0_f32.to_radians().sin_cos();
+    let centre = 31.5;
+    let mut values = Vec::with_capacity(2 * 64 * 64);
+    for slice in 0..2 {
+        for y in 0..64 {
+            for x in 0..64 {
+                let (x, y) = (x as f32 - centre, y as f32 - centre);
+                values.push(if slice == 0 {
+                    scene(x + centre, y + centre)
+                } else {
+                    scene(
+                        cos * x - sin * y + centre + 3.0,
+                        sin * x + cos * y + centre - 2.0,
+                    )
+                });
+            }
+        }
+    }
+    let data = Array::from_shape_vec(IxDyn(&[2, 64, 64]), values).expect("shape");
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Z, 2),
+            Dim::new(AxisKind::Y, 64),
+            Dim::new(AxisKind::X, 64),
+        ],
+        pixel_type: PixelType::F32,
+        ..Metadata::default()
+    };
+    let stack = Dataset::new(data, metadata).expect("stack");
+    let error = |dataset: &Dataset<f32>| {
+        let mut total = 0.0;
+        for y in 16..48 {
+            for x in 16..48 {
+                total += (dataset.data[[1, y, x]] - dataset.data[[0, y, x]]).abs();
+            }
+        }
+        total / (32.0 * 32.0)
+    };
+
+    for transform in ["rigid", "affine"] {
+        let output = execute_operation(
+            "image.register.stack",
+            &stack,
+            &json!({"transform": transform}),
+        )
+        .expect("register");
+        assert!(error(&output.dataset) < 0.1 * error(&stack), "{transform}");
+        let table = output.measurements.expect("table");
+        assert_eq!(table.values["transform"], json!(transform));
+        let rows = table.values["rows"].clone();
+        assert_eq!(rows[0]["matrix"], json!([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]));
+        let sine = rows[1]["matrix"][1][0].as_f64().expect("matrix entry");
+        assert!((sine + f64::from(sin)).abs() < 0.01, "{transform}: {sine}");
+
+        let reapplied =
+            execute_operation("image.register.apply", &stack, &json!({"transforms": rows}))
+                .expect("apply");
+        assert_eq!(reapplied.dataset.data, output.dataset.data);
+    }
+
+    assert!(
+        execute_operation(
+            "image.register.stack",
+            &stack,
+            &json!({"reference": "mean"})
+        )
+        .is_err()
+    );
+    assert!(
+        execute_operation(
+            "image.register.apply",
+            &stack,
+            &json!({"transforms": [{"z": 1, "matrix": [[1.0, 0.0]]}]})
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn image_psf_generate_builds_calibrated_widefield_psfs() {
+    let mut image = test_dataset(vec![0.0; 64 * 64], (64, 64));
+    for dim in &mut image.metadata.dims {
+        dim.spacing = Some(0.05);
+        dim.unit = Some("um".to_string());
+    }
+    let psf = execute_operation(
+        "image.psf.generate",
+        &image,
+        &json!({"na": 1.4, "wavelength": 520.0}),
+    )
+    .expect("born-wolf psf")
+    .dataset;
+    let size = psf.shape()[0];
+    assert_eq!(psf.shape(), &[size, size]);
+    assert_eq!(size % 2, 1);
+    assert_eq!(psf.metadata.dims[1].spacing, Some(0.05));
+    assert!((psf.data.sum() - 1.0).abs() < 1e-4);
+    let centre = size / 2;
+    let peak = psf.data[[centre, centre]];
+    assert!(psf.data.iter().all(|value| *value <= peak));
+    // The first dark ring sits at 0.61 λ / NA ≈ 0.227 µm ≈ 4.5 pixels.
+    assert!(psf.data[[centre, centre + 4]] < 0.02 * peak);
+    assert!(psf.data[[centre, centre + 2]] > 0.2 * peak);
+    assert!((psf.data[[centre, centre + 3]] - psf.data[[centre + 3, centre]]).abs() < 1e-7);
+
+    let volume = execute_operation(
+        "image.psf.generate",
+        &image,
+        &json!({
+            "model": "gaussian",
+            "na": 1.4,
+            "wavelength": 520.0,
+            "size_xy": 10,
+            "size_z": 9,
+            "z_step": 0.2,
+        }),
+    )
+    .expect("gaussian psf")
+    .dataset;
+    assert_eq!(volume.shape(), &[9, 11, 11]);
+    assert_eq!(volume.metadata.dims[0].axis, AxisKind::Z);
+    assert_eq!(volume.metadata.dims[0].spacing, Some(0.2));
+    assert!(volume.data[[4, 5, 5]] > volume.data[[3, 5, 5]]);
+    assert!((volume.data[[3, 5, 5]] - volume.data[[5, 5, 5]]).abs() < 1e-7);
+
+    let uncalibrated = test_dataset(vec![0.0; 16], (4, 4));
+    assert!(
+        execute_operation(
+            "image.psf.generate",
+            &uncalibrated,
+            &json!({"na": 1.4, "wavelength": 520.0})
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn richardson_lucy_sharpens_a_blurred_image() {
+    let mut values = vec![1.0; 32 * 32];
+    values[10 * 32 + 10] = 200.0;
+    values[20 * 32 + 18] = 100.0;
+    let sharp = test_dataset(values, (32, 32));
+    let psf = test_dataset(
+        (0..49)
+            .map(|index| {
+                let (dx, dy) = ((index % 7) as f32 - 3.0, (index / 7) as f32 - 3.0);
+                (-(dx * dx + dy * dy) / 4.0).exp()
+            })
+            .collect(),
+        (7, 7),
+    );
+    let blurred = execute_operation_with_inputs(
+        "image.fft_convolve",
+        &sharp,
+        &OpInputs::new().with("kernel", &psf),
+        &json!({}),
+    )
+    .expect("blur")
+    .dataset;
+
+    let inputs = OpInputs::new().with("psf", &psf);
+    for params in [
+        json!({"iterations": 40}),
+        json!({"iterations": 40, "tv_regularization": 0.002}),
+    ] {
+        let restored = execute_operation_with_inputs(
+            "image.deconvolve.richardson_lucy",
+            &blurred,
+            &inputs,
+            &params,
+        )
+        .expect("deconvolve")
+        .dataset;
+        assert_eq!(restored.metadata.pixel_type, PixelType::F32);
+        assert!(
+            restored
+                .data
+                .iter()
+                .all(|value| value.is_finite() && *value >= 0.0)
+        );
+        assert!(restored.data[[10, 10]] > 2.0 * blurred.data[[10, 10]]);
+        assert!(restored.data[[20, 18]] > 2.0 * blurred.data[[20, 18]]);
+        assert!((restored.data.sum() - blurred.data.sum()).abs() < 0.01 * blurred.data.sum());
+    }
+
+    assert!(execute_operation("image.deconvolve.richardson_lucy", &blurred, &json!({})).is_err());
+}
+
+#[test]
+fn feature_filters_respond_to_spots_and_ramps() {
+    let spot = test_dataset(gaussian_spot(32, (16.0, 16.0)), (32, 32));
+    let log = execute_operation("features.log", &spot, &json!({"sigma": 2.0}))
+        .expect("log")
+        .dataset;
+    let minimum = log.data.iter().copied().fold(f32::INFINITY, f32::min);
+    assert_eq!(log.data[[16, 16]], minimum);
+    assert!(minimum < 0.0);
+    let dog = execute_operation("features.dog", &spot, &json!({"sigma": [1.0, 1.0]}))
+        .expect("dog")
+        .dataset;
+    assert!(dog.data[[16, 16]] > 0.0);
+    assert!(dog.data[[16, 16]] > dog.data[[16, 20]]);
+
+    let mut ramp = test_dataset(
+        (0..400).map(|index| 3.0 * (index % 20) as f32).collect(),
+        (20, 20),
+    );
+    let gradient = execute_operation("features.gradient_magnitude", &ramp, &json!({}))
+        .expect("gradient")
+        .dataset;
+    assert!((gradient.data[[10, 10]] - 3.0).abs() < 1e-3);
+    ramp.metadata.dims[1].spacing = Some(0.5);
+    let calibrated = execute_operation("features.gradient_magnitude", &ramp, &json!({}))
+        .expect("calibrated gradient")
+        .dataset;
+    assert!((calibrated.data[[10, 10]] - 6.0).abs() < 1e-3);
+    let pixels = execute_operation(
+        "features.gradient_magnitude",
+        &ramp,
+        &json!({"calibrated": false}),
+    )
+    .expect("pixel gradient")
+    .dataset;
+    assert!((pixels.data[[10, 10]] - 3.0).abs() < 1e-3);
+
+    assert!(execute_operation("features.log", &spot, &json!({"sigma": 0.0})).is_err());
+    assert!(execute_operation("features.log", &spot, &json!({"sigma": [1.0]})).is_err());
+}
+
+#[test]
+fn hessian_and_structure_tensor_eigenvalues() {
+    let size = 9;
+    let data = Array::from_shape_fn(IxDyn(&[size, size, size]), |index| {
+        let (z, y, x) = (
+            index[0] as f32 - 4.0,
+            index[1] as f32 - 4.0,
+            index[2] as f32 - 4.0,
+        );
+        x * x + 2.0 * y * y + 3.0 * z * z + x * y
+    });
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Z, size),
+            Dim::new(AxisKind::Y, size),
+            Dim::new(AxisKind::X, size),
+        ],
+        pixel_type: PixelType::F32,
+        ..Metadata::default()
+    };
+    let volume = Dataset::new(data, metadata).expect("volume");
+    let hessian = execute_operation("features.hessian", &volume, &json!({}))
+        .expect("hessian")
+        .dataset;
+    assert_eq!(hessian.shape(), &[9, 9, 9, 3]);
+    assert_eq!(
+        hessian.metadata.channel_names,
+        vec!["eigenvalue 1", "eigenvalue 2", "eigenvalue 3"]
+    );
+    // [[2, 1, 0], [1, 4, 0], [0, 0, 6]] has eigenvalues 6 and 3 ± √2.
+    let expected = [6.0, 3.0 + 2.0_f32.sqrt(), 3.0 - 2.0_f32.sqrt()];
+    for (channel, expected) in expected.iter().enumerate() {
+        assert!((hessian.data[[4, 4, 4, channel]] - expected).abs() < 1e-2);
+    }
+    let planar = execute_operation("features.hessian", &volume, &json!({"stack_3d": false}))
+        .expect("planar hessian")
+        .dataset;
+    assert_eq!(planar.shape(), &[9, 9, 9, 2]);
+    assert!((planar.data[[4, 4, 4, 0]] - (3.0 + 2.0_f32.sqrt())).abs() < 1e-2);
+
+    let stripes = test_dataset(
+        (0..32 * 32)
+            .map(|index| (std::f32::consts::PI * (index % 32) as f32 / 4.0).sin())
+            .collect(),
+        (32, 32),
+    );
+    let tensor = execute_operation("features.structure_tensor", &stripes, &json!({}))
+        .expect("structure tensor")
+        .dataset;
+    assert_eq!(
+        tensor.metadata.channel_names,
+        vec!["eigenvalue 1", "eigenvalue 2", "coherency"]
+    );
+    assert!(tensor.data[[16, 16, 0]] > 0.0);
+    assert!(tensor.data[[16, 16, 1]].abs() < 1e-4);
+    assert!((tensor.data[[16, 16, 2]] - 1.0).abs() < 1e-3);
+}
+
+#[test]
+fn edge_preserving_denoisers_smooth_noise_but_keep_a_step_edge() {
+    let mut state = 12_345_u32;
+    let mut noise = || {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        ((state >> 16) % 1000) as f32 / 1000.0 * 20.0 - 10.0
+    };
+    let values = (0..32 * 32)
+        .map(|index| if index % 32 < 16 { 20.0 } else { 120.0 } + noise())
+        .collect::<Vec<_>>();
+    let noisy = test_dataset(values, (32, 32));
+    let spread = |dataset: &Dataset<f32>| {
+        let region = (4..28)
+            .flat_map(|y| (2..12).map(move |x| (y, x)))
+            .map(|(y, x)| dataset.data[[y, x]])
+            .collect::<Vec<_>>();
+        let mean = region.iter().sum::<f32>() / region.len() as f32;
+        (region
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f32>()
+            / region.len() as f32)
+            .sqrt()
+    };
+
+    for (op, params) in [
+        ("image.bilateral_filter", json!({})),
+        ("image.anisotropic_diffusion", json!({"iterations": 20})),
+        (
+            "image.anisotropic_diffusion",
+            json!({"iterations": 20, "conductance": "quadratic"}),
+        ),
+        ("image.non_local_means", json!({"search_radius": 4})),
+    ] {
+        let output = execute_operation(op, &noisy, &params).expect(op).dataset;
+        assert!(
+            spread(&output) < 0.6 * spread(&noisy),
+            "{op}: {}",
+            spread(&output)
+        );
+        for y in 4..28 {
+            assert!(output.data[[y, 14]] < 40.0, "{op}");
+            assert!(output.data[[y, 17]] > 100.0, "{op}");
+        }
+    }
+
+    assert!(
+        execute_operation(
+            "image.anisotropic_diffusion",
+            &noisy,
+            &json!({"time_step": 0.5})
+        )
+        .is_err()
+    );
+    assert!(
+        execute_operation(
+            "image.bilateral_filter",
+            &noisy,
+            &json!({"sigma_range": 0.0})
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn intensity_clahe_boosts_local_texture_within_the_slope_limit() {
+    let values = (0..64 * 64)
+        .map(|index| {
+            let (y, x) = (index / 64, index % 64);
+            let texture = ((x + y) % 2) as f32 * 2.0;
+            texture + if x < 32 { 10.0 } else { 200.0 }
+        })
+        .collect::<Vec<_>>();
+    let image = test_dataset(values, (64, 64));
+    let contrast = |dataset: &Dataset<f32>| (dataset.data[[16, 9]] - dataset.data[[16, 8]]).abs();
+
+    let gentle = execute_operation(
+        "intensity.clahe",
+        &image,
+        &json!({"block_size": 32, "slope": 3.0}),
+    )
+    .expect("clahe")
+    .dataset;
+    let strong = execute_operation(
+        "intensity.clahe",
+        &image,
+        &json!({"block_size": 32, "slope": 8.0}),
+    )
+    .expect("clahe")
+    .dataset;
+    assert!(contrast(&gentle) > 3.0, "{}", contrast(&gentle));
+    assert!(contrast(&strong) > contrast(&gentle));
+
+    let mask = test_dataset(
+        (0..64 * 64)
+            .map(|index| if index % 64 < 32 { 1.0 } else { 0.0 })
+            .collect(),
+        (64, 64),
+    );
+    let masked = execute_operation_with_inputs(
+        "intensity.clahe",
+        &image,
+        &OpInputs::new().with("mask", &mask),
+        &json!({"block_size": 32}),
+    )
+    .expect("masked clahe")
+    .dataset;
+    for y in 0..64 {
+        for x in 32..64 {
+            assert_eq!(masked.data[[y, x]], image.data[[y, x]]);
+        }
+    }
+    // Only the dim half feeds the histograms, so its range is already fully used.
+    assert!((contrast(&masked) - 2.0).abs() < 1e-3);
+    assert!(execute_operation("intensity.clahe", &image, &json!({"stack_3d": true})).is_err());
+}
+
+#[test]
+fn bleach_correction_and_histogram_matching_restore_the_first_frame() {
+    let decay = |t: usize| 0.6 * (-0.4 * t as f32).exp() + 0.4;
+    let frame = |t: usize| {
+        (0..16 * 16)
+            .map(|index| (50.0 + index as f32) * decay(t) + 20.0)
+            .collect::<Vec<_>>()
+    };
+    let data = Array::from_shape_vec(IxDyn(&[6, 16, 16]), (0..6).flat_map(frame).collect())
+        .expect("shape");
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Time, 6),
+            Dim::new(AxisKind::Y, 16),
+            Dim::new(AxisKind::X, 16),
+        ],
+        pixel_type: PixelType::F32,
+        ..Metadata::default()
+    };
+    let series = Dataset::new(data, metadata).expect("series");
+    let first = frame(0);
+    let max_error = |dataset: &Dataset<f32>| {
+        dataset
+            .data
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (value - first[index % 256]).abs())
+            .fold(0.0_f32, f32::max)
+    };
+
+    for (method, tolerance) in [
+        ("simple_ratio", 1e-3),
+        ("exponential", 0.05),
+        ("histogram_match", 1e-3),
+    ] {
+        let output = execute_operation(
+            "intensity.bleach_correction",
+            &series,
+            &json!({"method": method, "background": 20.0}),
+        )
+        .expect(method);
+        assert!(max_error(&output.dataset) < tolerance, "{method}");
+        let table = output.measurements.expect("table");
+        assert_eq!(table.values["count"], json!(6));
+        assert_eq!(table.values["rows"][5]["t"], json!(5));
+        if method == "exponential" {
+            let rate = table.values["fits"][0]["b"].as_f64().expect("rate");
+            assert!((rate - 0.4).abs() < 0.01, "{rate}");
+        }
+    }
+
+    let matched = execute_operation(
+        "intensity.histogram_match",
+        &series,
+        &json!({"reference_index": 0}),
+    )
+    .expect("match slices");
+    assert!(max_error(&matched.dataset) < 1e-3);
+
+    let late = test_dataset(frame(4), (16, 16));
+    let reference = test_dataset(first.clone(), (16, 16));
+    let matched = execute_operation_with_inputs(
+        "intensity.histogram_match",
+        &late,
+        &OpInputs::new().with("reference", &reference),
+        &json!({}),
+    )
+    .expect("match image");
+    assert!(max_error(&matched.dataset) < 1e-3);
+    assert!(execute_operation("intensity.histogram_match", &late, &json!({})).is_err());
+}
+
+#[test]
+fn ridge_filters_respond_to_lines_of_the_requested_polarity() {
+    let line = |y: usize| 100.0 * (-((y as f32 - 24.0).powi(2)) / 8.0).exp();
+    let image = test_dataset(
+        (0..48 * 48).map(|index| line(index / 48)).collect(),
+        (48, 48),
+    );
+
+    for op in ["features.frangi", "features.sato", "features.meijering"] {
+        let bright = execute_operation(op, &image, &json!({})).expect(op).dataset;
+        assert_eq!(bright.metadata.pixel_type, PixelType::F32);
+        let on = bright.data[[24, 20]];
+        assert!(on > 0.0, "{op}");
+        assert!(bright.data[[8, 20]] < 0.05 * on, "{op}");
+        let dark = execute_operation(op, &image, &json!({"polarity": "dark"}))
+            .expect(op)
+            .dataset;
+        assert!(dark.data[[24, 20]] < 0.05 * on, "{op}");
+    }
+
+    let scaled = execute_operation(
+        "features.sato",
+        &image,
+        &json!({"sigmas": [1.0, 2.0, 3.0, 4.0], "return_scale": true}),
+    )
+    .expect("sato with scale")
+    .dataset;
+    assert_eq!(scaled.metadata.channel_names, vec!["tubeness", "scale"]);
+    assert_eq!(scaled.data[[24, 20, 1]], 3.0);
+
+    let tube = |z: usize, y: usize| {
+        let distance = (z as f32 - 8.0).powi(2) + (y as f32 - 8.0).powi(2);
+        100.0 * (-distance / 4.0).exp()
+    };
+    let values = (0..16 * 16 * 16)
+        .map(|index| tube(index / 256, (index / 16) % 16))
+        .collect::<Vec<_>>();
+    let data = Array::from_shape_vec(IxDyn(&[16, 16, 16]), values).expect("shape");
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Z, 16),
+            Dim::new(AxisKind::Y, 16),
+            Dim::new(AxisKind::X, 16),
+        ],
+        pixel_type: PixelType::F32,
+        ..Metadata::default()
+    };
+    let volume = Dataset::new(data, metadata).expect("volume");
+    let vesselness = execute_operation("features.frangi", &volume, &json!({"sigmas": [1.5]}))
+        .expect("frangi 3d")
+        .dataset;
+    assert!(vesselness.data[[8, 8, 8]] > 0.5);
+    assert!(vesselness.data[[3, 3, 8]] < 0.05);
+
+    assert!(execute_operation("features.sato", &image, &json!({"sigmas": []})).is_err());
+    assert!(execute_operation("features.frangi", &image, &json!({"polarity": "up"})).is_err());
+}
+
+#[test]
+fn canny_traces_a_step_edge_with_a_single_pixel_line() {
+    let image = test_dataset(
+        (0..32 * 32)
+            .map(|index| if index % 32 < 16 { 0.0 } else { 100.0 })
+            .collect(),
+        (32, 32),
+    );
+    let output =
+        execute_operation("image.edges.canny", &image, &json!({"sigma": 1.0})).expect("canny");
+    assert_eq!(output.dataset.metadata.pixel_type, PixelType::U8);
+    for y in 2..30 {
+        let edges = (0..32)
+            .filter(|x| output.dataset.data[[y, *x]] == 255.0)
+            .collect::<Vec<_>>();
+        assert_eq!(edges.len(), 1, "row {y}: {edges:?}");
+        assert!((15..=16).contains(&edges[0]));
+    }
+    let rows = &output.measurements.expect("table").values["rows"];
+    assert!(rows[0]["high_threshold"].as_f64().expect("high") > 0.0);
+
+    let none = execute_operation(
+        "image.edges.canny",
+        &image,
+        &json!({"low_threshold": 1.0e6, "high_threshold": 2.0e6}),
+    )
+    .expect("canny");
+    assert!(none.dataset.data.iter().all(|value| *value == 0.0));
+    assert!(
+        execute_operation(
+            "image.edges.canny",
+            &image,
+            &json!({"low_threshold": 5.0, "high_threshold": 1.0})
+        )
+        .is_err()
+    );
+
+    // Default quantiles keep only the strongest gradients instead of flat regions.
+    let quantiles = execute_operation(
+        "image.edges.canny",
+        &image,
+        &json!({"sigma": 1.0, "use_quantiles": true}),
+    )
+    .expect("canny");
+    assert_eq!(quantiles.dataset.data, output.dataset.data);
+    // The order is checked after the missing threshold takes its default.
+    for params in [
+        json!({"low_threshold": 1.0e6}),
+        json!({"use_quantiles": true, "low_threshold": 0.95}),
+    ] {
+        assert!(execute_operation("image.edges.canny", &image, &params).is_err());
+    }
+}
+
+#[test]
+fn hough_transforms_find_lines_and_circles_in_edge_images() {
+    let lines = test_dataset(
+        (0..40 * 40)
+            .map(|index| {
+                let (y, x) = (index / 40, index % 40);
+                if x == 10 || y == 20 { 255.0 } else { 0.0 }
+            })
+            .collect(),
+        (40, 40),
+    );
+    let output = execute_operation("image.hough.lines", &lines, &json!({"render": true}))
+        .expect("hough lines");
+    let table = output.measurements.expect("table");
+    assert_eq!(table.values["count"], json!(2));
+    let found = table.values["rows"]
+        .as_array()
+        .expect("rows")
+        .iter()
+        .map(|row| {
+            (
+                row["rho"].as_f64().expect("rho").round(),
+                row["theta"].as_f64().expect("theta").round(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert!(found.contains(&(10.0, 0.0)), "{found:?}");
+    assert!(found.contains(&(-20.0, -90.0)), "{found:?}");
+    assert_eq!(output.dataset.data[[5, 10]], 255.0);
+    assert_eq!(output.dataset.data[[20, 33]], 255.0);
+    assert_eq!(output.dataset.data[[5, 5]], 0.0);
+
+    let mut ring = vec![0.0; 48 * 48];
+    for step in 0..360 {
+        let angle = (step as f32).to_radians();
+        let x = (20.0 + 8.0 * angle.cos()).round() as usize;
+        let y = (18.0 + 8.0 * angle.sin()).round() as usize;
+        ring[y * 48 + x] = 255.0;
+    }
+    let ring = test_dataset(ring, (48, 48));
+    let output = execute_operation(
+        "image.hough.circles",
+        &ring,
+        &json!({"radius_min": 6.0, "radius_max": 10.0, "max_circles": 1, "render": true}),
+    )
+    .expect("hough circles");
+    let table = output.measurements.expect("table");
+    let circle = &table.values["rows"][0];
+    assert_eq!(circle["x"], json!(20.0));
+    assert_eq!(circle["y"], json!(18.0));
+    assert_eq!(circle["radius"], json!(8.0));
+    assert!(circle["score"].as_f64().expect("score") > 0.9);
+    assert_eq!(output.dataset.data[[18, 28]], 255.0);
+    assert_eq!(output.dataset.data[[18, 20]], 0.0);
+
+    assert!(execute_operation("image.hough.circles", &ring, &json!({})).is_err());
+}
+
+#[test]
+fn morphology_erode_honors_iterations() {
+    let dataset = test_dataset(
+        vec![
+            0.0, 0.0, 0.0, 0.0, 0.0, //
+            0.0, 1.0, 1.0, 1.0, 0.0, //
+            0.0, 1.0, 1.0, 1.0, 0.0, //
+            0.0, 1.0, 1.0, 1.0, 0.0, //
+            0.0, 0.0, 0.0, 0.0, 0.0,
+        ],
+        (5, 5),
+    );
+
+    let once = execute_operation(
+        "morphology.erode",
+        &dataset,
+        &json!({
+            "iterations": 1
+        }),
+    )
+    .expect("erode once");
+    assert_eq!(once.dataset.data[IxDyn(&[2, 2])], 1.0);
+
+    let twice = execute_operation(
+        "morphology.erode",
+        &dataset,
+        &json!({
+            "iterations": 2
+        }),
+    )
+    .expect("erode twice");
+    assert_eq!(twice.dataset.data[IxDyn(&[2, 2])], 0.0);
+}
+
+#[test]
+fn morphology_erode_rejects_zero_iterations() {
+    let dataset = test_dataset(vec![1.0; 9], (3, 3));
+    let error = execute_operation(
+        "morphology.erode",
+        &dataset,
+        &json!({
+            "iterations": 0
+        }),
+    )
+    .expect_err("zero iterations should fail");
+
+    assert!(error.to_string().contains("iterations"));
+}
+
+#[test]
+fn morphology_erode_honors_neighbor_count() {
+    let dataset = test_dataset(
+        vec![
+            0.0, 1.0, 1.0, //
+            1.0, 1.0, 1.0, //
+            1.0, 1.0, 1.0,
+        ],
+        (3, 3),
+    );
+
+    let count_one = execute_operation(
+        "morphology.erode",
+        &dataset,
+        &json!({
+            "count": 1
+        }),
+    )
+    .expect("erode count one");
+    assert_eq!(count_one.dataset.data[IxDyn(&[1, 1])], 0.0);
+
+    let count_two = execute_operation(
+        "morphology.erode",
+        &dataset,
+        &json!({
+            "count": 2
+        }),
+    )
+    .expect("erode count two");
+    assert_eq!(count_two.dataset.data[IxDyn(&[1, 1])], 1.0);
+}
+
+#[test]
+fn morphology_dilate_honors_neighbor_count() {
+    let dataset = test_dataset(
+        vec![
+            0.0, 1.0, 0.0, //
+            0.0, 0.0, 0.0, //
+            0.0, 0.0, 0.0,
+        ],
+        (3, 3),
+    );
+
+    let count_one = execute_operation(
+        "morphology.dilate",
+        &dataset,
+        &json!({
+            "count": 1
+        }),
+    )
+    .expect("dilate count one");
+    assert_eq!(count_one.dataset.data[IxDyn(&[1, 1])], 1.0);
+
+    let count_two = execute_operation(
+        "morphology.dilate",
+        &dataset,
+        &json!({
+            "count": 2
+        }),
+    )
+    .expect("dilate count two");
+    assert_eq!(count_two.dataset.data[IxDyn(&[1, 1])], 0.0);
+}
+
+#[test]
+fn morphology_erode_rejects_zero_count() {
+    let dataset = test_dataset(vec![1.0; 9], (3, 3));
+    let error = execute_operation(
+        "morphology.erode",
+        &dataset,
+        &json!({
+            "count": 0
+        }),
+    )
+    .expect_err("zero count should fail");
+
+    assert!(error.to_string().contains("count"));
+}
+
+#[test]
+fn morphology_fill_holes_fills_enclosed_background() {
+    let dataset = test_dataset(
+        vec![
+            0.0, 0.0, 0.0, 0.0, 0.0, //
+            0.0, 1.0, 1.0, 1.0, 0.0, //
+            0.0, 1.0, 0.0, 1.0, 0.0, //
+            0.0, 1.0, 1.0, 1.0, 0.0, //
+            0.0, 0.0, 0.0, 0.0, 0.0,
+        ],
+        (5, 5),
+    );
+
+    let output =
+        execute_operation("morphology.fill_holes", &dataset, &json!({})).expect("fill holes");
+
+    assert_eq!(output.dataset.data[IxDyn(&[2, 2])], 1.0);
+    assert_eq!(output.dataset.data[IxDyn(&[0, 0])], 0.0);
+    assert_eq!(output.dataset.data[IxDyn(&[4, 4])], 0.0);
+}
+
+#[test]
+fn morphology_fill_holes_processes_xy_planes_independently() {
+    let data = Array::from_shape_vec(
+        (3, 3, 2),
+        vec![
+            1.0, 1.0, 1.0, 1.0, 1.0, 1.0, //
+            1.0, 0.0, 0.0, 0.0, 1.0, 1.0, //
+            1.0, 1.0, 1.0, 1.0, 1.0, 1.0,
+        ],
+    )
+    .expect("shape")
+    .into_dyn();
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Y, 3),
+            Dim::new(AxisKind::X, 3),
+            Dim::new(AxisKind::Z, 2),
+        ],
+        pixel_type: PixelType::F32,
+        ..Metadata::default()
+    };
+    let dataset = Dataset::new(data, metadata).expect("dataset");
+
+    let output =
+        execute_operation("morphology.fill_holes", &dataset, &json!({})).expect("fill holes");
+
+    assert_eq!(output.dataset.data[IxDyn(&[1, 1, 0])], 1.0);
+    assert_eq!(output.dataset.data[IxDyn(&[1, 1, 1])], 0.0);
+}
+
+#[test]
+fn morphology_outline_keeps_boundary_foreground() {
+    let dataset = test_dataset(vec![1.0; 9], (3, 3));
+
+    let output = execute_operation("morphology.outline", &dataset, &json!({})).expect("outline");
+
+    assert_eq!(
+        output.dataset.data.iter().copied().collect::<Vec<_>>(),
+        vec![
+            1.0, 1.0, 1.0, //
+            1.0, 0.0, 1.0, //
+            1.0, 1.0, 1.0,
+        ]
+    );
+}
+
+#[test]
+fn morphology_outline_processes_xy_planes_independently() {
+    let data = Array::from_shape_vec(
         (3, 3, 2),
         vec![
             1.0, 1.0, 1.0, 1.0, 1.0, 1.0, //
@@ -5067,6 +7008,240 @@ fn otsu_threshold_returns_measurement() {
     assert!(threshold > 0.0);
 }
 
+#[test]
+fn local_threshold_segments_unevenly_lit_spots() {
+    let mut values = Vec::new();
+    for y in 0..5 {
+        for x in 0..20 {
+            let spot = y == 2 && (x == 2 || x == 17);
+            values.push(x as f32 * 10.0 + if spot { 40.0 } else { 0.0 });
+        }
+    }
+    let dataset = test_dataset(values, (5, 20));
+
+    let global = execute_operation(
+        "threshold.make_binary",
+        &dataset,
+        &json!({"method": "otsu", "background": "dark"}),
+    )
+    .expect("global");
+    assert_eq!(global.dataset.data[IxDyn(&[2, 2])], 0.0);
+
+    let output = execute_operation(
+        "threshold.local",
+        &dataset,
+        &json!({"method": "mean", "radius": 2, "parameter_1": -10.0}),
+    )
+    .expect("local");
+    assert_eq!(output.dataset.metadata.pixel_type, PixelType::U8);
+    let foreground = output
+        .dataset
+        .data
+        .indexed_iter()
+        .filter(|(_, value)| **value == 255.0)
+        .map(|(index, _)| (index[0], index[1]))
+        .collect::<Vec<_>>();
+    assert_eq!(foreground, vec![(2, 2), (2, 17)]);
+
+    let error = execute_operation("threshold.local", &dataset, &json!({"method": "huang"}))
+        .expect_err("global-only method");
+    assert!(error.to_string().contains("huang"));
+
+    let error = execute_operation(
+        "threshold.local",
+        &dataset,
+        &json!({"method": "mean", "stack_3d": true}),
+    )
+    .expect_err("3D neighbourhood without a Z axis");
+    assert!(error.to_string().contains("Z"));
+}
+
+#[test]
+fn local_threshold_sliding_histogram_matches_a_direct_neighbourhood_scan() {
+    let (height, width) = (7, 9);
+    let values = (0..height * width)
+        .map(|index| ((index * 37 + index / width * 11) % 256) as f32)
+        .collect::<Vec<_>>();
+    let mut dataset = test_dataset(values.clone(), (height, width));
+    dataset.metadata.pixel_type = PixelType::U8;
+
+    let output = execute_operation(
+        "threshold.local",
+        &dataset,
+        &json!({"method": "mid_grey", "radius": 2}),
+    )
+    .expect("local");
+    for y in 0..height {
+        for x in 0..width {
+            // Disk of radius 2 with ImageJ's r * r + 1 extent and edge-clamped neighbours.
+            let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+            for dy in -2_isize..=2 {
+                for dx in -2_isize..=2 {
+                    if dx * dx + dy * dy > 5 {
+                        continue;
+                    }
+                    let ny = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+                    let nx = (x as isize + dx).clamp(0, width as isize - 1) as usize;
+                    min = min.min(values[ny * width + nx]);
+                    max = max.max(values[ny * width + nx]);
+                }
+            }
+            let expected = if values[y * width + x] > (min + max) / 2.0 {
+                255.0
+            } else {
+                0.0
+            };
+            assert_eq!(output.dataset.data[IxDyn(&[y, x])], expected, "({y}, {x})");
+        }
+    }
+}
+
+#[test]
+fn local_threshold_uses_neighbouring_slices_in_3d() {
+    let data = Array::from_shape_fn(IxDyn(&[5, 5, 3]), |index| index[2] as f32 * 100.0);
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Y, 5),
+            Dim::new(AxisKind::X, 5),
+            Dim::new(AxisKind::Z, 3),
+        ],
+        pixel_type: PixelType::U8,
+        ..Metadata::default()
+    };
+    let dataset = Dataset::new(data, metadata).expect("dataset");
+
+    let count_foreground = |params: serde_json::Value| {
+        let output = execute_operation("threshold.local", &dataset, &params).expect("local");
+        (0..3)
+            .map(|z| {
+                output
+                    .dataset
+                    .data
+                    .indexed_iter()
+                    .filter(|(index, value)| index[2] == z && **value == 255.0)
+                    .count()
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        count_foreground(json!({"method": "mean", "radius": 1})),
+        vec![0, 0, 0]
+    );
+    assert_eq!(
+        count_foreground(json!({"method": "mean", "radius": 1, "stack_3d": true})),
+        vec![0, 0, 25]
+    );
+}
+
+#[test]
+fn auto_thresholds_support_stack_slice_and_reference_scopes() {
+    let frame = [0.0, 0.1, 0.2, 0.8, 0.9, 1.0];
+    let data = Array::from_shape_fn(IxDyn(&[2, 3, 2]), |index| {
+        let value = frame[index[0] * 3 + index[1]];
+        if index[2] == 0 { value } else { value * 0.3 }
+    });
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Y, 2),
+            Dim::new(AxisKind::X, 3),
+            Dim::new(AxisKind::Time, 2),
+        ],
+        ..Metadata::default()
+    };
+    let dataset = Dataset::new(data, metadata).expect("dataset");
+    let foreground_per_frame = |output: &OpOutput| {
+        (0..2)
+            .map(|t| {
+                output
+                    .dataset
+                    .data
+                    .indexed_iter()
+                    .filter(|(index, value)| index[2] == t && **value > 0.0)
+                    .count()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for op in ["threshold.otsu", "threshold.make_binary"] {
+        let params = |scope: &str| json!({"method": "otsu", "background": "dark", "scope": scope});
+        let stack = foreground_per_frame(
+            &execute_operation(op, &dataset, &params("stack")).expect("stack"),
+        );
+        assert!(stack[1] < stack[0], "{op}: {stack:?}");
+
+        let slice = execute_operation(op, &dataset, &params("slice")).expect("slice");
+        let per_slice = foreground_per_frame(&slice);
+        assert_eq!(per_slice[0], per_slice[1], "{op}");
+        assert!(per_slice[1] > stack[1], "{op}");
+        let table = slice.measurements.expect("measurements");
+        assert!(!table.values.contains_key("threshold"), "{op}");
+        let rows = table.values["slice_thresholds"].as_array().expect("rows");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1]["t"], json!(1));
+        let first = rows[0]["threshold"].as_f64().expect("threshold");
+        let second = rows[1]["threshold"].as_f64().expect("threshold");
+        assert!(second < first, "{op}: {first} vs {second}");
+
+        let reference = execute_operation(
+            op,
+            &dataset,
+            &json!({"method": "otsu", "background": "dark", "scope": "reference", "reference_slice": 1}),
+        )
+        .expect("reference");
+        let table = reference.measurements.expect("measurements");
+        assert_eq!(table.values["threshold"], rows[1]["threshold"], "{op}");
+        assert_eq!(
+            table.values["slice_thresholds"][0]["threshold"],
+            rows[1]["threshold"]
+        );
+
+        let error = execute_operation(
+            op,
+            &dataset,
+            &json!({"scope": "reference", "reference_slice": 2}),
+        )
+        .expect_err("reference out of range");
+        assert!(error.to_string().contains("reference_slice"));
+    }
+}
+
+#[test]
+fn make_binary_reference_scope_keeps_slices_outside_the_reference_range() {
+    let stack = |values: Vec<f32>| {
+        let data = Array::from_shape_vec(IxDyn(&[2, 1, 4]), values).expect("shape");
+        let metadata = Metadata {
+            dims: vec![
+                Dim::new(AxisKind::Z, 2),
+                Dim::new(AxisKind::Y, 1),
+                Dim::new(AxisKind::X, 4),
+            ],
+            pixel_type: PixelType::F32,
+            ..Metadata::default()
+        };
+        Dataset::new(data, metadata).expect("stack")
+    };
+    let second_slice = |dataset: &Dataset<f32>, background: &str| {
+        let output = execute_operation(
+            "threshold.make_binary",
+            dataset,
+            &json!({"method": "otsu", "background": background, "scope": "reference", "reference_slice": 0}),
+        )
+        .expect("make binary");
+        (0..4)
+            .map(|x| output.dataset.data[[1, 0, x]])
+            .collect::<Vec<_>>()
+    };
+
+    let brighter = stack(vec![0.0, 1.0, 9.0, 10.0, 0.0, 1.0, 49.0, 50.0]);
+    assert_eq!(
+        second_slice(&brighter, "dark"),
+        vec![0.0, 0.0, 255.0, 255.0]
+    );
+
+    let darker = stack(vec![5.0, 6.0, 19.0, 20.0, -5.0, -4.0, 19.0, 20.0]);
+    assert_eq!(second_slice(&darker, "light"), vec![255.0, 255.0, 0.0, 0.0]);
+}
+
 #[test]
 fn make_binary_threshold_creates_imagej_style_u8_mask() {
     let dataset = test_dataset(vec![0.0, 0.1, 0.2, 0.8, 0.9, 1.0], (2, 3));
@@ -5577,3 +7752,175 @@ fn measurements_profile_supports_vertical_rectangles_lines_and_selection_errors(
         .expect_err("selection required");
     assert!(error.to_string().contains("line or rectangular selection"));
 }
+
+#[test]
+fn image_display_set_channel_records_lut_range_and_visibility() {
+    let data = Array::from_shape_vec((2, 2, 2), vec![0.0_f32; 8])
+        .expect("shape")
+        .into_dyn();
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Y, 2),
+            Dim::new(AxisKind::X, 2),
+            Dim::new(AxisKind::Channel, 2),
+        ],
+        pixel_type: PixelType::U16,
+        ..Metadata::default()
+    };
+    let dataset = Dataset::new(data, metadata).expect("dataset");
+
+    let output = execute_operation(
+        "image.display.set_channel",
+        &dataset,
+        &json!({"channel": 1, "lut": "magenta", "min": 10.0, "max": 500.0, "visible": false}),
+    )
+    .expect("set channel display");
+    let output = execute_operation(
+        "image.display.set_mode",
+        &output.dataset,
+        &json!({"mode": "color"}),
+    )
+    .expect("set display mode");
+
+    let display = output.dataset.metadata.display.expect("display settings");
+    assert_eq!(display.mode, CompositeMode::Color);
+    assert_eq!(display.channels.len(), 2);
+    assert_eq!(display.channels[0], ChannelDisplay::default());
+    assert_eq!(
+        display.channels[1].lut,
+        ChannelLut::Named("Magenta".to_string())
+    );
+    assert_eq!(display.channels[1].range(), Some((10.0, 500.0)));
+    assert!(!display.channels[1].visible);
+    assert_eq!(output.dataset.data, dataset.data);
+}
+
+#[test]
+fn image_display_set_channel_accepts_custom_tables_and_validates() {
+    let dataset = test_dataset(vec![0.0, 1.0, 2.0, 3.0], (2, 2));
+    let ramp = (0..=255).collect::<Vec<u32>>();
+    let output = execute_operation(
+        "image.display.set_channel",
+        &dataset,
+        &json!({"lut": {"red": ramp, "green": vec![0; 256], "blue": ramp}}),
+    )
+    .expect("custom lut");
+    let display = output.dataset.metadata.display.expect("display settings");
+    assert_eq!(display.channels[0].lut.color(200), Some([200, 0, 200]));
+
+    for params in [
+        json!({"channel": 1}),
+        json!({"lut": "not-a-lut"}),
+        json!({"lut": {"red": [0, 1], "green": [0, 1], "blue": [0, 1]}}),
+        json!({"min": 1.0}),
+        json!({"min": 5.0, "max": 1.0}),
+    ] {
+        assert!(execute_operation("image.display.set_channel", &dataset, &params).is_err());
+    }
+    assert!(execute_operation("image.display.set_mode", &dataset, &json!({"mode": "x"})).is_err());
+}
+
+#[test]
+fn channel_count_changes_drop_stale_display_settings() {
+    let data = Array::from_shape_vec((2, 2, 3), vec![0.0_f32; 12])
+        .expect("shape")
+        .into_dyn();
+    let metadata = Metadata {
+        dims: vec![
+            Dim::new(AxisKind::Y, 2),
+            Dim::new(AxisKind::X, 2),
+            Dim::new(AxisKind::Channel, 3),
+        ],
+        pixel_type: PixelType::U16,
+        ..Metadata::default()
+    };
+    let dataset = Dataset::new(data, metadata).expect("dataset");
+    let dataset = execute_operation(
+        "image.display.set_channel",
+        &dataset,
+        &json!({"channel": 2, "lut": "blue"}),
+    )
+    .expect("set channel display")
+    .dataset;
+
+    let all = execute_operation(
+        "image.hyperstack.subset",
+        &dataset,
+        &json!({"channels": "1-3"}),
+    )
+    .expect("full subset");
+    assert_eq!(all.dataset.metadata.display, dataset.metadata.display);
+
+    let subset = execute_operation(
+        "image.hyperstack.subset",
+        &dataset,
+        &json!({"channels": "1,3"}),
+    )
+    .expect("channel subset");
+    assert_eq!(subset.dataset.metadata.channel_count(), 2);
+    let entries = subset
+        .dataset
+        .metadata
+        .display
+        .as_ref()
+        .map_or(0, |display| display.channels.len());
+    assert!(entries <= subset.dataset.metadata.channel_count());
+    assert!(subset.dataset.metadata.display.is_none());
+}
+
+#[test]
+fn image_axes_permute_reorders_python_czyx_data() {
+    // C=2, Z=1, Y=2, X=3 in row-major order, value = 100c + 10y + x.
+    let values = (0..2)
+        .flat_map(|c| (0..2).flat_map(move |y| (0..3).map(move |x| (100 * c + 10 * y + x) as f32)))
+        .collect::<Vec<_>>();
+    let data = Array::from_shape_vec(IxDyn(&[2, 1, 2, 3]), values).expect("shape");
+    let dataset = Dataset::from_data_with_default_metadata(data, PixelType::U16);
+
+    let output = execute_operation(
+        "image.axes.permute",
+        &dataset,
+        &json!({"from": "CZYX", "order": "YXZC"}),
+    )
+    .expect("permute");
+    assert_eq!(output.dataset.shape(), &[2, 3, 1, 2]);
+    assert_eq!(output.dataset.metadata.axis_codes(), "YXZC");
+    assert_eq!(output.dataset.data[IxDyn(&[1, 2, 0, 1])], 112.0);
+    assert_eq!(output.dataset.data[IxDyn(&[0, 1, 0, 0])], 1.0);
+
+    let back = execute_operation(
+        "image.axes.permute",
+        &output.dataset,
+        &json!({"order": [3, 2, 0, 1]}),
+    )
+    .expect("permute back");
+    assert_eq!(back.dataset.metadata.axis_codes(), "CZYX");
+    assert_eq!(back.dataset.data, dataset.data);
+}
+
+#[test]
+fn image_axes_assign_supports_extended_axis_kinds_and_validates() {
+    let data = Array::from_shape_vec(IxDyn(&[2, 2, 3]), vec![0.0_f32; 12]).expect("shape");
+    let dataset = Dataset::from_data_with_default_metadata(data, PixelType::F32);
+
+    let output =
+        execute_operation("image.axes.assign", &dataset, &json!({"axes": "YXL"})).expect("assign");
+    assert_eq!(output.dataset.metadata.dims[2].axis, AxisKind::Spectral);
+    assert_eq!(output.dataset.data, dataset.data);
+
+    let blurred =
+        execute_operation("gaussian.blur", &output.dataset, &json!({"sigma": 1.0})).expect("blur");
+    assert_eq!(blurred.dataset.metadata.axis_codes(), "YXL");
+
+    for params in [
+        json!({"axes": "YX"}),
+        json!({"axes": "YYX"}),
+        json!({"axes": "YXQ"}),
+    ] {
+        assert!(execute_operation("image.axes.assign", &dataset, &params).is_err());
+    }
+    assert!(execute_operation("image.axes.permute", &dataset, &json!({"order": "YXC"})).is_err());
+    assert!(
+        execute_operation("image.axes.permute", &dataset, &json!({"order": [0, 0, 1]})).is_err()
+    );
+}
@@ -1,11 +1,11 @@
-use crate::model::{Dataset, DatasetF32, PixelType};
-use ndarray::{Array, IxDyn};
+use crate::model::{AxisKind, Dataset, DatasetF32, Metadata, PixelType};
+use ndarray::{Array, ArrayD, ArrayViewMutD, Axis, IxDyn};
 use rayon::prelude::*;
-use serde_json::{Value, json};
+use serde_json::{Map, Value, json};
 
 use super::{
-    MeasurementTable, OpOutput, OpSchema, Operation, OpsError, ParamSpec, Result, get_optional_f32,
-    util::min_max,
+    MeasurementTable, OpOutput, OpSchema, Operation, OpsError, ParamSpec, Result,
+    get_optional_bool, get_optional_f32, get_optional_usize, util::min_max,
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -57,26 +57,39 @@ impl Operation for ThresholdOtsuOp {
         OpSchema {
             name: self.name().to_string(),
             description: "Binary threshold using Otsu's method.".to_string(),
-            params: vec![],
+            params: scope_params(),
         }
     }
 
-    fn execute(&self, dataset: &DatasetF32, _params: &Value) -> Result<OpOutput> {
-        let values = dataset.data.iter().copied().collect::<Vec<_>>();
-        let threshold = otsu_threshold(&values);
-        let mut binary = values;
-        binary.par_iter_mut().for_each(|value| {
-            *value = if *value >= threshold { 1.0 } else { 0.0 };
-        });
-        let output = Array::from_shape_vec(IxDyn(dataset.shape()), binary)
-            .expect("shape is unchanged and valid");
-        let dataset = Dataset::new(output, dataset.metadata.clone())?;
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let planes = ThresholdPlanes::new(dataset);
+        let thresholds = planes.thresholds(dataset, params, |plane| {
+            let values = plane.data.iter().copied().collect::<Vec<_>>();
+            Ok(otsu_threshold(&values))
+        })?;
+        let mut output = dataset.data.clone();
+        for (index, threshold) in thresholds.iter().enumerate() {
+            planes
+                .view_mut(&mut output, index)
+                .par_mapv_inplace(|value| if value >= *threshold { 1.0 } else { 0.0 });
+        }
+        let output_dataset = Dataset::new(output, dataset.metadata.clone())?;
         let mut measurements = MeasurementTable::default();
+        if let Some(threshold) = uniform_threshold(&thresholds) {
+            measurements
+                .values
+                .insert("threshold".to_string(), json!(threshold));
+        }
+        let rows = thresholds
+            .iter()
+            .enumerate()
+            .map(|(index, threshold)| planes.row(dataset, index, [("threshold", *threshold)]))
+            .collect::<Vec<_>>();
         measurements
             .values
-            .insert("threshold".to_string(), json!(threshold));
+            .insert("slice_thresholds".to_string(), json!(rows));
         Ok(OpOutput {
-            dataset,
+            dataset: output_dataset,
             measurements: Some(measurements),
         })
     }
@@ -124,42 +137,562 @@ impl Operation for ThresholdMakeBinaryOp {
                     required: false,
                     kind: "float".to_string(),
                 },
+            ]
+            .into_iter()
+            .chain(scope_params())
+            .collect(),
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        let planes = ThresholdPlanes::new(dataset);
+        // A reference plane only decides the threshold and polarity; the open
+        // bound must cover the whole stack or brighter/darker slices drop out.
+        let bounds = match ThresholdScope::parse(params)? {
+            ThresholdScope::Reference(_) => Some(min_max(&finite_values(dataset))),
+            ThresholdScope::Stack | ThresholdScope::Slice => None,
+        };
+        let ranges = planes.thresholds(dataset, params, |plane| {
+            binary_threshold_range(plane, params, bounds)
+        })?;
+        let mut mask = dataset.data.clone();
+        for (index, (lower, upper, _)) in ranges.iter().enumerate() {
+            planes.view_mut(&mut mask, index).par_mapv_inplace(|value| {
+                if value.is_finite() && value >= *lower && value <= *upper {
+                    255.0
+                } else {
+                    0.0
+                }
+            });
+        }
+
+        let mut metadata = dataset.metadata.clone();
+        metadata.pixel_type = PixelType::U8;
+        let mut measurements = MeasurementTable::default();
+        if let Some((lower, upper, threshold)) = uniform_threshold(&ranges) {
+            metadata
+                .extras
+                .insert("threshold_min".to_string(), json!(lower));
+            metadata
+                .extras
+                .insert("threshold_max".to_string(), json!(upper));
+            measurements
+                .values
+                .insert("threshold".to_string(), json!(threshold));
+            measurements
+                .values
+                .insert("threshold_min".to_string(), json!(lower));
+            measurements
+                .values
+                .insert("threshold_max".to_string(), json!(upper));
+        }
+        let rows = ranges
+            .iter()
+            .enumerate()
+            .map(|(index, (lower, upper, threshold))| {
+                planes.row(
+                    dataset,
+                    index,
+                    [
+                        ("threshold", *threshold),
+                        ("threshold_min", *lower),
+                        ("threshold_max", *upper),
+                    ],
+                )
+            })
+            .collect::<Vec<_>>();
+        measurements
+            .values
+            .insert("slice_thresholds".to_string(), json!(rows));
+        let dataset = Dataset::new(mask, metadata)?;
+        Ok(OpOutput {
+            dataset,
+            measurements: Some(measurements),
+        })
+    }
+}
+
+/// Which values an auto-threshold is computed from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ThresholdScope {
+    /// One threshold from the histogram of the whole stack.
+    Stack,
+    /// A separate threshold for every X/Y plane.
+    Slice,
+    /// One threshold from the given plane, applied to the whole stack.
+    Reference(usize),
+}
+
+impl ThresholdScope {
+    fn parse(params: &Value) -> Result<Self> {
+        match params
+            .get("scope")
+            .and_then(Value::as_str)
+            .map(threshold_method_key)
+            .as_deref()
+        {
+            None | Some("stack") => Ok(Self::Stack),
+            Some("slice" | "perslice") => Ok(Self::Slice),
+            Some("reference") => Ok(Self::Reference(get_optional_usize(
+                params,
+                "reference_slice",
+                0,
+            ))),
+            Some(other) => Err(OpsError::InvalidParams(format!(
+                "unsupported threshold scope `{other}`; expected stack, slice, or reference"
+            ))),
+        }
+    }
+}
+
+fn scope_params() -> Vec<ParamSpec> {
+    vec![
+        ParamSpec {
+            name: "scope".to_string(),
+            description:
+                "Histogram used for the threshold: stack (default), slice for one threshold per X/Y plane, or reference."
+                    .to_string(),
+            required: false,
+            kind: "string".to_string(),
+        },
+        ParamSpec {
+            name: "reference_slice".to_string(),
+            description:
+                "Plane used when scope is reference, counted over the non-X/Y axes in axis order."
+                    .to_string(),
+            required: false,
+            kind: "int".to_string(),
+        },
+    ]
+}
+
+/// The X/Y planes of a dataset, enumerated over the remaining axes in axis order.
+/// Datasets without both X and Y axes are treated as a single plane.
+struct ThresholdPlanes {
+    outer_axes: Vec<usize>,
+    coords: Vec<Vec<usize>>,
+}
+
+impl ThresholdPlanes {
+    fn new(dataset: &DatasetF32) -> Self {
+        let (Some(x_axis), Some(y_axis)) = (
+            dataset.axis_index(AxisKind::X),
+            dataset.axis_index(AxisKind::Y),
+        ) else {
+            return Self {
+                outer_axes: Vec::new(),
+                coords: vec![Vec::new()],
+            };
+        };
+        let outer_axes = (0..dataset.ndim())
+            .filter(|axis| *axis != x_axis && *axis != y_axis)
+            .collect::<Vec<_>>();
+        let outer_shape = outer_axes
+            .iter()
+            .map(|axis| dataset.shape()[*axis])
+            .collect::<Vec<_>>();
+        let mut coords = Vec::new();
+        iterate_indices(&outer_shape, |coord| coords.push(coord.to_vec()));
+        Self { outer_axes, coords }
+    }
+
+    /// Computes one threshold per plane according to the `scope` parameter.
+    fn thresholds<T: Clone>(
+        &self,
+        dataset: &DatasetF32,
+        params: &Value,
+        compute: impl Fn(&DatasetF32) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        match ThresholdScope::parse(params)? {
+            ThresholdScope::Stack => Ok(vec![compute(dataset)?; self.coords.len()]),
+            ThresholdScope::Reference(index) => {
+                if index >= self.coords.len() {
+                    return Err(OpsError::InvalidParams(format!(
+                        "`reference_slice` {index} is out of range for {} slice(s)",
+                        self.coords.len()
+                    )));
+                }
+                Ok(vec![
+                    compute(&self.plane(dataset, index)?)?;
+                    self.coords.len()
+                ])
+            }
+            ThresholdScope::Slice => (0..self.coords.len())
+                .map(|index| compute(&self.plane(dataset, index)?))
+                .collect(),
+        }
+    }
+
+    fn plane(&self, dataset: &DatasetF32, index: usize) -> Result<DatasetF32> {
+        let mut view = dataset.data.view();
+        for (axis, position) in self.outer_axes.iter().zip(&self.coords[index]).rev() {
+            view = view.index_axis_move(Axis(*axis), *position);
+        }
+        let metadata = Metadata {
+            dims: (0..dataset.ndim())
+                .filter(|axis| !self.outer_axes.contains(axis))
+                .map(|axis| dataset.metadata.dims[axis].clone())
+                .collect(),
+            pixel_type: dataset.metadata.pixel_type,
+            ..Metadata::default()
+        };
+        Ok(Dataset::new(view.to_owned(), metadata)?)
+    }
+
+    fn view_mut<'a>(&self, data: &'a mut ArrayD<f32>, index: usize) -> ArrayViewMutD<'a, f32> {
+        let mut view = data.view_mut();
+        for (axis, position) in self.outer_axes.iter().zip(&self.coords[index]).rev() {
+            view = view.index_axis_move(Axis(*axis), *position);
+        }
+        view
+    }
+
+    fn row<const N: usize>(
+        &self,
+        dataset: &DatasetF32,
+        index: usize,
+        values: [(&str, f32); N],
+    ) -> Value {
+        let mut row = Map::new();
+        row.insert("slice".to_string(), json!(index));
+        for (axis, position) in self.outer_axes.iter().zip(&self.coords[index]) {
+            let code = dataset.metadata.dims[*axis]
+                .axis
+                .code()
+                .to_ascii_lowercase();
+            row.insert(code.to_string(), json!(position));
+        }
+        for (key, value) in values {
+            row.insert(key.to_string(), json!(value));
+        }
+        Value::Object(row)
+    }
+}
+
+/// The shared threshold when every plane uses the same one.
+fn uniform_threshold<T: Copy + PartialEq>(thresholds: &[T]) -> Option<T> {
+    let first = *thresholds.first()?;
+    thresholds
+        .iter()
+        .all(|threshold| *threshold == first)
+        .then_some(first)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ThresholdLocalOp;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LocalMethod {
+    Bernsen { contrast: f64 },
+    Contrast,
+    Mean { offset: f64 },
+    Median { offset: f64 },
+    MidGrey { offset: f64 },
+    Niblack { k: f64, offset: f64 },
+    Otsu,
+    Phansalkar { k: f64, r: f64 },
+    Sauvola { k: f64, r: f64 },
+}
+
+impl LocalMethod {
+    /// Parses the method with the ImageJ Auto Local Threshold defaults for
+    /// `parameter_1` and `parameter_2`.
+    fn parse(method: &str, parameter_1: Option<f64>, parameter_2: Option<f64>) -> Result<Self> {
+        let first = |default: f64| parameter_1.unwrap_or(default);
+        let second = |default: f64| parameter_2.unwrap_or(default);
+        Ok(match threshold_method_key(method).as_str() {
+            "bernsen" => Self::Bernsen {
+                contrast: first(15.0),
+            },
+            "contrast" => Self::Contrast,
+            "mean" => Self::Mean { offset: first(0.0) },
+            "median" => Self::Median { offset: first(0.0) },
+            "midgrey" | "midgray" => Self::MidGrey { offset: first(0.0) },
+            "niblack" => Self::Niblack {
+                k: first(0.2),
+                offset: second(0.0),
+            },
+            "otsu" => Self::Otsu,
+            "phansalkar" => Self::Phansalkar {
+                k: first(0.25),
+                r: second(0.5),
+            },
+            "sauvola" => Self::Sauvola {
+                k: first(0.5),
+                r: second(128.0),
+            },
+            _ => {
+                return Err(OpsError::InvalidParams(format!(
+                    "unsupported local threshold method `{method}`"
+                )));
+            }
+        })
+    }
+
+    /// Whether `value` is foreground given the 256-bin histogram of its neighbourhood.
+    fn is_foreground(self, value: u8, histogram: &[u64; 256]) -> bool {
+        let value = f64::from(value);
+        let Some(min) = first_nonzero_bin(histogram) else {
+            return false;
+        };
+        let max = last_nonzero_bin(histogram).unwrap_or(min);
+        let (min, max) = (min as f64, max as f64);
+        let total = histogram_total(histogram) as f64;
+        let mean = histogram_partial_weighted_sum(histogram, 255) / total;
+        let std_dev = || {
+            (histogram_partial_square_sum(histogram, 255) / total - mean * mean)
+                .max(0.0)
+                .sqrt()
+        };
+        match self {
+            Self::Bernsen { contrast } => {
+                let mid = ((min + max) / 2.0).floor();
+                if max - min < contrast {
+                    mid >= 128.0
+                } else {
+                    value >= mid
+                }
+            }
+            Self::Contrast => (max - value).abs() <= (value - min).abs(),
+            Self::Mean { offset } => value > mean - offset,
+            Self::Median { offset } => {
+                let median = percentile_bin(histogram, 0.5).unwrap_or(0) as f64;
+                value > median - offset
+            }
+            Self::MidGrey { offset } => value > (min + max) / 2.0 - offset,
+            Self::Niblack { k, offset } => value > mean + k * std_dev() - offset,
+            Self::Otsu => value > otsu_bin_slice(histogram).unwrap_or(0) as f64,
+            Self::Phansalkar { k, r } => {
+                let (value, mean) = (value / 255.0, mean / 255.0);
+                let std_dev = std_dev() / 255.0;
+                value > mean * (1.0 + 2.0 * (-10.0 * mean).exp() + k * (std_dev / r - 1.0))
+            }
+            Self::Sauvola { k, r } => value > mean * (1.0 + k * (std_dev() / r - 1.0)),
+        }
+    }
+}
+
+impl Operation for ThresholdLocalOp {
+    fn name(&self) -> &'static str {
+        "threshold.local"
+    }
+
+    fn schema(&self) -> OpSchema {
+        OpSchema {
+            name: self.name().to_string(),
+            description: "Binary mask from a threshold computed in each pixel's neighbourhood."
+                .to_string(),
+            params: vec![
+                ParamSpec {
+                    name: "method".to_string(),
+                    description:
+                        "Local method: bernsen, contrast, mean, median, mid_grey, niblack, otsu, phansalkar, or sauvola."
+                            .to_string(),
+                    required: true,
+                    kind: "string".to_string(),
+                },
+                ParamSpec {
+                    name: "radius".to_string(),
+                    description: "Neighbourhood radius in pixels (default 15).".to_string(),
+                    required: false,
+                    kind: "int".to_string(),
+                },
+                ParamSpec {
+                    name: "parameter_1".to_string(),
+                    description:
+                        "Bernsen contrast, mean/median/mid_grey offset, or Niblack/Phansalkar/Sauvola k."
+                            .to_string(),
+                    required: false,
+                    kind: "float".to_string(),
+                },
+                ParamSpec {
+                    name: "parameter_2".to_string(),
+                    description: "Niblack offset, or Phansalkar/Sauvola r.".to_string(),
+                    required: false,
+                    kind: "float".to_string(),
+                },
+                ParamSpec {
+                    name: "background".to_string(),
+                    description: "Foreground polarity: dark (default) or light.".to_string(),
+                    required: false,
+                    kind: "string".to_string(),
+                },
+                ParamSpec {
+                    name: "stack_3d".to_string(),
+                    description: "Use a spherical neighbourhood across Z instead of per-slice disks; requires a Z axis."
+                        .to_string(),
+                    required: false,
+                    kind: "bool".to_string(),
+                },
             ],
         }
     }
 
     fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
-        let (lower, upper, threshold) = binary_threshold_range(dataset, params)?;
-        let mut values = dataset.data.iter().copied().collect::<Vec<_>>();
-        values.par_iter_mut().for_each(|value| {
-            *value = if value.is_finite() && *value >= lower && *value <= upper {
-                255.0
+        let method_name = params
+            .get("method")
+            .and_then(Value::as_str)
+            .ok_or_else(|| OpsError::InvalidParams("`method` is required".to_string()))?;
+        let method = LocalMethod::parse(
+            method_name,
+            params.get("parameter_1").and_then(Value::as_f64),
+            params.get("parameter_2").and_then(Value::as_f64),
+        )?;
+        let radius = get_optional_usize(params, "radius", 15);
+        if radius == 0 {
+            return Err(OpsError::InvalidParams(
+                "`radius` must be at least 1".to_string(),
+            ));
+        }
+        let light_background = match params
+            .get("background")
+            .and_then(Value::as_str)
+            .unwrap_or("dark")
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "dark" | "default" => false,
+            "light" => true,
+            other => {
+                return Err(OpsError::InvalidParams(format!(
+                    "unsupported threshold background `{other}`"
+                )));
+            }
+        };
+        let x_axis = axis_index(dataset, AxisKind::X)?;
+        let y_axis = axis_index(dataset, AxisKind::Y)?;
+        let z_axis = if get_optional_bool(params, "stack_3d", false) {
+            Some(axis_index(dataset, AxisKind::Z)?)
+        } else {
+            None
+        };
+
+        // Local methods follow ImageJ and work on 8-bit grey levels.
+        let values = finite_values(dataset);
+        let (min, max) = min_max(&values);
+        let span = (max - min).max(f32::EPSILON);
+        let to_level = |value: f32| -> Option<u8> {
+            if !value.is_finite() {
+                return None;
+            }
+            let level = if dataset.metadata.pixel_type == PixelType::U8 {
+                value.round().clamp(0.0, 255.0)
             } else {
-                0.0
-            };
+                (((value - min) / span).clamp(0.0, 1.0) * 255.0).round()
+            } as u8;
+            Some(if light_background { 255 - level } else { level })
+        };
+
+        let shape = dataset.shape().to_vec();
+        let width = shape[x_axis];
+        let height = shape[y_axis];
+        let depth = z_axis.map(|axis| shape[axis]).unwrap_or(1);
+        let kernel_lines = neighbourhood_lines(radius, z_axis.is_some());
+        let volume_axes = [Some(x_axis), Some(y_axis), z_axis];
+        let outer_axes = (0..shape.len())
+            .filter(|axis| !volume_axes.contains(&Some(*axis)))
+            .collect::<Vec<_>>();
+        let outer_shape = outer_axes
+            .iter()
+            .map(|axis| shape[*axis])
+            .collect::<Vec<_>>();
+        let mut output = ArrayD::<f32>::zeros(IxDyn(&shape));
+
+        iterate_indices(&outer_shape, |outer_coord| {
+            let mut coord = vec![0_usize; shape.len()];
+            for (axis, index) in outer_axes.iter().zip(outer_coord) {
+                coord[*axis] = *index;
+            }
+            let mut levels = Vec::with_capacity(width * height * depth);
+            for z in 0..depth {
+                if let Some(z_axis) = z_axis {
+                    coord[z_axis] = z;
+                }
+                for y in 0..height {
+                    coord[y_axis] = y;
+                    for x in 0..width {
+                        coord[x_axis] = x;
+                        levels.push(to_level(dataset.data[IxDyn(&coord)]));
+                    }
+                }
+            }
+
+            // Each row keeps a running histogram that slides along X: moving one pixel drops
+            // the column leaving every kernel line and adds the column entering it, as in
+            // ImageJ's RankFilters.
+            let mut foreground = vec![false; levels.len()];
+            foreground
+                .par_chunks_mut(width.max(1))
+                .enumerate()
+                .for_each(|(row, foreground)| {
+                    let (y, z) = (row % height, row / height);
+                    // Out-of-image neighbours repeat the nearest edge pixel, as in RankFilters.
+                    let clamp = |position: usize, offset: isize, size: usize| {
+                        (position as isize + offset).clamp(0, size as isize - 1) as usize
+                    };
+                    let lines = kernel_lines
+                        .iter()
+                        .map(|(dy, dz, half_width)| {
+                            let start =
+                                width * (clamp(y, *dy, height) + height * clamp(z, *dz, depth));
+                            (start, *half_width)
+                        })
+                        .collect::<Vec<_>>();
+                    let mut histogram = [0_u64; 256];
+                    let update = |histogram: &mut [u64; 256], index: usize, add: bool| {
+                        if let Some(level) = levels[index] {
+                            let bin = &mut histogram[usize::from(level)];
+                            if add {
+                                *bin += 1;
+                            } else {
+                                *bin -= 1;
+                            }
+                        }
+                    };
+                    for (start, half_width) in &lines {
+                        for dx in -half_width..=*half_width {
+                            update(&mut histogram, start + clamp(0, dx, width), true);
+                        }
+                    }
+                    for x in 0..width {
+                        if x > 0 {
+                            for (start, half_width) in &lines {
+                                update(
+                                    &mut histogram,
+                                    start + clamp(x - 1, -half_width, width),
+                                    false,
+                                );
+                                update(&mut histogram, start + clamp(x, *half_width, width), true);
+                            }
+                        }
+                        foreground[x] = levels[row * width + x]
+                            .is_some_and(|value| method.is_foreground(value, &histogram));
+                    }
+                });
+
+            for (index, is_foreground) in foreground.into_iter().enumerate() {
+                if !is_foreground {
+                    continue;
+                }
+                coord[x_axis] = index % width;
+                coord[y_axis] = (index / width) % height;
+                if let Some(z_axis) = z_axis {
+                    coord[z_axis] = index / (width * height);
+                }
+                output[IxDyn(&coord)] = 255.0;
+            }
         });
 
-        let mask = Array::from_shape_vec(IxDyn(dataset.shape()), values)
-            .expect("shape is unchanged and valid");
         let mut metadata = dataset.metadata.clone();
         metadata.pixel_type = PixelType::U8;
-        metadata
-            .extras
-            .insert("threshold_min".to_string(), json!(lower));
-        metadata
-            .extras
-            .insert("threshold_max".to_string(), json!(upper));
-        let dataset = Dataset::new(mask, metadata)?;
+        let dataset = Dataset::new(output, metadata)?;
         let mut measurements = MeasurementTable::default();
         measurements
             .values
-            .insert("threshold".to_string(), json!(threshold));
-        measurements
-            .values
-            .insert("threshold_min".to_string(), json!(lower));
+            .insert("method".to_string(), json!(method_name));
         measurements
             .values
-            .insert("threshold_max".to_string(), json!(upper));
+            .insert("radius".to_string(), json!(radius));
         Ok(OpOutput {
             dataset,
             measurements: Some(measurements),
@@ -167,6 +700,30 @@ impl Operation for ThresholdMakeBinaryOp {
     }
 }
 
+/// Kernel lines of a disk, or a ball when `volumetric`, using ImageJ's `r * r + 1` extent.
+///
+/// Each entry is `(dy, dz, half_width)`: the line covers `dx` in `-half_width..=half_width`.
+fn neighbourhood_lines(radius: usize, volumetric: bool) -> Vec<(isize, isize, isize)> {
+    let radius = radius as isize;
+    let limit = radius * radius + 1;
+    let z_radius = if volumetric { radius } else { 0 };
+    let mut lines = Vec::new();
+    for dz in -z_radius..=z_radius {
+        for dy in -radius..=radius {
+            let remaining = limit - dy * dy - dz * dz;
+            if remaining < 0 {
+                continue;
+            }
+            let half_width = (0..=radius)
+                .take_while(|dx| dx * dx <= remaining)
+                .last()
+                .unwrap_or(0);
+            lines.push((dy, dz, half_width));
+        }
+    }
+    lines
+}
+
 fn otsu_threshold(values: &[f32]) -> f32 {
     let mut histogram = [0_u64; 256];
     let (min, max) = min_max(values);
@@ -213,7 +770,13 @@ fn otsu_threshold(values: &[f32]) -> f32 {
     (best_threshold as f32 / 255.0) * span + min
 }
 
-fn binary_threshold_range(dataset: &DatasetF32, params: &Value) -> Result<(f32, f32, f32)> {
+/// `(lower, upper, threshold)` for a binary mask. The bound away from the
+/// threshold is `bounds` when given, otherwise the dataset's own min/max.
+fn binary_threshold_range(
+    dataset: &DatasetF32,
+    params: &Value,
+    bounds: Option<(f32, f32)>,
+) -> Result<(f32, f32, f32)> {
     if let (Some(lower), Some(upper)) = (
         params.get("min").and_then(Value::as_f64),
         params.get("max").and_then(Value::as_f64),
@@ -296,7 +859,7 @@ fn binary_threshold_range(dataset: &DatasetF32, params: &Value) -> Result<(f32,
         .unwrap_or("default")
         .to_ascii_lowercase();
     let values = finite_values(dataset);
-    let (min, max) = min_max(&values);
+    let (min, max) = bounds.unwrap_or_else(|| min_max(&values));
     let mode = histogram_mode_value(&values);
     let foreground_is_high = match background.as_str() {
         "dark" => true,
@@ -1477,3 +2040,32 @@ fn threshold_bin_to_value(dataset: &DatasetF32, bin: usize) -> f32 {
     let (min, max) = min_max(&values);
     min + (bin.min(255) as f32 / 255.0) * (max - min).max(f32::EPSILON)
 }
+
+fn axis_index(dataset: &DatasetF32, axis: AxisKind) -> Result<usize> {
+    dataset
+        .axis_index(axis)
+        .ok_or_else(|| OpsError::UnsupportedLayout(format!("dataset has no {axis:?} axis")))
+}
+
+fn iterate_indices(shape: &[usize], mut callback: impl FnMut(&[usize])) {
+    if shape.is_empty() {
+        callback(&[]);
+        return;
+    }
+    let mut index = vec![0usize; shape.len()];
+    loop {
+        callback(&index);
+        let mut dim = shape.len();
+        while dim > 0 {
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+            if dim == 0 {
+                return;
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::model::{AxisKind, Dataset, DatasetF32, Dim, Metadata, PixelType};
+use crate::model::{AxisKind, Dataset, DatasetF32, DatasetKind, Dim, Metadata, PixelType};
 use ndarray::{ArrayD, IxDyn};
 use rustfft::{FftPlanner, num_complex::Complex};
 use serde_json::{Value, json};
@@ -241,6 +241,10 @@ impl Operation for ImageResizeOp {
         "image.resize"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -327,13 +331,15 @@ impl Operation for ImageResizeOp {
             .get("average_when_downsizing")
             .or_else(|| params.get("average"))
             .and_then(Value::as_bool)
-            .unwrap_or(true);
+            .unwrap_or(true)
+            && !dataset.is_label();
         let interpolation = params
             .get("interpolation")
             .and_then(Value::as_str)
             .map(ResizeInterpolation::parse)
             .transpose()?
-            .unwrap_or(ResizeInterpolation::Bilinear);
+            .unwrap_or(ResizeInterpolation::Bilinear)
+            .for_dataset(dataset);
         if let Some(fill) = params.get("fill").and_then(Value::as_f64)
             && !fill.is_finite()
         {
@@ -375,6 +381,10 @@ impl Operation for ImageScaleOp {
         "image.scale"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -412,7 +422,8 @@ impl Operation for ImageScaleOp {
         let height = ((dataset.shape()[y_axis] as f32) * y_scale)
             .round()
             .max(1.0) as usize;
-        let output = resize_xy(dataset, width, height, ResizeInterpolation::Bilinear, false)?;
+        let interpolation = ResizeInterpolation::Bilinear.for_dataset(dataset);
+        let output = resize_xy(dataset, width, height, interpolation, false)?;
         Ok(OpOutput::dataset_only(output))
     }
 }
@@ -422,6 +433,10 @@ impl Operation for ImageCanvasResizeOp {
         "image.canvas_resize"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -483,6 +498,10 @@ impl Operation for ImageCropOp {
         "image.crop"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -697,6 +716,10 @@ impl Operation for ImageSetScaleOp {
         "image.set_scale"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -752,6 +775,10 @@ impl Operation for ImageCalibrateOp {
         "image.calibrate"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -797,6 +824,10 @@ impl Operation for ImageStackAddSliceOp {
         "image.stack.add_slice"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -834,6 +865,10 @@ impl Operation for ImageStackDeleteSliceOp {
         "image.stack.delete_slice"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -858,6 +893,10 @@ impl Operation for ImageStackSubstackOp {
         "image.stack.substack"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -892,6 +931,10 @@ impl Operation for ImageStackResliceOp {
         "image.stack.reslice"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -915,6 +958,10 @@ impl Operation for ImageStackReduceOp {
         "image.stack.reduce"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -977,6 +1024,10 @@ impl Operation for ImageStackMontageOp {
         "image.stack.montage"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -1078,6 +1129,10 @@ impl Operation for ImageStackMontageToStackOp {
         "image.stack.montage_to_stack"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -1115,6 +1170,10 @@ impl Operation for ImageStackToHyperstackOp {
         "image.stack.to_hyperstack"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -1160,6 +1219,10 @@ impl Operation for ImageHyperstackToStackOp {
         "image.hyperstack.to_stack"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -1179,6 +1242,10 @@ impl Operation for ImageHyperstackReduceDimensionalityOp {
         "image.hyperstack.reduce_dimensionality"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -1241,6 +1308,10 @@ impl Operation for ImageHyperstackSubsetOp {
         "image.hyperstack.subset"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -1332,6 +1403,10 @@ impl Operation for ImageFlipOp {
         "image.flip"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -1368,6 +1443,10 @@ impl Operation for ImageRotate90Op {
         "image.rotate_90"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -1405,6 +1484,10 @@ impl Operation for ImageRotateOp {
         "image.rotate"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -1451,7 +1534,8 @@ impl Operation for ImageRotateOp {
             .and_then(Value::as_str)
             .map(TranslateInterpolation::parse)
             .transpose()?
-            .unwrap_or(TranslateInterpolation::Bilinear);
+            .unwrap_or(TranslateInterpolation::Bilinear)
+            .for_dataset(dataset);
         let enlarge = params
             .get("enlarge")
             .and_then(Value::as_bool)
@@ -1471,6 +1555,10 @@ impl Operation for ImageTranslateOp {
         "image.translate"
     }
 
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
     fn schema(&self) -> OpSchema {
         OpSchema {
             name: self.name().to_string(),
@@ -1515,7 +1603,8 @@ impl Operation for ImageTranslateOp {
             .and_then(Value::as_str)
             .map(TranslateInterpolation::parse)
             .transpose()?
-            .unwrap_or(TranslateInterpolation::Nearest);
+            .unwrap_or(TranslateInterpolation::Nearest)
+            .for_dataset(dataset);
         Ok(OpOutput::dataset_only(translate_xy(
             dataset,
             x_offset,
@@ -2777,6 +2866,15 @@ impl ResizeInterpolation {
             ))),
         }
     }
+
+    /// Label images only ever use nearest-neighbour sampling so ids are never blended.
+    pub(super) fn for_dataset(self, dataset: &DatasetF32) -> Self {
+        if dataset.is_label() {
+            Self::Nearest
+        } else {
+            self
+        }
+    }
 }
 
 fn resize_xy(
@@ -3896,6 +3994,9 @@ fn stack_reslice(dataset: &DatasetF32, params: &Value) -> Result<DatasetF32> {
         channel_names: dataset.metadata.channel_names.clone(),
         source: dataset.metadata.source.clone(),
         extras: dataset.metadata.extras.clone(),
+        display: dataset.metadata.display.clone(),
+        history: dataset.metadata.history.clone(),
+        kind: dataset.metadata.kind,
     };
     metadata
         .extras
@@ -4862,13 +4963,13 @@ fn ceil_dimension(value: f32) -> usize {
 }
 
 #[derive(Debug, Clone, Copy)]
-enum TranslateInterpolation {
+pub(super) enum TranslateInterpolation {
     Nearest,
     Bilinear,
 }
 
 impl TranslateInterpolation {
-    fn parse(value: &str) -> Result<Self> {
+    pub(super) fn parse(value: &str) -> Result<Self> {
         match value {
             "nearest" | "none" => Ok(Self::Nearest),
             "bilinear" => Ok(Self::Bilinear),
@@ -4877,9 +4978,17 @@ impl TranslateInterpolation {
             ))),
         }
     }
+
+    pub(super) fn for_dataset(self, dataset: &DatasetF32) -> Self {
+        if dataset.is_label() {
+            Self::Nearest
+        } else {
+            self
+        }
+    }
 }
 
-fn translate_xy(
+pub(super) fn translate_xy(
     dataset: &DatasetF32,
     x_offset: f32,
     y_offset: f32,
@@ -6266,7 +6375,7 @@ fn cubic_interpolate(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
     ((a0 * t + a1) * t + a2) * t + p1
 }
 
-fn bilinear_sample_or_fill(
+pub(super) fn bilinear_sample_or_fill(
     dataset: &DatasetF32,
     coord: &[usize],
     x_axis: usize,
@@ -6291,7 +6400,7 @@ fn bilinear_sample_or_fill(
     top * (1.0 - fy) + bottom * fy
 }
 
-fn sample_or_fill(
+pub(super) fn sample_or_fill(
     dataset: &DatasetF32,
     coord: &[usize],
     x_axis: usize,
@@ -6439,6 +6548,9 @@ fn surface_plot_xy(dataset: &DatasetF32, params: &Value) -> Result<DatasetF32> {
         channel_names: Vec::new(),
         source: dataset.metadata.source.clone(),
         extras: dataset.metadata.extras.clone(),
+        display: None,
+        history: dataset.metadata.history.clone(),
+        kind: DatasetKind::Intensity,
     };
     metadata.extras.insert(
         "surface_plot_source_shape".to_string(),
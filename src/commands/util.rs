@@ -32,6 +32,41 @@ pub(crate) fn gaussian_kernel(sigma: f32) -> Vec<f32> {
     kernel
 }
 
+/// Correlation kernel of the `order`-th (0, 1 or 2) Gaussian derivative, on
+/// the same support as [`gaussian_kernel`]. The first derivative responds
+/// with 1 to a unit ramp and the second with 1 to `x²/2`.
+pub(crate) fn gaussian_derivative_kernel(sigma: f32, order: usize) -> Vec<f32> {
+    let kernel = gaussian_kernel(sigma);
+    let radius = (kernel.len() / 2) as f32;
+    let offsets = (0..kernel.len()).map(|index| index as f32 - radius);
+    match order {
+        0 => kernel,
+        1 => {
+            let raw = offsets.zip(&kernel).map(|(x, g)| x * g).collect::<Vec<_>>();
+            let moment = raw
+                .iter()
+                .enumerate()
+                .map(|(index, value)| (index as f32 - radius) * value)
+                .sum::<f32>();
+            raw.into_iter().map(|value| value / moment).collect()
+        }
+        _ => {
+            let raw = offsets
+                .zip(&kernel)
+                .map(|(x, g)| (x * x - sigma * sigma) * g)
+                .collect::<Vec<_>>();
+            let mean = raw.iter().sum::<f32>() / raw.len() as f32;
+            let centred = raw.iter().map(|value| value - mean).collect::<Vec<_>>();
+            let moment = centred
+                .iter()
+                .enumerate()
+                .map(|(index, value)| (index as f32 - radius).powi(2) / 2.0 * value)
+                .sum::<f32>();
+            centred.into_iter().map(|value| value / moment).collect()
+        }
+    }
+}
+
 pub(crate) fn neighborhood_offsets(
     rank: usize,
     radius: usize,
@@ -0,0 +1,402 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::model::{AxisKind, Dataset, DatasetF32, DatasetKind, Metadata, PixelType};
+use ndarray::{ArrayD, IxDyn};
+use serde_json::{Value, json};
+
+use super::calculator::broadcast;
+use super::extrema::{
+    Priority, VolumeShape, connectivity_offsets, h_minima_transform, regional_minima,
+};
+use super::gaussian::row_major_strides;
+use super::{
+    INPUT_PARAM_KIND, MeasurementTable, OpInputs, OpOutput, OpSchema, Operation, OpsError,
+    ParamSpec, Result, get_optional_bool, get_optional_f32, get_optional_usize,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct MorphologyMarkerWatershedOp;
+
+/// Watershed-line value in the working label buffer.
+const DAM: i64 = -1;
+/// Pixels outside the mask or with non-finite values.
+const EXCLUDED: i64 = -2;
+
+#[derive(Debug, Clone)]
+enum MarkerSource {
+    /// A separate seed image, read through `indices` (one per pixel of the dataset).
+    Input {
+        values: Vec<f32>,
+        indices: Vec<usize>,
+    },
+    Channel(usize),
+    Minima,
+}
+
+impl Operation for MorphologyMarkerWatershedOp {
+    fn name(&self) -> &'static str {
+        "morphology.marker_watershed"
+    }
+
+    fn keeps_label_kind(&self) -> bool {
+        true
+    }
+
+    fn schema(&self) -> OpSchema {
+        let param = |name: &str, description: &str, kind: &str| ParamSpec {
+            name: name.to_string(),
+            description: description.to_string(),
+            required: false,
+            kind: kind.to_string(),
+        };
+        OpSchema {
+            name: self.name().to_string(),
+            description:
+                "Marker-controlled watershed: flood a gradient or intensity image from seed labels."
+                    .to_string(),
+            params: vec![
+                param(
+                    "channel",
+                    "Channel holding the image to flood (default 0).",
+                    "int",
+                ),
+                param(
+                    "markers",
+                    "Seed label image; takes precedence over `marker_channel`.",
+                    INPUT_PARAM_KIND,
+                ),
+                param(
+                    "marker_channel",
+                    "Channel holding seed labels when no `markers` image is given; when neither is set, seeds are the regional minima of the image.",
+                    "int",
+                ),
+                param(
+                    "h",
+                    "Minimum depth of minima used as seeds (h-minima); 0 keeps all regional minima.",
+                    "float",
+                ),
+                param(
+                    "mask_channel",
+                    "Channel restricting the flood to pixels with value > 0.5.",
+                    "int",
+                ),
+                param(
+                    "dams",
+                    "Keep one-pixel watershed lines (label 0) between basins.",
+                    "bool",
+                ),
+                param(
+                    "connectivity",
+                    "4 (default) or 8 in 2D; 6 (default), 18 or 26 in 3D.",
+                    "int",
+                ),
+                param(
+                    "stack_3d",
+                    "Flood X/Y/Z volumes instead of single slices when a Z axis exists (default true).",
+                    "bool",
+                ),
+            ],
+        }
+    }
+
+    fn execute(&self, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(dataset, &OpInputs::new(), params)
+    }
+
+    fn execute_with_inputs(
+        &self,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
+        let x_axis = axis_index(dataset, AxisKind::X)?;
+        let y_axis = axis_index(dataset, AxisKind::Y)?;
+        let z_axis = get_optional_bool(params, "stack_3d", true)
+            .then(|| dataset.axis_index(AxisKind::Z))
+            .flatten();
+        let channel_axis = dataset.axis_index(AxisKind::Channel);
+        let channel_count = dataset.metadata.channel_count();
+        let channel = get_optional_usize(params, "channel", 0);
+        let optional_channel = |key: &str| {
+            params
+                .get(key)
+                .and_then(Value::as_u64)
+                .map(|value| value as usize)
+        };
+        let marker_channel = optional_channel("marker_channel");
+        let mask_channel = optional_channel("mask_channel");
+        for (key, value) in [
+            ("channel", Some(channel)),
+            ("marker_channel", marker_channel),
+            ("mask_channel", mask_channel),
+        ] {
+            if let Some(value) = value
+                && value >= channel_count
+            {
+                return Err(OpsError::InvalidParams(format!(
+                    "`{key}` {value} is out of range for {channel_count} channel(s)"
+                )));
+            }
+        }
+        let markers = match inputs.get("markers") {
+            Some(markers) => MarkerSource::Input {
+                indices: broadcast(dataset, markers, "markers")?,
+                values: markers.data.iter().copied().collect(),
+            },
+            None => marker_channel
+                .map(MarkerSource::Channel)
+                .unwrap_or(MarkerSource::Minima),
+        };
+        let h = get_optional_f32(params, "h", 0.0);
+        if !h.is_finite() || h < 0.0 {
+            return Err(OpsError::InvalidParams(
+                "`h` must be finite and >= 0".to_string(),
+            ));
+        }
+        let dams = get_optional_bool(params, "dams", false);
+        let offsets = connectivity_offsets(
+            get_optional_usize(params, "connectivity", if z_axis.is_some() { 6 } else { 4 }),
+            z_axis.is_some(),
+        )?;
+
+        let shape = dataset.shape().to_vec();
+        let volume = VolumeShape {
+            width: shape[x_axis],
+            height: shape[y_axis],
+            depth: z_axis.map(|axis| shape[axis]).unwrap_or(1),
+        };
+        let volume_axes = [Some(x_axis), Some(y_axis), z_axis];
+        let outer_axes = (0..shape.len())
+            .filter(|axis| !volume_axes.contains(&Some(*axis)) && Some(*axis) != channel_axis)
+            .collect::<Vec<_>>();
+        let outer_shape = outer_axes
+            .iter()
+            .map(|axis| shape[*axis])
+            .collect::<Vec<_>>();
+        let output_axes = (0..shape.len())
+            .filter(|axis| Some(*axis) != channel_axis)
+            .collect::<Vec<_>>();
+        let output_shape = output_axes
+            .iter()
+            .map(|axis| shape[*axis])
+            .collect::<Vec<_>>();
+        let mut output = ArrayD::<f32>::zeros(IxDyn(&output_shape));
+
+        let input = dataset.data.as_standard_layout();
+        let input = input
+            .as_slice()
+            .expect("standard layout arrays are contiguous");
+        let strides = row_major_strides(&shape);
+        // The output has no channel axis; a zero stride lets it share the volume walk.
+        let mut output_strides = row_major_strides(&output_shape);
+        if let Some(channel_axis) = channel_axis {
+            output_strides.insert(channel_axis, 0);
+        }
+        // Voxel offsets inside a volume are shared by every volume; only the base moves.
+        let volume_offsets = |strides: &[usize]| {
+            let z_stride = z_axis.map(|axis| strides[axis]).unwrap_or(0);
+            let mut offsets = Vec::with_capacity(volume.len());
+            for z in 0..volume.depth {
+                for y in 0..volume.height {
+                    let row = z * z_stride + y * strides[y_axis];
+                    offsets.extend((0..volume.width).map(|x| row + x * strides[x_axis]));
+                }
+            }
+            offsets
+        };
+        let local = volume_offsets(&strides);
+        let output_local = volume_offsets(&output_strides);
+        let labels_out = output
+            .as_slice_mut()
+            .expect("standard layout arrays are contiguous");
+
+        let mut next_label = 1_i64;
+        let mut basin_count = 0_usize;
+        iterate_indices(&outer_shape, |outer_coord| {
+            let base = |strides: &[usize]| {
+                outer_axes
+                    .iter()
+                    .zip(outer_coord)
+                    .map(|(axis, index)| index * strides[*axis])
+                    .sum::<usize>()
+            };
+            let (input_base, output_base) = (base(&strides), base(&output_strides));
+            // Flat dataset index of every voxel of this volume in `channel`.
+            let positions = |channel: usize| {
+                let channel_offset = channel_axis.map_or(0, |axis| channel * strides[axis]);
+                local
+                    .iter()
+                    .map(move |offset| input_base + channel_offset + offset)
+            };
+            let read = |channel: usize| {
+                positions(channel)
+                    .map(|position| input[position])
+                    .collect::<Vec<_>>()
+            };
+
+            let values = read(channel);
+            let mask = mask_channel.map(|channel| {
+                read(channel)
+                    .into_iter()
+                    .map(|value| value > 0.5)
+                    .collect::<Vec<_>>()
+            });
+            let seed = |value: f32| if value >= 1.0 { value as i64 } else { 0 };
+            let mut labels = match &markers {
+                MarkerSource::Input { values, indices } => positions(channel)
+                    .map(|position| seed(values[indices[position]]))
+                    .collect::<Vec<_>>(),
+                MarkerSource::Channel(channel) => {
+                    read(*channel).into_iter().map(seed).collect::<Vec<_>>()
+                }
+                MarkerSource::Minima => {
+                    let seeds = if h > 0.0 {
+                        regional_minima(
+                            &h_minima_transform(&values, h, volume, &offsets),
+                            volume,
+                            &offsets,
+                        )
+                    } else {
+                        regional_minima(&values, volume, &offsets)
+                    };
+                    let count = seeds.iter().copied().max().unwrap_or(0);
+                    let offset = next_label - 1;
+                    next_label += count;
+                    seeds
+                        .into_iter()
+                        .map(|label| if label > 0 { label + offset } else { 0 })
+                        .collect()
+                }
+            };
+            for (index, label) in labels.iter_mut().enumerate() {
+                let outside = mask.as_ref().is_some_and(|mask| !mask[index]);
+                if outside || !values[index].is_finite() {
+                    *label = EXCLUDED;
+                }
+            }
+
+            flood(&values, &mut labels, volume, &offsets, dams);
+            let mut basins = labels
+                .iter()
+                .copied()
+                .filter(|label| *label > 0)
+                .collect::<Vec<_>>();
+            basins.sort_unstable();
+            basins.dedup();
+            basin_count += basins.len();
+
+            for (offset, label) in output_local.iter().zip(labels) {
+                labels_out[output_base + offset] = label.max(0) as f32;
+            }
+        });
+
+        let mut metadata = Metadata {
+            dims: output_axes
+                .iter()
+                .map(|axis| dataset.metadata.dims[*axis].clone())
+                .collect(),
+            ..dataset.metadata.clone()
+        };
+        if channel_axis.is_some() {
+            metadata.channel_names.clear();
+            metadata.display = None;
+        }
+        metadata.kind = DatasetKind::Label;
+        metadata.pixel_type = PixelType::F32;
+        let mut measurements = MeasurementTable::default();
+        measurements
+            .values
+            .insert("count".to_string(), json!(basin_count));
+        Ok(OpOutput {
+            dataset: Dataset::new(output, metadata)?,
+            measurements: Some(measurements),
+        })
+    }
+}
+
+/// Meyer's priority flood. Unlabelled pixels (0) are claimed in order of
+/// increasing value by the basin they touch; with `dams`, pixels reached by
+/// two basins become watershed lines instead.
+fn flood(
+    values: &[f32],
+    labels: &mut [i64],
+    volume: VolumeShape,
+    offsets: &[(isize, isize, isize)],
+    dams: bool,
+) {
+    let mut queued = vec![false; labels.len()];
+    let mut heap = BinaryHeap::new();
+    let mut order = 0_u64;
+    for index in 0..labels.len() {
+        if labels[index] <= 0 {
+            continue;
+        }
+        for neighbour in volume.neighbours(index, offsets) {
+            if labels[neighbour] == 0 && !queued[neighbour] {
+                queued[neighbour] = true;
+                heap.push(Reverse((Priority(values[neighbour], order), neighbour)));
+                order += 1;
+            }
+        }
+    }
+
+    while let Some(Reverse((_, index))) = heap.pop() {
+        let mut basin = None;
+        let mut touches_several = false;
+        for neighbour in volume.neighbours(index, offsets) {
+            let label = labels[neighbour];
+            if label > 0 {
+                match basin {
+                    None => basin = Some(label),
+                    Some(existing) if existing != label => touches_several = true,
+                    Some(_) => {}
+                }
+            }
+        }
+        let Some(basin) = basin else {
+            continue;
+        };
+        if dams && touches_several {
+            labels[index] = DAM;
+            continue;
+        }
+        labels[index] = basin;
+        for neighbour in volume.neighbours(index, offsets) {
+            if labels[neighbour] == 0 && !queued[neighbour] {
+                queued[neighbour] = true;
+                heap.push(Reverse((Priority(values[neighbour], order), neighbour)));
+                order += 1;
+            }
+        }
+    }
+}
+
+fn axis_index(dataset: &DatasetF32, axis: AxisKind) -> Result<usize> {
+    dataset
+        .axis_index(axis)
+        .ok_or_else(|| OpsError::UnsupportedLayout(format!("dataset has no {axis:?} axis")))
+}
+
+fn iterate_indices(shape: &[usize], mut callback: impl FnMut(&[usize])) {
+    if shape.is_empty() {
+        callback(&[]);
+        return;
+    }
+    let mut index = vec![0usize; shape.len()];
+    loop {
+        callback(&index);
+        let mut dim = shape.len();
+        while dim > 0 {
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+            if dim == 0 {
+                return;
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 mod api;
 mod codec;
+mod description;
 mod error;
 mod raster;
 mod tiff;
@@ -9,8 +10,8 @@ mod util;
 mod tests;
 
 pub use api::{
-    read_dataset, read_dataset_bytes, read_native_image, read_native_image_bytes, save_slice_png,
-    source_path, supported_formats, write_dataset, write_native_image,
+    probe_shape, read_dataset, read_dataset_bytes, read_native_image, read_native_image_bytes,
+    save_slice_png, source_path, supported_formats, write_dataset, write_native_image,
 };
 pub use codec::{DefaultImageCodec, ImageReader, ImageWriter};
 pub use error::{IoError, Result};
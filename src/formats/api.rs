@@ -3,10 +3,10 @@ use std::path::{Path, PathBuf};
 use crate::model::DatasetF32;
 
 use super::raster::{
-    NativeRasterImage, read_common_raster, read_common_raster_bytes, read_native_raster,
-    read_native_raster_bytes, write_common_raster, write_native_raster,
+    NativeRasterImage, probe_common_raster, read_common_raster, read_common_raster_bytes,
+    read_native_raster, read_native_raster_bytes, write_common_raster, write_native_raster,
 };
-use super::tiff::{read_tiff, read_tiff_bytes, write_tiff};
+use super::tiff::{probe_tiff, read_tiff, read_tiff_bytes, write_tiff};
 use super::util::extension;
 use super::{IoError, Result};
 
@@ -20,6 +20,17 @@ pub fn read_dataset(path: impl AsRef<Path>) -> Result<DatasetF32> {
     }
 }
 
+/// Returns the shape `read_dataset` would produce, reading only headers.
+pub fn probe_shape(path: impl AsRef<Path>) -> Result<Vec<usize>> {
+    let path = path.as_ref();
+    let extension = extension(path)?;
+    match extension.as_str() {
+        "png" | "jpg" | "jpeg" => probe_common_raster(path),
+        "tif" | "tiff" => probe_tiff(path),
+        other => Err(IoError::UnsupportedFormat(other.to_string())),
+    }
+}
+
 pub fn read_dataset_bytes(bytes: &[u8], format_hint: &str) -> Result<DatasetF32> {
     match format_hint.to_ascii_lowercase().as_str() {
         "png" | "jpg" | "jpeg" => read_common_raster_bytes(bytes, format_hint),
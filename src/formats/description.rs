@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+
+use crate::model::{DatasetKind, Dim, DisplaySettings, Metadata, ProvenanceEntry};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{IoError, Result};
+
+const DESCRIPTION_KEY: &str = "image_rs";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PersistedMetadata {
+    #[serde(default)]
+    dims: Vec<Dim>,
+    #[serde(default)]
+    channel_names: Vec<String>,
+    #[serde(default)]
+    display: Option<DisplaySettings>,
+    #[serde(default)]
+    extras: BTreeMap<String, Value>,
+    #[serde(default)]
+    history: Vec<ProvenanceEntry>,
+    #[serde(default)]
+    kind: DatasetKind,
+}
+
+pub(crate) fn encode_description(metadata: &Metadata) -> Result<String> {
+    let persisted = PersistedMetadata {
+        dims: metadata.dims.clone(),
+        channel_names: metadata.channel_names.clone(),
+        display: metadata.display.clone(),
+        extras: metadata.extras.clone(),
+        history: metadata.history.clone(),
+        kind: metadata.kind,
+    };
+    let mut document = serde_json::Map::new();
+    document.insert(
+        DESCRIPTION_KEY.to_string(),
+        serde_json::to_value(persisted).map_err(|error| {
+            IoError::Io(std::io::Error::other(format!(
+                "metadata serialization failed: {error}"
+            )))
+        })?,
+    );
+    let serialized = Value::Object(document).to_string();
+    Ok(escape_non_ascii(&serialized))
+}
+
+/// Restores metadata written by [`encode_description`]. Descriptions written by
+/// other software are ignored, and dimension semantics are only restored when
+/// the decoded shape still matches the one that was saved.
+pub(crate) fn apply_description(metadata: &mut Metadata, description: &str) {
+    let Ok(mut document) = serde_json::from_str::<Value>(description) else {
+        return;
+    };
+    let Some(persisted) = document
+        .get_mut(DESCRIPTION_KEY)
+        .map(Value::take)
+        .and_then(|value| serde_json::from_value::<PersistedMetadata>(value).ok())
+    else {
+        return;
+    };
+
+    let saved_shape = persisted
+        .dims
+        .iter()
+        .map(|dim| dim.size)
+        .collect::<Vec<_>>();
+    if saved_shape == metadata.shape() {
+        metadata.dims = persisted.dims;
+    }
+    if !persisted.channel_names.is_empty() {
+        metadata.channel_names = persisted.channel_names;
+    }
+    if persisted.display.is_some() {
+        metadata.display = persisted.display;
+    }
+    metadata.extras.extend(persisted.extras);
+    metadata.kind = persisted.kind;
+    if !persisted.history.is_empty() {
+        metadata.history = persisted.history;
+    }
+}
+
+fn escape_non_ascii(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        if character.is_ascii() {
+            escaped.push(character);
+        } else {
+            let mut units = [0u16; 2];
+            for unit in character.encode_utf16(&mut units) {
+                escaped.push_str(&format!("\\u{unit:04x}"));
+            }
+        }
+    }
+    escaped
+}
@@ -1,7 +1,7 @@
 use std::path::Path;
 
 use crate::model::{AxisKind, Dataset, DatasetF32, Dim, Metadata, PixelType};
-use image::{DynamicImage, ImageBuffer, Luma, Rgb};
+use image::{DynamicImage, ImageBuffer, ImageDecoder, Luma, Rgb};
 use ndarray::{Array, IxDyn};
 
 use super::util::{metadata_for_dims, scale_to_u8};
@@ -151,6 +151,18 @@ pub(crate) fn read_common_raster(path: &Path) -> Result<DatasetF32> {
     dataset_from_dynamic_image(image, Some(path))
 }
 
+pub(crate) fn probe_common_raster(path: &Path) -> Result<Vec<usize>> {
+    let decoder = image::ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    let mut shape = vec![height as usize, width as usize];
+    if decoder.color_type().channel_count() > 1 {
+        shape.push(3);
+    }
+    Ok(shape)
+}
+
 pub(crate) fn read_native_raster(path: &Path) -> Result<Option<NativeRasterImage>> {
     let image = image::open(path)?;
     Ok(native_raster_from_dynamic_image(image, Some(path)))
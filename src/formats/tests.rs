@@ -6,7 +6,10 @@ use ndarray::Array;
 use tempfile::tempdir;
 
 use super::{NativeRasterImage, read_dataset, read_native_image, write_dataset};
-use crate::model::{AxisKind, Dataset, Dim, Metadata, PixelType};
+use crate::model::{
+    AxisKind, ChannelLut, CompositeMode, Dataset, DatasetKind, Dim, DisplaySettings, Metadata,
+    PixelType, ProvenanceEntry,
+};
 
 #[test]
 fn tiff_roundtrip_preserves_shape_and_type() {
@@ -28,6 +31,80 @@ fn tiff_roundtrip_preserves_shape_and_type() {
     assert_eq!(restored.metadata.pixel_type, PixelType::F32);
 }
 
+#[test]
+fn tiff_roundtrip_preserves_display_settings_and_axes() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("composite.tif");
+    let data = Array::from_shape_vec((2, 2, 2), (0..8).map(|v| v as f32).collect())
+        .expect("shape")
+        .into_dyn();
+    let mut x = Dim::new(AxisKind::X, 2);
+    x.spacing = Some(0.25);
+    x.unit = Some("µm".to_string());
+    let mut display = DisplaySettings {
+        mode: CompositeMode::Composite,
+        ..DisplaySettings::default()
+    };
+    display.channel_mut(0).lut = ChannelLut::Named("Green".to_string());
+    display.channel_mut(1).min = Some(1.0);
+    display.channel_mut(1).max = Some(6.0);
+    display.channel_mut(1).visible = false;
+    let metadata = Metadata {
+        dims: vec![Dim::new(AxisKind::Y, 2), x, Dim::new(AxisKind::Channel, 2)],
+        pixel_type: PixelType::U16,
+        channel_names: vec!["GFP".into(), "DAPI".into()],
+        display: Some(display.clone()),
+        ..Metadata::default()
+    };
+    let dataset = Dataset::new(data, metadata).expect("dataset");
+    write_dataset(&path, &dataset).expect("write tiff");
+
+    let restored = read_dataset(&path).expect("read tiff");
+    assert_eq!(restored.data, dataset.data);
+    assert_eq!(restored.metadata.dims, dataset.metadata.dims);
+    assert_eq!(
+        restored.metadata.channel_names,
+        dataset.metadata.channel_names
+    );
+    assert_eq!(restored.metadata.display, Some(display));
+}
+
+#[test]
+fn tiff_roundtrip_preserves_processing_history() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("history.tif");
+    let data = Array::from_shape_vec((2, 2), vec![0.0_f32, 0.25, 0.5, 1.0])
+        .expect("shape")
+        .into_dyn();
+    let mut dataset = Dataset::from_data_with_default_metadata(data, PixelType::F32);
+    dataset.metadata.history.push(ProvenanceEntry::record(
+        "gaussian.blur",
+        &serde_json::json!({"sigma": 1.5}),
+        &dataset,
+    ));
+    write_dataset(&path, &dataset).expect("write tiff");
+
+    let restored = read_dataset(&path).expect("read tiff");
+    assert_eq!(restored.metadata.history, dataset.metadata.history);
+}
+
+#[test]
+fn tiff_roundtrip_preserves_label_kind() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("labels.tif");
+    let data = Array::from_shape_vec((2, 2), vec![0_u32, 1, 2, 70_000])
+        .expect("shape")
+        .into_dyn();
+    let dataset = Dataset::from_data_with_default_metadata(data, PixelType::F32)
+        .to_label_dataset()
+        .expect("labels");
+    write_dataset(&path, &dataset).expect("write tiff");
+
+    let restored = read_dataset(&path).expect("read tiff");
+    assert_eq!(restored.metadata.kind, DatasetKind::Label);
+    assert_eq!(restored.data, dataset.data);
+}
+
 #[test]
 fn png_and_jpeg_decode_channel_mapping() {
     let dir = tempdir().expect("tempdir");
@@ -6,7 +6,9 @@ use crate::model::{AxisKind, Dataset, DatasetF32, Dim, PixelType};
 use ndarray::{Array, IxDyn};
 use tiff::decoder::{Decoder, DecodingResult};
 use tiff::encoder::{TiffEncoder, colortype};
+use tiff::tags::Tag;
 
+use super::description::{apply_description, encode_description};
 use super::util::{metadata_for_dims, to_u8_samples, to_u16_samples};
 use super::{IoError, Result};
 
@@ -15,6 +17,21 @@ pub(crate) fn read_tiff(path: &Path) -> Result<DatasetF32> {
     read_tiff_decoder(Decoder::new(file)?, path)
 }
 
+pub(crate) fn probe_tiff(path: &Path) -> Result<Vec<usize>> {
+    let mut decoder = Decoder::new(File::open(path)?)?;
+    let (width, height) = decoder.dimensions()?;
+    let mut pages = 1;
+    while decoder.more_images() {
+        decoder.next_image()?;
+        pages += 1;
+    }
+    let mut shape = vec![height as usize, width as usize];
+    if pages > 1 {
+        shape.push(pages);
+    }
+    Ok(shape)
+}
+
 pub(crate) fn read_tiff_bytes(bytes: &[u8], format_hint: &str) -> Result<DatasetF32> {
     let cursor = Cursor::new(bytes.to_vec());
     read_tiff_decoder(Decoder::new(cursor)?, Path::new(format_hint))
@@ -22,6 +39,9 @@ pub(crate) fn read_tiff_bytes(bytes: &[u8], format_hint: &str) -> Result<Dataset
 
 fn read_tiff_decoder<R: Read + Seek>(mut decoder: Decoder<R>, path: &Path) -> Result<DatasetF32> {
     let (width, height) = decoder.dimensions()?;
+    let description = decoder
+        .find_tag(Tag::ImageDescription)?
+        .and_then(|value| value.into_string().ok());
     let mut pages = Vec::new();
     let mut pixel_type = PixelType::F32;
 
@@ -73,7 +93,10 @@ fn read_tiff_decoder<R: Read + Seek>(mut decoder: Decoder<R>, path: &Path) -> Re
             .into_dyn()
     };
 
-    let metadata = metadata_for_dims(path, dims, pixel_type);
+    let mut metadata = metadata_for_dims(path, dims, pixel_type);
+    if let Some(description) = description {
+        apply_description(&mut metadata, &description);
+    }
     Ok(Dataset::new(data, metadata)?)
 }
 
@@ -130,6 +153,7 @@ pub(crate) fn write_tiff(path: &Path, dataset: &DatasetF32) -> Result<()> {
     let height = shape[0] as u32;
     let width = shape[1] as u32;
     let depth = if shape.len() == 2 { 1 } else { shape[2] };
+    let description = encode_description(&dataset.metadata)?;
     let file = File::create(path)?;
     let mut encoder = TiffEncoder::new(file)?;
 
@@ -142,7 +166,12 @@ pub(crate) fn write_tiff(path: &Path, dataset: &DatasetF32) -> Result<()> {
                     extract_tiff_page(dataset, z)?
                 };
                 let page = to_u8_samples(&page);
-                let image = encoder.new_image::<colortype::Gray8>(width, height)?;
+                let mut image = encoder.new_image::<colortype::Gray8>(width, height)?;
+                if z == 0 {
+                    image
+                        .encoder()
+                        .write_tag(Tag::ImageDescription, description.as_str())?;
+                }
                 image.write_data(&page)?;
             }
             PixelType::U16 => {
@@ -152,7 +181,12 @@ pub(crate) fn write_tiff(path: &Path, dataset: &DatasetF32) -> Result<()> {
                     extract_tiff_page(dataset, z)?
                 };
                 let page = to_u16_samples(&page);
-                let image = encoder.new_image::<colortype::Gray16>(width, height)?;
+                let mut image = encoder.new_image::<colortype::Gray16>(width, height)?;
+                if z == 0 {
+                    image
+                        .encoder()
+                        .write_tag(Tag::ImageDescription, description.as_str())?;
+                }
                 image.write_data(&page)?;
             }
             PixelType::F32 => {
@@ -161,7 +195,12 @@ pub(crate) fn write_tiff(path: &Path, dataset: &DatasetF32) -> Result<()> {
                 } else {
                     extract_tiff_page(dataset, z)?
                 };
-                let image = encoder.new_image::<colortype::Gray32Float>(width, height)?;
+                let mut image = encoder.new_image::<colortype::Gray32Float>(width, height)?;
+                if z == 0 {
+                    image
+                        .encoder()
+                        .write_tag(Tag::ImageDescription, description.as_str())?;
+                }
                 image.write_data(&page)?;
             }
         }
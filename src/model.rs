@@ -1,12 +1,24 @@
 mod axis;
 mod dataset;
+mod display;
 mod error;
+mod label;
+mod memory;
 mod metadata;
+mod provenance;
 
 #[cfg(test)]
 mod tests;
 
 pub use axis::{AxisKind, PixelType, default_axis_for_index};
 pub use dataset::{Dataset, DatasetF32};
+pub use display::{
+    ChannelDisplay, ChannelLut, CompositeMode, DisplaySettings, LUT_TABLE_SIZE, NAMED_LUTS,
+};
 pub use error::{CoreError, Result};
+pub use label::{DatasetKind, MAX_LABEL};
+pub use memory::{
+    MEMORY_LIMIT_ENV, MemoryBudget, MemoryBudgetExceeded, estimate_bytes, physical_memory_bytes,
+};
 pub use metadata::{Dim, Metadata};
+pub use provenance::{ProvenanceEntry, content_hash};
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use super::{CoreError, Result};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AxisKind {
     X,
@@ -7,9 +9,61 @@ pub enum AxisKind {
     Z,
     Channel,
     Time,
+    Spectral,
+    Angle,
+    Position,
     Unknown,
 }
 
+impl AxisKind {
+    pub fn code(self) -> char {
+        match self {
+            Self::X => 'X',
+            Self::Y => 'Y',
+            Self::Z => 'Z',
+            Self::Channel => 'C',
+            Self::Time => 'T',
+            Self::Spectral => 'L',
+            Self::Angle => 'A',
+            Self::Position => 'P',
+            Self::Unknown => 'U',
+        }
+    }
+
+    pub fn from_code(code: char) -> Option<Self> {
+        Some(match code.to_ascii_uppercase() {
+            'X' => Self::X,
+            'Y' => Self::Y,
+            'Z' => Self::Z,
+            'C' => Self::Channel,
+            'T' => Self::Time,
+            'L' => Self::Spectral,
+            'A' => Self::Angle,
+            'P' => Self::Position,
+            'U' => Self::Unknown,
+            _ => return None,
+        })
+    }
+
+    /// Parses axis codes such as `TZCYX`; `L` is spectral (lambda), `A` angle,
+    /// `P` position/tile and `U` an axis without known semantics.
+    pub fn parse_codes(codes: &str) -> Result<Vec<Self>> {
+        codes
+            .chars()
+            .filter(|code| !code.is_whitespace())
+            .map(|code| {
+                Self::from_code(code).ok_or_else(|| {
+                    CoreError::InvalidAxisOrder(format!("unknown axis code `{code}` in `{codes}`"))
+                })
+            })
+            .collect()
+    }
+
+    pub fn is_spatial(self) -> bool {
+        matches!(self, Self::X | Self::Y | Self::Z | Self::Unknown)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum PixelType {
     U8,
@@ -18,6 +72,16 @@ pub enum PixelType {
     F32,
 }
 
+impl PixelType {
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16 => 2,
+            Self::F32 => 4,
+        }
+    }
+}
+
 pub fn default_axis_for_index(index: usize) -> AxisKind {
     match index {
         0 => AxisKind::Y,
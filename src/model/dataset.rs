@@ -1,4 +1,4 @@
-use ndarray::ArrayD;
+use ndarray::{ArrayD, IxDyn};
 
 use super::{AxisKind, Metadata, PixelType, Result};
 
@@ -36,6 +36,25 @@ impl<T> Dataset<T> {
     }
 }
 
+impl<T: Clone> Dataset<T> {
+    /// Reorders data and dimensions together; output axis `i` is input axis `order[i]`.
+    pub fn permute_axes(&self, order: &[usize]) -> Result<Self> {
+        let metadata = self.metadata.permuted(order)?;
+        let data = self
+            .data
+            .view()
+            .permuted_axes(IxDyn(order))
+            .as_standard_layout()
+            .into_owned();
+        Self::new(data, metadata)
+    }
+
+    pub fn to_axis_order(&self, target: &[AxisKind]) -> Result<Self> {
+        let order = self.metadata.permutation_to(target)?;
+        self.permute_axes(&order)
+    }
+}
+
 impl Dataset<f32> {
     pub fn min_max(&self) -> Option<(f32, f32)> {
         let mut iter = self.data.iter().copied();
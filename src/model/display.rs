@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+
+use super::{CoreError, Result};
+
+pub const LUT_TABLE_SIZE: usize = 256;
+
+pub const NAMED_LUTS: &[&str] = &[
+    "Grays",
+    "Invert",
+    "Fire",
+    "Ice",
+    "Spectrum",
+    "3-3-2 RGB",
+    "Red",
+    "Green",
+    "Blue",
+    "Cyan",
+    "Magenta",
+    "Yellow",
+    "Red/Green",
+    "Glasbey",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelLut {
+    Named(String),
+    Custom {
+        red: Vec<u8>,
+        green: Vec<u8>,
+        blue: Vec<u8>,
+    },
+}
+
+impl Default for ChannelLut {
+    fn default() -> Self {
+        Self::Named("Grays".to_string())
+    }
+}
+
+impl ChannelLut {
+    pub fn named(name: &str) -> Option<Self> {
+        NAMED_LUTS
+            .iter()
+            .find(|candidate| candidate.eq_ignore_ascii_case(name.trim()))
+            .map(|candidate| Self::Named((*candidate).to_string()))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::Named(name) if name.trim().is_empty() => Err(CoreError::InvalidMetadata(
+                "LUT name must not be empty".to_string(),
+            )),
+            Self::Named(_) => Ok(()),
+            Self::Custom { red, green, blue } => {
+                if [red.len(), green.len(), blue.len()]
+                    .iter()
+                    .any(|len| *len != LUT_TABLE_SIZE)
+                {
+                    return Err(CoreError::InvalidMetadata(format!(
+                        "custom LUT tables must have {LUT_TABLE_SIZE} entries per component"
+                    )));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn color(&self, index: u8) -> Option<[u8; 3]> {
+        match self {
+            Self::Named(_) => None,
+            Self::Custom { red, green, blue } => {
+                let index = usize::from(index);
+                Some([*red.get(index)?, *green.get(index)?, *blue.get(index)?])
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CompositeMode {
+    #[default]
+    Composite,
+    Color,
+    Grayscale,
+}
+
+impl CompositeMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "composite" => Some(Self::Composite),
+            "color" | "colour" => Some(Self::Color),
+            "grayscale" | "greyscale" | "gray" | "grey" => Some(Self::Grayscale),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelDisplay {
+    #[serde(default)]
+    pub lut: ChannelLut,
+    #[serde(default)]
+    pub min: Option<f32>,
+    #[serde(default)]
+    pub max: Option<f32>,
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+impl Default for ChannelDisplay {
+    fn default() -> Self {
+        Self {
+            lut: ChannelLut::default(),
+            min: None,
+            max: None,
+            visible: true,
+        }
+    }
+}
+
+impl ChannelDisplay {
+    pub fn range(&self) -> Option<(f32, f32)> {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) if max > min => Some((min, max)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct DisplaySettings {
+    #[serde(default)]
+    pub mode: CompositeMode,
+    #[serde(default)]
+    pub channels: Vec<ChannelDisplay>,
+}
+
+impl DisplaySettings {
+    pub fn channel(&self, channel: usize) -> Option<&ChannelDisplay> {
+        self.channels.get(channel)
+    }
+
+    pub fn channel_mut(&mut self, channel: usize) -> &mut ChannelDisplay {
+        if self.channels.len() <= channel {
+            self.channels
+                .resize_with(channel + 1, ChannelDisplay::default);
+        }
+        &mut self.channels[channel]
+    }
+
+    pub fn validate(&self, channel_count: usize) -> Result<()> {
+        if self.channels.len() > channel_count.max(1) {
+            return Err(CoreError::InvalidMetadata(format!(
+                "display settings describe {} channels but the dataset has {}",
+                self.channels.len(),
+                channel_count.max(1)
+            )));
+        }
+        for (index, channel) in self.channels.iter().enumerate() {
+            channel.lut.validate()?;
+            if let (Some(min), Some(max)) = (channel.min, channel.max)
+                && !(min.is_finite() && max.is_finite() && max > min)
+            {
+                return Err(CoreError::InvalidMetadata(format!(
+                    "channel {index} display range must be finite with max > min"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
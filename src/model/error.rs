@@ -23,4 +23,7 @@ pub enum CoreError {
 
     #[error("invalid metadata: {0}")]
     InvalidMetadata(String),
+
+    #[error("invalid axis order: {0}")]
+    InvalidAxisOrder(String),
 }
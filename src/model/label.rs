@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use super::{CoreError, Dataset, DatasetF32, PixelType, Result};
+
+/// Largest label id that survives the round trip through `f32` samples.
+pub const MAX_LABEL: u32 = 1 << 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DatasetKind {
+    #[default]
+    Intensity,
+    /// Object labels: non-negative integer ids with 0 as background.
+    Label,
+}
+
+impl Dataset<f32> {
+    pub fn is_label(&self) -> bool {
+        self.metadata.kind == DatasetKind::Label
+    }
+
+    pub fn to_labels(&self) -> Result<Dataset<u32>> {
+        let data = self
+            .data
+            .iter()
+            .map(|value| {
+                if value.is_finite() && *value >= 0.0 && value.fract() == 0.0 {
+                    let label = *value as u32;
+                    if label <= MAX_LABEL {
+                        return Ok(label);
+                    }
+                }
+                Err(CoreError::InvalidMetadata(format!(
+                    "label images need integer ids in 0..={MAX_LABEL}, found {value}"
+                )))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let data = ndarray::ArrayD::from_shape_vec(self.data.raw_dim(), data)
+            .expect("shape is unchanged and valid");
+        let mut metadata = self.metadata.clone();
+        metadata.kind = DatasetKind::Label;
+        Dataset::new(data, metadata)
+    }
+}
+
+impl Dataset<u32> {
+    pub fn to_label_dataset(&self) -> Result<DatasetF32> {
+        if let Some(label) = self.data.iter().find(|label| **label > MAX_LABEL) {
+            return Err(CoreError::InvalidMetadata(format!(
+                "label {label} exceeds the maximum id {MAX_LABEL}"
+            )));
+        }
+        let mut metadata = self.metadata.clone();
+        metadata.kind = DatasetKind::Label;
+        metadata.pixel_type = PixelType::F32;
+        Dataset::new(self.data.mapv(|label| label as f32), metadata)
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+
+use thiserror::Error;
+
+use super::PixelType;
+
+/// Used when the amount of physical memory cannot be determined.
+const FALLBACK_PHYSICAL_BYTES: u64 = 8 << 30;
+
+/// Environment variable that overrides the default budget, e.g. `IMAGE_RS_MEMORY_LIMIT=4G`.
+pub const MEMORY_LIMIT_ENV: &str = "IMAGE_RS_MEMORY_LIMIT";
+
+pub fn estimate_bytes(shape: &[usize], pixel_type: PixelType) -> u64 {
+    shape
+        .iter()
+        .fold(pixel_type.bytes_per_sample() as u64, |bytes, size| {
+            bytes.saturating_mul(*size as u64)
+        })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error(
+    "memory budget exceeded while {context}: needs {} but the limit is {}",
+    format_bytes(*.requested),
+    format_bytes(*.limit)
+)]
+pub struct MemoryBudgetExceeded {
+    pub context: String,
+    pub requested: u64,
+    pub limit: u64,
+}
+
+/// Upper bound on the memory that dataset allocations may claim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget {
+    limit: u64,
+}
+
+impl Default for MemoryBudget {
+    fn default() -> Self {
+        Self::from_physical_share(Self::DEFAULT_SHARE)
+    }
+}
+
+impl MemoryBudget {
+    pub const DEFAULT_SHARE: f64 = 0.75;
+
+    pub fn new(limit: u64) -> Self {
+        Self { limit }
+    }
+
+    pub fn unlimited() -> Self {
+        Self { limit: u64::MAX }
+    }
+
+    /// The budget from [`MEMORY_LIMIT_ENV`] when set and valid, otherwise the default share.
+    pub fn configured() -> Self {
+        std::env::var(MEMORY_LIMIT_ENV)
+            .ok()
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+
+    pub fn from_physical_share(share: f64) -> Self {
+        let physical = physical_memory_bytes().unwrap_or(FALLBACK_PHYSICAL_BYTES);
+        Self::new((physical as f64 * share.clamp(0.0, 1.0)) as u64)
+    }
+
+    /// Parses a byte count with an optional `K`, `M`, `G` or `T` suffix
+    /// (binary multiples, an optional trailing `B` is accepted).
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_ascii_uppercase();
+        let value = value.strip_suffix('B').unwrap_or(&value);
+        let (number, shift) = match value.chars().last()? {
+            'K' => (&value[..value.len() - 1], 10),
+            'M' => (&value[..value.len() - 1], 20),
+            'G' => (&value[..value.len() - 1], 30),
+            'T' => (&value[..value.len() - 1], 40),
+            _ => (value, 0),
+        };
+        let number = number.trim().parse::<f64>().ok()?;
+        if !number.is_finite() || number <= 0.0 {
+            return None;
+        }
+        Some(Self::new((number * (1_u64 << shift) as f64) as u64))
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn check(&self, context: &str, requested: u64) -> Result<(), MemoryBudgetExceeded> {
+        if requested > self.limit {
+            return Err(MemoryBudgetExceeded {
+                context: context.to_string(),
+                requested,
+                limit: self.limit,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MemoryBudget {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.limit == u64::MAX {
+            formatter.write_str("unlimited")
+        } else {
+            formatter.write_str(&format_bytes(self.limit))
+        }
+    }
+}
+
+/// Total physical memory as reported by the operating system, when available.
+pub fn physical_memory_bytes() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|line| line.starts_with("MemTotal:"))?;
+    let kib = line
+        .trim_start_matches("MemTotal:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kib.saturating_mul(1024))
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
@@ -3,7 +3,10 @@ use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
 
-use super::{AxisKind, CoreError, PixelType, Result, default_axis_for_index};
+use super::{
+    AxisKind, CoreError, DatasetKind, DisplaySettings, PixelType, ProvenanceEntry, Result,
+    default_axis_for_index, estimate_bytes,
+};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Dim {
@@ -31,6 +34,12 @@ pub struct Metadata {
     pub channel_names: Vec<String>,
     pub source: Option<PathBuf>,
     pub extras: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub display: Option<DisplaySettings>,
+    #[serde(default)]
+    pub history: Vec<ProvenanceEntry>,
+    #[serde(default)]
+    pub kind: DatasetKind,
 }
 
 impl Default for Metadata {
@@ -41,6 +50,9 @@ impl Default for Metadata {
             channel_names: Vec::new(),
             source: None,
             extras: BTreeMap::new(),
+            display: None,
+            history: Vec::new(),
+            kind: DatasetKind::Intensity,
         }
     }
 }
@@ -67,6 +79,88 @@ impl Metadata {
         self.dims.iter().position(|d| d.axis == axis)
     }
 
+    pub fn is_label(&self) -> bool {
+        self.kind == DatasetKind::Label
+    }
+
+    pub fn element_count(&self) -> usize {
+        self.dims.iter().map(|dim| dim.size).product()
+    }
+
+    /// Bytes needed to hold the samples in memory. Datasets are always
+    /// decoded to `f32`, so this is independent of `pixel_type`.
+    pub fn estimated_bytes(&self) -> u64 {
+        estimate_bytes(&self.shape(), PixelType::F32)
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.axis_index(AxisKind::Channel)
+            .map(|axis| self.dims[axis].size)
+            .unwrap_or(1)
+    }
+
+    pub fn axis_codes(&self) -> String {
+        self.dims.iter().map(|dim| dim.axis.code()).collect()
+    }
+
+    /// Returns the source index for every output position so that the axes
+    /// appear in `target` order. `Unknown` axes are matched in their current order.
+    pub fn permutation_to(&self, target: &[AxisKind]) -> Result<Vec<usize>> {
+        if target.len() != self.dims.len() {
+            return Err(CoreError::InvalidAxisOrder(format!(
+                "target order has {} axes but the dataset has {}",
+                target.len(),
+                self.dims.len()
+            )));
+        }
+        let mut used = vec![false; self.dims.len()];
+        target
+            .iter()
+            .map(|axis| {
+                let index = self
+                    .dims
+                    .iter()
+                    .enumerate()
+                    .position(|(index, dim)| !used[index] && dim.axis == *axis)
+                    .ok_or_else(|| {
+                        CoreError::InvalidAxisOrder(format!(
+                            "axis {axis:?} is not available in `{}`",
+                            self.axis_codes()
+                        ))
+                    })?;
+                used[index] = true;
+                Ok(index)
+            })
+            .collect()
+    }
+
+    pub fn permuted(&self, order: &[usize]) -> Result<Self> {
+        validate_permutation(order, self.dims.len())?;
+        let mut metadata = self.clone();
+        metadata.dims = order
+            .iter()
+            .map(|index| self.dims[*index].clone())
+            .collect();
+        Ok(metadata)
+    }
+
+    pub fn validate_axes(&self) -> Result<()> {
+        for (index, dim) in self.dims.iter().enumerate() {
+            if dim.axis != AxisKind::Unknown
+                && self.dims[..index]
+                    .iter()
+                    .any(|other| other.axis == dim.axis)
+            {
+                return Err(CoreError::InvalidAxisOrder(format!(
+                    "axis {:?} appears more than once in `{}`",
+                    dim.axis,
+                    self.axis_codes()
+                )));
+            }
+        }
+        Ok(())
+    }
+
     pub fn validate_shape(&self, shape: &[usize]) -> Result<()> {
         if self.dims.len() != shape.len() {
             return Err(CoreError::DimensionalityMismatch {
@@ -89,3 +183,21 @@ impl Metadata {
         Ok(())
     }
 }
+
+pub(crate) fn validate_permutation(order: &[usize], ndim: usize) -> Result<()> {
+    let mut seen = vec![false; ndim];
+    if order.len() != ndim {
+        return Err(CoreError::InvalidAxisOrder(format!(
+            "permutation {order:?} must list each of the {ndim} axes exactly once"
+        )));
+    }
+    for index in order {
+        if *index >= ndim || seen[*index] {
+            return Err(CoreError::InvalidAxisOrder(format!(
+                "permutation {order:?} must list each of the {ndim} axes exactly once"
+            )));
+        }
+        seen[*index] = true;
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::DatasetF32;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProvenanceEntry {
+    pub op: String,
+    #[serde(default)]
+    pub params: Value,
+    pub crate_version: String,
+    pub input_hash: String,
+    pub timestamp: String,
+}
+
+impl ProvenanceEntry {
+    pub fn record(op: &str, params: &Value, input: &DatasetF32) -> Self {
+        Self {
+            op: op.to_string(),
+            params: params.clone(),
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            input_hash: content_hash(input),
+            timestamp: utc_timestamp(SystemTime::now()),
+        }
+    }
+}
+
+/// FNV-1a over the shape and the raw sample bits, so the digest is stable
+/// across platforms and compiler versions.
+pub fn content_hash(dataset: &DatasetF32) -> String {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = OFFSET;
+    let mut feed = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    };
+    for size in dataset.shape() {
+        feed(&(*size as u64).to_le_bytes());
+    }
+    for value in dataset.data.iter() {
+        feed(&value.to_bits().to_le_bytes());
+    }
+    format!("fnv1a64:{hash:016x}")
+}
+
+pub(crate) fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let days = (seconds / 86_400) as i64;
+    let second_of_day = seconds % 86_400;
+
+    // Civil-from-days conversion (Howard Hinnant's algorithm).
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        second_of_day / 3_600,
+        (second_of_day % 3_600) / 60,
+        second_of_day % 60
+    )
+}
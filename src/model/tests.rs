@@ -1,6 +1,9 @@
 use ndarray::Array;
 
-use super::{AxisKind, Dataset, Dim, Metadata, PixelType};
+use super::{
+    AxisKind, Dataset, DatasetKind, Dim, MAX_LABEL, MemoryBudget, Metadata, PixelType,
+    content_hash, estimate_bytes,
+};
 
 #[test]
 fn metadata_roundtrip_json() {
@@ -42,3 +45,120 @@ fn dataset_validates_dimension_sizes() {
     assert_eq!(dataset.axis_index(AxisKind::X), Some(1));
     assert_eq!(dataset.axis_index(AxisKind::Y), Some(0));
 }
+
+#[test]
+fn metadata_without_display_settings_still_deserializes() {
+    let metadata = Metadata::from_shape(&[2, 2], PixelType::F32);
+    let mut value = serde_json::to_value(&metadata).expect("serialize metadata");
+    value.as_object_mut().expect("object").remove("display");
+    let restored: Metadata = serde_json::from_value(value).expect("deserialize metadata");
+    assert_eq!(restored, metadata);
+    assert_eq!(restored.channel_count(), 1);
+}
+
+#[test]
+fn provenance_timestamps_are_rfc3339_utc() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::provenance::utc_timestamp;
+
+    assert_eq!(utc_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    assert_eq!(
+        utc_timestamp(UNIX_EPOCH + Duration::from_secs(951_825_661)),
+        "2000-02-29T12:01:01Z"
+    );
+}
+
+#[test]
+fn content_hash_depends_on_shape_and_values() {
+    let flat = Dataset::from_data_with_default_metadata(
+        Array::from_shape_vec((1, 4), vec![0.0_f32, 1.0, 2.0, 3.0])
+            .expect("shape")
+            .into_dyn(),
+        PixelType::F32,
+    );
+    let square = Dataset::from_data_with_default_metadata(
+        Array::from_shape_vec((2, 2), vec![0.0_f32, 1.0, 2.0, 3.0])
+            .expect("shape")
+            .into_dyn(),
+        PixelType::F32,
+    );
+    assert_eq!(content_hash(&flat), content_hash(&flat.clone()));
+    assert_ne!(content_hash(&flat), content_hash(&square));
+}
+
+#[test]
+fn dataset_permute_axes_moves_data_and_dims_together() {
+    let data = Array::from_shape_vec((2, 3), vec![0.0_f32, 1.0, 2.0, 3.0, 4.0, 5.0])
+        .expect("shape")
+        .into_dyn();
+    let mut dataset = Dataset::from_data_with_default_metadata(data, PixelType::F32);
+    dataset.metadata.dims[1].spacing = Some(0.5);
+
+    let permuted = dataset
+        .to_axis_order(&AxisKind::parse_codes("XY").expect("codes"))
+        .expect("permute");
+    assert_eq!(permuted.shape(), &[3, 2]);
+    assert_eq!(permuted.metadata.dims[0].axis, AxisKind::X);
+    assert_eq!(permuted.metadata.dims[0].spacing, Some(0.5));
+    assert_eq!(
+        permuted.data.iter().copied().collect::<Vec<_>>(),
+        vec![0.0, 3.0, 1.0, 4.0, 2.0, 5.0]
+    );
+    assert!(dataset.permute_axes(&[0]).is_err());
+    assert!(dataset.permute_axes(&[1, 1]).is_err());
+    assert!(AxisKind::parse_codes("TZCYXLAPU").is_ok());
+    assert!(AxisKind::parse_codes("YXW").is_err());
+}
+
+#[test]
+fn label_conversion_requires_integer_ids_and_sets_kind() {
+    let data = Array::from_shape_vec((2, 2), vec![0.0_f32, 1.0, 2.0, 3.0])
+        .expect("shape")
+        .into_dyn();
+    let dataset = Dataset::from_data_with_default_metadata(data, PixelType::U8);
+    assert!(!dataset.is_label());
+
+    let labels = dataset.to_labels().expect("labels");
+    assert_eq!(
+        labels.data.iter().copied().collect::<Vec<_>>(),
+        vec![0, 1, 2, 3]
+    );
+    let restored = labels.to_label_dataset().expect("label dataset");
+    assert!(restored.is_label());
+    assert_eq!(restored.metadata.kind, DatasetKind::Label);
+    assert_eq!(restored.metadata.pixel_type, PixelType::F32);
+    assert_eq!(restored.data, dataset.data);
+
+    for invalid in [-1.0_f32, 0.5, f32::NAN, (MAX_LABEL as f32) * 2.0] {
+        let mut bad = dataset.clone();
+        bad.data[[0, 0]] = invalid;
+        assert!(bad.to_labels().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn memory_budget_parses_sizes_and_checks_estimates() {
+    let metadata = Metadata::from_shape(&[4, 5, 3], PixelType::U8);
+    assert_eq!(metadata.element_count(), 60);
+    assert_eq!(metadata.estimated_bytes(), 240);
+    assert_eq!(estimate_bytes(&[4, 5, 3], PixelType::U16), 120);
+
+    assert_eq!(MemoryBudget::parse("512").map(|b| b.limit()), Some(512));
+    assert_eq!(MemoryBudget::parse("2k").map(|b| b.limit()), Some(2048));
+    assert_eq!(
+        MemoryBudget::parse("1.5 GB").map(|b| b.limit()),
+        Some(3 << 29)
+    );
+    assert!(MemoryBudget::parse("-1G").is_none());
+    assert!(MemoryBudget::parse("lots").is_none());
+
+    let budget = MemoryBudget::new(240);
+    assert!(budget.check("loading", metadata.estimated_bytes()).is_ok());
+    let error = budget.check("loading", 241).expect_err("over budget");
+    assert_eq!(
+        error.to_string(),
+        "memory budget exceeded while loading: needs 241 B but the limit is 240 B"
+    );
+    assert!(MemoryBudget::default().limit() > 0);
+}
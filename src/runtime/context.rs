@@ -1,3 +1,5 @@
+use crate::model::MemoryBudget;
+
 use super::{DatasetService, IoService, OpsService, PipelineService};
 
 #[derive(Debug, Clone, Default)]
@@ -10,7 +12,25 @@ pub struct AppContext {
 
 impl AppContext {
     pub fn new() -> Self {
-        Self::default()
+        Self::with_memory_budget(MemoryBudget::configured())
+    }
+
+    pub fn with_memory_budget(budget: MemoryBudget) -> Self {
+        Self {
+            dataset_service: DatasetService,
+            io_service: IoService::new(budget),
+            ops_service: OpsService::default(),
+            pipeline_service: PipelineService::new(budget),
+        }
+    }
+
+    pub fn memory_budget(&self) -> MemoryBudget {
+        self.io_service.budget()
+    }
+
+    pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+        self.io_service = IoService::new(budget);
+        self.pipeline_service.set_budget(budget);
     }
 
     pub fn dataset_service(&self) -> &DatasetService {
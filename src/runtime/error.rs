@@ -1,6 +1,6 @@
 use crate::commands::OpsError;
 use crate::formats::IoError;
-use crate::model::CoreError;
+use crate::model::{CoreError, MemoryBudgetExceeded};
 use crate::workflow::PipelineError;
 use thiserror::Error;
 
@@ -19,4 +19,7 @@ pub enum AppError {
 
     #[error("pipeline service error: {0}")]
     Pipeline(#[from] PipelineError),
+
+    #[error("{0}")]
+    Memory(#[from] MemoryBudgetExceeded),
 }
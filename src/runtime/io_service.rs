@@ -1,19 +1,37 @@
 use std::path::Path;
 
 use crate::formats::{
-    IoError, NativeRasterImage, read_dataset, read_dataset_bytes, read_native_image, write_dataset,
-    write_native_image,
+    IoError, NativeRasterImage, probe_shape, read_dataset, read_dataset_bytes, read_native_image,
+    write_dataset, write_native_image,
+};
+use crate::model::{
+    AxisKind, Dataset, DatasetF32, Dim, MemoryBudget, Metadata, PixelType, estimate_bytes,
 };
-use crate::model::{AxisKind, Dataset, DatasetF32, Dim, Metadata, PixelType};
 use ndarray::{ArrayD, IxDyn};
 
 use super::Result;
 
 #[derive(Debug, Default, Clone, Copy)]
-pub struct IoService;
+pub struct IoService {
+    budget: MemoryBudget,
+}
 
 impl IoService {
+    pub fn new(budget: MemoryBudget) -> Self {
+        Self { budget }
+    }
+
+    pub fn budget(&self) -> MemoryBudget {
+        self.budget
+    }
+
     pub fn read(&self, path: impl AsRef<Path>) -> Result<DatasetF32> {
+        let path = path.as_ref();
+        let shape = probe_shape(path)?;
+        self.budget.check(
+            &format!("reading {}", path.display()),
+            estimate_bytes(&shape, PixelType::F32),
+        )?;
         Ok(read_dataset(path)?)
     }
 
@@ -53,6 +71,10 @@ impl IoService {
             .saturating_mul(height)
             .saturating_mul(slices.max(1))
             .saturating_mul(channels.max(1));
+        self.budget.check(
+            "importing raw data",
+            estimate_bytes(&[voxel_count], PixelType::F32),
+        )?;
         let values = match pixel_type {
             PixelType::U8 => bytes
                 .iter()
@@ -118,7 +140,7 @@ mod tests {
 
     #[test]
     fn read_raw_preserves_integer_sample_values() {
-        let service = IoService;
+        let service = IoService::default();
         let u8_dataset = service
             .read_raw(&[0, 10, 128, 255], 2, 2, 1, 1, PixelType::U8, true, 0)
             .expect("u8 raw");
@@ -141,4 +163,20 @@ mod tests {
             vec![0.0, 10.0, 4096.0, 65_535.0]
         );
     }
+
+    #[test]
+    fn read_raw_refuses_allocations_over_budget() {
+        let service = IoService::new(MemoryBudget::new(15));
+        let error = service
+            .read_raw(&[0, 10, 128, 255], 2, 2, 1, 1, PixelType::U8, true, 0)
+            .expect_err("over budget");
+        assert!(matches!(error, crate::runtime::AppError::Memory(_)));
+        assert!(error.to_string().contains("needs 16 B"));
+
+        assert!(
+            IoService::new(MemoryBudget::new(16))
+                .read_raw(&[0, 10, 128, 255], 2, 2, 1, 1, PixelType::U8, true, 0)
+                .is_ok()
+        );
+    }
 }
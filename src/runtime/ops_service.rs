@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::commands::{
-    OpOutput, OpSchema, Operation, default_registry, execute_operation_with_registry,
+    OpInputs, OpOutput, OpSchema, Operation, default_registry, execute_operation_with_registry,
 };
 use crate::model::DatasetF32;
 use serde_json::Value;
@@ -43,10 +43,21 @@ impl OpsService {
     }
 
     pub fn execute(&self, op: &str, dataset: &DatasetF32, params: &Value) -> Result<OpOutput> {
+        self.execute_with_inputs(op, dataset, &OpInputs::new(), params)
+    }
+
+    pub fn execute_with_inputs(
+        &self,
+        op: &str,
+        dataset: &DatasetF32,
+        inputs: &OpInputs<'_>,
+        params: &Value,
+    ) -> Result<OpOutput> {
         Ok(execute_operation_with_registry(
             &self.registry,
             op,
             dataset,
+            inputs,
             params,
         )?)
     }
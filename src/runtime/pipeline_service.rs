@@ -1,16 +1,34 @@
 use std::path::Path;
 
-use crate::model::DatasetF32;
-use crate::workflow::{PipelineReport, PipelineSpec, load_spec, run_pipeline, save_report};
+use crate::model::{DatasetF32, MemoryBudget};
+use crate::workflow::{
+    PipelineError, PipelineReport, PipelineSpec, load_spec, run_pipeline_with_budget, save_report,
+};
 
-use super::{OpsService, Result};
+use super::{AppError, OpsService, Result};
 
 #[derive(Debug, Clone, Default)]
 pub struct PipelineService {
     ops: OpsService,
+    budget: MemoryBudget,
 }
 
 impl PipelineService {
+    pub fn new(budget: MemoryBudget) -> Self {
+        Self {
+            ops: OpsService::default(),
+            budget,
+        }
+    }
+
+    pub fn budget(&self) -> MemoryBudget {
+        self.budget
+    }
+
+    pub fn set_budget(&mut self, budget: MemoryBudget) {
+        self.budget = budget;
+    }
+
     pub fn load_spec(&self, path: impl AsRef<Path>) -> Result<PipelineSpec> {
         Ok(load_spec(path)?)
     }
@@ -20,7 +38,12 @@ impl PipelineService {
         spec: &PipelineSpec,
         input: &DatasetF32,
     ) -> Result<(DatasetF32, PipelineReport)> {
-        Ok(run_pipeline(spec, input, self.ops.registry())?)
+        run_pipeline_with_budget(spec, input, self.ops.registry(), &self.budget).map_err(|error| {
+            match error {
+                PipelineError::Memory(error) => AppError::Memory(error),
+                other => other.into(),
+            }
+        })
     }
 
     pub fn save_report(&self, path: impl AsRef<Path>, report: &PipelineReport) -> Result<()> {
@@ -26,9 +26,12 @@ use super::interaction::tooling::{
 };
 use super::interaction::transform::{ViewerTransformState, zoom_level_down, zoom_level_up};
 use super::lut::*;
-use crate::commands::MeasurementTable;
+use crate::commands::{INPUT_PARAM_KIND, MeasurementTable, OpInputs};
 use crate::formats::supported_formats;
-use crate::model::{AxisKind, Dataset, DatasetF32, Dim, Metadata, PixelType};
+use crate::model::{
+    AxisKind, ChannelLut, Dataset, DatasetF32, Dim, DisplaySettings, MemoryBudget, Metadata,
+    PixelType, physical_memory_bytes,
+};
 use crate::runtime::AppContext;
 use eframe::egui;
 use image::load_from_memory;
@@ -126,6 +129,7 @@ struct ViewerSession {
     committed_summary: ImageSummary,
     display_range: Option<(f32, f32)>,
     channel_display_ranges: HashMap<usize, (f32, f32)>,
+    channel_luts: HashMap<usize, ChannelLut>,
     threshold_overlay: Option<ThresholdOverlay>,
     undo_stack: Vec<Arc<DatasetF32>>,
     redo_stack: Vec<Arc<DatasetF32>>,
@@ -139,13 +143,14 @@ struct ViewerSession {
 impl ViewerSession {
     fn new(path: PathBuf, source: ViewerImageSource) -> Self {
         let committed_summary = summarize_source(&source, &path);
-        Self {
+        let mut session = Self {
             path,
             base_source: source.clone(),
             committed_source: source,
             committed_summary,
             display_range: None,
             channel_display_ranges: HashMap::new(),
+            channel_luts: HashMap::new(),
             threshold_overlay: None,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
@@ -154,9 +159,66 @@ impl ViewerSession {
             frame_cache: HashMap::new(),
             generation: 0,
             active_job: None,
+        };
+        session.seed_display_from_metadata();
+        session
+    }
+
+    fn seed_display_from_metadata(&mut self) {
+        self.channel_luts.clear();
+        let Some(dataset) = self.committed_dataset() else {
+            return;
+        };
+        let Some(display) = dataset.metadata.display.clone() else {
+            if dataset.is_label() {
+                self.channel_luts.insert(
+                    0,
+                    ChannelLut::Named(LookupTable::Glasbey.label().to_string()),
+                );
+            }
+            return;
+        };
+        for (channel, settings) in display.channels.into_iter().enumerate() {
+            if let Some(range) = settings.range() {
+                self.channel_display_ranges.insert(channel, range);
+            }
+            self.channel_luts.insert(channel, settings.lut);
         }
     }
 
+    fn display_settings(&self, fallback_lut: LookupTable) -> Option<DisplaySettings> {
+        let existing = self
+            .committed_dataset()
+            .and_then(|dataset| dataset.metadata.display.clone());
+        if existing.is_none()
+            && self.display_range.is_none()
+            && self.channel_display_ranges.is_empty()
+            && self.channel_luts.is_empty()
+            && fallback_lut == LookupTable::Grays
+        {
+            return None;
+        }
+        let mut display = existing.unwrap_or_default();
+        for channel in 0..self.committed_summary.channels.max(1) {
+            let entry = display.channel_mut(channel);
+            if let Some((low, high)) = self
+                .channel_display_ranges
+                .get(&channel)
+                .copied()
+                .or(self.display_range)
+            {
+                entry.min = Some(low);
+                entry.max = Some(high);
+            }
+            entry.lut = self
+                .channel_luts
+                .get(&channel)
+                .cloned()
+                .unwrap_or_else(|| ChannelLut::Named(fallback_lut.label().to_string()));
+        }
+        Some(display)
+    }
+
     fn current_source_kind(&self) -> String {
         if let Some(key) = &self.active_preview {
             format!("{SOURCE_PREVIEW_PREFIX}{key}")
@@ -243,6 +305,7 @@ impl ViewerSession {
         self.frame_cache.clear();
         self.channel_display_ranges.clear();
         self.threshold_overlay = None;
+        self.seed_display_from_metadata();
     }
 
     fn can_undo(&self) -> bool {
@@ -286,6 +349,7 @@ impl ViewerSession {
             self.threshold_overlay = None;
             self.undo_stack.clear();
             self.redo_stack.clear();
+            self.seed_display_from_metadata();
             return false;
         }
 
@@ -298,6 +362,7 @@ impl ViewerSession {
         self.threshold_overlay = None;
         self.redo_stack.clear();
         self.undo_stack.clear();
+        self.seed_display_from_metadata();
         true
     }
 
@@ -851,6 +916,40 @@ impl ImageUiApp {
         self.refresh_launcher_status();
     }
 
+    /// Shows the memory budget, or replaces it when `maximum_mb` is given.
+    fn apply_memory_options(
+        &mut self,
+        params: Option<&Value>,
+    ) -> command_registry::CommandExecuteResult {
+        if let Some(maximum) = params.and_then(|params| params.get("maximum_mb")) {
+            let Some(maximum_mb) = maximum.as_f64().filter(|value| *value >= 1.0) else {
+                return command_registry::CommandExecuteResult::blocked(
+                    "`maximum_mb` must be a number of at least 1",
+                );
+            };
+            self.state
+                .app
+                .set_memory_budget(MemoryBudget::new((maximum_mb * 1024.0 * 1024.0) as u64));
+        }
+        let budget = self.state.app.memory_budget();
+        let physical = physical_memory_bytes();
+        let message = match physical {
+            Some(physical) => format!(
+                "memory limit {budget} of {} physical",
+                MemoryBudget::new(physical)
+            ),
+            None => format!("memory limit {budget}"),
+        };
+        self.set_fallback_status(message.clone());
+        command_registry::CommandExecuteResult::with_payload(
+            message,
+            json!({
+                "maximum_mb": budget.limit() / (1024 * 1024),
+                "physical_mb": physical.map(|bytes| bytes / (1024 * 1024)),
+            }),
+        )
+    }
+
     fn set_fallback_status(&mut self, status: impl Into<String>) {
         self.launcher_ui.fallback_text = status.into();
         self.refresh_launcher_status();
@@ -1322,11 +1421,25 @@ impl ImageUiApp {
                 }
             }
             ViewerImageSource::Dataset(dataset) => {
-                self.state
-                    .app
-                    .io_service()
-                    .write(&normalized_target, dataset.as_ref())
-                    .map_err(|error| error.to_string())?;
+                let fallback_lut = self
+                    .viewers_ui
+                    .get(window_label)
+                    .map(|viewer| viewer.lookup_table)
+                    .unwrap_or(LookupTable::Grays);
+                let display = self
+                    .state
+                    .label_to_session
+                    .get(window_label)
+                    .and_then(|session| session.display_settings(fallback_lut));
+                let io = self.state.app.io_service();
+                if display == dataset.metadata.display {
+                    io.write(&normalized_target, dataset.as_ref())
+                } else {
+                    let mut dataset = dataset.as_ref().clone();
+                    dataset.metadata.display = display;
+                    io.write(&normalized_target, &dataset)
+                }
+                .map_err(|error| error.to_string())?;
             }
         }
 
@@ -2580,16 +2693,59 @@ impl ImageUiApp {
             .ok_or_else(|| "a loaded image is required for particle analysis".to_string())?
             .to_string();
         let (slice, _, z, t, channel) = self.measurement_context(&viewer_label)?;
-        let binary = threshold_slice_otsu(&slice.values);
-        let particles = connected_components_2d(slice.width, slice.height, &binary);
+        let plane = |values: Vec<f32>| {
+            let data = ArrayD::from_shape_vec(IxDyn(&[slice.height, slice.width]), values)
+                .map_err(|error| error.to_string())?;
+            let metadata = Metadata {
+                dims: vec![
+                    Dim::new(AxisKind::Y, slice.height),
+                    Dim::new(AxisKind::X, slice.width),
+                ],
+                ..Metadata::default()
+            };
+            Dataset::new(data, metadata).map_err(|error| error.to_string())
+        };
+        let mask = plane(
+            threshold_slice_otsu(&slice.values)
+                .into_iter()
+                .map(f32::from)
+                .collect(),
+        )?;
+        let intensities = plane(slice.values.clone())?;
+        let measurements = crate::commands::execute_operation_with_inputs(
+            "analyze.particles",
+            &mask,
+            &OpInputs::new().with("intensity", &intensities),
+            &json!({}),
+        )
+        .map_err(|error| error.to_string())?
+        .measurements
+        .ok_or_else(|| "particle analysis produced no measurements".to_string())?;
+        let particles = measurements
+            .values
+            .get("rows")
+            .and_then(Value::as_array)
+            .map(|rows| {
+                rows.iter()
+                    .filter_map(|row| row.as_object().cloned())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
         if particles.is_empty() {
             return Ok("no particles found".to_string());
         }
         if let Some(viewer) = self.viewers_ui.get_mut(&viewer_label) {
             for particle in &particles {
+                // `analyze.particles` reports `bbox` as `[x, y, width, height]`.
+                let Some([x, y, width, height]) = particle
+                    .get("bbox")
+                    .and_then(|bbox| serde_json::from_value::<[usize; 4]>(bbox.clone()).ok())
+                else {
+                    continue;
+                };
                 let rect = RoiKind::Rect {
-                    start: egui::pos2(particle.min_x as f32, particle.min_y as f32),
-                    end: egui::pos2((particle.max_x + 1) as f32, (particle.max_y + 1) as f32),
+                    start: egui::pos2(x as f32, y as f32),
+                    end: egui::pos2((x + width) as f32, (y + height) as f32),
                     rounded: false,
                     rotated: false,
                 };
@@ -2597,27 +2753,23 @@ impl ImageUiApp {
                     .rois
                     .begin_active(rect, interaction::roi::RoiPosition { channel, z, t });
                 if let Some(active) = viewer.rois.active_roi.as_mut() {
-                    active.name = format!("Particle {}", particle.label);
+                    active.name = format!("Particle {}", particle["label"]);
                 }
                 viewer.rois.commit_active(true);
             }
         }
+        let settings = &self.desktop_state.measurement_settings;
         for particle in particles {
-            let row = measurement_row_from_slice(
-                &particle.values,
-                particle.width(),
-                particle.height(),
-                Some((
-                    particle.min_x,
-                    particle.min_y,
-                    particle.max_x,
-                    particle.max_y,
-                )),
-                &self.desktop_state.measurement_settings,
-                z,
-                t,
-                channel,
-            );
+            let mut row = particle.into_iter().collect::<BTreeMap<_, _>>();
+            if settings.slice {
+                row.insert("slice".into(), json!(z));
+            }
+            if settings.time {
+                row.insert("time".into(), json!(t));
+            }
+            if settings.channel {
+                row.insert("channel".into(), json!(channel));
+            }
             self.results_table.add_row(row);
         }
         self.desktop_state.utility_windows.results_open = true;
@@ -4684,9 +4836,7 @@ impl ImageUiApp {
         }
 
         if command_id == "edit.options.memory" {
-            return Some(command_registry::CommandExecuteResult::ok(
-                "memory dialog is informational in image-rs",
-            ));
+            return Some(self.apply_memory_options(params));
         }
 
         if command_id == "edit.options.line_width" || command_id == "image.adjust.line_width" {
@@ -5125,9 +5275,13 @@ impl ImageUiApp {
             | "image.lookup.cyan"
             | "image.lookup.magenta"
             | "image.lookup.yellow"
-            | "image.lookup.red_green" => {
+            | "image.lookup.red_green"
+            | "image.lookup.glasbey" => {
                 let lut = lookup_table_from_command(command_id).expect("lookup command");
                 viewer.lookup_table = lut;
+                if let Some(session) = self.state.label_to_session.get_mut(window_label) {
+                    session.channel_luts.clear();
+                }
                 viewer.texture = None;
                 viewer.last_request = None;
                 Some(command_registry::CommandExecuteResult::ok(format!(
@@ -7218,6 +7372,53 @@ impl ImageUiApp {
         }
     }
 
+    /// Datasets for the op's secondary inputs: params of kind
+    /// `INPUT_PARAM_KIND` name an open window by label or file name.
+    fn resolve_op_inputs(
+        &mut self,
+        request: &ViewerOpRequest,
+    ) -> Result<Vec<(String, Arc<DatasetF32>)>, String> {
+        let Some(schema) = self
+            .state
+            .app
+            .ops_service()
+            .registry()
+            .get(request.op.as_str())
+            .map(|op| op.schema())
+        else {
+            return Ok(Vec::new());
+        };
+        let mut inputs = Vec::new();
+        for param in schema
+            .params
+            .iter()
+            .filter(|param| param.kind == INPUT_PARAM_KIND)
+        {
+            let Some(name) = request.params.get(&param.name).and_then(Value::as_str) else {
+                continue;
+            };
+            let label = if self.state.label_to_session.contains_key(name) {
+                name.to_string()
+            } else {
+                self.state
+                    .label_to_session
+                    .iter()
+                    .find(|(_, session)| {
+                        session.path.file_name().and_then(|file| file.to_str()) == Some(name)
+                    })
+                    .map(|(label, _)| label.clone())
+                    .ok_or_else(|| format!("no open image `{name}` for input `{}`", param.name))?
+            };
+            let session = self
+                .state
+                .label_to_session
+                .get_mut(&label)
+                .expect("session label was just looked up");
+            inputs.push((param.name.clone(), session.ensure_committed_dataset()?));
+        }
+        Ok(inputs)
+    }
+
     fn viewer_start_op(
         &mut self,
         window_label: &str,
@@ -7261,6 +7462,7 @@ impl ImageUiApp {
             return Ok(JobTicket { job_id });
         }
 
+        let op_inputs = self.resolve_op_inputs(&request)?;
         let (input_dataset, generation) = {
             let session = self
                 .state
@@ -7293,8 +7495,13 @@ impl ImageUiApp {
         let ops_service = self.state.app.ops_service().clone();
 
         std::thread::spawn(move || {
+            let inputs = op_inputs
+                .iter()
+                .fold(OpInputs::new(), |inputs, (name, dataset)| {
+                    inputs.with(name.as_str(), dataset.as_ref())
+                });
             let result = ops_service
-                .execute(&op_name, input_dataset.as_ref(), &params)
+                .execute_with_inputs(&op_name, input_dataset.as_ref(), &inputs, &params)
                 .map(|output| OpRunOutput {
                     dataset: Arc::new(output.dataset),
                     measurements: output.measurements,
@@ -7344,13 +7551,16 @@ impl ImageUiApp {
 
         match compute_viewer_frame(&mut self.state, label, &request, None) {
             Ok(frame) => {
-                let threshold_overlay = self
-                    .state
-                    .label_to_session
-                    .get(label)
-                    .and_then(|session| session.threshold_overlay);
-                let color =
-                    to_color_image_with_threshold(&frame, viewer.lookup_table, threshold_overlay);
+                let session = self.state.label_to_session.get(label);
+                let threshold_overlay = session.and_then(|session| session.threshold_overlay);
+                let channel_lut =
+                    session.and_then(|session| session.channel_luts.get(&request.channel));
+                let color = to_color_image_with_threshold(
+                    &frame,
+                    channel_lut,
+                    viewer.lookup_table,
+                    threshold_overlay,
+                );
                 let viewer_state = self
                     .viewers_ui
                     .get_mut(label)
@@ -12572,6 +12782,9 @@ fn current_zt_plane_dataset(
         channel_names: dataset.metadata.channel_names.clone(),
         source: dataset.metadata.source.clone(),
         extras: dataset.metadata.extras.clone(),
+        display: dataset.metadata.display.clone(),
+        history: dataset.metadata.history.clone(),
+        kind: dataset.metadata.kind,
     };
     let data = ArrayD::from_shape_vec(IxDyn(&shape), values)
         .map_err(|error| format!("current slice shape error: {error}"))?;
@@ -14346,90 +14559,16 @@ fn threshold_slice_otsu(values: &[f32]) -> Vec<u8> {
             best_threshold = index;
         }
     }
-    let threshold = (best_threshold as f32 / 255.0) * span + min;
+    // As in ImageJ, the Otsu level is the last background bin.
     values
         .iter()
-        .map(|value| if *value >= threshold { 1 } else { 0 })
+        .map(|value| {
+            let normalized = ((*value - min) / span).clamp(0.0, 1.0);
+            u8::from((normalized * 255.0).round() as usize > best_threshold)
+        })
         .collect()
 }
 
-#[derive(Debug, Clone)]
-struct Particle {
-    label: usize,
-    min_x: usize,
-    min_y: usize,
-    max_x: usize,
-    max_y: usize,
-    values: Vec<f32>,
-}
-
-impl Particle {
-    fn width(&self) -> usize {
-        self.max_x.saturating_sub(self.min_x) + 1
-    }
-
-    fn height(&self) -> usize {
-        self.max_y.saturating_sub(self.min_y) + 1
-    }
-}
-
-fn connected_components_2d(width: usize, height: usize, binary: &[u8]) -> Vec<Particle> {
-    let mut visited = vec![false; binary.len()];
-    let mut particles = Vec::new();
-    let mut label = 0usize;
-    for y in 0..height {
-        for x in 0..width {
-            let idx = x + y * width;
-            if visited[idx] || binary[idx] == 0 {
-                continue;
-            }
-            label += 1;
-            let mut queue = std::collections::VecDeque::from([(x, y)]);
-            visited[idx] = true;
-            let mut points = Vec::new();
-            let (mut min_x, mut min_y, mut max_x, mut max_y) = (x, y, x, y);
-            while let Some((cx, cy)) = queue.pop_front() {
-                points.push((cx, cy));
-                min_x = min_x.min(cx);
-                min_y = min_y.min(cy);
-                max_x = max_x.max(cx);
-                max_y = max_y.max(cy);
-                for (nx, ny) in [
-                    (cx.wrapping_sub(1), cy),
-                    (cx + 1, cy),
-                    (cx, cy.wrapping_sub(1)),
-                    (cx, cy + 1),
-                ] {
-                    if nx >= width || ny >= height {
-                        continue;
-                    }
-                    let nidx = nx + ny * width;
-                    if visited[nidx] || binary[nidx] == 0 {
-                        continue;
-                    }
-                    visited[nidx] = true;
-                    queue.push_back((nx, ny));
-                }
-            }
-            let particle_width = max_x - min_x + 1;
-            let particle_height = max_y - min_y + 1;
-            let mut values = vec![0.0_f32; particle_width * particle_height];
-            for (px, py) in points {
-                values[(px - min_x) + (py - min_y) * particle_width] = 1.0;
-            }
-            particles.push(Particle {
-                label,
-                min_x,
-                min_y,
-                max_x,
-                max_y,
-                values,
-            });
-        }
-    }
-    particles
-}
-
 fn measurement_row_from_slice(
     values: &[f32],
     width: usize,
@@ -15092,11 +15231,42 @@ fn color_balance_uses_lut_color(session: &ViewerSession) -> bool {
 
 fn to_color_image_with_threshold(
     frame: &ViewerFrameBuffer,
+    channel_lut: Option<&ChannelLut>,
     lut: LookupTable,
     threshold: Option<ThresholdOverlay>,
 ) -> egui::ColorImage {
+    // Glasbey colours label ids directly rather than display-scaled gray levels.
+    let labels = match channel_lut {
+        Some(ChannelLut::Named(name)) => {
+            lookup_table_from_label(name).unwrap_or(lut) == LookupTable::Glasbey
+        }
+        Some(ChannelLut::Custom { .. }) => false,
+        None => lut == LookupTable::Glasbey,
+    };
+    let gray_color = |index: usize, gray: u8| {
+        if labels {
+            let value = frame.values.get(index).copied().unwrap_or_default();
+            return glasbey_color(if value.is_finite() && value > 0.0 {
+                value as u32
+            } else {
+                0
+            });
+        }
+        match channel_lut {
+            Some(channel_lut) => channel_lut_color(channel_lut, lut, gray),
+            None => lookup_table_color(lut, gray),
+        }
+    };
     let Some(threshold) = threshold else {
-        return to_color_image(frame, lut);
+        if channel_lut.is_none() && !labels {
+            return to_color_image(frame, lut);
+        }
+        let mut rgba = Vec::with_capacity(frame.pixels_u8.len() * 4);
+        for (index, gray) in frame.pixels_u8.iter().enumerate() {
+            let color = gray_color(index, *gray);
+            rgba.extend_from_slice(&[color.r(), color.g(), color.b(), 255]);
+        }
+        return egui::ColorImage::from_rgba_unmultiplied([frame.width, frame.height], &rgba);
     };
 
     let mut rgba = Vec::with_capacity(frame.pixels_u8.len() * 4);
@@ -15118,7 +15288,7 @@ fn to_color_image_with_threshold(
             ThresholdOverlayMode::OverUnder if value.is_finite() && value > threshold.high => {
                 egui::Color32::RED
             }
-            _ => lookup_table_color(lut, *gray),
+            _ => gray_color(index, *gray),
         };
         rgba.extend_from_slice(&[color.r(), color.g(), color.b(), 255]);
     }
@@ -16259,7 +16429,9 @@ mod tests {
     use std::sync::Arc;
 
     use crate::formats::NativeRasterImage;
-    use crate::model::{AxisKind, DatasetF32, Dim, Metadata, PixelType};
+    use crate::model::{
+        AxisKind, ChannelLut, DatasetF32, Dim, DisplaySettings, Metadata, PixelType,
+    };
     use crate::runtime::AppContext;
     use crate::ui::interaction::roi::{RoiPosition, RoiStore};
     use crate::ui::interaction::transform::ViewerTransformState;
@@ -16283,10 +16455,10 @@ mod tests {
         compute_viewer_frame, concatenate_stack_datasets, coordinates_dialog_is_stack,
         create_circular_masks_dataset, dominant_scroll_component, effective_scroll_delta,
         first_report_line, flatten_overlay_slice, format_launcher_status, full_image_rect_roi,
-        function_key_for_macro_shortcut, image_draw_rect, image_slice_to_results_rows,
-        imagej_color_from_name, imagej_color_to_string, images_to_stack_dataset,
-        init_coordinates_dialog, initialize_view_to_open_state, insert_stack_dataset,
-        install_macro_file_to_dir, installed_macro_file_name,
+        function_key_for_macro_shortcut, glasbey_color, image_draw_rect,
+        image_slice_to_results_rows, imagej_color_from_name, imagej_color_to_string,
+        images_to_stack_dataset, init_coordinates_dialog, initialize_view_to_open_state,
+        insert_stack_dataset, install_macro_file_to_dir, installed_macro_file_name,
         installed_macro_menu_entry_from_block, interpolate_roi_kind, line_width_from_params,
         list_installed_macro_files_in_dir, lookup_table_color, lookup_table_from_command,
         lookup_table_slice_to_rgb, macro_display_name, macro_name_shortcut,
@@ -17024,6 +17196,43 @@ mod tests {
         assert!(line_width_from_params(&json!({"width": -1.0})).is_err());
     }
 
+    #[test]
+    fn memory_options_show_and_set_the_runtime_budget() {
+        let mut app = ImageUiApp::new_for_test();
+
+        let shown = app.dispatch_command("main", "edit.options.memory", None);
+        assert!(matches!(
+            shown.status,
+            crate::ui::command_registry::CommandExecuteStatus::Ok
+        ));
+        assert!(shown.message.starts_with("memory limit"));
+
+        let set = app.dispatch_command(
+            "main",
+            "edit.options.memory",
+            Some(json!({"maximum_mb": 256})),
+        );
+        assert_eq!(
+            set.payload
+                .as_ref()
+                .and_then(|payload| payload["maximum_mb"].as_u64()),
+            Some(256)
+        );
+        assert_eq!(app.state.app.memory_budget().limit(), 256 << 20);
+        assert_eq!(app.state.app.io_service().budget().limit(), 256 << 20);
+        assert_eq!(app.state.app.pipeline_service().budget().limit(), 256 << 20);
+
+        let rejected = app.dispatch_command(
+            "main",
+            "edit.options.memory",
+            Some(json!({"maximum_mb": 0})),
+        );
+        assert!(matches!(
+            rejected.status,
+            crate::ui::command_registry::CommandExecuteStatus::Blocked
+        ));
+    }
+
     #[test]
     fn line_width_spline_fit_toggles_selected_polygon_roi_like_imagej() {
         let mut viewer = ViewerUiState::new("viewer-1", "test".to_string());
@@ -18967,6 +19176,44 @@ mod tests {
         assert_eq!(session.committed_summary.max, 20.0);
     }
 
+    #[test]
+    fn viewer_session_seeds_and_exports_metadata_display_settings() {
+        let mut dataset = dataset_2x2_with_pixel_type([0.0, 5.0, 10.0, 20.0], PixelType::U8)
+            .as_ref()
+            .clone();
+        let mut display = DisplaySettings::default();
+        display.channel_mut(0).lut = ChannelLut::Named("Fire".to_string());
+        display.channel_mut(0).min = Some(0.0);
+        display.channel_mut(0).max = Some(10.0);
+        dataset.metadata.display = Some(display.clone());
+        let mut session = ViewerSession::new(
+            PathBuf::from("/tmp/display-metadata.tif"),
+            ViewerImageSource::Dataset(Arc::new(dataset)),
+        );
+
+        assert_eq!(session.channel_display_ranges.get(&0), Some(&(0.0, 10.0)));
+        assert_eq!(
+            session.channel_luts.get(&0),
+            Some(&ChannelLut::Named("Fire".to_string()))
+        );
+        assert_eq!(session.display_settings(LookupTable::Grays), Some(display));
+
+        session.set_channel_display_range(0, Some((2.0, 8.0)));
+        let exported = session
+            .display_settings(LookupTable::Grays)
+            .expect("display settings");
+        assert_eq!(exported.channels[0].range(), Some((2.0, 8.0)));
+
+        let plain = ViewerSession::new(
+            PathBuf::from("/tmp/display-plain.tif"),
+            ViewerImageSource::Dataset(dataset_2x2_with_pixel_type(
+                [0.0, 1.0, 2.0, 3.0],
+                PixelType::U8,
+            )),
+        );
+        assert_eq!(plain.display_settings(LookupTable::Grays), None);
+    }
+
     #[test]
     fn compute_viewer_frame_prefers_channel_display_range() {
         let data = Array::from_shape_vec(
@@ -19025,6 +19272,64 @@ mod tests {
         assert!(!session.can_undo());
     }
 
+    #[test]
+    fn analyze_particles_adds_one_rect_roi_per_particle_bounding_box() {
+        let label = "viewer-1".to_string();
+        let mut values = vec![0.0_f32; 8 * 12];
+        for y in 2..4 {
+            for x in 5..8 {
+                values[y * 12 + x] = 255.0;
+            }
+        }
+        values[6 * 12 + 10] = 255.0;
+        let data = Array::from_shape_vec((8, 12), values)
+            .expect("shape")
+            .into_dyn();
+        let dataset = Arc::new(DatasetF32::from_data_with_default_metadata(
+            data,
+            PixelType::U8,
+        ));
+        let mut app = ImageUiApp::new_for_test();
+        app.state.label_to_session.insert(
+            label.clone(),
+            ViewerSession::new(
+                PathBuf::from("/tmp/particles.tif"),
+                ViewerImageSource::Dataset(dataset),
+            ),
+        );
+        app.viewers_ui.insert(
+            label.clone(),
+            ViewerUiState::new(&label, "particles".to_string()),
+        );
+
+        let message = app.analyze_particles(&label).expect("particle analysis");
+        assert_eq!(message, "particle analysis added to results");
+        let rects = app.viewers_ui[&label]
+            .rois
+            .overlay_rois
+            .iter()
+            .map(|roi| match &roi.kind {
+                RoiKind::Rect { start, end, .. } => (roi.name.clone(), *start, *end),
+                other => panic!("unexpected ROI {other:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rects,
+            vec![
+                (
+                    "Particle 1".to_string(),
+                    egui::pos2(5.0, 2.0),
+                    egui::pos2(8.0, 4.0)
+                ),
+                (
+                    "Particle 2".to_string(),
+                    egui::pos2(10.0, 6.0),
+                    egui::pos2(11.0, 7.0)
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn adjust_apply_lut_rejects_float_images_like_imagej() {
         let label = "viewer-1".to_string();
@@ -20185,8 +20490,12 @@ mod tests {
             session.display_range,
         )
         .expect("frame");
-        let image =
-            to_color_image_with_threshold(&frame, LookupTable::Grays, session.threshold_overlay);
+        let image = to_color_image_with_threshold(
+            &frame,
+            None,
+            LookupTable::Grays,
+            session.threshold_overlay,
+        );
 
         assert_eq!(image.pixels[0], egui::Color32::BLACK);
         assert_eq!(image.pixels[1], egui::Color32::RED);
@@ -20199,6 +20508,36 @@ mod tests {
         assert!(!session.can_undo());
     }
 
+    #[test]
+    fn label_datasets_render_with_glasbey_by_label_id() {
+        let labels = dataset_2x2_with_pixel_type([0.0, 1.0, 2.0, 300.0], PixelType::F32)
+            .to_labels()
+            .expect("labels")
+            .to_label_dataset()
+            .expect("label dataset");
+        let session = ViewerSession::new(
+            PathBuf::from("/tmp/labels.tif"),
+            ViewerImageSource::Dataset(Arc::new(labels)),
+        );
+        let channel_lut = session.channel_luts.get(&0).cloned();
+        assert_eq!(channel_lut, ChannelLut::named("Glasbey"));
+
+        let frame = build_frame(
+            &session.committed_source(),
+            &ViewerFrameRequest::default(),
+            session.display_range,
+        )
+        .expect("frame");
+        let image =
+            to_color_image_with_threshold(&frame, channel_lut.as_ref(), LookupTable::Grays, None);
+
+        assert_eq!(image.pixels[0], egui::Color32::BLACK);
+        assert_eq!(image.pixels[1], glasbey_color(1));
+        assert_eq!(image.pixels[3], glasbey_color(300));
+        assert_ne!(image.pixels[1], image.pixels[2]);
+        assert_ne!(glasbey_color(300), glasbey_color(44));
+    }
+
     #[test]
     fn threshold_reset_honors_no_reset_range_checkbox() {
         let label = "viewer-1".to_string();
@@ -293,13 +293,21 @@ pub fn metadata(command_id: &str) -> CommandMetadata {
             None,
             Some("Invert active image intensities using ImageJ-style ranges."),
         ),
-        "edit.options.appearance" | "edit.options.memory" => CommandMetadata::with(
+        "edit.options.appearance" => CommandMetadata::with(
             CommandScope::Both,
             true,
             true,
             false,
             None,
-            Some("Open informational utility dialogs for appearance and memory."),
+            Some("Open the informational appearance and shortcuts window."),
+        ),
+        "edit.options.memory" => CommandMetadata::with(
+            CommandScope::Both,
+            true,
+            true,
+            false,
+            None,
+            Some("Show the memory budget or set it with `maximum_mb`."),
         ),
         "edit.options.line_width" => CommandMetadata::with(
             CommandScope::Both,
@@ -370,7 +378,8 @@ pub fn metadata(command_id: &str) -> CommandMetadata {
         | "image.lookup.cyan"
         | "image.lookup.magenta"
         | "image.lookup.yellow"
-        | "image.lookup.red_green" => CommandMetadata::with(
+        | "image.lookup.red_green"
+        | "image.lookup.glasbey" => CommandMetadata::with(
             CommandScope::Viewer,
             true,
             true,
@@ -2000,6 +2009,7 @@ mod tests {
                 "Magenta",
                 "Yellow",
                 "Red/Green",
+                "Glasbey",
             ]
         );
     }
@@ -2109,6 +2119,7 @@ mod tests {
             "image.lookup.magenta",
             "image.lookup.yellow",
             "image.lookup.red_green",
+            "image.lookup.glasbey",
             "image.overlay.add_selection",
             "image.overlay.flatten",
             "image.overlay.from_roi_manager",
@@ -1,3 +1,4 @@
+use crate::model::ChannelLut;
 use eframe::egui;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -15,6 +16,7 @@ pub(super) enum LookupTable {
     Magenta,
     Yellow,
     RedGreen,
+    Glasbey,
 }
 
 impl LookupTable {
@@ -33,10 +35,35 @@ impl LookupTable {
             Self::Magenta => "Magenta",
             Self::Yellow => "Yellow",
             Self::RedGreen => "Red/Green",
+            Self::Glasbey => "Glasbey",
         }
     }
 }
 
+pub(super) fn lookup_table_from_label(label: &str) -> Option<LookupTable> {
+    ALL_LOOKUP_TABLES
+        .iter()
+        .copied()
+        .find(|lut| lut.label().eq_ignore_ascii_case(label.trim()))
+}
+
+const ALL_LOOKUP_TABLES: [LookupTable; 14] = [
+    LookupTable::Grays,
+    LookupTable::Inverted,
+    LookupTable::Fire,
+    LookupTable::Ice,
+    LookupTable::Spectrum,
+    LookupTable::Rgb332,
+    LookupTable::Red,
+    LookupTable::Green,
+    LookupTable::Blue,
+    LookupTable::Cyan,
+    LookupTable::Magenta,
+    LookupTable::Yellow,
+    LookupTable::RedGreen,
+    LookupTable::Glasbey,
+];
+
 pub(super) fn lookup_table_from_command(command_id: &str) -> Option<LookupTable> {
     Some(match command_id {
         "image.lookup.invert_lut" => LookupTable::Inverted,
@@ -53,6 +80,7 @@ pub(super) fn lookup_table_from_command(command_id: &str) -> Option<LookupTable>
         "image.lookup.magenta" => LookupTable::Magenta,
         "image.lookup.yellow" => LookupTable::Yellow,
         "image.lookup.red_green" => LookupTable::RedGreen,
+        "image.lookup.glasbey" => LookupTable::Glasbey,
         _ => return None,
     })
 }
@@ -72,6 +100,7 @@ pub(super) fn lookup_table_color(lut: LookupTable, gray: u8) -> egui::Color32 {
         LookupTable::Magenta => egui::Color32::from_rgb(g, 0, g),
         LookupTable::Yellow => egui::Color32::from_rgb(g, g, 0),
         LookupTable::RedGreen => egui::Color32::from_rgb(255 - g, g, 0),
+        LookupTable::Glasbey => glasbey_color(u32::from(g)),
         LookupTable::Rgb332 => {
             let r = (g & 0b1110_0000) | ((g & 0b1110_0000) >> 3) | ((g & 0b1110_0000) >> 6);
             let green = ((g & 0b0001_1100) << 3) | (g & 0b0001_1100) | ((g & 0b0001_1100) >> 3);
@@ -107,3 +136,44 @@ pub(super) fn lookup_table_color(lut: LookupTable, gray: u8) -> egui::Color32 {
         }
     }
 }
+
+/// Distinct categorical colours for label ids. Hues follow the golden-ratio
+/// sequence so neighbouring ids never look alike; id 0 is background.
+pub(super) fn glasbey_color(label: u32) -> egui::Color32 {
+    if label == 0 {
+        return egui::Color32::BLACK;
+    }
+    let hue = (f64::from(label) * 0.618_033_988_749_895).fract();
+    let saturation = [0.95, 0.7, 0.85][label as usize % 3];
+    let value = [1.0, 0.8, 0.9, 0.7][(label as usize / 3) % 4];
+    let sector = hue * 6.0;
+    let chroma = value * saturation;
+    let secondary = chroma * (1.0 - ((sector % 2.0) - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, secondary, 0.0),
+        1 => (secondary, chroma, 0.0),
+        2 => (0.0, chroma, secondary),
+        3 => (0.0, secondary, chroma),
+        4 => (secondary, 0.0, chroma),
+        _ => (chroma, 0.0, secondary),
+    };
+    let offset = value - chroma;
+    let channel = |component: f64| ((component + offset) * 255.0).round() as u8;
+    egui::Color32::from_rgb(channel(r), channel(g), channel(b))
+}
+
+pub(super) fn channel_lut_color(
+    lut: &ChannelLut,
+    fallback: LookupTable,
+    gray: u8,
+) -> egui::Color32 {
+    match lut {
+        ChannelLut::Named(name) => {
+            lookup_table_color(lookup_table_from_label(name).unwrap_or(fallback), gray)
+        }
+        ChannelLut::Custom { .. } => lut
+            .color(gray)
+            .map(|[r, g, b]| egui::Color32::from_rgb(r, g, b))
+            .unwrap_or_else(|| lookup_table_color(fallback, gray)),
+    }
+}
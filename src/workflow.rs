@@ -8,7 +8,7 @@ mod spec;
 mod tests;
 
 pub use error::{PipelineError, Result};
-pub use execute::run_pipeline;
+pub use execute::{run_pipeline, run_pipeline_with_budget};
 pub use io::{load_spec, save_report};
 pub use report::{PipelineReport, StepReport};
-pub use spec::{OpInvocation, PipelineSpec};
+pub use spec::{OpInvocation, PIPELINE_INPUT, PipelineSpec};
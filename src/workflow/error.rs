@@ -1,5 +1,5 @@
 use crate::commands::OpsError;
-use crate::model::CoreError;
+use crate::model::{CoreError, MemoryBudgetExceeded};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, PipelineError>;
@@ -23,4 +23,7 @@ pub enum PipelineError {
 
     #[error("dataset validation failed: {0}")]
     Core(#[from] CoreError),
+
+    #[error("{0}")]
+    Memory(#[from] MemoryBudgetExceeded),
 }
@@ -2,32 +2,75 @@ use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::time::Instant;
 
-use crate::commands::{Operation, execute_operation_with_registry};
-use crate::model::DatasetF32;
+use crate::commands::{OpInputs, Operation, execute_operation_with_registry};
+use crate::model::{DatasetF32, MemoryBudget, ProvenanceEntry};
 
-use super::{PipelineReport, PipelineSpec, Result, StepReport};
+use super::{PIPELINE_INPUT, PipelineReport, PipelineSpec, Result, StepReport};
 
 pub fn run_pipeline(
     spec: &PipelineSpec,
     dataset: &DatasetF32,
     registry: &HashMap<&'static str, Arc<dyn Operation>>,
+) -> Result<(DatasetF32, PipelineReport)> {
+    run_pipeline_with_budget(spec, dataset, registry, &MemoryBudget::default())
+}
+
+/// Runs the pipeline, refusing any step whose input and same-sized output,
+/// together with the outputs kept by `save_as`, would not fit in `budget`.
+pub fn run_pipeline_with_budget(
+    spec: &PipelineSpec,
+    dataset: &DatasetF32,
+    registry: &HashMap<&'static str, Arc<dyn Operation>>,
+    budget: &MemoryBudget,
 ) -> Result<(DatasetF32, PipelineReport)> {
     spec.validate()?;
     dataset.validate()?;
 
     let mut current = dataset.clone();
+    let mut peak_memory_bytes = current.metadata.estimated_bytes();
     let mut steps = Vec::with_capacity(spec.operations.len());
     let mut final_measurements = BTreeMap::new();
+    let mut history = dataset.metadata.history.clone();
+    let mut saved = HashMap::<&str, DatasetF32>::new();
+    let mut saved_bytes = 0_u64;
 
     for invocation in &spec.operations {
+        let named = |name: &str| {
+            if name == PIPELINE_INPUT {
+                dataset
+            } else {
+                &saved[name]
+            }
+        };
+        let source = invocation.input.as_deref().map_or(&current, named);
+        let input_bytes = source.metadata.estimated_bytes();
+        budget.check(
+            &format!("running `{}`", invocation.op),
+            input_bytes.saturating_mul(2).saturating_add(saved_bytes),
+        )?;
+        history.push(ProvenanceEntry::record(
+            &invocation.op,
+            &invocation.params,
+            source,
+        ));
+        let mut inputs = OpInputs::new();
+        for (input, name) in &invocation.inputs {
+            inputs.insert(input.as_str(), named(name));
+        }
         let started = Instant::now();
         let output = execute_operation_with_registry(
             registry,
             &invocation.op,
-            &current,
+            source,
+            &inputs,
             &invocation.params,
         )?;
         let duration_ms = started.elapsed().as_millis();
+        let step_bytes = input_bytes
+            .saturating_add(output.dataset.metadata.estimated_bytes())
+            .saturating_add(saved_bytes);
+        peak_memory_bytes = peak_memory_bytes.max(step_bytes);
+        budget.check(&format!("running `{}`", invocation.op), step_bytes)?;
         if let Some(measurements) = &output.measurements {
             for (key, value) in &measurements.values {
                 final_measurements.insert(key.clone(), value.clone());
@@ -39,6 +82,13 @@ pub fn run_pipeline(
             measurements: output.measurements.clone(),
         });
         current = output.dataset;
+        current.metadata.history = history.clone();
+        if let Some(name) = &invocation.save_as {
+            saved_bytes = saved_bytes.saturating_add(current.metadata.estimated_bytes());
+            if let Some(replaced) = saved.insert(name.as_str(), current.clone()) {
+                saved_bytes = saved_bytes.saturating_sub(replaced.metadata.estimated_bytes());
+            }
+        }
     }
 
     let report = PipelineReport {
@@ -46,6 +96,7 @@ pub fn run_pipeline(
         steps,
         final_measurements,
         output_metadata: current.metadata.clone(),
+        peak_memory_bytes,
     };
     Ok((current, report))
 }
@@ -18,4 +18,7 @@ pub struct PipelineReport {
     pub steps: Vec<StepReport>,
     pub final_measurements: BTreeMap<String, Value>,
     pub output_metadata: Metadata,
+    /// Largest estimated number of bytes held by step inputs and outputs at once.
+    #[serde(default)]
+    pub peak_memory_bytes: u64,
 }
@@ -1,3 +1,5 @@
+use std::collections::{BTreeMap, BTreeSet};
+
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -15,8 +17,22 @@ pub struct OpInvocation {
     pub op: String,
     #[serde(default)]
     pub params: Value,
+    /// Dataset the step runs on instead of the previous step's output:
+    /// [`PIPELINE_INPUT`] or the `save_as` name of an earlier step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<String>,
+    /// Secondary op inputs, each naming a dataset: [`PIPELINE_INPUT`] or the
+    /// `save_as` name of an earlier step.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub inputs: BTreeMap<String, String>,
+    /// Keeps this step's output under a name later steps can use as an input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub save_as: Option<String>,
 }
 
+/// Name under which the pipeline's own input dataset can be used as a step input.
+pub const PIPELINE_INPUT: &str = "input";
+
 impl PipelineSpec {
     pub fn validate(&self) -> Result<()> {
         if self.operations.is_empty() {
@@ -24,6 +40,7 @@ impl PipelineSpec {
                 "pipeline must include at least one operation".to_string(),
             ));
         }
+        let mut saved = BTreeSet::from([PIPELINE_INPUT]);
         for (index, step) in self.operations.iter().enumerate() {
             if step.op.trim().is_empty() {
                 return Err(PipelineError::Parse(format!(
@@ -36,6 +53,28 @@ impl PipelineSpec {
                     step.op
                 )));
             }
+            let primary = step.input.iter().map(|dataset| ("input", dataset));
+            let secondary = step
+                .inputs
+                .iter()
+                .map(|(input, dataset)| (input.as_str(), dataset));
+            for (input, dataset) in primary.chain(secondary) {
+                if !saved.contains(dataset.as_str()) {
+                    return Err(PipelineError::Parse(format!(
+                        "operation `{}` input `{input}` refers to `{dataset}`, which no earlier step saves",
+                        step.op
+                    )));
+                }
+            }
+            if let Some(name) = &step.save_as {
+                if name.trim().is_empty() || name == PIPELINE_INPUT {
+                    return Err(PipelineError::Parse(format!(
+                        "operation `{}` cannot save its output as `{name}`",
+                        step.op
+                    )));
+                }
+                saved.insert(name.as_str());
+            }
         }
         Ok(())
     }
@@ -1,12 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
 use crate::commands::{Operation, default_registry};
-use crate::model::{AxisKind, Dataset, Dim, Metadata, PixelType};
+use crate::model::{AxisKind, Dataset, Dim, MemoryBudget, Metadata, PixelType, content_hash};
 use ndarray::Array;
 use serde_json::json;
 
-use super::{OpInvocation, PipelineSpec, run_pipeline};
+use super::{OpInvocation, PipelineError, PipelineSpec, run_pipeline, run_pipeline_with_budget};
 
 fn test_dataset() -> Dataset<f32> {
     let data = Array::from_shape_vec((2, 2), vec![0.1_f32, 0.3, 0.8, 0.9])
@@ -28,10 +28,16 @@ fn pipeline_executes_in_order() {
             OpInvocation {
                 op: "intensity.normalize".to_string(),
                 params: json!({}),
+                input: None,
+                inputs: BTreeMap::new(),
+                save_as: None,
             },
             OpInvocation {
                 op: "threshold.fixed".to_string(),
                 params: json!({"threshold": 0.5}),
+                input: None,
+                inputs: BTreeMap::new(),
+                save_as: None,
             },
         ],
     };
@@ -47,6 +53,31 @@ fn pipeline_executes_in_order() {
     );
 }
 
+#[test]
+fn pipeline_reports_peak_memory_and_enforces_budget() {
+    let spec = PipelineSpec {
+        name: None,
+        operations: vec![OpInvocation {
+            op: "intensity.normalize".to_string(),
+            params: json!({}),
+            input: None,
+            inputs: BTreeMap::new(),
+            save_as: None,
+        }],
+    };
+    let dataset = test_dataset();
+    let registry: HashMap<&'static str, Arc<dyn Operation>> = default_registry();
+
+    let (_, report) = run_pipeline_with_budget(&spec, &dataset, &registry, &MemoryBudget::new(32))
+        .expect("pipeline within budget");
+    assert_eq!(report.peak_memory_bytes, 32);
+
+    let error = run_pipeline_with_budget(&spec, &dataset, &registry, &MemoryBudget::new(31))
+        .expect_err("budget exceeded");
+    assert!(matches!(error, PipelineError::Memory(_)));
+    assert!(error.to_string().contains("intensity.normalize"));
+}
+
 #[test]
 fn invalid_pipeline_is_rejected() {
     let spec = PipelineSpec {
@@ -57,3 +88,73 @@ fn invalid_pipeline_is_rejected() {
     let registry: HashMap<&'static str, Arc<dyn Operation>> = default_registry();
     assert!(run_pipeline(&spec, &dataset, &registry).is_err());
 }
+
+#[test]
+fn pipeline_appends_provenance_entry_per_step() {
+    let spec = PipelineSpec {
+        name: Some("provenance".to_string()),
+        operations: vec![
+            OpInvocation {
+                op: "intensity.normalize".to_string(),
+                params: json!({}),
+                input: None,
+                inputs: BTreeMap::new(),
+                save_as: None,
+            },
+            OpInvocation {
+                op: "threshold.fixed".to_string(),
+                params: json!({"threshold": 0.5}),
+                input: None,
+                inputs: BTreeMap::new(),
+                save_as: None,
+            },
+        ],
+    };
+    let dataset = test_dataset();
+    let registry: HashMap<&'static str, Arc<dyn Operation>> = default_registry();
+    let (result, report) = run_pipeline(&spec, &dataset, &registry).expect("pipeline");
+
+    let history = &result.metadata.history;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].op, "intensity.normalize");
+    assert_eq!(history[1].op, "threshold.fixed");
+    assert_eq!(history[1].params, json!({"threshold": 0.5}));
+    assert_eq!(history[0].input_hash, content_hash(&dataset));
+    assert_ne!(history[1].input_hash, history[0].input_hash);
+    assert_eq!(history[0].crate_version, env!("CARGO_PKG_VERSION"));
+    assert!(history[0].timestamp.ends_with('Z'));
+    assert_eq!(report.output_metadata.history, *history);
+
+    let (rerun, _) = run_pipeline(&spec, &result, &registry).expect("second pipeline");
+    assert_eq!(rerun.metadata.history.len(), 4);
+}
+
+#[test]
+fn pipeline_passes_saved_outputs_as_named_inputs() {
+    let spec: PipelineSpec = serde_json::from_value(json!({
+        "operations": [
+            {"op": "intensity.math", "params": {"operation": "multiply", "value": 2.0}, "save_as": "doubled"},
+            {"op": "image.calculator", "params": {"operation": "subtract"}, "inputs": {"image2": "input"}},
+            {"op": "image.calculator", "params": {"operation": "divide"}, "inputs": {"image2": "doubled"}}
+        ]
+    }))
+    .expect("spec");
+    let dataset = test_dataset();
+    let registry: HashMap<&'static str, Arc<dyn Operation>> = default_registry();
+    let (result, _) = run_pipeline(&spec, &dataset, &registry).expect("pipeline");
+    assert!(result.data.iter().all(|value| (value - 0.5).abs() < 1e-6));
+
+    let ratio: PipelineSpec = serde_json::from_value(json!({
+        "operations": [
+            {"op": "intensity.math", "params": {"operation": "add", "value": 1.0}, "save_as": "shifted"},
+            {"op": "image.calculator", "params": {"operation": "divide"}, "input": "input", "inputs": {"image2": "shifted"}}
+        ]
+    }))
+    .expect("ratio spec");
+    let (ratio_result, _) = run_pipeline(&ratio, &dataset, &registry).expect("ratio pipeline");
+    assert!((ratio_result.data[[0, 0]] - 0.1 / 1.1).abs() < 1e-6);
+
+    let mut unknown = spec.clone();
+    unknown.operations[1].inputs = BTreeMap::from([("image2".to_string(), "later".to_string())]);
+    assert!(matches!(unknown.validate(), Err(PipelineError::Parse(_))));
+}